@@ -0,0 +1,98 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+use televerknet::{DataEnd, Parser, Perform};
+
+/// Counts callbacks without allocating, so only the parser is measured
+#[derive(Default)]
+struct Counter {
+    data: usize,
+    other: usize,
+}
+
+impl Perform for Counter {
+    fn data(&mut self, intermediates: &[u8], _ignore: bool, _end: DataEnd) {
+        self.data += intermediates.len();
+    }
+
+    fn execute(&mut self, _byte: u8) {
+        self.other += 1;
+    }
+
+    fn iac_dispatch(&mut self, _byte: u8) {
+        self.other += 1;
+    }
+
+    fn sub_dispatch(&mut self, _subs: &[u8]) {
+        self.other += 1;
+    }
+
+    fn negotiate_dispatch(&mut self, _cmd: u8, _opt: u8) {
+        self.other += 1;
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+
+    fn compress_dispatch(&mut self, _state: u8) {}
+}
+
+/// Lines of plain text
+fn ascii(len: usize) -> Vec<u8> {
+    b"The quick brown fox jumps over the lazy dog.\r\n"
+        .iter()
+        .cycle()
+        .take(len)
+        .cloned()
+        .collect()
+}
+
+/// Short words separated by escaped IAC bytes and GA
+fn iac_dense(len: usize) -> Vec<u8> {
+    [b'o', b'k', 255, 255, b'!', 255, 249]
+        .iter()
+        .cycle()
+        .take(len)
+        .cloned()
+        .collect()
+}
+
+/// Back to back subnegotiations, e.g. GMCP
+fn subneg_heavy(len: usize) -> Vec<u8> {
+    [255, 250, 201, b'a', b'b', b'c', 255, 240]
+        .iter()
+        .cycle()
+        .take(len)
+        .cloned()
+        .collect()
+}
+
+fn parse(c: &mut Criterion) {
+    let len = 64 * 1024;
+    let mut group = c.benchmark_group("advance_bytes");
+    group.throughput(Throughput::Bytes(len as u64));
+    for (name, input) in &[
+        ("ascii", ascii(len)),
+        ("iac_dense", iac_dense(len)),
+        ("subneg_heavy", subneg_heavy(len)),
+    ] {
+        group.bench_with_input(BenchmarkId::from_parameter(name), input, |b, input| {
+            let mut parser = Parser::new();
+            b.iter(|| {
+                let mut counter = Counter::default();
+                // Read sized chunks, as from a socket
+                for chunk in input.chunks(4096) {
+                    parser.advance_bytes(&mut counter, black_box(chunk));
+                }
+                counter.data + counter.other
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
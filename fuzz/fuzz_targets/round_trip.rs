@@ -0,0 +1,88 @@
+//! Encode arbitrary events, parse them back and compare
+//!
+//! Events the parser reports differently than they were sent are normalized first, e.g. control
+//! bytes in data come back as `Execute`. Events which the parser cannot represent are skipped,
+//! see `normalize`.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use televerknet::command::Command;
+use televerknet::event::Event;
+use televerknet::ParserBuilder;
+
+fuzz_target!(|events: Vec<Event>| {
+    let mut parser = ParserBuilder::new().build();
+    // The subnegotiation buffer also holds the option byte and the IAC before SE
+    let max_params = parser.max_subnegotiation() - 2;
+
+    let mut wire = Vec::new();
+    let mut expected = Vec::new();
+    for event in &events {
+        if let Some(normalized) = normalize(event, max_params) {
+            event.encode_into(&mut wire);
+            normalized.into_iter().for_each(|e| push(&mut expected, e));
+        }
+    }
+
+    let mut events = Vec::new();
+    parser.advance_bytes(&mut events, &wire);
+    parser.flush(&mut events);
+    let mut parsed = Vec::new();
+    events.into_iter().for_each(|e| push(&mut parsed, e));
+    assert_eq!(parsed, expected);
+});
+
+/// The events the parser reports for `event`, or `None` to skip it
+fn normalize(event: &Event, max_params: usize) -> Option<Vec<Event>> {
+    match event {
+        Event::Data(data) => Some(data.iter().map(|b| byte(*b)).collect()),
+        Event::Execute(255) => None,
+        Event::Execute(b) => Some(vec![byte(*b)]),
+        Event::Command(Command::IAC) => Some(vec![Event::Data(vec![255])]),
+        // These start sequences which consume the bytes following them
+        Event::Command(command) if is_sequence_start(*command) => None,
+        Event::Command(_) => Some(vec![event.clone()]),
+        Event::Negotiate(command, _) if is_negotiation(*command) => Some(vec![event.clone()]),
+        Event::Negotiate(..) => None,
+        // SE or IAC as the option byte end the subnegotiation or start a command, and longer
+        // parameters are truncated
+        Event::Subnegotiate(option, params)
+            if option.as_u8() < 240
+                && params.len() + params.iter().filter(|b| **b == 255).count() <= max_params =>
+        {
+            Some(vec![event.clone()])
+        }
+        Event::Subnegotiate(..) => None,
+        // Added by TelnetSession, not on the wire
+        _ => None,
+    }
+}
+
+fn byte(b: u8) -> Event {
+    match b {
+        0x20..=0x7f | 0xff => Event::Data(vec![b]),
+        _ => Event::Execute(b),
+    }
+}
+
+fn is_negotiation(command: Command) -> bool {
+    [Command::WILL, Command::WONT, Command::DO, Command::DONT].contains(&command)
+}
+
+fn is_sequence_start(command: Command) -> bool {
+    command == Command::SB || is_negotiation(command)
+}
+
+/// Push an event, merging consecutive data like the parser does
+fn push(events: &mut Vec<Event>, event: Event) {
+    if let (Some(Event::Data(last)), Event::Data(data)) = (events.last_mut(), &event) {
+        last.extend_from_slice(data);
+        return;
+    }
+    if let Event::Data(data) = &event {
+        if data.is_empty() {
+            return;
+        }
+    }
+    events.push(event);
+}
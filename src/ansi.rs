@@ -0,0 +1,262 @@
+//! Removal of ANSI escape sequences
+//!
+//! [`AnsiStrip`] is a performer which removes escape sequences, e.g. colours and cursor movement,
+//! from data before passing it on, so bots, loggers and triggers see plain text. A sequence split
+//! between reads or interrupted by a telnet command is still recognized. The sequences removed are
+//! passed to [`AnsiPerform::escape`], which drops them unless the performer wants them.
+//!
+//! CSI sequences, OSC, DCS, SOS, PM and APC strings, and other sequences starting with ESC are
+//! recognized. CAN and SUB abort a sequence, as on a terminal.
+//!
+//! [`AnsiStrip`]: struct.AnsiStrip.html
+//! [`AnsiPerform::escape`]: trait.AnsiPerform.html#method.escape
+use crate::event::Event;
+use crate::{DataEnd, ParseError, Perform};
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1a;
+
+/// A performer which can receive the escape sequences removed by [`AnsiStrip`]
+///
+/// [`AnsiStrip`]: struct.AnsiStrip.html
+pub trait AnsiPerform: Perform {
+    /// A complete escape sequence, starting with ESC
+    fn escape(&mut self, _sequence: &[u8]) {}
+}
+
+impl AnsiPerform for Vec<Event> {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// After ESC
+    Escape,
+    /// After ESC and intermediate bytes
+    EscapeIntermediate,
+    /// After ESC [
+    Csi,
+    /// Within a string, ended by ST or, for OSC, by BEL
+    String,
+    /// After ESC within a string, the start of ST
+    StringEscape,
+}
+
+/// Removes ANSI escape sequences from data before passing it to `P`
+#[derive(Debug)]
+pub struct AnsiStrip<P> {
+    inner: P,
+    state: State,
+    sequence: Vec<u8>,
+    text: Vec<u8>,
+}
+
+impl<P: AnsiPerform> AnsiStrip<P> {
+    pub fn new(inner: P) -> AnsiStrip<P> {
+        AnsiStrip {
+            inner,
+            state: State::Ground,
+            sequence: Vec::new(),
+            text: Vec::new(),
+        }
+    }
+
+    /// Returns true within an escape sequence
+    pub fn in_sequence(&self) -> bool {
+        self.state != State::Ground
+    }
+
+    pub fn get_ref(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    /// Advance the sequence state by `byte`, returning true if it is part of a sequence
+    fn advance(&mut self, byte: u8) -> bool {
+        if self.state != State::Ground && (byte == CAN || byte == SUB) {
+            self.abort();
+            return true;
+        }
+        match (self.state, byte) {
+            (State::Ground, ESC) => self.state = State::Escape,
+            (State::Ground, _) => return false,
+            (State::Escape, b'[') => self.state = State::Csi,
+            (State::Escape, b']' | b'P' | b'X' | b'^' | b'_') => self.state = State::String,
+            (State::Escape, 0x20..=0x2f) => self.state = State::EscapeIntermediate,
+            (State::Escape | State::EscapeIntermediate, 0x30..=0x7e)
+            | (State::Csi, 0x40..=0x7e)
+            | (State::String, BEL)
+            | (State::StringEscape, b'\\') => {
+                self.sequence.push(byte);
+                self.end();
+                return true;
+            }
+            (State::String, ESC) => self.state = State::StringEscape,
+            // ESC outside of strings starts a new sequence
+            (_, ESC) => {
+                self.sequence.clear();
+                self.state = State::Escape;
+            }
+            (State::StringEscape, _) => self.state = State::String,
+            // Control bytes within a sequence are executed as usual
+            (_, 0x00..=0x1f) => return false,
+            _ => (),
+        }
+        self.sequence.push(byte);
+        true
+    }
+
+    fn end(&mut self) {
+        self.inner.escape(&self.sequence);
+        self.sequence.clear();
+        self.state = State::Ground;
+    }
+
+    fn abort(&mut self) {
+        self.sequence.clear();
+        self.state = State::Ground;
+    }
+}
+
+impl<P: AnsiPerform> Perform for AnsiStrip<P> {
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        let mut text = std::mem::take(&mut self.text);
+        text.clear();
+        for &byte in intermediates {
+            if !self.advance(byte) {
+                text.push(byte);
+            }
+        }
+        if !text.is_empty() {
+            self.inner.data(&text, ignore, end);
+        }
+        self.text = text;
+    }
+
+    fn execute(&mut self, byte: u8) {
+        if !self.advance(byte) {
+            self.inner.execute(byte);
+        }
+    }
+
+    fn error(&mut self, error: ParseError) {
+        self.inner.error(error);
+    }
+
+    fn should_break(&mut self) -> bool {
+        self.inner.should_break()
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        self.inner.iac_dispatch(byte);
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        self.inner.sub_dispatch(subs);
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        self.inner.negotiate_dispatch(cmd, opt);
+    }
+
+    fn subnegotiate_dispatch(&mut self, params: &[u8], opt: u8) {
+        self.inner.subnegotiate_dispatch(params, opt);
+    }
+
+    fn zmp_dispatch(&mut self, params: &[&[u8]]) {
+        self.inner.zmp_dispatch(params);
+    }
+
+    fn ttypes_dispatch(&mut self, cmd: u8, terminal_type: &[u8]) {
+        self.inner.ttypes_dispatch(cmd, terminal_type);
+    }
+
+    fn compress_dispatch(&mut self, state: u8) {
+        self.inner.compress_dispatch(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnsiPerform, AnsiStrip};
+    use crate::event::Event;
+    use crate::{DataEnd, Parser, Perform};
+
+    #[derive(Default)]
+    struct Extracted {
+        events: Vec<Event>,
+        sequences: Vec<Vec<u8>>,
+    }
+
+    impl Perform for Extracted {
+        fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+            self.events.data(intermediates, ignore, end);
+        }
+
+        fn execute(&mut self, byte: u8) {
+            self.events.execute(byte);
+        }
+
+        fn iac_dispatch(&mut self, byte: u8) {
+            self.events.iac_dispatch(byte);
+        }
+
+        fn sub_dispatch(&mut self, subs: &[u8]) {
+            self.events.sub_dispatch(subs);
+        }
+
+        fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+            self.events.negotiate_dispatch(cmd, opt);
+        }
+
+        fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+        fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+        fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+        fn compress_dispatch(&mut self, _state: u8) {}
+    }
+
+    impl AnsiPerform for Extracted {
+        fn escape(&mut self, sequence: &[u8]) {
+            self.sequences.push(sequence.to_vec());
+        }
+    }
+
+    #[test]
+    fn strip_across_reads() {
+        let mut parser = Parser::new();
+        let mut strip = AnsiStrip::new(Vec::new());
+        parser.advance_bytes(&mut strip, b"\x1b[1;3");
+        parser.flush(&mut strip);
+        assert!(strip.in_sequence());
+        parser.advance_bytes(&mut strip, b"1mred\xff\xf1\x1b[0m plain");
+        parser.flush(&mut strip);
+        let text: Vec<u8> = strip
+            .into_inner()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Data(data) => Some(data),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert_eq!(text, b"red plain");
+
+        let mut extract = AnsiStrip::new(Extracted::default());
+        parser.advance_bytes(&mut extract, b"a\x1b]0;title\x07b\x1b(Bc\x1b[3\x18d");
+        parser.flush(&mut extract);
+        let extracted = extract.into_inner();
+        assert_eq!(
+            extracted.sequences,
+            &[b"\x1b]0;title\x07".to_vec(), b"\x1b(B".to_vec()]
+        );
+        assert_eq!(extracted.events, &[Event::Data(b"abcd".to_vec())]);
+    }
+}
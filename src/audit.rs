@@ -0,0 +1,152 @@
+//! Audit trail of security relevant actions
+//!
+//! A [`TelnetSession`] with an [`AuditSink`] registered records what changes the security of the
+//! connection as [`AuditEvent`]s: options enabled and disabled, authentication attempts,
+//! encryption starting and ending, the START_TLS hand-off and logout requests. The trail is kept
+//! apart from the events of the data path, and holds no credentials.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//!
+//! use televerknet::audit::{AuditEvent, Side};
+//! use televerknet::option::Opt;
+//! use televerknet::session::{Role, TelnetSession};
+//!
+//! let trail = Arc::new(Mutex::new(Vec::new()));
+//! let sink = trail.clone();
+//! let mut session = TelnetSession::with_role(Role::Client);
+//! session.register_audit_sink(move |event: &AuditEvent| sink.lock().unwrap().push(event.clone()));
+//! session.feed(b"\xff\xfb\x01").for_each(drop);
+//! assert_eq!(
+//!     *trail.lock().unwrap(),
+//!     &[AuditEvent::Enabled(Opt::ECHO, Side::Remote)]
+//! );
+//! ```
+//!
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+//! [`AuditSink`]: trait.AuditSink.html
+//! [`AuditEvent`]: enum.AuditEvent.html
+use crate::option::Opt;
+use crate::session::StartTls;
+
+const AUTH_IS: u8 = 0;
+const ENCRYPT_START: u8 = 3;
+const ENCRYPT_END: u8 = 4;
+
+/// Which party performs an option
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Side {
+    /// We do
+    Local,
+    /// The other party does
+    Remote,
+}
+
+/// A security relevant action
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AuditEvent {
+    /// An option was enabled after negotiation
+    Enabled(Opt, Side),
+    /// An option was disabled after negotiation
+    Disabled(Opt, Side),
+    /// The other party sent `AUTHENTICATION IS` ([RFC 2941]) with an authentication type and
+    /// its modifiers. The authentication data itself is not recorded.
+    ///
+    /// [RFC 2941]: https://www.rfc-editor.org/rfc/rfc2941.html
+    AuthenticationAttempt { auth_type: u8, modifiers: u8 },
+    /// The other party started encrypting what it sends, with `ENCRYPT START` ([RFC 2946])
+    ///
+    /// [RFC 2946]: https://www.rfc-editor.org/rfc/rfc2946.html
+    EncryptionStarted,
+    /// The other party stopped encrypting what it sends, with `ENCRYPT END`
+    EncryptionEnded,
+    /// The START_TLS handshake progressed
+    StartTls(StartTls),
+    /// The other party asked to end the session with LOGOUT
+    LogoutRequested,
+}
+
+impl AuditEvent {
+    /// The audit event for a received subnegotiation, if it is one
+    pub(crate) fn from_subnegotiation(option: Opt, params: &[u8]) -> Option<AuditEvent> {
+        match (option, params) {
+            (Opt::AUTHENTICATION, [AUTH_IS, auth_type, modifiers, ..]) => {
+                Some(AuditEvent::AuthenticationAttempt {
+                    auth_type: *auth_type,
+                    modifiers: *modifiers,
+                })
+            }
+            (Opt::ENCRYPT, [ENCRYPT_START, ..]) => Some(AuditEvent::EncryptionStarted),
+            (Opt::ENCRYPT, [ENCRYPT_END, ..]) => Some(AuditEvent::EncryptionEnded),
+            _ => None,
+        }
+    }
+}
+
+/// Receives the audit trail of a session
+pub trait AuditSink: Send {
+    fn record(&mut self, event: &AuditEvent);
+}
+
+impl<F: FnMut(&AuditEvent) + Send> AuditSink for F {
+    fn record(&mut self, event: &AuditEvent) {
+        self(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{AuditEvent, Side};
+    use crate::option::Opt;
+    use crate::session::{Policy, StartTls, TelnetSession};
+
+    #[test]
+    fn trail() {
+        let policy = Policy::new()
+            .accept_local(Opt::START_TLS)
+            .accept_remote(Opt::AUTHENTICATION)
+            .accept_remote(Opt::ENCRYPT)
+            .accept_remote(Opt::LOGOUT);
+        let mut session = TelnetSession::new(policy);
+        let trail = Arc::new(Mutex::new(Vec::new()));
+        let sink = trail.clone();
+        session.register_audit_sink(move |event: &AuditEvent| {
+            sink.lock().unwrap().push(event.clone())
+        });
+
+        session
+            .feed(b"\xff\xfb\x25\xff\xfa\x25\x00\x05\x02secret\xff\xf0")
+            .for_each(drop);
+        session
+            .feed(b"\xff\xfb\x26\xff\xfa\x26\x03\x01\xff\xf0\xff\xfa\x26\x04\xff\xf0")
+            .for_each(drop);
+        session.feed(b"\xff\xfc\x26\xff\xfb\x12").for_each(drop);
+        session.feed(b"\xff\xfd\x2e").for_each(drop);
+        session.feed(b"\xff\xfa\x2e\x01\xff\xf0").for_each(drop);
+
+        assert_eq!(
+            *trail.lock().unwrap(),
+            &[
+                AuditEvent::Enabled(Opt::AUTHENTICATION, Side::Remote),
+                AuditEvent::AuthenticationAttempt {
+                    auth_type: 5,
+                    modifiers: 2
+                },
+                AuditEvent::Enabled(Opt::ENCRYPT, Side::Remote),
+                AuditEvent::EncryptionStarted,
+                AuditEvent::EncryptionEnded,
+                AuditEvent::Disabled(Opt::ENCRYPT, Side::Remote),
+                AuditEvent::Enabled(Opt::LOGOUT, Side::Remote),
+                AuditEvent::LogoutRequested,
+                AuditEvent::Enabled(Opt::START_TLS, Side::Local),
+                AuditEvent::StartTls(StartTls::Ready),
+            ]
+        );
+    }
+}
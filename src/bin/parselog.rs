@@ -0,0 +1,186 @@
+//! Parse telnet input and log its events
+//!
+//! Reads from stdin, a file, or a connection to a server, and writes one line per event, as text
+//! or as JSON. Run with `--help` for the options.
+extern crate televerknet;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::net::TcpStream;
+use std::process;
+
+use televerknet::dump::Dump;
+use televerknet::encode;
+use televerknet::event::Event;
+use televerknet::option::Opt;
+use televerknet::Parser;
+
+const USAGE: &str = "\
+Usage: parselog [OPTIONS] [FILE]
+
+Parse telnet input from FILE, or stdin if FILE is missing or -, and log its events.
+
+Options:
+  -c, --connect HOST:PORT  Read from a connection to HOST:PORT instead
+  -j, --json               Log events as JSON lines
+  -t, --type TYPE          Only log events of TYPE: data, execute, command, negotiate or
+                           subnegotiate. May be repeated
+  -o, --option OPTION      Only log negotiations and subnegotiations of OPTION, a name such as
+                           NAWS or a number. May be repeated
+  -x, --hex                Log an annotated hex dump of the input instead of events
+  -h, --help               Print this help
+";
+
+const KINDS: &[&str] = &["data", "execute", "command", "negotiate", "subnegotiate"];
+
+#[derive(Default)]
+struct Args {
+    connect: Option<String>,
+    file: Option<String>,
+    json: bool,
+    hex: bool,
+    kinds: Vec<String>,
+    options: Vec<Opt>,
+}
+
+impl Args {
+    fn parse() -> Result<Args, String> {
+        let mut args = Args::default();
+        let mut argv = std::env::args().skip(1);
+        while let Some(arg) = argv.next() {
+            let mut value = |name: &str| {
+                argv.next()
+                    .ok_or_else(|| format!("missing value for {}", name))
+            };
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    print!("{}", USAGE);
+                    process::exit(0);
+                }
+                "-c" | "--connect" => args.connect = Some(value(&arg)?),
+                "-j" | "--json" => args.json = true,
+                "-x" | "--hex" => args.hex = true,
+                "-t" | "--type" => {
+                    let kind = value(&arg)?.to_ascii_lowercase();
+                    if !KINDS.contains(&kind.as_str()) {
+                        return Err(format!("unknown event type {}", kind));
+                    }
+                    args.kinds.push(kind);
+                }
+                "-o" | "--option" => {
+                    let name = value(&arg)?;
+                    let option = name
+                        .parse::<u8>()
+                        .map(Opt::from)
+                        .or_else(|_| name.parse::<Opt>())
+                        .map_err(|_| format!("unknown option {}", name))?;
+                    args.options.push(option);
+                }
+                "-" => args.file = None,
+                arg if arg.starts_with('-') => return Err(format!("unknown flag {}", arg)),
+                arg => args.file = Some(arg.to_string()),
+            }
+        }
+        Ok(args)
+    }
+
+    fn input(&self) -> io::Result<Box<dyn Read>> {
+        match (&self.connect, &self.file) {
+            (Some(address), _) => Ok(Box::new(TcpStream::connect(address)?)),
+            (None, Some(path)) => Ok(Box::new(File::open(path)?)),
+            (None, None) => Ok(Box::new(io::stdin())),
+        }
+    }
+
+    /// Returns true if `event` passes the filters
+    fn wants(&self, event: &Event) -> bool {
+        let (kind, option) = match event {
+            Event::Data(_) => ("data", None),
+            Event::Execute(_) => ("execute", None),
+            Event::Command(_) => ("command", None),
+            Event::Negotiate(_, option) => ("negotiate", Some(*option)),
+            Event::Subnegotiate(option, _) => ("subnegotiate", Some(*option)),
+            Event::LogoutRequested => ("logout", None),
+            Event::EndOfTurn => ("end of turn", None),
+            Event::ProtocolAbuse(_) => ("protocol abuse", None),
+            Event::StatusMismatch(discrepancy) => ("status mismatch", Some(discrepancy.option)),
+            Event::EchoChanged(_) => ("echo changed", None),
+            // Events of later versions, only shown without filters
+            _ => ("other", None),
+        };
+        let kind_matches = self.kinds.is_empty() || self.kinds.iter().any(|k| k == kind);
+        let option_matches =
+            self.options.is_empty() || option.is_some_and(|option| self.options.contains(&option));
+        kind_matches && option_matches
+    }
+
+    fn log<W: Write>(&self, out: &mut W, event: &Event) -> io::Result<()> {
+        if !self.wants(event) {
+            return Ok(());
+        }
+        if self.json {
+            serde_json::to_writer(&mut *out, event)?;
+            writeln!(out)
+        } else {
+            writeln!(out, "{:?}", event)
+        }
+    }
+}
+
+/// Length of the complete sequences at the start of `bytes`
+fn complete_len(bytes: &[u8]) -> usize {
+    let mut len = 0;
+    while let Some(sequence) = encode::sequence_len(&bytes[len..]) {
+        len += sequence;
+    }
+    len
+}
+
+fn run(args: &Args) -> io::Result<()> {
+    let mut input = args.input()?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut parser = Parser::new();
+    let mut events: Vec<Event> = Vec::new();
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buf = [0u8; 2048];
+
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if args.hex {
+            // Hold back a sequence split between reads until it is complete
+            pending.extend_from_slice(&buf[..n]);
+            let len = complete_len(&pending);
+            write!(out, "{}", Dump::new(&pending[..len]))?;
+            pending.drain(..len);
+        } else {
+            parser.advance_bytes(&mut events, &buf[..n]);
+            parser.flush(&mut events);
+            for event in events.drain(..) {
+                args.log(&mut out, &event)?;
+            }
+        }
+        out.flush()?;
+    }
+    write!(out, "{}", Dump::new(&pending))?;
+    out.flush()
+}
+
+fn main() {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("parselog: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(&args) {
+        eprintln!("parselog: {}", err);
+        process::exit(1);
+    }
+}
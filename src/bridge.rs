@@ -0,0 +1,180 @@
+//! WebSocket to telnet bridge
+//!
+//! [`Bridge`] connects a WebSocket-like transport, a `Stream` and `Sink` of binary frames, to a
+//! [`TelnetSession`]. Received frames are fed to the session regardless of where telnet
+//! sequences are split between them, and session output is sent in frames which never end in the
+//! middle of a telnet sequence, so clients parsing frame by frame see whole commands.
+//!
+//! [`Bridge`]: struct.Bridge.html
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+
+use crate::encode;
+use crate::event::Event;
+use crate::session::TelnetSession;
+
+const DEFAULT_MAX_FRAME: usize = 16 * 1024;
+
+/// Pumps frames between a WebSocket-like transport and a telnet session
+pub struct Bridge<S> {
+    frames: S,
+    session: TelnetSession,
+    max_frame: usize,
+}
+
+impl<S, E> Bridge<S>
+where
+    S: Stream<Item = Result<Vec<u8>, E>> + Sink<Vec<u8>, Error = E> + Unpin,
+{
+    pub fn new(frames: S, session: TelnetSession) -> Bridge<S> {
+        Bridge {
+            frames,
+            session,
+            max_frame: DEFAULT_MAX_FRAME,
+        }
+    }
+
+    /// Limit the size of sent frames; a single sequence longer than the limit is still sent whole
+    pub fn max_frame(mut self, max_frame: usize) -> Bridge<S> {
+        self.max_frame = max_frame;
+        self
+    }
+
+    pub fn session(&self) -> &TelnetSession {
+        &self.session
+    }
+
+    /// The session, e.g. to queue data. Queued output is sent by [`flush`].
+    ///
+    /// [`flush`]: #method.flush
+    pub fn session_mut(&mut self) -> &mut TelnetSession {
+        &mut self.session
+    }
+
+    /// Receive the next frame and feed it to the session
+    ///
+    /// Returns the events found in the frame, or `None` when the transport is closed. Answers
+    /// to negotiation are queued on the session and sent by [`flush`].
+    ///
+    /// [`flush`]: #method.flush
+    pub async fn recv(&mut self) -> Option<Result<Vec<Event>, E>> {
+        match self.frames.next().await? {
+            Ok(frame) => Some(Ok(self.session.feed(&frame).collect())),
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    /// Send all session output, split into frames at telnet sequence boundaries
+    pub async fn flush(&mut self) -> Result<(), E> {
+        while !self.session.output().is_empty() {
+            let n = frame_len(self.session.output(), self.max_frame);
+            let frame = self.session.output()[..n].to_vec();
+            self.frames.feed(frame).await?;
+            self.session.consume_output(n);
+        }
+        self.frames.flush().await
+    }
+
+    pub fn into_inner(self) -> (S, TelnetSession) {
+        (self.frames, self.session)
+    }
+}
+
+/// Length of the longest run of whole sequences in `output` of at most `max` bytes
+fn frame_len(output: &[u8], max: usize) -> usize {
+    let mut len = 0;
+    while let Some(n) = encode::sequence_len(&output[len..]) {
+        if len > 0 && len + n > max {
+            break;
+        }
+        len += n;
+    }
+    // Output is produced by the session, so it never ends within a sequence
+    if len == 0 {
+        output.len()
+    } else {
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_len, Bridge};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::session::TelnetSession;
+    use futures::{Sink, Stream};
+    use std::collections::VecDeque;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    #[derive(Default)]
+    struct Frames {
+        incoming: VecDeque<Vec<u8>>,
+        sent: Vec<Vec<u8>>,
+    }
+
+    impl Stream for Frames {
+        type Item = Result<Vec<u8>, ()>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(self.incoming.pop_front().map(Ok))
+        }
+    }
+
+    impl Sink<Vec<u8>> for Frames {
+        type Error = ();
+
+        fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, frame: Vec<u8>) -> Result<(), ()> {
+            self.sent.push(frame);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn frames_split_at_sequences() {
+        assert_eq!(frame_len(&[b'a', 255, 251, 1, b'b'], 3), 1);
+        assert_eq!(frame_len(&[255, 251, 1, b'b'], 2), 3);
+        assert_eq!(frame_len(&[255, 251, 1, b'b'], 4), 4);
+    }
+
+    #[test]
+    fn pumps_frames() {
+        let mut frames = Frames::default();
+        // DO NAWS is split over two frames
+        frames.incoming.push_back(vec![b'h', b'i', 255, 253]);
+        frames.incoming.push_back(vec![31]);
+        let mut bridge = Bridge::new(frames, TelnetSession::default()).max_frame(3);
+
+        futures::executor::block_on(async {
+            assert_eq!(
+                bridge.recv().await.unwrap().unwrap(),
+                &[Event::Data(b"hi".to_vec())]
+            );
+            assert_eq!(
+                bridge.recv().await.unwrap().unwrap(),
+                &[Event::Negotiate(Command::DO, Opt::NAWS)]
+            );
+            assert!(bridge.recv().await.is_none());
+
+            bridge.session_mut().send_data(b"ok");
+            bridge.flush().await.unwrap();
+        });
+
+        let (frames, _) = bridge.into_inner();
+        assert_eq!(frames.sent, &[vec![255, 252, 31], b"ok".to_vec()]);
+    }
+}
@@ -0,0 +1,182 @@
+//! `bytes` integration
+//!
+//! [`Parser::advance_buf`] parses from any `Buf`, and [`put_event`] and [`put_message`] encode
+//! into a `BytesMut`. [`parse_bytes`] avoids copying data: its [`BytesEvent::Data`] events are
+//! `Bytes` slices sharing the memory of the input.
+//!
+//! [`Parser::advance_buf`]: ../struct.Parser.html#method.advance_buf
+//! [`put_event`]: fn.put_event.html
+//! [`put_message`]: fn.put_message.html
+//! [`parse_bytes`]: fn.parse_bytes.html
+//! [`BytesEvent::Data`]: enum.BytesEvent.html#variant.Data
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::event::Event;
+use crate::message::OutMessage;
+use crate::{DataEnd, ParseError, Parser, Perform};
+
+impl Parser {
+    /// Advance the parser over the bytes remaining in `buf`, consuming them
+    ///
+    /// Each chunk of `buf` is parsed with [`advance_bytes`]. Stops early when
+    /// [`Perform::should_break`] returns true, leaving the rest in `buf`. Returns the number of
+    /// bytes consumed.
+    ///
+    /// [`advance_bytes`]: #method.advance_bytes
+    /// [`Perform::should_break`]: trait.Perform.html#method.should_break
+    pub fn advance_buf<P, B>(&mut self, performer: &mut P, buf: &mut B) -> usize
+    where
+        P: Perform + ?Sized,
+        B: Buf + ?Sized,
+    {
+        let mut consumed = 0;
+        while buf.has_remaining() {
+            let chunk = buf.chunk();
+            let len = chunk.len();
+            let n = self.advance_bytes(performer, chunk);
+            buf.advance(n);
+            consumed += n;
+            if n < len {
+                break;
+            }
+        }
+        consumed
+    }
+}
+
+/// An event with data shared with the parsed `Bytes`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BytesEvent {
+    /// Data, a slice of the input unless it was collected by the parser, e.g. across calls
+    Data(Bytes),
+    /// Any other event
+    Other(Event),
+}
+
+/// Parse `input`, appending events to `events`
+///
+/// Data found in `input` is passed on without copying it, and may be split over several `Data`
+/// events. Data is flushed at the end of `input`, as it would be per read from a socket.
+pub fn parse_bytes(parser: &mut Parser, input: &Bytes, events: &mut Vec<BytesEvent>) {
+    let mut collector = Collector {
+        input,
+        events,
+        other: Vec::new(),
+    };
+    parser.advance_bytes(&mut collector, input);
+    parser.flush(&mut collector);
+}
+
+/// Append the wire representation of `event` to `dst`
+pub fn put_event(event: &Event, dst: &mut BytesMut) {
+    event.encode_into(dst);
+}
+
+/// Append the wire representation of `message` to `dst`
+pub fn put_message(message: &OutMessage, dst: &mut BytesMut) {
+    message.encode_into(dst);
+}
+
+struct Collector<'a> {
+    input: &'a Bytes,
+    events: &'a mut Vec<BytesEvent>,
+    other: Vec<Event>,
+}
+
+impl<'a> Collector<'a> {
+    fn push_other(&mut self) {
+        self.events
+            .extend(self.other.drain(..).map(BytesEvent::Other));
+    }
+}
+
+impl<'a> Perform for Collector<'a> {
+    fn data(&mut self, intermediates: &[u8], _ignore: bool, _end: DataEnd) {
+        let input = self.input.as_ptr() as usize..self.input.as_ptr() as usize + self.input.len();
+        let data = intermediates.as_ptr() as usize;
+        let bytes = if input.contains(&data) {
+            self.input.slice_ref(intermediates)
+        } else {
+            Bytes::copy_from_slice(intermediates)
+        };
+        self.events.push(BytesEvent::Data(bytes));
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.other.execute(byte);
+        self.push_other();
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        self.other.iac_dispatch(byte);
+        // An escaped IAC is data
+        if let Some(Event::Data(data)) = self.other.pop() {
+            self.events.push(BytesEvent::Data(Bytes::from(data)));
+        }
+        self.push_other();
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        self.other.sub_dispatch(subs);
+        self.push_other();
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        self.other.negotiate_dispatch(cmd, opt);
+        self.push_other();
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+    fn compress_dispatch(&mut self, _state: u8) {}
+
+    fn error(&mut self, error: ParseError) {
+        self.other.error(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::{Buf, Bytes, BytesMut};
+
+    use super::{parse_bytes, put_event, BytesEvent};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::Parser;
+
+    #[test]
+    fn zero_copy() {
+        let input = Bytes::from_static(b"hello\xff\xfb\x01world\xff\xff");
+        let mut parser = Parser::new();
+        let mut events = Vec::new();
+        parse_bytes(&mut parser, &input, &mut events);
+        assert_eq!(
+            events,
+            &[
+                BytesEvent::Data(Bytes::from_static(b"hello")),
+                BytesEvent::Other(Event::Negotiate(Command::WILL, Opt::ECHO)),
+                BytesEvent::Data(Bytes::from_static(b"world")),
+                BytesEvent::Data(Bytes::from_static(b"\xff")),
+            ]
+        );
+        if let BytesEvent::Data(hello) = &events[0] {
+            assert_eq!(hello.as_ptr(), input.as_ptr());
+        }
+
+        let mut buf = BytesMut::new();
+        put_event(&Event::Negotiate(Command::DO, Opt::NAWS), &mut buf);
+        let mut chained = Bytes::from_static(b"a").chain(buf.freeze());
+        let mut collected: Vec<Event> = Vec::new();
+        assert_eq!(parser.advance_buf(&mut collected, &mut chained), 4);
+        assert!(!chained.has_remaining());
+        assert_eq!(
+            collected,
+            &[
+                Event::Data(b"a".to_vec()),
+                Event::Negotiate(Command::DO, Opt::NAWS),
+            ]
+        );
+    }
+}
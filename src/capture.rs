@@ -0,0 +1,366 @@
+//! Capture of raw and parsed input, e.g. for honeypots
+//!
+//! [`Capture`] records every read with the time it arrived, together with how a strict parser
+//! interpreted it: the events found and the malformed sequences, flagged as such. Both views come
+//! from one pass, so they always agree. [`CaptureReader`] reads a capture back.
+//!
+//! A capture starts with the magic bytes `TVKC` and a format version byte, currently 1. Then
+//! follows an entry per read, made of the time since the start of the capture in microseconds as
+//! a little endian `u64`, the length of the raw bytes as a little endian `u32`, the raw bytes, the
+//! number of interpretations as a little endian `u32`, and the interpretations. An
+//! interpretation is a tag byte, the length of its payload as a little endian `u32` and the
+//! payload:
+//!
+//! | Tag | Interpretation | Payload |
+//! |-----|----------------|---------|
+//! | 0 | Data | The data |
+//! | 1 | Control byte | The byte |
+//! | 2 | Command | The command byte |
+//! | 3 | Negotiation | The command and option bytes |
+//! | 4 | Subnegotiation | The option byte and the parameters |
+//! | 128 | Data truncated | Nothing |
+//! | 129 | Subnegotiation truncated | The option byte |
+//! | 130 | Invalid command | The byte after IAC |
+//! | 131 | Subnegotiation without option | Nothing |
+//! | 132 | Subnegotiation interrupted by a command | Option and command |
+//!
+//! [`Capture`]: struct.Capture.html
+//! [`CaptureReader`]: struct.CaptureReader.html
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::option::Opt;
+use crate::{DataEnd, ParseError, Parser, ParserBuilder, Perform};
+
+const MAGIC: &[u8; 4] = b"TVKC";
+const VERSION: u8 = 1;
+
+const DATA: u8 = 0;
+const EXECUTE: u8 = 1;
+const COMMAND: u8 = 2;
+const NEGOTIATE: u8 = 3;
+const SUBNEGOTIATE: u8 = 4;
+const DATA_TRUNCATED: u8 = 128;
+const SUBNEGOTIATION_TRUNCATED: u8 = 129;
+const INVALID_COMMAND: u8 = 130;
+const MISSING_OPTION: u8 = 131;
+const INTERRUPTED_SUBNEGOTIATION: u8 = 132;
+
+/// How part of a read was interpreted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Interpretation {
+    /// A well formed event
+    Event(Event),
+    /// A malformed sequence
+    Malformed(ParseError),
+}
+
+/// One read of a capture
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureEntry {
+    /// Time since the start of the capture
+    pub offset: Duration,
+    pub raw: Vec<u8>,
+    pub parsed: Vec<Interpretation>,
+}
+
+/// Writes raw input and its interpretation to a capture
+pub struct Capture<W: Write> {
+    writer: W,
+    start: Instant,
+    parser: Parser,
+    collector: Collector,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> Capture<W> {
+    /// Start a capture, offsets are measured from now
+    ///
+    /// Writes the header of the capture to `writer`.
+    pub fn new(mut writer: W) -> io::Result<Capture<W>> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(Capture {
+            writer,
+            start: Instant::now(),
+            parser: ParserBuilder::new().strict(true).build(),
+            collector: Collector::default(),
+            buf: Vec::new(),
+        })
+    }
+
+    /// Parse with `parser` instead of the default strict parser
+    pub fn parser(mut self, parser: Parser) -> Capture<W> {
+        self.parser = parser;
+        self
+    }
+
+    /// Record bytes received now, returning their interpretation
+    pub fn record(&mut self, bytes: &[u8]) -> io::Result<&[Interpretation]> {
+        let offset = self.start.elapsed();
+        self.write_entry(offset, bytes)
+    }
+
+    /// Record bytes with an explicit offset, e.g. when converting other captures
+    pub fn write_entry(&mut self, offset: Duration, bytes: &[u8]) -> io::Result<&[Interpretation]> {
+        let len = u32::try_from(bytes.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "entry too large"))?;
+        self.collector.parsed.clear();
+        self.parser.advance_bytes(&mut self.collector, bytes);
+        self.parser.flush(&mut self.collector);
+
+        let micros = u64::try_from(offset.as_micros()).unwrap_or(u64::MAX);
+        self.buf.clear();
+        self.buf.extend_from_slice(&micros.to_le_bytes());
+        self.buf.extend_from_slice(&len.to_le_bytes());
+        self.buf.extend_from_slice(bytes);
+        let count = self.collector.parsed.len() as u32;
+        self.buf.extend_from_slice(&count.to_le_bytes());
+        for interpretation in &self.collector.parsed {
+            encode_interpretation(interpretation, &mut self.buf);
+        }
+        self.writer.write_all(&self.buf)?;
+        Ok(&self.collector.parsed)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn encode_interpretation(interpretation: &Interpretation, dst: &mut Vec<u8>) {
+    let mut item = |tag: u8, payload: &[&[u8]]| {
+        let len: usize = payload.iter().map(|part| part.len()).sum();
+        dst.push(tag);
+        dst.extend_from_slice(&(len as u32).to_le_bytes());
+        for part in payload {
+            dst.extend_from_slice(part);
+        }
+    };
+    match interpretation {
+        Interpretation::Event(Event::Data(data)) => item(DATA, &[data]),
+        Interpretation::Event(Event::Execute(byte)) => item(EXECUTE, &[&[*byte]]),
+        Interpretation::Event(Event::Command(command)) => item(COMMAND, &[&[command.as_u8()]]),
+        Interpretation::Event(Event::Negotiate(command, option)) => {
+            item(NEGOTIATE, &[&[command.as_u8(), option.as_u8()]])
+        }
+        Interpretation::Event(Event::Subnegotiate(option, params)) => {
+            item(SUBNEGOTIATE, &[&[option.as_u8()], params])
+        }
+        // Never produced by the parser
+        Interpretation::Event(
+            Event::LogoutRequested
+            | Event::EndOfTurn
+            | Event::ProtocolAbuse(_)
+            | Event::StatusMismatch(_)
+            | Event::EchoChanged(_),
+        ) => (),
+        Interpretation::Malformed(ParseError::DataTruncated) => item(DATA_TRUNCATED, &[]),
+        Interpretation::Malformed(ParseError::SubnegotiationTruncated { option }) => {
+            item(SUBNEGOTIATION_TRUNCATED, &[&[*option]])
+        }
+        Interpretation::Malformed(ParseError::InvalidCommand { byte }) => {
+            item(INVALID_COMMAND, &[&[*byte]])
+        }
+        Interpretation::Malformed(ParseError::MissingOption) => item(MISSING_OPTION, &[]),
+        Interpretation::Malformed(ParseError::InterruptedSubnegotiation { option, command }) => {
+            item(INTERRUPTED_SUBNEGOTIATION, &[&[*option, *command]])
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn decode_interpretation(tag: u8, payload: Vec<u8>) -> io::Result<Interpretation> {
+    let command = |byte: u8| Command::from_u8(byte).map_err(|_| invalid("invalid command"));
+    let interpretation = match (tag, payload.as_slice()) {
+        (DATA, _) => Interpretation::Event(Event::Data(payload)),
+        (EXECUTE, [byte]) => Interpretation::Event(Event::Execute(*byte)),
+        (COMMAND, [byte]) => Interpretation::Event(Event::Command(command(*byte)?)),
+        (NEGOTIATE, [byte, option]) => {
+            Interpretation::Event(Event::Negotiate(command(*byte)?, Opt::from_u8(*option)))
+        }
+        (SUBNEGOTIATE, [option, params @ ..]) => {
+            Interpretation::Event(Event::Subnegotiate(Opt::from_u8(*option), params.to_vec()))
+        }
+        (DATA_TRUNCATED, []) => Interpretation::Malformed(ParseError::DataTruncated),
+        (SUBNEGOTIATION_TRUNCATED, [option]) => {
+            Interpretation::Malformed(ParseError::SubnegotiationTruncated { option: *option })
+        }
+        (INVALID_COMMAND, [byte]) => {
+            Interpretation::Malformed(ParseError::InvalidCommand { byte: *byte })
+        }
+        (MISSING_OPTION, []) => Interpretation::Malformed(ParseError::MissingOption),
+        (INTERRUPTED_SUBNEGOTIATION, [option, command]) => {
+            Interpretation::Malformed(ParseError::InterruptedSubnegotiation {
+                option: *option,
+                command: *command,
+            })
+        }
+        _ => return Err(invalid("invalid interpretation")),
+    };
+    Ok(interpretation)
+}
+
+/// Reads a capture back
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Read the header of a capture
+    pub fn new(mut reader: R) -> io::Result<CaptureReader<R>> {
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a capture"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid("unsupported capture version"));
+        }
+        Ok(CaptureReader { reader })
+    }
+
+    /// Read the next entry, or `None` at the end of the capture
+    pub fn next_entry(&mut self) -> io::Result<Option<CaptureEntry>> {
+        let mut micros = [0u8; 8];
+        match self.reader.read_exact(&mut micros) {
+            Ok(()) => (),
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let raw = self.read_bytes()?;
+        let count = self.read_u32()?;
+        let mut parsed = Vec::new();
+        for _ in 0..count {
+            let mut tag = [0u8; 1];
+            self.reader.read_exact(&mut tag)?;
+            let payload = self.read_bytes()?;
+            parsed.push(decode_interpretation(tag[0], payload)?);
+        }
+        Ok(Some(CaptureEntry {
+            offset: Duration::from_micros(u64::from_le_bytes(micros)),
+            raw,
+            parsed,
+        }))
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn read_bytes(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.read_u32()?;
+        let mut bytes = Vec::new();
+        // Read through take, so a corrupt length doesn't allocate more than the capture holds
+        (&mut self.reader)
+            .take(u64::from(len))
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != len as usize {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(bytes)
+    }
+}
+
+/// Collects events and errors in the order the parser finds them
+#[derive(Default)]
+struct Collector {
+    events: Vec<Event>,
+    parsed: Vec<Interpretation>,
+}
+
+impl Collector {
+    fn push_events(&mut self) {
+        self.parsed
+            .extend(self.events.drain(..).map(Interpretation::Event));
+    }
+}
+
+impl Perform for Collector {
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        self.events.data(intermediates, ignore, end);
+        self.push_events();
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.events.execute(byte);
+        self.push_events();
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        self.events.iac_dispatch(byte);
+        self.push_events();
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        self.events.sub_dispatch(subs);
+        self.push_events();
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        self.events.negotiate_dispatch(cmd, opt);
+        self.push_events();
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+    fn compress_dispatch(&mut self, _state: u8) {}
+
+    fn error(&mut self, error: ParseError) {
+        self.parsed.push(Interpretation::Malformed(error));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Capture, CaptureEntry, CaptureReader, Interpretation};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::ParseError;
+
+    #[test]
+    fn capture_and_read() {
+        let mut capture = Capture::new(Vec::new()).unwrap();
+        let parsed = capture
+            .write_entry(Duration::from_millis(3), b"root\xff\x01\xff\xfd\x18")
+            .unwrap()
+            .to_vec();
+        capture.record(b"\xff\xfa\xff\xf0").unwrap();
+        let bytes = capture.into_inner();
+        assert_eq!(&bytes[..5], b"TVKC\x01");
+
+        let mut reader = CaptureReader::new(&bytes[..]).unwrap();
+        let entry = reader.next_entry().unwrap();
+        assert_eq!(
+            entry,
+            Some(CaptureEntry {
+                offset: Duration::from_millis(3),
+                raw: b"root\xff\x01\xff\xfd\x18".to_vec(),
+                parsed: vec![
+                    Interpretation::Event(Event::Data(b"root".to_vec())),
+                    Interpretation::Malformed(ParseError::InvalidCommand { byte: 1 }),
+                    Interpretation::Event(Event::Negotiate(Command::DO, Opt::TTYPE)),
+                ],
+            })
+        );
+        assert_eq!(parsed, entry.unwrap().parsed);
+        assert_eq!(
+            reader.next_entry().unwrap().unwrap().parsed,
+            &[Interpretation::Malformed(ParseError::MissingOption)]
+        );
+        assert_eq!(reader.next_entry().unwrap(), None);
+        assert!(CaptureReader::new(&b"TVKR\x01"[..]).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! Forwarding events to a channel
+//!
+//! [`ChannelPerformer`] is a performer which sends the events it receives as owned [`Event`]s
+//! into a channel, so that parsing on one thread and handling the events on another are
+//! decoupled. It sends into anything implementing [`EventSender`]: the `std::sync::mpsc`
+//! senders, the `tokio::sync::mpsc` senders with the `tokio` feature, or e.g. a crossbeam sender
+//! wrapped by the application.
+//!
+//! When a bounded channel is full, the [`Backpressure`] of the performer decides whether parsing
+//! waits for room, holding up reading from the connection, or drops the event. Once the
+//! receiver is gone, parsing stops: [`Parser::advance_bytes`] returns right after the event
+//! which could not be sent.
+//!
+//! [`ChannelPerformer`]: struct.ChannelPerformer.html
+//! [`Event`]: ../event/enum.Event.html
+//! [`EventSender`]: trait.EventSender.html
+//! [`Backpressure`]: enum.Backpressure.html
+//! [`Parser::advance_bytes`]: ../struct.Parser.html#method.advance_bytes
+use std::sync::mpsc;
+
+use crate::event::Event;
+use crate::{DataEnd, ParseError, Perform};
+
+/// What [`ChannelPerformer`] does with an event when the channel is full
+///
+/// [`ChannelPerformer`]: struct.ChannelPerformer.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Wait until there is room, blocking the parsing thread
+    Block,
+    /// Drop the event and count it, see [`ChannelPerformer::dropped`]
+    ///
+    /// [`ChannelPerformer::dropped`]: struct.ChannelPerformer.html#method.dropped
+    Drop,
+}
+
+/// Why an event was not sent
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SendError {
+    /// The channel is full, only returned when not blocking
+    Full,
+    /// The receiver is gone
+    Disconnected,
+}
+
+/// The sending half of a channel of events
+pub trait EventSender {
+    /// Send `event`, waiting for room in a bounded channel if `block`
+    fn send_event(&mut self, event: Event, block: bool) -> Result<(), SendError>;
+}
+
+impl EventSender for mpsc::Sender<Event> {
+    fn send_event(&mut self, event: Event, _block: bool) -> Result<(), SendError> {
+        self.send(event).map_err(|_| SendError::Disconnected)
+    }
+}
+
+impl EventSender for mpsc::SyncSender<Event> {
+    fn send_event(&mut self, event: Event, block: bool) -> Result<(), SendError> {
+        if block {
+            return self.send(event).map_err(|_| SendError::Disconnected);
+        }
+        self.try_send(event).map_err(|err| match err {
+            mpsc::TrySendError::Full(_) => SendError::Full,
+            mpsc::TrySendError::Disconnected(_) => SendError::Disconnected,
+        })
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl EventSender for tokio::sync::mpsc::UnboundedSender<Event> {
+    fn send_event(&mut self, event: Event, _block: bool) -> Result<(), SendError> {
+        self.send(event).map_err(|_| SendError::Disconnected)
+    }
+}
+
+/// Blocking uses `blocking_send`, which panics when called from within the tokio runtime: parse
+/// on a thread of its own, e.g. with `spawn_blocking`, or use [`Backpressure::Drop`].
+///
+/// [`Backpressure::Drop`]: enum.Backpressure.html#variant.Drop
+#[cfg(feature = "tokio")]
+impl EventSender for tokio::sync::mpsc::Sender<Event> {
+    fn send_event(&mut self, event: Event, block: bool) -> Result<(), SendError> {
+        use tokio::sync::mpsc::error::TrySendError;
+
+        if block {
+            return self
+                .blocking_send(event)
+                .map_err(|_| SendError::Disconnected);
+        }
+        self.try_send(event).map_err(|err| match err {
+            TrySendError::Full(_) => SendError::Full,
+            TrySendError::Closed(_) => SendError::Disconnected,
+        })
+    }
+}
+
+/// Sends parser events into a channel
+///
+/// Each callback is sent as an event of its own, so data is not merged as in a `Vec<Event>`.
+/// Errors are not forwarded.
+pub struct ChannelPerformer<S> {
+    sender: S,
+    backpressure: Backpressure,
+    events: Vec<Event>,
+    dropped: usize,
+    disconnected: bool,
+}
+
+impl<S: EventSender> ChannelPerformer<S> {
+    /// Send into `sender`, waiting for room when the channel is full
+    pub fn new(sender: S) -> ChannelPerformer<S> {
+        ChannelPerformer {
+            sender,
+            backpressure: Backpressure::Block,
+            events: Vec::new(),
+            dropped: 0,
+            disconnected: false,
+        }
+    }
+
+    /// Set what happens to events when the channel is full
+    pub fn backpressure(mut self, backpressure: Backpressure) -> ChannelPerformer<S> {
+        self.backpressure = backpressure;
+        self
+    }
+
+    /// Number of events dropped because the channel was full
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Whether the receiver is gone
+    pub fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.sender
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.sender
+    }
+
+    pub fn into_inner(self) -> S {
+        self.sender
+    }
+
+    /// Send the events collected by the last callback
+    fn send(&mut self) {
+        let block = self.backpressure == Backpressure::Block;
+        for event in self.events.drain(..) {
+            if self.disconnected {
+                break;
+            }
+            match self.sender.send_event(event, block) {
+                Ok(()) => (),
+                Err(SendError::Full) => self.dropped += 1,
+                Err(SendError::Disconnected) => self.disconnected = true,
+            }
+        }
+    }
+}
+
+impl<S: EventSender> Perform for ChannelPerformer<S> {
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        Perform::data(&mut self.events, intermediates, ignore, end);
+        self.send();
+    }
+
+    fn execute(&mut self, byte: u8) {
+        Perform::execute(&mut self.events, byte);
+        self.send();
+    }
+
+    fn error(&mut self, _error: ParseError) {}
+
+    fn should_break(&mut self) -> bool {
+        self.disconnected
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        Perform::iac_dispatch(&mut self.events, byte);
+        self.send();
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        Perform::sub_dispatch(&mut self.events, subs);
+        self.send();
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        Perform::negotiate_dispatch(&mut self.events, cmd, opt);
+        self.send();
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+    fn compress_dispatch(&mut self, _state: u8) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::{Backpressure, ChannelPerformer};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::Parser;
+
+    #[test]
+    fn backpressure() {
+        let (sender, receiver) = mpsc::sync_channel(8);
+        let mut performer = ChannelPerformer::new(sender);
+        let mut parser = Parser::new();
+        parser.advance_bytes(&mut performer, b"hi\r\xff\xfb\x18");
+        parser.flush(&mut performer);
+        assert_eq!(
+            receiver.try_iter().collect::<Vec<_>>(),
+            &[
+                Event::Data(b"hi".to_vec()),
+                Event::Execute(b'\r'),
+                Event::Negotiate(Command::WILL, Opt::TTYPE),
+            ]
+        );
+
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let mut performer = ChannelPerformer::new(sender).backpressure(Backpressure::Drop);
+        parser.advance_bytes(&mut performer, b"\r\n\xff\xf9");
+        assert_eq!(performer.dropped(), 2);
+        assert_eq!(receiver.recv(), Ok(Event::Execute(b'\r')));
+
+        // Parsing stops once the receiver is gone
+        drop(receiver);
+        assert_eq!(parser.advance_bytes(&mut performer, b"\r\n"), 1);
+        assert!(performer.is_disconnected());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_channel() {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+        let mut performer = ChannelPerformer::new(sender).backpressure(Backpressure::Drop);
+        Parser::new().advance_bytes(&mut performer, b"\r\n");
+        assert_eq!(performer.dropped(), 1);
+        assert_eq!(receiver.try_recv(), Ok(Event::Execute(b'\r')));
+    }
+}
@@ -0,0 +1,212 @@
+//! CHARSET as described in [RFC 2066]
+//!
+//! [`Message`] parses and encodes the parameters of `IAC SB CHARSET ... IAC SE`. With the
+//! `encoding` feature, [`Transcoder`] converts data between the negotiated character set and
+//! UTF-8.
+//!
+//! [`Message`]: enum.Message.html
+//! [`Transcoder`]: struct.Transcoder.html
+//! [RFC 2066]: https://www.rfc-editor.org/rfc/rfc2066.html
+#[cfg(feature = "encoding")]
+use crate::event::Event;
+
+const REQUEST: u8 = 1;
+const ACCEPTED: u8 = 2;
+const REJECTED: u8 = 3;
+const TTABLE_REJECTED: u8 = 5;
+const TTABLE_ACK: u8 = 6;
+const TTABLE_NAK: u8 = 7;
+
+/// Marks a request which also offers translation tables
+const TTABLE: &[u8] = b"[TTABLE]";
+
+/// A CHARSET subnegotiation
+///
+/// Translation tables themselves (`TTABLE-IS`) are not supported and are not parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// `REQUEST [TTABLE] <sep> <charset> <sep> <charset> ...`
+    Request {
+        ttable: bool,
+        charsets: Vec<Vec<u8>>,
+    },
+    /// `ACCEPTED <charset>`
+    Accepted(Vec<u8>),
+    /// `REJECTED`
+    Rejected,
+    /// `TTABLE-REJECTED`
+    TtableRejected,
+    /// `TTABLE-ACK`
+    TtableAck,
+    /// `TTABLE-NAK`
+    TtableNak,
+}
+
+impl Message {
+    /// Parse subnegotiation parameters, without the option byte
+    pub fn parse(params: &[u8]) -> Option<Message> {
+        match params {
+            [REQUEST, rest @ ..] => {
+                let (ttable, rest) = if rest.starts_with(TTABLE) {
+                    // The version of the translation table follows the marker
+                    (true, rest.get(TTABLE.len() + 1..)?)
+                } else {
+                    (false, rest)
+                };
+                let (sep, list) = rest.split_first()?;
+                let charsets = list
+                    .split(|b| b == sep)
+                    .filter(|name| !name.is_empty())
+                    .map(|name| name.to_vec())
+                    .collect();
+                Some(Message::Request { ttable, charsets })
+            }
+            [ACCEPTED, charset @ ..] => Some(Message::Accepted(charset.to_vec())),
+            [REJECTED] => Some(Message::Rejected),
+            [TTABLE_REJECTED] => Some(Message::TtableRejected),
+            [TTABLE_ACK] => Some(Message::TtableAck),
+            [TTABLE_NAK] => Some(Message::TtableNak),
+            // TTABLE-IS (4) is not supported
+            _ => None,
+        }
+    }
+
+    /// Append the subnegotiation parameters, without the option byte, to `dst`
+    ///
+    /// A request offering translation tables is sent with version 1.
+    pub fn encode_into(&self, dst: &mut Vec<u8>) {
+        match self {
+            Message::Request { ttable, charsets } => {
+                dst.push(REQUEST);
+                if *ttable {
+                    dst.extend_from_slice(TTABLE);
+                    dst.push(1);
+                }
+                for charset in charsets {
+                    dst.push(b';');
+                    dst.extend_from_slice(charset);
+                }
+            }
+            Message::Accepted(charset) => {
+                dst.push(ACCEPTED);
+                dst.extend_from_slice(charset);
+            }
+            Message::Rejected => dst.push(REJECTED),
+            Message::TtableRejected => dst.push(TTABLE_REJECTED),
+            Message::TtableAck => dst.push(TTABLE_ACK),
+            Message::TtableNak => dst.push(TTABLE_NAK),
+        }
+    }
+}
+
+/// Converts data between a character set and UTF-8
+///
+/// Character sets are looked up by their WHATWG labels, e.g. `ISO-8859-1`, `IBM866` or `Big5`.
+/// Incoming bytes split between reads are decoded once complete. Characters which can not be
+/// encoded are sent as HTML numeric character references.
+#[cfg(feature = "encoding")]
+pub struct Transcoder {
+    encoding: &'static encoding_rs::Encoding,
+    decoder: encoding_rs::Decoder,
+}
+
+#[cfg(feature = "encoding")]
+impl Transcoder {
+    /// Transcoder for the character set named `label`, e.g. from [`Message::Accepted`]
+    ///
+    /// [`Message::Accepted`]: enum.Message.html#variant.Accepted
+    pub fn for_label(label: &[u8]) -> Option<Transcoder> {
+        let encoding = encoding_rs::Encoding::for_label(label)?;
+        Some(Transcoder {
+            encoding,
+            decoder: encoding.new_decoder_without_bom_handling(),
+        })
+    }
+
+    /// Canonical name of the character set
+    pub fn name(&self) -> &'static str {
+        self.encoding.name()
+    }
+
+    /// Decode `bytes` to UTF-8, keeping an incomplete character for the next call
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        let capacity = self
+            .decoder
+            .max_utf8_buffer_length(bytes.len())
+            .unwrap_or(bytes.len());
+        let mut text = String::with_capacity(capacity);
+        let _ = self.decoder.decode_to_string(bytes, &mut text, false);
+        text
+    }
+
+    /// Decode the data in `event`, returning `None` for events without data
+    ///
+    /// Bytes above 0x7F reported as [`Event::Execute`] are treated as data, as servers rarely
+    /// negotiate TRANSMIT-BINARY for them.
+    ///
+    /// [`Event::Execute`]: ../event/enum.Event.html#variant.Execute
+    pub fn push(&mut self, event: &Event) -> Option<String> {
+        match event {
+            Event::Data(data) => Some(self.decode(data)),
+            Event::Execute(byte) if *byte >= 0x80 => Some(self.decode(&[*byte])),
+            _ => None,
+        }
+    }
+
+    /// Encode `text` in the character set
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        self.encoding.encode(text).0.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Message;
+
+    #[test]
+    fn messages() {
+        assert_eq!(
+            Message::parse(b"\x01;UTF-8;ISO-8859-1"),
+            Some(Message::Request {
+                ttable: false,
+                charsets: vec![b"UTF-8".to_vec(), b"ISO-8859-1".to_vec()],
+            })
+        );
+        assert_eq!(
+            Message::parse(b"\x01[TTABLE]\x01 UTF-8"),
+            Some(Message::Request {
+                ttable: true,
+                charsets: vec![b"UTF-8".to_vec()],
+            })
+        );
+        assert_eq!(
+            Message::parse(b"\x02CP437"),
+            Some(Message::Accepted(b"CP437".to_vec()))
+        );
+
+        let mut params = Vec::new();
+        Message::Request {
+            ttable: false,
+            charsets: vec![b"UTF-8".to_vec()],
+        }
+        .encode_into(&mut params);
+        assert_eq!(params, b"\x01;UTF-8");
+    }
+
+    #[cfg(feature = "encoding")]
+    #[test]
+    fn transcode() {
+        use super::Transcoder;
+        use crate::event::Event;
+
+        let mut transcoder = Transcoder::for_label(b"ISO-8859-1").unwrap();
+        assert_eq!(transcoder.name(), "windows-1252");
+        assert_eq!(transcoder.push(&Event::Execute(0xe4)).unwrap(), "ä");
+        assert_eq!(transcoder.encode("ä"), &[0xe4]);
+
+        let mut transcoder = Transcoder::for_label(b"Big5").unwrap();
+        // A two byte character split between reads
+        assert_eq!(transcoder.decode(&[0xa4]), "");
+        assert_eq!(transcoder.decode(&[0x40]), "一");
+    }
+}
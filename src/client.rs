@@ -0,0 +1,531 @@
+//! A telnet client performing the usual handshake
+//!
+//! [`TelnetClient`] wraps a blocking socket and a [`TelnetSession`]. Given a [`ClientConfig`], it
+//! negotiates SGA, and answers the server for TTYPE, NAWS, NEW-ENVIRON and CHARSET. What is left
+//! for the application is a stream of [`ClientEvent`]s: lines, prompts and the events the client
+//! does not handle itself.
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//!
+//! use televerknet::client::{ClientConfig, ClientEvent, TelnetClient};
+//!
+//! let config = ClientConfig::new()
+//!     .terminal_type(b"XTERM-256COLOR")
+//!     .window_size(80, 24)
+//!     .charset(b"UTF-8");
+//! let mut client = TelnetClient::connect(TcpStream::connect("localhost:23")?, config)?;
+//! while !client.is_closed() {
+//!     for event in client.read()? {
+//!         if let ClientEvent::Prompt(prompt) = event {
+//!             println!("{}", String::from_utf8_lossy(&prompt));
+//!         }
+//!     }
+//! }
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! [`TelnetClient`]: struct.TelnetClient.html
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+//! [`ClientConfig`]: struct.ClientConfig.html
+//! [`ClientEvent`]: enum.ClientEvent.html
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::handler::{Charset, TerminalType, WindowSize};
+use crate::option::Opt;
+use crate::session::{Policy, TelnetSession};
+
+const TTYPE_SEND: u8 = 1;
+
+const ENVIRON_IS: u8 = 0;
+const ENVIRON_SEND: u8 = 1;
+const ENVIRON_VAR: u8 = 0;
+const ENVIRON_VALUE: u8 = 1;
+const ENVIRON_ESC: u8 = 2;
+const ENVIRON_USERVAR: u8 = 3;
+
+/// The variables of RFC 1572 sent as `VAR`, any others are sent as `USERVAR`
+const ENVIRON_WELL_KNOWN: &[&[u8]] = &[
+    b"USER",
+    b"JOB",
+    b"ACCT",
+    b"PRINTER",
+    b"SYSTEMTYPE",
+    b"DISPLAY",
+];
+
+/// What the client tells the server about itself
+#[derive(Debug, Clone, Default)]
+pub struct ClientConfig {
+    terminal_types: Vec<Vec<u8>>,
+    window_size: Option<(u16, u16)>,
+    environment: Vec<(Vec<u8>, Vec<u8>)>,
+    charsets: Vec<Vec<u8>>,
+    options: Vec<Opt>,
+}
+
+impl ClientConfig {
+    pub fn new() -> ClientConfig {
+        ClientConfig::default()
+    }
+
+    /// Add a terminal type, the most specific first
+    ///
+    /// The server cycles through the types with repeated requests, as described in RFC 1091.
+    pub fn terminal_type(mut self, name: &[u8]) -> ClientConfig {
+        self.terminal_types.push(name.to_vec());
+        self
+    }
+
+    /// Set the window size in characters, sent with NAWS
+    pub fn window_size(mut self, width: u16, height: u16) -> ClientConfig {
+        self.window_size = Some((width, height));
+        self
+    }
+
+    /// Add an environment variable, e.g. `USER`, sent with NEW-ENVIRON
+    ///
+    /// The well-known variables of RFC 1572, `USER`, `JOB`, `ACCT`, `PRINTER`, `SYSTEMTYPE` and
+    /// `DISPLAY`, are sent as `VAR` and any others as `USERVAR`.
+    pub fn environment(mut self, var: &[u8], value: &[u8]) -> ClientConfig {
+        self.environment.push((var.to_vec(), value.to_vec()));
+        self
+    }
+
+    /// Add a character set to accept with CHARSET, the most preferred first
+    pub fn charset(mut self, name: &[u8]) -> ClientConfig {
+        self.charsets.push(name.to_vec());
+        self
+    }
+
+    /// Ask the server to perform `option` when connecting
+    pub fn want_remote(mut self, option: Opt) -> ClientConfig {
+        self.options.push(option);
+        self
+    }
+
+    fn policy(&self) -> Policy {
+        let mut policy = Policy::new()
+            .accept_remote(Opt::ECHO)
+            .accept_remote(Opt::SGA)
+            .accept_local(Opt::SGA);
+        if !self.terminal_types.is_empty() {
+            policy = policy.accept_local(Opt::TTYPE);
+        }
+        if self.window_size.is_some() {
+            policy = policy.accept_local(Opt::NAWS);
+        }
+        if !self.environment.is_empty() {
+            policy = policy.accept_local(Opt::NEW_ENVIRON);
+        }
+        if !self.charsets.is_empty() {
+            policy = policy
+                .accept_local(Opt::CHARSET)
+                .accept_remote(Opt::CHARSET);
+        }
+        for option in &self.options {
+            policy = policy.accept_remote(*option);
+        }
+        policy
+    }
+}
+
+/// What the application gets from a [`TelnetClient`](struct.TelnetClient.html)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// A line of text, without the line ending
+    Line(Vec<u8>),
+    /// Text ended by GA or EOR, usually a prompt
+    Prompt(Vec<u8>),
+    /// An event the client does not handle, e.g. a GMCP subnegotiation
+    Other(Event),
+}
+
+/// A blocking telnet client
+///
+/// Control bytes other than CR, LF and NUL, e.g. the ESC of ANSI escape sequences, are kept in
+/// the text of lines and prompts.
+pub struct TelnetClient<S> {
+    stream: S,
+    session: TelnetSession,
+    config: ClientConfig,
+    charset: Option<Vec<u8>>,
+    accepted_charset: Arc<Mutex<Option<Vec<u8>>>>,
+    line: Vec<u8>,
+}
+
+impl<S: Read + Write> TelnetClient<S> {
+    /// Start the handshake on a connected `stream`
+    ///
+    /// Requests SGA and the options of the config, and offers NAWS if a window size is set.
+    /// Terminal types, the window size and the character set are answered by the handlers of the
+    /// session, see [`TerminalType`], [`WindowSize`] and [`Charset`].
+    ///
+    /// [`TerminalType`]: ../handler/struct.TerminalType.html
+    /// [`WindowSize`]: ../handler/struct.WindowSize.html
+    /// [`Charset`]: ../handler/struct.Charset.html
+    pub fn connect(stream: S, config: ClientConfig) -> io::Result<TelnetClient<S>> {
+        let mut session = TelnetSession::new(config.policy());
+        if !config.terminal_types.is_empty() {
+            let names: Vec<&[u8]> = config.terminal_types.iter().map(Vec::as_slice).collect();
+            session.register_handler(Opt::TTYPE, TerminalType::new(&names));
+        }
+        if let Some((width, height)) = config.window_size {
+            session.register_handler(Opt::NAWS, WindowSize::new(width, height));
+        }
+        let accepted_charset = Arc::new(Mutex::new(None));
+        if !config.charsets.is_empty() {
+            let names: Vec<&[u8]> = config.charsets.iter().map(Vec::as_slice).collect();
+            let accepted = accepted_charset.clone();
+            let handler = Charset::new(&names).on_accept(move |name| {
+                *accepted.lock().unwrap_or_else(|err| err.into_inner()) = Some(name.to_vec());
+            });
+            session.register_handler(Opt::CHARSET, handler);
+        }
+        let mut client = TelnetClient {
+            stream,
+            session,
+            config,
+            charset: None,
+            accepted_charset,
+            line: Vec::new(),
+        };
+        // Options requested twice are only negotiated once
+        let _ = client.session.request_remote(Opt::SGA);
+        for option in client.config.options.clone() {
+            let _ = client.session.request_remote(option);
+        }
+        if client.config.window_size.is_some() {
+            let _ = client.session.offer_local(Opt::NAWS);
+        }
+        client.write_output()?;
+        Ok(client)
+    }
+
+    /// Perform a single read, answer the server and return the events found
+    ///
+    /// Text not yet ended by a newline or a prompt is kept, see [`partial_line`].
+    ///
+    /// [`partial_line`]: #method.partial_line
+    pub fn read(&mut self) -> io::Result<Vec<ClientEvent>> {
+        let mut events = Vec::new();
+        for event in self.session.read_from(&mut self.stream)? {
+            self.handle(event, &mut events);
+        }
+        let accepted = self
+            .accepted_charset
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+        if accepted.is_some() {
+            self.charset = accepted;
+        }
+        self.write_output()?;
+        Ok(events)
+    }
+
+    /// Send a line of text, ending it with CR LF
+    pub fn send_line(&mut self, line: &[u8]) -> io::Result<()> {
+        self.session.send_data(line);
+        self.session.send_data(b"\r\n");
+        self.write_output()
+    }
+
+    /// Change the window size, telling the server if NAWS is enabled
+    pub fn set_window_size(&mut self, width: u16, height: u16) -> io::Result<()> {
+        self.config.window_size = Some((width, height));
+        self.session
+            .register_handler(Opt::NAWS, WindowSize::new(width, height));
+        if self.session.is_local_enabled(Opt::NAWS) {
+            let mut params = Vec::with_capacity(4);
+            params.extend_from_slice(&width.to_be_bytes());
+            params.extend_from_slice(&height.to_be_bytes());
+            self.send_subnegotiation(Opt::NAWS, &params);
+        }
+        self.write_output()
+    }
+
+    /// Text received since the last line or prompt
+    pub fn partial_line(&self) -> &[u8] {
+        &self.line
+    }
+
+    /// Take the text received since the last line or prompt, e.g. a prompt sent without GA
+    pub fn take_partial_line(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.line)
+    }
+
+    /// The character set accepted with CHARSET
+    pub fn charset(&self) -> Option<&[u8]> {
+        self.charset.as_deref()
+    }
+
+    /// Returns true once the server closed the connection
+    pub fn is_closed(&self) -> bool {
+        self.session.is_closed()
+    }
+
+    pub fn session(&self) -> &TelnetSession {
+        &self.session
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    fn write_output(&mut self) -> io::Result<()> {
+        while self.session.wants_write() {
+            self.session.write_to(&mut self.stream)?;
+        }
+        self.stream.flush()
+    }
+
+    fn handle(&mut self, event: Event, events: &mut Vec<ClientEvent>) {
+        match event {
+            Event::Data(data) => self.line.extend_from_slice(&data),
+            Event::Execute(b'\n') => events.push(ClientEvent::Line(self.take_partial_line())),
+            Event::Execute(b'\r') | Event::Execute(0) => (),
+            Event::Execute(byte) => self.line.push(byte),
+            Event::Command(Command::GA) | Event::Command(Command::EOR) => {
+                if !self.line.is_empty() {
+                    events.push(ClientEvent::Prompt(self.take_partial_line()));
+                }
+            }
+            Event::Negotiate(..) => (),
+            // Answered by the TerminalType handler
+            Event::Subnegotiate(Opt::TTYPE, params) if params.as_slice() == [TTYPE_SEND] => (),
+            Event::Subnegotiate(Opt::NEW_ENVIRON, params)
+                if params.first() == Some(&ENVIRON_SEND) =>
+            {
+                self.send_environment(&params[1..]);
+            }
+            // Answered by the Charset handler
+            Event::Subnegotiate(Opt::CHARSET, _) if !self.config.charsets.is_empty() => (),
+            event => events.push(ClientEvent::Other(event)),
+        }
+    }
+
+    /// Send the requested variables, or all of them if none were named
+    ///
+    /// An empty name asks for all variables of its type, as described in RFC 1572.
+    fn send_environment(&mut self, requested: &[u8]) {
+        if !self.session.is_local_enabled(Opt::NEW_ENVIRON) {
+            return;
+        }
+        let names = environ_names(requested);
+        let mut params = vec![ENVIRON_IS];
+        for (var, value) in &self.config.environment {
+            let kind = environ_kind(var);
+            let wanted = names.is_empty()
+                || (names.iter()).any(|(k, name)| name == var || (name.is_empty() && *k == kind));
+            if wanted {
+                params.push(kind);
+                environ_escape_into(var, &mut params);
+                params.push(ENVIRON_VALUE);
+                environ_escape_into(value, &mut params);
+            }
+        }
+        self.send_subnegotiation(Opt::NEW_ENVIRON, &params);
+    }
+
+    fn send_subnegotiation(&mut self, option: Opt, params: &[u8]) {
+        self.session
+            .send(&Event::Subnegotiate(option, params.to_vec()));
+    }
+}
+
+/// `VAR` for the well-known variables, `USERVAR` for any others
+fn environ_kind(var: &[u8]) -> u8 {
+    if ENVIRON_WELL_KNOWN.contains(&var) {
+        ENVIRON_VAR
+    } else {
+        ENVIRON_USERVAR
+    }
+}
+
+/// Types and names listed in a NEW-ENVIRON SEND, unescaped
+fn environ_names(list: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let mut names: Vec<(u8, Vec<u8>)> = Vec::new();
+    let mut escaped = false;
+    for byte in list {
+        match *byte {
+            _ if escaped => {
+                escaped = false;
+                if let Some((_, name)) = names.last_mut() {
+                    name.push(*byte);
+                }
+            }
+            ENVIRON_ESC => escaped = true,
+            ENVIRON_VAR | ENVIRON_USERVAR => names.push((*byte, Vec::new())),
+            _ => {
+                if let Some((_, name)) = names.last_mut() {
+                    name.push(*byte);
+                }
+            }
+        }
+    }
+    names
+}
+
+fn environ_escape_into(bytes: &[u8], dst: &mut Vec<u8>) {
+    for byte in bytes {
+        if let ENVIRON_VAR | ENVIRON_VALUE | ENVIRON_ESC | ENVIRON_USERVAR = *byte {
+            dst.push(ENVIRON_ESC);
+        }
+        dst.push(*byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+
+    use super::{ClientConfig, ClientEvent, TelnetClient};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    /// A socket with canned input, recording output
+    struct Socket {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Socket {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Socket {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn encode(events: &[Event]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for event in events {
+            event.encode_into(&mut bytes);
+        }
+        bytes
+    }
+
+    #[test]
+    fn handshake() {
+        let mut input = encode(&[
+            Event::Negotiate(Command::WILL, Opt::SGA),
+            Event::Negotiate(Command::DO, Opt::TTYPE),
+            Event::Negotiate(Command::DO, Opt::NAWS),
+            Event::Negotiate(Command::DO, Opt::NEW_ENVIRON),
+            Event::Negotiate(Command::DO, Opt::CHARSET),
+            Event::Subnegotiate(Opt::TTYPE, vec![1]),
+            Event::Subnegotiate(Opt::TTYPE, vec![1]),
+            Event::Subnegotiate(Opt::NEW_ENVIRON, vec![1, 0, b'U', b'S', b'E', b'R']),
+            Event::Subnegotiate(Opt::CHARSET, b"\x01;ISO-8859-1;UTF-8".to_vec()),
+        ]);
+        input.extend_from_slice(b"Welcome\r\nlogin: \xff\xf9");
+        let socket = Socket {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+        };
+        let config = ClientConfig::new()
+            .terminal_type(b"XTERM")
+            .terminal_type(b"ANSI")
+            .window_size(80, 24)
+            .environment(b"USER", b"alice")
+            .environment(b"LANG", b"C")
+            .charset(b"utf-8");
+
+        let mut client = TelnetClient::connect(socket, config).unwrap();
+        let events = client.read().unwrap();
+        assert_eq!(
+            events,
+            &[
+                ClientEvent::Line(b"Welcome".to_vec()),
+                ClientEvent::Prompt(b"login: ".to_vec()),
+            ]
+        );
+        assert_eq!(client.charset(), Some(&b"UTF-8"[..]));
+
+        let expected = encode(&[
+            Event::Negotiate(Command::DO, Opt::SGA),
+            Event::Negotiate(Command::WILL, Opt::NAWS),
+            Event::Negotiate(Command::WILL, Opt::TTYPE),
+            // Sent by the handler as soon as NAWS is enabled
+            Event::Subnegotiate(Opt::NAWS, vec![0, 80, 0, 24]),
+            Event::Negotiate(Command::WILL, Opt::NEW_ENVIRON),
+            Event::Negotiate(Command::WILL, Opt::CHARSET),
+            Event::Subnegotiate(Opt::TTYPE, b"\x00XTERM".to_vec()),
+            Event::Subnegotiate(Opt::TTYPE, b"\x00ANSI".to_vec()),
+            // Handlers answer while the session reads, before the client sees the events
+            Event::Subnegotiate(Opt::CHARSET, b"\x02UTF-8".to_vec()),
+            Event::Subnegotiate(Opt::NEW_ENVIRON, b"\x00\x00USER\x01alice".to_vec()),
+        ]);
+        assert_eq!(client.get_ref().output, expected);
+    }
+
+    #[test]
+    fn charset_needs_negotiation() {
+        let input = encode(&[Event::Subnegotiate(Opt::CHARSET, b"\x01;UTF-8".to_vec())]);
+        let socket = Socket {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+        };
+        let config = ClientConfig::new().charset(b"UTF-8");
+
+        let mut client = TelnetClient::connect(socket, config).unwrap();
+        client.get_mut().output.clear();
+        assert!(client.read().unwrap().is_empty());
+        assert_eq!(client.charset(), None);
+        assert!(client.get_ref().output.is_empty());
+    }
+
+    #[test]
+    fn environment_wildcards() {
+        let input = encode(&[
+            Event::Negotiate(Command::DO, Opt::NEW_ENVIRON),
+            // SEND VAR, SEND USERVAR and SEND VAR "LANG"
+            Event::Subnegotiate(Opt::NEW_ENVIRON, vec![1, 0]),
+            Event::Subnegotiate(Opt::NEW_ENVIRON, vec![1, 3]),
+            Event::Subnegotiate(Opt::NEW_ENVIRON, b"\x01\x00LANG".to_vec()),
+        ]);
+        let socket = Socket {
+            input: io::Cursor::new(input),
+            output: Vec::new(),
+        };
+        let config = ClientConfig::new()
+            .environment(b"USER", b"alice")
+            .environment(b"LANG", b"C")
+            .environment(b"DISPLAY", b":0");
+
+        let mut client = TelnetClient::connect(socket, config).unwrap();
+        client.get_mut().output.clear();
+        client.read().unwrap();
+        let expected = encode(&[
+            Event::Negotiate(Command::WILL, Opt::NEW_ENVIRON),
+            Event::Subnegotiate(
+                Opt::NEW_ENVIRON,
+                b"\x00\x00USER\x01alice\x00DISPLAY\x01:0".to_vec(),
+            ),
+            Event::Subnegotiate(Opt::NEW_ENVIRON, b"\x00\x03LANG\x01C".to_vec()),
+            Event::Subnegotiate(Opt::NEW_ENVIRON, b"\x00\x03LANG\x01C".to_vec()),
+        ]);
+        assert_eq!(client.get_ref().output, expected);
+    }
+}
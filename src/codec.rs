@@ -0,0 +1,212 @@
+//! Tokio codec for telnet streams
+//!
+//! [`TelnetCodec`] decodes bytes into [`Event`]s and encodes events with the proper IAC escaping,
+//! for use with `tokio_util::codec::Framed` and friends. [`EventStream`] is a `Stream` of events
+//! read from an `AsyncRead`.
+//!
+//! [`TelnetCodec`]: struct.TelnetCodec.html
+//! [`EventStream`]: struct.EventStream.html
+//! [`Event`]: ../event/enum.Event.html
+use std::collections::VecDeque;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::BytesMut;
+use futures_core::Stream;
+use tokio::io::AsyncRead;
+use tokio_util::codec::{Decoder, Encoder, FramedRead};
+
+use crate::buf;
+use crate::event::Event;
+use crate::message::OutMessage;
+use crate::Parser;
+
+/// A `Decoder` and `Encoder` of telnet [`Event`]s
+///
+/// [`Event`]: ../event/enum.Event.html
+#[derive(Default)]
+pub struct TelnetCodec {
+    parser: Parser,
+    events: Vec<Event>,
+    pending: VecDeque<Event>,
+}
+
+impl TelnetCodec {
+    pub fn new() -> TelnetCodec {
+        TelnetCodec::default()
+    }
+
+    /// A codec decoding with `parser`, e.g. one with a larger subnegotiation buffer
+    pub fn with_parser(parser: Parser) -> TelnetCodec {
+        TelnetCodec {
+            parser,
+            ..TelnetCodec::default()
+        }
+    }
+}
+
+impl Decoder for TelnetCodec {
+    type Item = Event;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Event>, io::Error> {
+        if self.pending.is_empty() && !src.is_empty() {
+            self.parser.advance_bytes(&mut self.events, src);
+            // Data is delivered per read rather than held until the next control byte
+            self.parser.flush(&mut self.events);
+            src.clear();
+            self.pending.extend(self.events.drain(..));
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+impl Encoder<Event> for TelnetCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, event: Event, dst: &mut BytesMut) -> Result<(), io::Error> {
+        buf::put_event(&event, dst);
+        Ok(())
+    }
+}
+
+impl Encoder<OutMessage> for TelnetCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, message: OutMessage, dst: &mut BytesMut) -> Result<(), io::Error> {
+        buf::put_message(&message, dst);
+        Ok(())
+    }
+}
+
+/// A `Stream` of [`Event`]s read from an `AsyncRead`
+///
+/// ```no_run
+/// # async fn run(socket: impl tokio::io::AsyncRead + Unpin) {
+/// use futures::StreamExt;
+/// use televerknet::codec::EventStream;
+///
+/// let mut events = EventStream::new(socket);
+/// while let Some(event) = events.next().await {
+///     println!("{:?}", event);
+/// }
+/// # }
+/// ```
+///
+/// [`Event`]: ../event/enum.Event.html
+pub struct EventStream<R> {
+    inner: FramedRead<R, TelnetCodec>,
+}
+
+impl<R: AsyncRead> EventStream<R> {
+    pub fn new(reader: R) -> EventStream<R> {
+        EventStream::with_parser(reader, Parser::new())
+    }
+
+    pub fn with_parser(reader: R, parser: Parser) -> EventStream<R> {
+        EventStream {
+            inner: FramedRead::new(reader, TelnetCodec::with_parser(parser)),
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for EventStream<R> {
+    type Item = io::Result<Event>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.inner).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventStream, TelnetCodec};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::Parser;
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    #[test]
+    fn decode() {
+        let mut codec = TelnetCodec::new();
+        let mut src = BytesMut::from(&[b'h', b'i', b'\n', 255, 253][..]);
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Event::Data(b"hi".to_vec()))
+        );
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Event::Execute(b'\n')));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+
+        // the negotiation continues in the next read
+        src.extend_from_slice(&[31]);
+        assert_eq!(
+            codec.decode(&mut src).unwrap(),
+            Some(Event::Negotiate(Command::DO, Opt::NAWS))
+        );
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn decode_with_parser() {
+        let mut message = b"\xff\xfa\xc9Room.Info ".to_vec();
+        message.resize(500, b'x');
+        message.extend_from_slice(b"\xff\xf0");
+
+        // The default buffer keeps the message whole, a smaller one truncates it
+        let mut codec = TelnetCodec::new();
+        let mut src = BytesMut::from(&message[..]);
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(Event::Subnegotiate(Opt::GMCP, params)) if params.len() == 497
+        ));
+        let parser = Parser::builder().max_subnegotiation(64).build();
+        let mut codec = TelnetCodec::with_parser(parser);
+        let mut src = BytesMut::from(&message[..]);
+        assert!(matches!(
+            codec.decode(&mut src).unwrap(),
+            Some(Event::Subnegotiate(Opt::GMCP, params)) if params.len() < 64
+        ));
+    }
+
+    #[tokio::test]
+    async fn event_stream() {
+        use futures::StreamExt;
+
+        let input: &[u8] = &[255, 251, 1, b'>', b' '];
+        let events: Vec<Event> = EventStream::new(input)
+            .map(|event| event.unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            events,
+            &[
+                Event::Negotiate(Command::WILL, Opt::ECHO),
+                Event::Data(b"> ".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn encode() {
+        let mut codec = TelnetCodec::new();
+        let mut dst = BytesMut::new();
+        codec
+            .encode(Event::Data(vec![b'a', 255]), &mut dst)
+            .unwrap();
+        codec
+            .encode(Event::Negotiate(Command::WILL, Opt::NAWS), &mut dst)
+            .unwrap();
+        assert_eq!(&dst[..], &[b'a', 255, 255, 255, 251, 31]);
+    }
+}
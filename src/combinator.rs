@@ -0,0 +1,269 @@
+//! Combining performers
+//!
+//! [`Tee`] passes every callback to two performers and [`Filter`] drops or changes events before
+//! passing them on, so logging, statistics and the application can each be a performer of their
+//! own:
+//!
+//! ```
+//! use televerknet::combinator::{Filter, Tee};
+//! use televerknet::event::Event;
+//! use televerknet::Parser;
+//!
+//! let commands_only = Filter::new(Vec::new(), |event| match event {
+//!     Event::Data(_) | Event::Execute(_) => None,
+//!     event => Some(event),
+//! });
+//! let mut performer = Tee::new(Vec::<Event>::new(), commands_only);
+//! Parser::new().advance_bytes(&mut performer, b"ok\xff\xf9");
+//!
+//! let (all, commands_only) = performer.into_inner();
+//! assert_eq!(all.len(), 2);
+//! assert_eq!(commands_only.into_inner().len(), 1);
+//! ```
+//!
+//! [`Tee`]: struct.Tee.html
+//! [`Filter`]: struct.Filter.html
+use crate::command::Command;
+use crate::event::Event;
+use crate::option::Opt;
+use crate::{DataEnd, ParseError, Perform};
+
+/// Passes every callback to `A`, then to `B`
+#[derive(Debug, Default)]
+pub struct Tee<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A: Perform, B: Perform> Tee<A, B> {
+    pub fn new(a: A, b: B) -> Tee<A, B> {
+        Tee { a, b }
+    }
+
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.a, &self.b)
+    }
+
+    pub fn get_mut(&mut self) -> (&mut A, &mut B) {
+        (&mut self.a, &mut self.b)
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A: Perform, B: Perform> Perform for Tee<A, B> {
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        self.a.data(intermediates, ignore, end);
+        self.b.data(intermediates, ignore, end);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.a.execute(byte);
+        self.b.execute(byte);
+    }
+
+    fn error(&mut self, error: ParseError) {
+        self.a.error(error);
+        self.b.error(error);
+    }
+
+    /// Breaks when either performer does
+    fn should_break(&mut self) -> bool {
+        // Both are asked, as either may keep state about it
+        let a = self.a.should_break();
+        let b = self.b.should_break();
+        a || b
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        self.a.iac_dispatch(byte);
+        self.b.iac_dispatch(byte);
+    }
+
+    fn sub_start(&mut self) {
+        self.a.sub_start();
+        self.b.sub_start();
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        self.a.sub_dispatch(subs);
+        self.b.sub_dispatch(subs);
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        self.a.negotiate_dispatch(cmd, opt);
+        self.b.negotiate_dispatch(cmd, opt);
+    }
+
+    fn subnegotiate_dispatch(&mut self, params: &[u8], opt: u8) {
+        self.a.subnegotiate_dispatch(params, opt);
+        self.b.subnegotiate_dispatch(params, opt);
+    }
+
+    fn zmp_dispatch(&mut self, params: &[&[u8]]) {
+        self.a.zmp_dispatch(params);
+        self.b.zmp_dispatch(params);
+    }
+
+    fn ttypes_dispatch(&mut self, cmd: u8, terminal_type: &[u8]) {
+        self.a.ttypes_dispatch(cmd, terminal_type);
+        self.b.ttypes_dispatch(cmd, terminal_type);
+    }
+
+    fn compress_dispatch(&mut self, state: u8) {
+        self.a.compress_dispatch(state);
+        self.b.compress_dispatch(state);
+    }
+}
+
+/// Passes events through `F` before passing them to `P`
+///
+/// `F` gets each callback as an [`Event`] and returns the event to pass on, or `None` to drop
+/// it. Data which is passed on as data keeps its truncation flag and [`DataEnd`]. Callbacks which
+/// can not be represented as an event, e.g. errors, are passed on unchanged.
+///
+/// [`Event`]: ../event/enum.Event.html
+/// [`DataEnd`]: ../enum.DataEnd.html
+pub struct Filter<P, F> {
+    inner: P,
+    f: F,
+}
+
+impl<P, F> Filter<P, F>
+where
+    P: Perform,
+    F: FnMut(Event) -> Option<Event>,
+{
+    pub fn new(inner: P, f: F) -> Filter<P, F> {
+        Filter { inner, f }
+    }
+
+    pub fn get_ref(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    fn filter(&mut self, event: Event) {
+        if let Some(event) = (self.f)(event) {
+            event.perform(&mut self.inner);
+        }
+    }
+}
+
+impl<P, F> Perform for Filter<P, F>
+where
+    P: Perform,
+    F: FnMut(Event) -> Option<Event>,
+{
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        match (self.f)(Event::Data(intermediates.to_vec())) {
+            Some(Event::Data(data)) => self.inner.data(&data, ignore, end),
+            Some(event) => event.perform(&mut self.inner),
+            None => (),
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.filter(Event::Execute(byte));
+    }
+
+    fn error(&mut self, error: ParseError) {
+        self.inner.error(error);
+    }
+
+    fn should_break(&mut self) -> bool {
+        self.inner.should_break()
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        match Command::from_u8(byte) {
+            Ok(command) => self.filter(Event::Command(command)),
+            Err(_) => self.inner.iac_dispatch(byte),
+        }
+    }
+
+    fn sub_start(&mut self) {
+        self.inner.sub_start();
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        // Convert through the collector, which unescapes the parameters
+        let mut events: Vec<Event> = Vec::with_capacity(1);
+        events.sub_dispatch(subs);
+        for event in events {
+            self.filter(event);
+        }
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        match Command::from_u8(cmd) {
+            Ok(command) => self.filter(Event::Negotiate(command, Opt::from_u8(opt))),
+            Err(_) => self.inner.negotiate_dispatch(cmd, opt),
+        }
+    }
+
+    fn subnegotiate_dispatch(&mut self, params: &[u8], opt: u8) {
+        self.inner.subnegotiate_dispatch(params, opt);
+    }
+
+    fn zmp_dispatch(&mut self, params: &[&[u8]]) {
+        self.inner.zmp_dispatch(params);
+    }
+
+    fn ttypes_dispatch(&mut self, cmd: u8, terminal_type: &[u8]) {
+        self.inner.ttypes_dispatch(cmd, terminal_type);
+    }
+
+    fn compress_dispatch(&mut self, state: u8) {
+        self.inner.compress_dispatch(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, Tee};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::Parser;
+
+    #[test]
+    fn tee_and_filter() {
+        let bytes = [b'h', b'i', 255, 251, 1, 255, 250, 24, 0, 255, 255, 255, 240];
+
+        // Refuse to see ECHO, and upper case all data
+        let filter = Filter::new(Vec::new(), |event| match event {
+            Event::Negotiate(_, Opt::ECHO) => None,
+            Event::Data(data) => Some(Event::Data(data.to_ascii_uppercase())),
+            event => Some(event),
+        });
+        let mut tee = Tee::new(Vec::new(), filter);
+        Parser::new().advance_bytes(&mut tee, &bytes);
+
+        let (all, filter) = tee.into_inner();
+        assert_eq!(
+            all,
+            &[
+                Event::Data(b"hi".to_vec()),
+                Event::Negotiate(Command::WILL, Opt::ECHO),
+                Event::Subnegotiate(Opt::TTYPE, vec![0, 255]),
+            ]
+        );
+        assert_eq!(
+            filter.into_inner(),
+            &[
+                Event::Data(b"HI".to_vec()),
+                Event::Subnegotiate(Opt::TTYPE, vec![0, 255]),
+            ]
+        );
+    }
+}
@@ -1,67 +1,22 @@
-use std::convert::From;
-use std::error::Error;
+use std::convert::{From, TryFrom};
 use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
+use crate::option::names_eq;
 
 /// A telnet command or special values.
 #[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
 pub struct Command(u8);
 
-/// A possible error value when converting a `Command` from a `u8`.
-#[derive(Debug)]
-pub struct InvalidCommand {
-    invalid_src: u8,
-}
-
-impl fmt::Display for InvalidCommand {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "invalid command")
-    }
-}
-
-impl Error for InvalidCommand {
-    fn description(&self) -> &str {
-        "invalid command"
-    }
-}
-
-// #[derive(Debug)]
-// pub enum ParseError {
-//     InvalidCommand,
-// }
-
-// #[derive(Debug)]
-// pub enum TelnetError {
-//     ParseError(ParseError),
-// }
-
-// impl fmt::Display for TelnetError {
-//     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//         fmt::Debug::fmt(self, f)
-//     }
-// }
-
-// impl Error for TelnetError {
-//     fn description(&self) -> &str {
-//         match self {
-//             &TelnetError::ParseError(ref e) => match *e {
-//                 ParseError::InvalidCommand => "invalid command",
-//             },
-//         }
-//     }
-
-//     fn cause(&self) -> Option<&dyn Error> {
-//         match self {
-//             &TelnetError::ParseError(..) => None,
-//         }
-//     }
-// }
-
 impl Command {
-    // TODO: return ParseError?
-    pub fn from_u8(src: u8) -> Result<Command, InvalidCommand> {
+    /// Fails with [`Error::InvalidCommand`] for bytes which are not commands.
+    ///
+    /// [`Error::InvalidCommand`]: ../error/enum.Error.html#variant.InvalidCommand
+    pub fn from_u8(src: u8) -> Result<Command, Error> {
         match src {
             236..=255 => Ok(Command(src)),
-            _ => Err(InvalidCommand { invalid_src: src }),
+            _ => Err(Error::InvalidCommand(src)),
         }
     }
 
@@ -72,6 +27,16 @@ impl Command {
     pub fn canonical_reason(&self) -> Option<&'static str> {
         canonical_reason(self.0)
     }
+
+    /// Short name of the command as used in the RFCs, e.g. `"WILL"` or `"IAC"`.
+    pub fn name(&self) -> &'static str {
+        name(self.0)
+    }
+
+    /// Iterate over all defined commands, starting with `IAC` and descending in value.
+    pub fn iter() -> impl Iterator<Item = Command> {
+        Command::ALL.iter().copied()
+    }
 }
 
 impl PartialEq<u8> for Command {
@@ -93,9 +58,31 @@ impl From<Command> for u8 {
     }
 }
 
+impl TryFrom<u8> for Command {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(src: u8) -> Result<Command, Error> {
+        Command::from_u8(src)
+    }
+}
+
+/// Parses a command from its name, e.g. `"WILL"`, or its canonical reason, e.g. `"WON'T"`.
+/// Matching is case-insensitive, and words may be separated by a space, `-` or `_`, e.g.
+/// `"go-ahead"`.
+impl FromStr for Command {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Command, Error> {
+        from_name(s)
+            .map(Command)
+            .ok_or_else(|| Error::UnknownCommand(s.to_owned()))
+    }
+}
+
 impl<'a> From<&'a Command> for Command {
     fn from(t: &'a Command) -> Self {
-        t.clone()
+        *t
     }
 }
 
@@ -116,6 +103,59 @@ impl fmt::Display for Command {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for Command {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=u8} {=str}", self.0, self.name());
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Command {
+    /// Serializes as the command name when known, otherwise as the numeric value.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.canonical_reason().map(|_| self.name()) {
+            Some(name) => serializer.serialize_str(name),
+            None => serializer.serialize_u8(self.0),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Command {
+    /// Deserializes from either a command name or a numeric value.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Command, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Command;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a telnet command name or value")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Command, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Command, E> {
+                let src = u8::try_from(v).map_err(E::custom)?;
+                Command::from_u8(src).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Command {
+    /// Picks one of the known commands.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Command> {
+        u.choose(Command::ALL).copied()
+    }
+}
+
 macro_rules! telnet_commands {
     (
         $(
@@ -129,6 +169,49 @@ macro_rules! telnet_commands {
             pub const $konst: Command = Command($num);
         )+
 
+            /// All defined commands, starting with `IAC` and descending in value.
+            pub const ALL: &'static [Command] = &[
+                $(
+                Command::$konst,
+                )+
+            ];
+        }
+
+        /// The commands as an enum, to `match` with exhaustiveness checking.
+        ///
+        /// Variants have the names of the [`Command`] constants.
+        ///
+        /// [`Command`]: struct.Command.html
+        #[allow(clippy::upper_case_acronyms)]
+        #[non_exhaustive]
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        pub enum CommandKind {
+        $(
+            $(#[$docs])*
+            $konst,
+        )+
+        }
+
+        impl From<Command> for CommandKind {
+            fn from(command: Command) -> CommandKind {
+                match command.0 {
+                    $(
+                    $num => CommandKind::$konst,
+                    )+
+                    // Command::from_u8 only accepts defined commands
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        impl From<CommandKind> for Command {
+            fn from(kind: CommandKind) -> Command {
+                match kind {
+                    $(
+                    CommandKind::$konst => Command::$konst,
+                    )+
+                }
+            }
         }
 
         fn canonical_reason(num: u8) -> Option<&'static str> {
@@ -139,6 +222,24 @@ macro_rules! telnet_commands {
                 _ => None
             }
         }
+
+        fn from_name(name: &str) -> Option<u8> {
+            $(
+            if name.eq_ignore_ascii_case(stringify!($konst)) || names_eq(name, $phrase) {
+                return Some($num);
+            }
+            )+
+            None
+        }
+
+        fn name(num: u8) -> &'static str {
+            match num {
+                $(
+                $num => stringify!($konst),
+                )+
+                _ => "<unknown command>"
+            }
+        }
     }
 }
 
@@ -188,12 +289,73 @@ telnet_commands! {
 
 #[cfg(test)]
 mod test {
-    use super::Command;
+    use super::{Command, CommandKind};
+    use crate::error::Error;
+    use std::convert::TryFrom;
 
     #[test]
     fn command_from_u8() {
         assert_eq!(Command::from_u8(255).unwrap(), Command::IAC);
         assert_eq!(Command::IAC, 255);
-        assert_eq!(Command::from_u8(235).unwrap_err().invalid_src, 235);
+        assert!(matches!(
+            Command::from_u8(235),
+            Err(Error::InvalidCommand(235))
+        ));
+    }
+
+    #[test]
+    fn command_iter() {
+        assert_eq!(Command::iter().count(), 20);
+        assert_eq!(Command::iter().next(), Some(Command::IAC));
+        for command in Command::iter() {
+            assert_eq!(Command::from_u8(command.as_u8()).unwrap(), command);
+        }
+    }
+
+    #[test]
+    fn command_typed() {
+        for command in Command::iter() {
+            assert_eq!(Command::from(CommandKind::from(command)), command);
+        }
+        assert_eq!(CommandKind::from(Command::WILL), CommandKind::WILL);
+    }
+
+    #[test]
+    fn command_try_from() {
+        assert_eq!(Command::try_from(251).unwrap(), Command::WILL);
+        assert!(Command::try_from(0).is_err());
+    }
+
+    #[test]
+    fn command_from_str() {
+        assert_eq!("WILL".parse::<Command>().unwrap(), Command::WILL);
+        assert_eq!("iac".parse::<Command>().unwrap(), Command::IAC);
+        assert_eq!("don't".parse::<Command>().unwrap(), Command::DONT);
+        assert_eq!("Go ahead".parse::<Command>().unwrap(), Command::GA);
+        assert_eq!("are_you_there".parse::<Command>().unwrap(), Command::AYT);
+        assert_eq!("Data-Mark".parse::<Command>().unwrap(), Command::DM);
+        assert!("NAWS".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn command_name() {
+        assert_eq!(Command::WONT.name(), "WONT");
+        assert_eq!(Command::WONT.canonical_reason(), Some("WON'T"));
+        assert_eq!(Command::GA.name(), "GA");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn command_serde() {
+        assert_eq!(serde_json::to_string(&Command::WILL).unwrap(), "\"WILL\"");
+        assert_eq!(
+            serde_json::from_str::<Command>("\"will\"").unwrap(),
+            Command::WILL
+        );
+        assert_eq!(
+            serde_json::from_str::<Command>("255").unwrap(),
+            Command::IAC
+        );
+        assert!(serde_json::from_str::<Command>("1").is_err());
     }
 }
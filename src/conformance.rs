@@ -0,0 +1,165 @@
+//! Conformance test vectors
+//!
+//! A test vector is input bytes and the events a parser should find in them, optionally with the
+//! bytes a [`TelnetSession`] should answer with under a given policy. Vectors are stored as a
+//! JSON array, so that other projects and other implementations can share a corpus:
+//!
+//! ```json
+//! [
+//!   {
+//!     "name": "accepted DO is answered with WILL",
+//!     "reference": "RFC 1143",
+//!     "input": [255, 253, 1],
+//!     "events": [{ "Negotiate": ["DO", "ECHO"] }],
+//!     "accept_local": ["ECHO"],
+//!     "output": [255, 251, 1]
+//!   }
+//! ]
+//! ```
+//!
+//! Events use the serde representation of [`Event`]. [`run`] checks a vector, feeding the input
+//! both at once and a byte at a time, as the events must not depend on how the input is split.
+//! The vectors of this crate, covering the examples of RFC 854, 855 and 1143, are
+//! [`RFC_VECTORS`].
+//!
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+//! [`Event`]: ../event/enum.Event.html
+//! [`run`]: fn.run.html
+//! [`RFC_VECTORS`]: constant.RFC_VECTORS.html
+use std::fmt;
+use std::io::Read;
+
+use serde::{Deserialize, Serialize};
+
+use crate::event::Event;
+use crate::option::Opt;
+use crate::session::{Policy, TelnetSession};
+use crate::Parser;
+
+/// Vectors for the examples of RFC 854, 855 and 1143, as JSON
+pub const RFC_VECTORS: &str = include_str!("../tests/vectors/rfc.json");
+
+/// A conformance test vector
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    pub name: String,
+    /// Where the expected behaviour is specified, e.g. `RFC 854`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    pub input: Vec<u8>,
+    /// The events found by a parser with the default settings
+    pub events: Vec<Event>,
+    /// Options the session accepts performing itself
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accept_local: Vec<Opt>,
+    /// Options the session accepts the other party performing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accept_remote: Vec<Opt>,
+    /// The answer of a session with the policy above, not checked if missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<Vec<u8>>,
+}
+
+/// How a vector failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The parser found other events, with the input fed at once or a byte at a time
+    Events { split: bool, actual: Vec<Event> },
+    /// The session answered with other bytes
+    Output { actual: Vec<u8> },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Events {
+                split: false,
+                actual,
+            } => write!(f, "events {:?}", actual),
+            Mismatch::Events {
+                split: true,
+                actual,
+            } => {
+                write!(f, "events {:?} when fed a byte at a time", actual)
+            }
+            Mismatch::Output { actual } => write!(f, "output {:?}", actual),
+        }
+    }
+}
+
+/// Read vectors from a JSON array
+pub fn load<R: Read>(reader: R) -> serde_json::Result<Vec<TestVector>> {
+    serde_json::from_reader(reader)
+}
+
+/// Check the behaviour of this crate against `vector`
+pub fn run(vector: &TestVector) -> Result<(), Mismatch> {
+    let mut parser = Parser::new();
+    let mut events: Vec<Event> = Vec::new();
+    parser.advance_bytes(&mut events, &vector.input);
+    parser.flush(&mut events);
+    if events != vector.events {
+        return Err(Mismatch::Events {
+            split: false,
+            actual: events,
+        });
+    }
+
+    let mut parser = Parser::new();
+    let mut events: Vec<Event> = Vec::new();
+    for byte in &vector.input {
+        parser.advance_bytes(&mut events, &[*byte]);
+    }
+    parser.flush(&mut events);
+    if events != vector.events {
+        return Err(Mismatch::Events {
+            split: true,
+            actual: events,
+        });
+    }
+
+    if let Some(output) = &vector.output {
+        let mut policy = Policy::new();
+        for option in &vector.accept_local {
+            policy = policy.accept_local(*option);
+        }
+        for option in &vector.accept_remote {
+            policy = policy.accept_remote(*option);
+        }
+        let mut session = TelnetSession::new(policy);
+        session.feed(&vector.input).for_each(drop);
+        let actual = session.take_output();
+        if actual != *output {
+            return Err(Mismatch::Output { actual });
+        }
+    }
+    Ok(())
+}
+
+/// Run all `vectors`, returning the names of those which failed with how they failed
+pub fn run_all(vectors: &[TestVector]) -> Vec<(String, Mismatch)> {
+    vectors
+        .iter()
+        .filter_map(|vector| run(vector).err().map(|err| (vector.name.clone(), err)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, run, run_all, Mismatch, RFC_VECTORS};
+    use crate::event::Event;
+
+    #[test]
+    fn rfc_vectors() {
+        let vectors = load(RFC_VECTORS.as_bytes()).unwrap();
+        assert!(vectors.len() >= 20);
+        assert_eq!(run_all(&vectors), &[]);
+
+        let mut wrong = vectors[0].clone();
+        wrong.events.push(Event::Execute(b'\n'));
+        assert!(matches!(
+            run(&wrong),
+            Err(Mismatch::Events { split: false, .. })
+        ));
+    }
+}
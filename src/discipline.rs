@@ -0,0 +1,131 @@
+//! Line discipline of a client
+//!
+//! Whether a client edits lines locally or sends every character follows from three options:
+//! ECHO and SGA as offered by the server, and LINEMODE ([RFC 1184]) as performed by the client.
+//! [`DisciplineTracker`] derives the [`LineDiscipline`] from the events of a connection, and
+//! [`LineEditor`] edits the lines under LINEMODE.
+//!
+//! [`DisciplineTracker`]: struct.DisciplineTracker.html
+//! [`LineDiscipline`]: enum.LineDiscipline.html
+//! [`LineEditor`]: ../linemode/struct.LineEditor.html
+//! [RFC 1184]: https://www.rfc-editor.org/rfc/rfc1184.html
+use crate::command::Command;
+use crate::echo::EchoState;
+use crate::event::Event;
+use crate::option::Opt;
+
+const LINEMODE_MODE: u8 = 1;
+const MODE_EDIT: u8 = 1;
+
+/// How a client handles typed input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineDiscipline {
+    /// Edit and echo lines locally, sending whole lines
+    Line,
+    /// Edit lines locally without echoing them, e.g. while a password is typed
+    LineNoEcho,
+    /// Send every character as it is typed, leaving echo to the server
+    Character,
+}
+
+/// Derives the line discipline from ECHO, SGA and LINEMODE
+///
+/// Without LINEMODE the server asks for character at a time by both echoing and suppressing go
+/// ahead. With LINEMODE the EDIT bit of its MODE decides. Offers and requests of the server are
+/// assumed to be accepted.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DisciplineTracker {
+    echo: EchoState,
+    linemode: bool,
+    edit: bool,
+}
+
+impl DisciplineTracker {
+    pub fn new() -> DisciplineTracker {
+        DisciplineTracker::default()
+    }
+
+    /// Observe an event from the server
+    ///
+    /// Returns the new discipline when it changed.
+    pub fn observe(&mut self, event: &Event) -> Option<LineDiscipline> {
+        let before = self.discipline();
+        self.echo.observe(event);
+        match event {
+            Event::Negotiate(Command::DO, Opt::LINEMODE) => self.linemode = true,
+            Event::Negotiate(Command::DONT, Opt::LINEMODE) => {
+                self.linemode = false;
+                self.edit = false;
+            }
+            Event::Subnegotiate(Opt::LINEMODE, params) => {
+                if let [LINEMODE_MODE, mask] = params.as_slice() {
+                    self.edit = mask & MODE_EDIT != 0;
+                }
+            }
+            _ => (),
+        }
+        let after = self.discipline();
+        if after == before {
+            None
+        } else {
+            Some(after)
+        }
+    }
+
+    /// The current discipline
+    pub fn discipline(&self) -> LineDiscipline {
+        let local_editing = if self.linemode {
+            self.edit
+        } else {
+            !self.echo.character_mode()
+        };
+        match (local_editing, self.echo.local_echo_enabled()) {
+            (false, _) => LineDiscipline::Character,
+            (true, true) => LineDiscipline::Line,
+            (true, false) => LineDiscipline::LineNoEcho,
+        }
+    }
+
+    /// Echo state derived from ECHO and SGA
+    pub fn echo(&self) -> &EchoState {
+        &self.echo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisciplineTracker, LineDiscipline};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    #[test]
+    fn derived_discipline() {
+        let mut tracker = DisciplineTracker::new();
+        assert_eq!(tracker.discipline(), LineDiscipline::Line);
+
+        let mut observe = |event| tracker.observe(&event);
+        assert_eq!(
+            observe(Event::Negotiate(Command::WILL, Opt::ECHO)),
+            Some(LineDiscipline::LineNoEcho)
+        );
+        assert_eq!(
+            observe(Event::Negotiate(Command::WILL, Opt::SGA)),
+            Some(LineDiscipline::Character)
+        );
+        // LINEMODE without EDIT keeps character at a time
+        assert_eq!(observe(Event::Negotiate(Command::DO, Opt::LINEMODE)), None);
+        assert_eq!(
+            observe(Event::Subnegotiate(Opt::LINEMODE, vec![1, 1])),
+            Some(LineDiscipline::LineNoEcho)
+        );
+        assert_eq!(
+            observe(Event::Negotiate(Command::WONT, Opt::ECHO)),
+            Some(LineDiscipline::Line)
+        );
+        assert_eq!(
+            observe(Event::Negotiate(Command::DONT, Opt::LINEMODE)),
+            None
+        );
+    }
+}
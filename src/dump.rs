@@ -0,0 +1,150 @@
+//! Annotated hex dumps
+//!
+//! [`Dump`] formats a byte stream a sequence per line, with the bytes in hex and what they mean
+//! using the names of [`Command`] and [`Opt`]:
+//!
+//! ```
+//! use televerknet::dump::Dump;
+//!
+//! let dump = Dump::new(b"\xff\xfb\x18login: ").to_string();
+//! assert_eq!(
+//!     dump,
+//!     "ff fb 18                                         IAC WILL TTYPE\n\
+//!      6c 6f 67 69 6e 3a 20                             \"login: \"\n"
+//! );
+//! ```
+//!
+//! [`Dump`]: struct.Dump.html
+//! [`Command`]: ../command/struct.Command.html
+//! [`Opt`]: ../option/struct.Opt.html
+use std::fmt;
+
+use crate::command::Command;
+use crate::encode;
+use crate::option::Opt;
+
+const IAC: u8 = 255;
+const BYTES_PER_LINE: usize = 16;
+const HEX_WIDTH: usize = BYTES_PER_LINE * 3 + 1;
+
+/// Formats bytes as an annotated hex dump
+#[derive(Debug, Copy, Clone)]
+pub struct Dump<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Dump<'a> {
+    pub fn new(bytes: &'a [u8]) -> Dump<'a> {
+        Dump { bytes }
+    }
+}
+
+impl<'a> fmt::Display for Dump<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.bytes;
+        while !rest.is_empty() {
+            if rest[0] != IAC {
+                let len = rest
+                    .iter()
+                    .take(BYTES_PER_LINE)
+                    .position(|b| *b == IAC)
+                    .unwrap_or_else(|| rest.len().min(BYTES_PER_LINE));
+                line(f, &rest[..len], |f| text(f, &rest[..len]))?;
+                rest = &rest[len..];
+                continue;
+            }
+            match encode::sequence_len(rest) {
+                Some(len) => {
+                    line(f, &rest[..len], |f| annotate(f, &rest[..len]))?;
+                    rest = &rest[len..];
+                }
+                None => {
+                    line(f, rest, |f| f.write_str("incomplete"))?;
+                    rest = &[];
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `bytes` in hex, padded to align the annotation, then the annotation
+fn line<F>(f: &mut fmt::Formatter<'_>, bytes: &[u8], annotation: F) -> fmt::Result
+where
+    F: FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result,
+{
+    let mut width = 0;
+    for byte in bytes {
+        write!(f, "{:02x} ", byte)?;
+        width += 3;
+    }
+    write!(f, "{:1$}", "", HEX_WIDTH.saturating_sub(width).max(1))?;
+    annotation(f)?;
+    writeln!(f)
+}
+
+/// Write data as a quoted, escaped string
+fn text(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    f.write_str("\"")?;
+    for byte in bytes {
+        write!(f, "{}", std::ascii::escape_default(*byte))?;
+    }
+    f.write_str("\"")
+}
+
+fn option_name(f: &mut fmt::Formatter<'_>, option: u8) -> fmt::Result {
+    match Opt::from_u8(option).canonical_reason() {
+        Some(name) => f.write_str(name),
+        None => write!(f, "{}", option),
+    }
+}
+
+/// Annotate a complete sequence starting with IAC
+fn annotate(f: &mut fmt::Formatter<'_>, sequence: &[u8]) -> fmt::Result {
+    let command = match Command::from_u8(sequence[1]) {
+        Ok(command) => command,
+        Err(_) => return write!(f, "IAC {} (invalid command)", sequence[1]),
+    };
+    match sequence {
+        [IAC, IAC] => f.write_str("IAC IAC (data ff)"),
+        [IAC, 250, option, params @ .., IAC, 240] => {
+            f.write_str("IAC SB ")?;
+            option_name(f, *option)?;
+            for byte in params {
+                write!(f, " {:02x}", byte)?;
+            }
+            f.write_str(" IAC SE")
+        }
+        [IAC, 251..=254, option] => {
+            write!(f, "IAC {} ", command.name())?;
+            option_name(f, *option)
+        }
+        _ => write!(f, "IAC {}", command.name()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dump;
+
+    #[test]
+    fn annotations() {
+        let dump = Dump::new(
+            b"0123456789abcdefXY\xff\xff\xff\xfa\x1f\x00\x50\xff\xf0\xff\xfd\xe6\xff\x41\xff\xfb",
+        )
+        .to_string();
+        let lines: Vec<&str> = dump.lines().map(|line| &line[49..]).collect();
+        assert_eq!(
+            lines,
+            &[
+                "\"0123456789abcdef\"",
+                "\"XY\"",
+                "IAC IAC (data ff)",
+                "IAC SB NAWS 00 50 IAC SE",
+                "IAC DO 230",
+                "IAC 65 (invalid command)",
+                "incomplete",
+            ]
+        );
+    }
+}
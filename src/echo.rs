@@ -0,0 +1,137 @@
+//! Echo state of a client
+//!
+//! [`EchoState`] follows ECHO and SGA as announced by the server. While the server echoes, a
+//! client must not echo typed characters itself, which is also how servers hide passwords: they
+//! offer to echo and then don't.
+//!
+//! [`EchoHint`] sums this up for a user interface, e.g. to mask a password as it is typed. A
+//! [`TelnetSession`] tracks it from its own negotiation state, see
+//! [`TelnetSession::echo_hint`].
+//!
+//! [`EchoState`]: struct.EchoState.html
+//! [`EchoHint`]: enum.EchoHint.html
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+//! [`TelnetSession::echo_hint`]: ../session/struct.TelnetSession.html#method.echo_hint
+use crate::command::Command;
+use crate::event::Event;
+use crate::option::Opt;
+
+/// How a client should show what the user types
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EchoHint {
+    /// The server doesn't echo, the client echoes typed lines itself
+    Local,
+    /// The server took over echoing while lines are edited locally, which is how servers ask
+    /// for a password: mask or hide the input
+    Masked,
+    /// The server echoes in character at a time mode, the client shows what comes back
+    Remote,
+}
+
+impl EchoHint {
+    /// The hint for a server which does or doesn't echo and suppress go ahead
+    pub fn new(remote_echo: bool, remote_sga: bool) -> EchoHint {
+        match (remote_echo, remote_sga) {
+            (false, _) => EchoHint::Local,
+            (true, false) => EchoHint::Masked,
+            (true, true) => EchoHint::Remote,
+        }
+    }
+}
+
+/// Tracks whether the server echoes and suppresses go ahead
+///
+/// Offers of the server are assumed to be accepted, as clients accept ECHO and SGA.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EchoState {
+    remote_echo: bool,
+    remote_sga: bool,
+}
+
+impl EchoState {
+    pub fn new() -> EchoState {
+        EchoState::default()
+    }
+
+    /// Observe an event from the server
+    ///
+    /// Returns the new value of [`local_echo_enabled`] when it changed.
+    ///
+    /// [`local_echo_enabled`]: #method.local_echo_enabled
+    pub fn observe(&mut self, event: &Event) -> Option<bool> {
+        let before = self.local_echo_enabled();
+        if let Event::Negotiate(command, option) = event {
+            let enabled = match *command {
+                Command::WILL => true,
+                Command::WONT => false,
+                _ => return None,
+            };
+            match *option {
+                Opt::ECHO => self.remote_echo = enabled,
+                Opt::SGA => self.remote_sga = enabled,
+                _ => (),
+            }
+        }
+        if self.local_echo_enabled() == before {
+            None
+        } else {
+            Some(!before)
+        }
+    }
+
+    /// Returns true if the client should echo typed characters itself
+    pub fn local_echo_enabled(&self) -> bool {
+        !self.remote_echo
+    }
+
+    /// Returns true if the server suppresses go ahead
+    pub fn suppress_go_ahead(&self) -> bool {
+        self.remote_sga
+    }
+
+    /// How typed input should be shown
+    pub fn hint(&self) -> EchoHint {
+        EchoHint::new(self.remote_echo, self.remote_sga)
+    }
+
+    /// Returns true if each character should be sent as it is typed
+    ///
+    /// Servers ask for this by both echoing and suppressing go ahead.
+    pub fn character_mode(&self) -> bool {
+        self.remote_echo && self.remote_sga
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EchoHint, EchoState};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    #[test]
+    fn password_prompt() {
+        let mut echo = EchoState::new();
+        assert!(echo.local_echo_enabled());
+        assert_eq!(echo.observe(&Event::Data(b"Password: ".to_vec())), None);
+        assert_eq!(
+            echo.observe(&Event::Negotiate(Command::WILL, Opt::ECHO)),
+            Some(false)
+        );
+        assert!(!echo.character_mode());
+        assert_eq!(echo.hint(), EchoHint::Masked);
+        assert_eq!(
+            echo.observe(&Event::Negotiate(Command::WILL, Opt::SGA)),
+            None
+        );
+        assert!(echo.character_mode());
+        assert_eq!(echo.hint(), EchoHint::Remote);
+        assert_eq!(
+            echo.observe(&Event::Negotiate(Command::WONT, Opt::ECHO)),
+            Some(true)
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! `embedded-io` adapters
+//!
+//! [`TelnetReader`] and [`TelnetWriter`] work like their [`std::io`](../io/index.html)
+//! counterparts over the `embedded_io` traits, e.g. a UART or a TCP socket of an RTOS network
+//! stack. With the `embedded-async` feature they also implement the `embedded_io_async` traits
+//! when the inner transport does.
+//!
+//! [`TelnetReader`]: struct.TelnetReader.html
+//! [`TelnetWriter`]: struct.TelnetWriter.html
+use embedded_io::{ErrorType, Read, Write};
+
+use crate::event::Event;
+use crate::Parser;
+
+const READ_BUF_SIZE: usize = 256;
+
+const IAC: u8 = 255;
+
+/// Reads application data, stripping telnet commands
+pub struct TelnetReader<R> {
+    inner: R,
+    parser: Parser,
+    read_buf: [u8; READ_BUF_SIZE],
+    readable: Vec<u8>,
+    readable_pos: usize,
+    parsed: Vec<Event>,
+    events: Vec<Event>,
+}
+
+impl<R> TelnetReader<R> {
+    pub fn new(inner: R) -> TelnetReader<R> {
+        TelnetReader::with_parser(inner, Parser::new())
+    }
+
+    /// A reader parsing with `parser`, e.g. one with a larger subnegotiation buffer
+    pub fn with_parser(inner: R, parser: Parser) -> TelnetReader<R> {
+        TelnetReader {
+            inner,
+            parser,
+            read_buf: [0; READ_BUF_SIZE],
+            readable: Vec::new(),
+            readable_pos: 0,
+            parsed: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Take the events read so far which are not application data, e.g. negotiation
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Copy parsed application data to `buf`, returning `None` if there is none
+    fn take_readable(&mut self, buf: &mut [u8]) -> Option<usize> {
+        if self.readable_pos == self.readable.len() {
+            return None;
+        }
+        let readable = &self.readable[self.readable_pos..];
+        let n = readable.len().min(buf.len());
+        buf[..n].copy_from_slice(&readable[..n]);
+        self.readable_pos += n;
+        if self.readable_pos == self.readable.len() {
+            self.readable.clear();
+            self.readable_pos = 0;
+        }
+        Some(n)
+    }
+
+    /// Parse the first `n` bytes of the read buffer
+    fn parse(&mut self, n: usize) {
+        self.parser
+            .advance_bytes(&mut self.parsed, &self.read_buf[..n]);
+        self.parser.flush(&mut self.parsed);
+        for event in self.parsed.drain(..) {
+            match event {
+                Event::Data(data) => self.readable.extend_from_slice(&data),
+                Event::Execute(byte) => self.readable.push(byte),
+                event => self.events.push(event),
+            }
+        }
+    }
+}
+
+impl<R: ErrorType> ErrorType for TelnetReader<R> {
+    type Error = R::Error;
+}
+
+impl<R: Read> Read for TelnetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, R::Error> {
+        loop {
+            if let Some(n) = self.take_readable(buf) {
+                return Ok(n);
+            }
+            let n = self.inner.read(&mut self.read_buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.parse(n);
+        }
+    }
+}
+
+#[cfg(feature = "embedded-async")]
+impl<R: embedded_io_async::Read> embedded_io_async::Read for TelnetReader<R> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, R::Error> {
+        loop {
+            if let Some(n) = self.take_readable(buf) {
+                return Ok(n);
+            }
+            let n = self.inner.read(&mut self.read_buf).await?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.parse(n);
+        }
+    }
+}
+
+/// Writes application data, escaping IAC bytes
+pub struct TelnetWriter<W> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W> TelnetWriter<W> {
+    pub fn new(inner: W) -> TelnetWriter<W> {
+        TelnetWriter {
+            inner,
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn encode(&mut self, event: &Event) {
+        self.buf.clear();
+        event.encode_into(&mut self.buf);
+    }
+}
+
+impl<W: Write> TelnetWriter<W> {
+    /// Write an event, e.g. a command or negotiation, as is
+    pub fn send(&mut self, event: &Event) -> Result<(), W::Error> {
+        self.encode(event);
+        self.inner.write_all(&self.buf)
+    }
+}
+
+#[cfg(feature = "embedded-async")]
+impl<W: embedded_io_async::Write> TelnetWriter<W> {
+    /// Write an event, e.g. a command or negotiation, as is
+    pub async fn send_async(&mut self, event: &Event) -> Result<(), W::Error> {
+        self.encode(event);
+        self.inner.write_all(&self.buf).await
+    }
+}
+
+impl<W: ErrorType> ErrorType for TelnetWriter<W> {
+    type Error = W::Error;
+}
+
+/// Split `data` after each IAC byte, each run but the last to be followed by another IAC
+fn escaped_runs(data: &[u8]) -> impl Iterator<Item = (&[u8], bool)> {
+    data.split_inclusive(|b| *b == IAC)
+        .map(|run| (run, run.last() == Some(&IAC)))
+}
+
+impl<W: Write> Write for TelnetWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, W::Error> {
+        for (run, iac) in escaped_runs(buf) {
+            self.inner.write_all(run)?;
+            if iac {
+                self.inner.write_all(&[IAC])?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), W::Error> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "embedded-async")]
+impl<W: embedded_io_async::Write> embedded_io_async::Write for TelnetWriter<W> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, W::Error> {
+        for (run, iac) in escaped_runs(buf) {
+            self.inner.write_all(run).await?;
+            if iac {
+                self.inner.write_all(&[IAC]).await?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), W::Error> {
+        self.inner.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_io::{Read, Write};
+
+    use super::{TelnetReader, TelnetWriter};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    #[test]
+    fn read_and_write() {
+        let input: &[u8] = b"ab\xff\xfb\x01c\xff\xffd";
+        let mut reader = TelnetReader::new(input);
+        let mut data = [0u8; 16];
+        let n = reader.read(&mut data).unwrap();
+        assert_eq!(&data[..n], b"abc\xffd");
+        assert_eq!(reader.read(&mut data).unwrap(), 0);
+        assert_eq!(
+            reader.take_events(),
+            &[Event::Negotiate(Command::WILL, Opt::ECHO)]
+        );
+
+        let mut out = [0u8; 16];
+        let mut writer = TelnetWriter::new(&mut out[..]);
+        writer
+            .send(&Event::Negotiate(Command::DO, Opt::NAWS))
+            .unwrap();
+        writer.write_all(b"x\xffy").unwrap();
+        let left = writer.into_inner().len();
+        assert_eq!(&out[..16 - left], b"\xff\xfd\x1fx\xff\xffy");
+
+        #[cfg(feature = "embedded-async")]
+        futures::executor::block_on(async {
+            use embedded_io_async::{Read as AsyncRead, Write as AsyncWrite};
+
+            let mut reader = TelnetReader::new(input);
+            let n = AsyncRead::read(&mut reader, &mut data).await.unwrap();
+            assert_eq!(&data[..n], b"abc\xffd");
+
+            let mut out = [0u8; 16];
+            let mut writer = TelnetWriter::new(&mut out[..]);
+            AsyncWrite::write_all(&mut writer, b"\xff").await.unwrap();
+            assert_eq!(writer.into_inner().len(), 14);
+        });
+    }
+}
@@ -0,0 +1,165 @@
+//! Encoding of outgoing telnet data and commands
+//!
+//! All functions append to a `Vec<u8>` so a single buffer can be reused for many messages. Those
+//! encoding a single message append to anything that is `Extend<u8>`, e.g. a `BytesMut`.
+//! [`escape_slices`] escapes without copying, for vectored writes of large data.
+//!
+//! [`escape_slices`]: fn.escape_slices.html
+use std::io::IoSlice;
+
+use crate::command::Command;
+use crate::option::Opt;
+
+const IAC: u8 = 255;
+
+/// Append `data` to `dst`, doubling every IAC byte.
+pub fn escape_into<D: Extend<u8>>(data: &[u8], dst: &mut D) {
+    let mut rest = data;
+    while let Some(pos) = rest.iter().position(|b| *b == IAC) {
+        dst.extend(rest[..=pos].iter().copied());
+        dst.extend([IAC]);
+        rest = &rest[pos + 1..];
+    }
+    dst.extend(rest.iter().copied());
+}
+
+/// Append `text` to `dst` with the line endings of the network virtual terminal, doubling every
+/// IAC byte.
+///
+/// `\n` and `\r\n` become `CR LF`, and any other `\r` becomes `CR NUL`. With `binary`, when
+/// TRANSMIT-BINARY is enabled for sending, line endings are left alone.
+pub fn text_into(text: &[u8], binary: bool, dst: &mut Vec<u8>) {
+    if binary {
+        return escape_into(text, dst);
+    }
+    let mut bytes = text.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\r' if bytes.peek() == Some(&b'\n') => {
+                bytes.next();
+                dst.extend_from_slice(b"\r\n");
+            }
+            b'\r' => dst.extend_from_slice(b"\r\0"),
+            b'\n' => dst.extend_from_slice(b"\r\n"),
+            IAC => dst.extend_from_slice(&[IAC, IAC]),
+            byte => dst.push(byte),
+        }
+    }
+}
+
+/// Append slices of `data` to `dst` which, written in order, are `data` with every IAC byte
+/// doubled.
+///
+/// Runs of data are borrowed rather than copied, with a shared IAC slice after every IAC. See
+/// [`write_escaped`](../io/fn.write_escaped.html) to write them.
+pub fn escape_slices<'a>(data: &'a [u8], dst: &mut Vec<IoSlice<'a>>) {
+    let mut rest = data;
+    while let Some(pos) = rest.iter().position(|b| *b == IAC) {
+        dst.push(IoSlice::new(&rest[..=pos]));
+        dst.push(IoSlice::new(&[IAC]));
+        rest = &rest[pos + 1..];
+    }
+    if !rest.is_empty() {
+        dst.push(IoSlice::new(rest));
+    }
+}
+
+/// Append `IAC <command>` to `dst`.
+pub fn command_into<D: Extend<u8>>(command: Command, dst: &mut D) {
+    dst.extend([IAC, command.as_u8()]);
+}
+
+/// Append `IAC GA` to `dst`, handing the turn to the other party on a half-duplex connection.
+pub fn end_of_turn_into<D: Extend<u8>>(dst: &mut D) {
+    command_into(Command::GA, dst);
+}
+
+/// Append `IAC <command> <option>` to `dst`, where command is one of WILL, WONT, DO or DONT.
+pub fn negotiate_into<D: Extend<u8>>(command: Command, option: Opt, dst: &mut D) {
+    dst.extend([IAC, command.as_u8(), option.as_u8()]);
+}
+
+/// Append `IAC SB <option> <params> IAC SE` to `dst`, escaping IAC bytes in `params`.
+pub fn subnegotiate_into<D: Extend<u8>>(option: Opt, params: &[u8], dst: &mut D) {
+    dst.extend([IAC, Command::SB.as_u8(), option.as_u8()]);
+    escape_into(params, dst);
+    dst.extend([IAC, Command::SE.as_u8()]);
+}
+
+/// Length of the complete telnet sequence at the start of `bytes`
+///
+/// A sequence is a data byte, `IAC <command>`, `IAC <WILL|WONT|DO|DONT> <option>` or a whole
+/// subnegotiation. Returns `None` if `bytes` is empty or ends within the sequence.
+pub fn sequence_len(bytes: &[u8]) -> Option<usize> {
+    match bytes {
+        [] => None,
+        [IAC] => None,
+        [IAC, 250, rest @ ..] => {
+            let mut iac = false;
+            for (i, byte) in rest.iter().enumerate() {
+                match (iac, *byte) {
+                    (true, 240) => return Some(i + 3),
+                    (true, _) => iac = false,
+                    (false, IAC) => iac = true,
+                    (false, _) => (),
+                }
+            }
+            None
+        }
+        [IAC, 251..=254] => None,
+        [IAC, 251..=254, _, ..] => Some(3),
+        [IAC, _, ..] => Some(2),
+        [_, ..] => Some(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        let mut dst = Vec::new();
+        escape_into(&[b'a', 255, b'b', 255], &mut dst);
+        assert_eq!(dst, &[b'a', 255, 255, b'b', 255, 255]);
+    }
+
+    #[test]
+    fn text() {
+        let mut dst = Vec::new();
+        text_into(b"a\nb\r\nc\rd\xff\r", false, &mut dst);
+        assert_eq!(dst, b"a\r\nb\r\nc\r\0d\xff\xff\r\0");
+        dst.clear();
+        text_into(b"a\nb\r\xff", true, &mut dst);
+        assert_eq!(dst, b"a\nb\r\xff\xff");
+    }
+
+    #[test]
+    fn escape_vectored() {
+        let data = [b'a', 255, b'b', 255];
+        let mut slices = Vec::new();
+        escape_slices(&data, &mut slices);
+        let joined: Vec<u8> = slices.iter().flat_map(|s| s.iter().copied()).collect();
+        assert_eq!(joined, &[b'a', 255, 255, b'b', 255, 255]);
+        assert_eq!(slices[0].as_ptr(), data.as_ptr());
+        assert_eq!(slices.len(), 4);
+    }
+
+    #[test]
+    fn sequences() {
+        assert_eq!(sequence_len(b"ab"), Some(1));
+        assert_eq!(sequence_len(&[IAC]), None);
+        assert_eq!(sequence_len(&[IAC, IAC]), Some(2));
+        assert_eq!(sequence_len(&[IAC, 251]), None);
+        assert_eq!(sequence_len(&[IAC, 251, 1, b'a']), Some(3));
+        assert_eq!(sequence_len(&[IAC, 250, 24, IAC, IAC, 240]), None);
+        assert_eq!(sequence_len(&[IAC, 250, 24, 0, IAC, 240, b'a']), Some(6));
+    }
+
+    #[test]
+    fn subnegotiate() {
+        let mut dst = Vec::new();
+        subnegotiate_into(Opt::NAWS, &[0, 80, 0, 255], &mut dst);
+        assert_eq!(dst, &[255, 250, 31, 0, 80, 0, 255, 255, 255, 240]);
+    }
+}
@@ -0,0 +1,100 @@
+//! The error type of the crate
+//!
+//! Conversions of commands and options fail with an [`Error`] directly. The parser and option
+//! negotiation keep errors of their own, [`ParseError`] and [`NegotiatorError`], which are small
+//! and `Copy` for the callbacks and return values of the hot paths; [`Error`] wraps them, with the
+//! original error as its source, so that an application handling several can use `?`
+//! throughout. Encoding into a buffer cannot fail; writing the result can, as an `io::Error`.
+//!
+//! [`ParseError`]: ../enum.ParseError.html
+//! [`NegotiatorError`]: ../q/enum.NegotiatorError.html
+//! [`Error`]: enum.Error.html
+use std::io;
+
+use crate::q::NegotiatorError;
+use crate::ParseError;
+
+/// Any error of the crate
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Malformed input, reported to [`Perform::error`](../trait.Perform.html#method.error)
+    #[error("malformed telnet input")]
+    Parse(#[from] ParseError),
+    /// Negotiation of an option could not be started or was answered wrongly
+    #[error("option negotiation failed")]
+    Negotiation(#[from] NegotiatorError),
+    /// A byte which is not a command
+    #[error("invalid telnet command {0}")]
+    InvalidCommand(u8),
+    /// A name which is not a command
+    #[error("unknown telnet command name {0:?}")]
+    UnknownCommand(String),
+    /// A name which is not an option
+    #[error("unknown telnet option name {0:?}")]
+    UnknownOption(String),
+    /// Reading or writing the connection failed
+    #[error("telnet I/O failed")]
+    Io(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+    use std::io;
+
+    use super::Error;
+    use crate::command::Command;
+    use crate::option::Opt;
+    use crate::q::NegotiatorError;
+    use crate::ParseError;
+
+    #[test]
+    fn from_parse_error() {
+        let err = Error::from(ParseError::InvalidCommand { byte: 0x41 });
+        assert!(matches!(
+            err,
+            Error::Parse(ParseError::InvalidCommand { byte: 0x41 })
+        ));
+        assert_eq!(err.to_string(), "malformed telnet input");
+        assert_eq!(
+            err.source().unwrap().to_string(),
+            ParseError::InvalidCommand { byte: 0x41 }.to_string()
+        );
+    }
+
+    #[test]
+    fn from_negotiator_error() {
+        let err = Error::from(NegotiatorError::AlreadyNegotiating);
+        assert!(matches!(
+            err,
+            Error::Negotiation(NegotiatorError::AlreadyNegotiating)
+        ));
+        assert_eq!(err.to_string(), "option negotiation failed");
+        assert_eq!(
+            err.source().unwrap().to_string(),
+            "option already being negotiated"
+        );
+    }
+
+    #[test]
+    fn from_io_error() {
+        let err = Error::from(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert!(matches!(&err, Error::Io(io) if io.kind() == io::ErrorKind::BrokenPipe));
+        assert_eq!(err.to_string(), "telnet I/O failed");
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn conversions() {
+        let err = Command::from_u8(0x41).unwrap_err();
+        assert!(matches!(err, Error::InvalidCommand(0x41)));
+        assert_eq!(err.to_string(), "invalid telnet command 65");
+        assert!(err.source().is_none());
+
+        let err = "FOO".parse::<Command>().unwrap_err();
+        assert_eq!(err.to_string(), "unknown telnet command name \"FOO\"");
+        let err = "FOO".parse::<Opt>().unwrap_err();
+        assert!(matches!(&err, Error::UnknownOption(name) if name == "FOO"));
+    }
+}
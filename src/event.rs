@@ -0,0 +1,337 @@
+//! Owned parser events
+//!
+//! [`Event`] is an owned representation of the callbacks made by [`Parser`], for consumers which
+//! would rather handle a list of values than implement [`Perform`]. A `Vec<Event>` implements
+//! [`Perform`] by collecting events.
+//!
+//! [`Event`]: enum.Event.html
+//! [`Parser`]: ../struct.Parser.html
+//! [`Perform`]: ../trait.Perform.html
+use std::ops::ControlFlow;
+
+use crate::command::Command;
+use crate::echo::EchoHint;
+use crate::encode;
+use crate::limits::Abuse;
+use crate::option::Opt;
+use crate::status::Discrepancy;
+use crate::{DataEnd, ParseError, Perform, PerformBorrowed};
+
+/// An event produced by the parser
+///
+/// The variants after `Subnegotiate` are never produced by the parser. [`TelnetSession`] adds
+/// them next to the events they were derived from, and they have no wire representation of their
+/// own. More of them may be added, hence `#[non_exhaustive]`.
+///
+/// [`TelnetSession`]: ../session/struct.TelnetSession.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Event {
+    /// Data, with escaped IAC bytes unescaped
+    Data(Vec<u8>),
+    /// Control byte found in the data stream, e.g. CR or LF
+    Execute(u8),
+    /// `IAC <command>`
+    Command(Command),
+    /// `IAC <WILL|WONT|DO|DONT> <option>`
+    Negotiate(Command, Opt),
+    /// `IAC SB <option> <params> IAC SE`
+    Subnegotiate(Opt, Vec<u8>),
+    /// The other party asked to end the session with LOGOUT ([RFC 727]), after the negotiation
+    ///
+    /// [RFC 727]: https://www.rfc-editor.org/rfc/rfc727.html
+    LogoutRequested,
+    /// The other party ended its turn with `IAC GA`, which it sends while it does not suppress
+    /// go ahead ([RFC 858]), after the GA command
+    ///
+    /// [RFC 858]: https://www.rfc-editor.org/rfc/rfc858.html
+    EndOfTurn,
+    /// The other party exceeded a limit, in place of the event exceeding it, see
+    /// [`limits`](../limits/index.html)
+    ProtocolAbuse(Abuse),
+    /// A STATUS report of the other party disagrees with us on an option, after the report, see
+    /// [`status`](../status/index.html)
+    StatusMismatch(Discrepancy),
+    /// The negotiation before it changed how typed input should be shown, so data before it was
+    /// sent under the previous hint, see [`echo`](../echo/index.html)
+    EchoChanged(EchoHint),
+}
+
+impl Event {
+    /// Append the wire representation of the event to `dst`, e.g. a `Vec<u8>` or a `BytesMut`.
+    pub fn encode_into<D: Extend<u8>>(&self, dst: &mut D) {
+        match self {
+            Event::Data(data) => encode::escape_into(data, dst),
+            Event::Execute(byte) => dst.extend([*byte]),
+            Event::Command(command) => encode::command_into(*command, dst),
+            Event::Negotiate(command, option) => encode::negotiate_into(*command, *option, dst),
+            Event::Subnegotiate(option, params) => encode::subnegotiate_into(*option, params, dst),
+            // Not on the wire, the negotiation or GA is an event of its own
+            Event::LogoutRequested
+            | Event::EndOfTurn
+            | Event::ProtocolAbuse(_)
+            | Event::StatusMismatch(_)
+            | Event::EchoChanged(_) => (),
+        }
+    }
+
+    /// Make the callback for the event on `performer`, as the parser would
+    ///
+    /// Data is passed as not truncated and ended by a flush.
+    pub fn perform<P: Perform>(&self, performer: &mut P) {
+        match self {
+            Event::Data(data) => performer.data(data, false, DataEnd::Flush),
+            Event::Execute(byte) => performer.execute(*byte),
+            Event::Command(command) => performer.iac_dispatch(command.as_u8()),
+            Event::Negotiate(command, option) => {
+                performer.negotiate_dispatch(command.as_u8(), option.as_u8())
+            }
+            Event::Subnegotiate(option, params) => {
+                // The parser keeps the option byte and the IAC preceding SE
+                let mut subs = Vec::with_capacity(params.len() + 2);
+                subs.push(option.as_u8());
+                encode::escape_into(params, &mut subs);
+                subs.push(255);
+                performer.sub_dispatch(&subs);
+            }
+            Event::LogoutRequested
+            | Event::EndOfTurn
+            | Event::ProtocolAbuse(_)
+            | Event::StatusMismatch(_)
+            | Event::EchoChanged(_) => (),
+        }
+    }
+}
+
+/// Remove escaping of IAC bytes in subnegotiation parameters.
+fn unescape(params: &[u8]) -> Vec<u8> {
+    let mut unescaped = Vec::with_capacity(params.len());
+    let mut iac = false;
+    for byte in params {
+        if *byte == 255 && !iac {
+            iac = true;
+            continue;
+        }
+        iac = false;
+        unescaped.push(*byte);
+    }
+    unescaped
+}
+
+/// Collects borrowed parser callbacks as events, copying them
+impl<'a> PerformBorrowed<'a> for Vec<Event> {
+    fn data(&mut self, data: &'a [u8]) {
+        Perform::data(self, data, false, DataEnd::Flush);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        Perform::execute(self, byte);
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        Perform::iac_dispatch(self, byte);
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        Perform::negotiate_dispatch(self, cmd, opt);
+    }
+
+    fn sub_dispatch(&mut self, subs: &'a [u8]) {
+        Perform::sub_dispatch(self, subs);
+    }
+}
+
+/// Collects parser callbacks as events
+impl Perform for Vec<Event> {
+    fn data(&mut self, intermediates: &[u8], _ignore: bool, _end: DataEnd) {
+        match self.last_mut() {
+            Some(Event::Data(data)) => data.extend_from_slice(intermediates),
+            _ => self.push(Event::Data(intermediates.to_vec())),
+        }
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.push(Event::Execute(byte));
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        match Command::from_u8(byte) {
+            // An escaped IAC is data
+            Ok(Command::IAC) => Perform::data(self, &[byte], false, DataEnd::IacInterrupted),
+            Ok(command) => self.push(Event::Command(command)),
+            Err(_) => debug!("ignoring unknown command {:02x}", byte),
+        }
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        let option = Opt::from_u8(subs[0]);
+        // The IAC preceding SE is kept by the parser
+        let params = match subs[1..].split_last() {
+            Some((255, params)) => params,
+            _ => &subs[1..],
+        };
+        self.push(Event::Subnegotiate(option, unescape(params)));
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        if let Ok(command) = Command::from_u8(cmd) {
+            self.push(Event::Negotiate(command, Opt::from_u8(opt)));
+        }
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+    fn compress_dispatch(&mut self, _state: u8) {}
+
+    fn error(&mut self, error: ParseError) {
+        debug!("{}", error);
+    }
+}
+
+/// Passes events to a closure as each callback produces them, for `Parser::advance_with`
+pub(crate) struct EachEvent<F> {
+    events: Vec<Event>,
+    f: F,
+    stop: bool,
+}
+
+impl<F: FnMut(Event) -> ControlFlow<()>> EachEvent<F> {
+    pub(crate) fn new(f: F) -> EachEvent<F> {
+        EachEvent {
+            events: Vec::with_capacity(1),
+            f,
+            stop: false,
+        }
+    }
+
+    fn pass(&mut self) {
+        for event in self.events.drain(..) {
+            // Events after a break are dropped, as the parser would have stopped before them
+            if !self.stop {
+                self.stop = (self.f)(event).is_break();
+            }
+        }
+    }
+}
+
+impl<F: FnMut(Event) -> ControlFlow<()>> Perform for EachEvent<F> {
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        Perform::data(&mut self.events, intermediates, ignore, end);
+        self.pass();
+    }
+
+    fn execute(&mut self, byte: u8) {
+        Perform::execute(&mut self.events, byte);
+        self.pass();
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        Perform::iac_dispatch(&mut self.events, byte);
+        self.pass();
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        Perform::sub_dispatch(&mut self.events, subs);
+        self.pass();
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        Perform::negotiate_dispatch(&mut self.events, cmd, opt);
+        self.pass();
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+    fn compress_dispatch(&mut self, _state: u8) {}
+
+    fn error(&mut self, error: ParseError) {
+        Perform::error(&mut self.events, error);
+    }
+
+    fn should_break(&mut self) -> bool {
+        self.stop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use super::Event;
+    use crate::command::Command;
+    use crate::option::Opt;
+    use crate::Parser;
+
+    fn parse(bytes: &[u8]) -> Vec<Event> {
+        let mut events = Vec::new();
+        Parser::new().advance_bytes(&mut events, bytes);
+        events
+    }
+
+    #[test]
+    fn data_before_execute() {
+        assert_eq!(
+            parse(b"rs\r\n"),
+            &[
+                Event::Data(b"rs".to_vec()),
+                Event::Execute(b'\r'),
+                Event::Execute(b'\n'),
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_iac_is_data() {
+        assert_eq!(
+            parse(&[b'a', 255, 255, b'b', b'\n']),
+            &[Event::Data(vec![b'a', 255, b'b']), Event::Execute(b'\n')]
+        );
+    }
+
+    #[test]
+    fn each_event_as_parsed() {
+        let mut parser = Parser::new();
+        let mut events = Vec::new();
+        let bytes = [b'a', 255, 255, b'\n', 255, 251, 1, b'b'];
+        let consumed = parser.advance_with(&bytes, |event| {
+            let negotiated = matches!(event, Event::Negotiate(..));
+            events.push(event);
+            if negotiated {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(consumed, 7);
+        assert_eq!(
+            events,
+            &[
+                Event::Data(vec![b'a']),
+                Event::Data(vec![255]),
+                Event::Execute(b'\n'),
+                Event::Negotiate(Command::WILL, Opt::ECHO),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let events = vec![
+            Event::Data(b"hello".to_vec()),
+            Event::Execute(b'\n'),
+            Event::Command(Command::GA),
+            Event::Negotiate(Command::WILL, Opt::TTYPE),
+            Event::Subnegotiate(Opt::TTYPE, vec![1]),
+        ];
+        let mut bytes = Vec::new();
+        for event in &events {
+            event.encode_into(&mut bytes);
+        }
+        assert_eq!(parse(&bytes), events);
+    }
+}
@@ -0,0 +1,305 @@
+//! Generic MUD Communication Protocol
+//!
+//! A GMCP subnegotiation is a message name, e.g. `Char.Vitals`, optionally followed by a space
+//! and JSON data. Clients tell the server which packages they want with `Core.Supports.Set`,
+//! `Core.Supports.Add` and `Core.Supports.Remove`. [`Gmcp`] keeps the announced packages in
+//! line with a declared list and routes incoming messages by package.
+//!
+//! [`Gmcp`]: struct.Gmcp.html
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::encode;
+use crate::event::Event;
+use crate::option::Opt;
+use crate::session::TelnetSession;
+
+/// Split GMCP parameters, without the option byte, into the message name and its data
+///
+/// The data is empty for messages without it. Returns `None` if the message isn't UTF-8.
+pub fn decode(params: &[u8]) -> Option<(&str, &str)> {
+    let message = std::str::from_utf8(params).ok()?;
+    match message.find(|c: char| c.is_ascii_whitespace()) {
+        Some(end) => Some((&message[..end], message[end..].trim())),
+        None => Some((message, "")),
+    }
+}
+
+/// Append a GMCP subnegotiation sending `name` with JSON `data` to `dst`
+pub fn encode_into<D: Extend<u8>>(name: &str, data: &str, dst: &mut D) {
+    let mut params = name.as_bytes().to_vec();
+    if !data.is_empty() {
+        params.push(b' ');
+        params.extend_from_slice(data.as_bytes());
+    }
+    encode::subnegotiate_into(Opt::GMCP, &params, dst);
+}
+
+type Route = Box<dyn FnMut(&str, &str) + Send>;
+
+/// Tracks supported GMCP packages and routes incoming messages
+///
+/// Packages are declared with [`package`](#method.package) and [`remove_package`]. Once the
+/// server performs GMCP the declared packages are announced with `Core.Supports.Set`, and later
+/// changes with `Core.Supports.Add` and `Core.Supports.Remove`. Output is appended to a buffer
+/// passed to each method, to be sent to the server.
+///
+/// GMCP itself is negotiated by the [`TelnetSession`], whose policy has to accept it from the
+/// server.
+///
+/// [`remove_package`]: #method.remove_package
+/// [`TelnetSession`]: ../session/struct.TelnetSession.html
+pub struct Gmcp {
+    declared: BTreeMap<String, u32>,
+    announced: BTreeMap<String, u32>,
+    enabled: bool,
+    routes: Vec<(String, Route)>,
+}
+
+impl Gmcp {
+    pub fn new() -> Gmcp {
+        Gmcp {
+            declared: BTreeMap::new(),
+            announced: BTreeMap::new(),
+            enabled: false,
+            routes: Vec::new(),
+        }
+    }
+
+    /// Declare a package, e.g. `Char` or `Char.Skills`, replacing its version if it was declared
+    pub fn package(&mut self, name: &str, version: u32, out: &mut Vec<u8>) {
+        self.declared.insert(name.to_owned(), version);
+        self.sync(out);
+    }
+
+    /// Stop declaring a package
+    pub fn remove_package(&mut self, name: &str, out: &mut Vec<u8>) {
+        self.declared.remove(name);
+        self.sync(out);
+    }
+
+    /// Call `route` with the name and data of messages in the package `prefix`
+    ///
+    /// A message goes to the route with the longest matching prefix; `Char` matches `Char.Vitals`
+    /// but not `Character`.
+    pub fn route<F>(&mut self, prefix: &str, route: F)
+    where
+        F: FnMut(&str, &str) + Send + 'static,
+    {
+        self.routes.push((prefix.to_owned(), Box::new(route)));
+    }
+
+    /// Observe an event of `session`, following whether the server performs GMCP and routing
+    /// messages
+    pub fn observe(&mut self, session: &TelnetSession, event: &Event, out: &mut Vec<u8>) {
+        self.set_enabled(session.is_remote_enabled(Opt::GMCP), out);
+        if let Event::Subnegotiate(Opt::GMCP, params) = event {
+            self.dispatch(params);
+        }
+    }
+
+    /// Set whether the server performs GMCP, announcing the declared packages when it starts
+    pub fn set_enabled(&mut self, enabled: bool, out: &mut Vec<u8>) {
+        if enabled == self.enabled {
+            return;
+        }
+        self.enabled = enabled;
+        if enabled {
+            self.sync(out);
+        } else {
+            self.announced.clear();
+        }
+    }
+
+    /// Route a message, without the option byte
+    ///
+    /// Returns false if the message isn't in an announced package or no route matches.
+    pub fn dispatch(&mut self, params: &[u8]) -> bool {
+        let (name, data) = match decode(params) {
+            Some(message) => message,
+            None => return false,
+        };
+        if !self.is_announced(name) {
+            return false;
+        }
+        let route = self
+            .routes
+            .iter_mut()
+            .filter(|(prefix, _)| in_package(name, prefix))
+            .max_by_key(|(prefix, _)| prefix.len());
+        match route {
+            Some((_, route)) => {
+                route(name, data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns true if the message belongs to an announced package
+    ///
+    /// `Core` messages are always announced.
+    pub fn is_announced(&self, message: &str) -> bool {
+        in_package(message, "Core")
+            || self
+                .announced
+                .keys()
+                .any(|package| in_package(message, package))
+    }
+
+    /// Packages announced to the server, with their versions
+    pub fn announced(&self) -> &BTreeMap<String, u32> {
+        &self.announced
+    }
+
+    /// Returns true while the server performs GMCP
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn sync(&mut self, out: &mut Vec<u8>) {
+        if !self.enabled || self.declared == self.announced {
+            return;
+        }
+        if self.announced.is_empty() {
+            let set = package_list(self.declared.iter().map(|(name, v)| (name, Some(*v))));
+            encode_into("Core.Supports.Set", &set, out);
+        } else {
+            let removed: Vec<_> = (self.announced.keys())
+                .filter(|name| !self.declared.contains_key(*name))
+                .map(|name| (name, None))
+                .collect();
+            if !removed.is_empty() {
+                encode_into("Core.Supports.Remove", &package_list(removed), out);
+            }
+            let added: Vec<_> = (self.declared.iter())
+                .filter(|(name, v)| self.announced.get(*name) != Some(v))
+                .map(|(name, v)| (name, Some(*v)))
+                .collect();
+            if !added.is_empty() {
+                encode_into("Core.Supports.Add", &package_list(added), out);
+            }
+        }
+        self.announced = self.declared.clone();
+    }
+}
+
+impl Default for Gmcp {
+    fn default() -> Gmcp {
+        Gmcp::new()
+    }
+}
+
+impl fmt::Debug for Gmcp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Gmcp")
+            .field("declared", &self.declared)
+            .field("announced", &self.announced)
+            .field("enabled", &self.enabled)
+            .field(
+                "routes",
+                &self.routes.iter().map(|(p, _)| p).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Returns true if `message` is `package` or one of its messages, ignoring case
+fn in_package(message: &str, package: &str) -> bool {
+    message.len() >= package.len()
+        && message.as_bytes()[..package.len()].eq_ignore_ascii_case(package.as_bytes())
+        && matches!(message.as_bytes().get(package.len()), None | Some(b'.'))
+}
+
+/// A JSON array of package names, with a version if given
+fn package_list<'a, I>(packages: I) -> String
+where
+    I: IntoIterator<Item = (&'a String, Option<u32>)>,
+{
+    let mut json = String::from("[");
+    for (i, (name, version)) in packages.into_iter().enumerate() {
+        if i > 0 {
+            json.push_str(", ");
+        }
+        json.push('"');
+        for c in name.chars() {
+            if c == '"' || c == '\\' {
+                json.push('\\');
+            }
+            json.push(c);
+        }
+        if let Some(version) = version {
+            json.push_str(&format!(" {}", version));
+        }
+        json.push('"');
+    }
+    json.push(']');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{decode, Gmcp};
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::session::{Policy, TelnetSession};
+
+    #[test]
+    fn supports_and_routes() {
+        assert_eq!(
+            decode(b"Char.Vitals { \"hp\": 10 }"),
+            Some(("Char.Vitals", "{ \"hp\": 10 }"))
+        );
+
+        let mut session = TelnetSession::new(Policy::new().accept_remote(Opt::GMCP));
+        let mut gmcp = Gmcp::new();
+        let mut out = Vec::new();
+        gmcp.package("Char", 1, &mut out);
+        assert!(out.is_empty());
+
+        // The session answers the offer, the packages are announced once it is accepted
+        let events: Vec<Event> = session.feed(&[255, 251, 201]).collect();
+        for event in &events {
+            gmcp.observe(&session, event, &mut out);
+        }
+        assert_eq!(session.take_output(), &[255, 253, 201]);
+        let mut expected = vec![255, 250, 201];
+        expected.extend_from_slice(b"Core.Supports.Set [\"Char 1\"]");
+        expected.extend_from_slice(&[255, 240]);
+        assert_eq!(out, expected);
+        out.clear();
+
+        gmcp.package("Room", 1, &mut out);
+        gmcp.remove_package("Char", &mut out);
+        assert!(out.ends_with(b"Core.Supports.Remove [\"Char\"]\xff\xf0"));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let route_seen = seen.clone();
+        gmcp.route("Room", move |name, data| {
+            route_seen
+                .lock()
+                .unwrap()
+                .push((name.to_owned(), data.to_owned()));
+        });
+        assert!(gmcp.dispatch(b"room.info {}"));
+        assert!(!gmcp.dispatch(b"Char.Vitals {}"));
+        assert!(!gmcp.dispatch(b"Rooms.Info {}"));
+        assert_eq!(
+            *seen.lock().unwrap(),
+            &[("room.info".to_owned(), "{}".to_owned())]
+        );
+
+        // Withdrawn GMCP forgets the announced packages, which are announced again when offered
+        out.clear();
+        let events: Vec<Event> = session.feed(&[255, 252, 201]).collect();
+        for event in &events {
+            gmcp.observe(&session, event, &mut out);
+        }
+        assert!(!gmcp.is_enabled() && gmcp.announced().is_empty());
+        assert!(out.is_empty());
+        gmcp.set_enabled(true, &mut out);
+        assert!(out.ends_with(b"Core.Supports.Set [\"Room 1\"]\xff\xf0"));
+    }
+}
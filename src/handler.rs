@@ -0,0 +1,294 @@
+//! Per option handlers
+//!
+//! An [`OptionHandler`] registered with [`TelnetSession::register_handler`] is told when its
+//! option is enabled or disabled on either side, and gets the subnegotiations of the option. It
+//! answers through a [`Reply`]. Whether an option is enabled at all is still up to the
+//! [`Policy`] of the session.
+//!
+//! Handlers for TTYPE, NAWS, CHARSET and SNDLOC are included:
+//!
+//! ```
+//! use televerknet::handler::{Charset, TerminalType, WindowSize};
+//! use televerknet::option::Opt;
+//! use televerknet::session::{Policy, TelnetSession};
+//!
+//! let policy = Policy::new()
+//!     .accept_local(Opt::TTYPE)
+//!     .accept_local(Opt::NAWS)
+//!     .accept_local(Opt::CHARSET);
+//! let mut session = TelnetSession::new(policy);
+//! session.register_handler(Opt::TTYPE, TerminalType::new(&[b"XTERM", b"ANSI"]));
+//! session.register_handler(Opt::NAWS, WindowSize::new(80, 24));
+//! session.register_handler(Opt::CHARSET, Charset::new(&[b"UTF-8"]));
+//! ```
+//!
+//! [`OptionHandler`]: trait.OptionHandler.html
+//! [`Reply`]: struct.Reply.html
+//! [`TelnetSession::register_handler`]: ../session/struct.TelnetSession.html#method.register_handler
+//! [`Policy`]: ../session/struct.Policy.html
+use crate::charset;
+use crate::encode;
+use crate::event::Event;
+#[cfg(feature = "mud")]
+use crate::mtts::Mtts;
+use crate::option::Opt;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+/// Output of a handler, queued as output of the session
+pub struct Reply<'a> {
+    option: Opt,
+    output: &'a mut Vec<u8>,
+}
+
+impl<'a> Reply<'a> {
+    pub(crate) fn new(option: Opt, output: &'a mut Vec<u8>) -> Reply<'a> {
+        Reply { option, output }
+    }
+
+    /// The option of the handler
+    pub fn option(&self) -> Opt {
+        self.option
+    }
+
+    /// Send a subnegotiation of the handler's option, escaping IAC bytes in `params`
+    pub fn subnegotiate(&mut self, params: &[u8]) {
+        encode::subnegotiate_into(self.option, params, self.output);
+    }
+
+    /// Send any event
+    pub fn send(&mut self, event: &Event) {
+        event.encode_into(self.output);
+    }
+}
+
+/// Lifecycle of one option
+///
+/// All methods do nothing by default.
+pub trait OptionHandler: Send {
+    /// We started performing the option
+    fn on_local_enable(&mut self, _reply: &mut Reply<'_>) {}
+
+    /// We stopped performing the option
+    fn on_local_disable(&mut self, _reply: &mut Reply<'_>) {}
+
+    /// The other party started performing the option
+    fn on_remote_enable(&mut self, _reply: &mut Reply<'_>) {}
+
+    /// The other party stopped performing the option
+    fn on_remote_disable(&mut self, _reply: &mut Reply<'_>) {}
+
+    /// A subnegotiation of the option, with IAC bytes in `params` unescaped
+    fn on_subnegotiation(&mut self, _params: &[u8], _reply: &mut Reply<'_>) {}
+}
+
+/// Answers TTYPE SEND, cycling through terminal types as described in RFC 1091
+///
+/// The last type is sent twice to tell the server that the list ended, then the list starts
+/// over.
+pub struct TerminalType {
+    names: Vec<Vec<u8>>,
+    next: usize,
+}
+
+impl TerminalType {
+    /// Handler sending `names`, the most specific first
+    pub fn new(names: &[&[u8]]) -> TerminalType {
+        TerminalType {
+            names: names.iter().map(|name| name.to_vec()).collect(),
+            next: 0,
+        }
+    }
+
+    /// Handler sending the client name, the terminal type and `MTTS <n>`, as MUD clients do
+    #[cfg(feature = "mud")]
+    pub fn mtts(client: &[u8], terminal_type: &[u8], mtts: Mtts) -> TerminalType {
+        TerminalType::new(&[client, terminal_type, &mtts.to_terminal_type()])
+    }
+}
+
+impl OptionHandler for TerminalType {
+    fn on_local_disable(&mut self, _reply: &mut Reply<'_>) {
+        self.next = 0;
+    }
+
+    fn on_subnegotiation(&mut self, params: &[u8], reply: &mut Reply<'_>) {
+        if params != [TTYPE_SEND] || self.names.is_empty() {
+            return;
+        }
+        let mut is = vec![TTYPE_IS];
+        is.extend_from_slice(&self.names[self.next.min(self.names.len() - 1)]);
+        self.next = if self.next >= self.names.len() {
+            0
+        } else {
+            self.next + 1
+        };
+        reply.subnegotiate(&is);
+    }
+}
+
+/// Sends the window size once NAWS is enabled
+pub struct WindowSize {
+    width: u16,
+    height: u16,
+}
+
+impl WindowSize {
+    pub fn new(width: u16, height: u16) -> WindowSize {
+        WindowSize { width, height }
+    }
+}
+
+impl OptionHandler for WindowSize {
+    fn on_local_enable(&mut self, reply: &mut Reply<'_>) {
+        let mut params = Vec::with_capacity(4);
+        params.extend_from_slice(&self.width.to_be_bytes());
+        params.extend_from_slice(&self.height.to_be_bytes());
+        reply.subnegotiate(&params);
+    }
+}
+
+/// Sends the location of the client once SNDLOC is enabled, as described in [RFC 779]
+///
+/// [RFC 779]: https://www.rfc-editor.org/rfc/rfc779.html
+pub struct SendLocation {
+    location: Vec<u8>,
+}
+
+impl SendLocation {
+    /// Handler sending `location`, e.g. `b"Building 4, rack 12"`
+    pub fn new(location: &[u8]) -> SendLocation {
+        SendLocation {
+            location: location.to_vec(),
+        }
+    }
+}
+
+impl OptionHandler for SendLocation {
+    fn on_local_enable(&mut self, reply: &mut Reply<'_>) {
+        reply.subnegotiate(&self.location);
+    }
+}
+
+/// Passes the location sent by a client with SNDLOC to `F`
+pub struct ReceiveLocation<F> {
+    callback: F,
+}
+
+impl<F: FnMut(&[u8]) + Send> ReceiveLocation<F> {
+    pub fn new(callback: F) -> ReceiveLocation<F> {
+        ReceiveLocation { callback }
+    }
+}
+
+impl<F: FnMut(&[u8]) + Send> OptionHandler for ReceiveLocation<F> {
+    fn on_subnegotiation(&mut self, params: &[u8], _reply: &mut Reply<'_>) {
+        (self.callback)(params);
+    }
+}
+
+type Callback = Box<dyn FnMut(&[u8]) + Send>;
+
+/// Accepts the most preferred character set of a CHARSET request
+pub struct Charset {
+    preferred: Vec<Vec<u8>>,
+    on_accept: Option<Callback>,
+}
+
+impl Charset {
+    /// Handler accepting `preferred`, the most preferred first, and rejecting anything else
+    pub fn new(preferred: &[&[u8]]) -> Charset {
+        Charset {
+            preferred: preferred.iter().map(|name| name.to_vec()).collect(),
+            on_accept: None,
+        }
+    }
+
+    /// Call `callback` with the accepted character set, as spelled in the request
+    pub fn on_accept<F>(mut self, callback: F) -> Charset
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+    {
+        self.on_accept = Some(Box::new(callback));
+        self
+    }
+}
+
+impl OptionHandler for Charset {
+    fn on_subnegotiation(&mut self, params: &[u8], reply: &mut Reply<'_>) {
+        let offered = match charset::Message::parse(params) {
+            Some(charset::Message::Request { charsets, .. }) => charsets,
+            _ => return,
+        };
+        // Accepted as spelled in the request
+        let accepted = self.preferred.iter().find_map(|wanted| {
+            offered
+                .iter()
+                .find(|name| name.eq_ignore_ascii_case(wanted))
+        });
+        let message = match accepted {
+            Some(name) => {
+                if let Some(on_accept) = &mut self.on_accept {
+                    on_accept(name);
+                }
+                charset::Message::Accepted(name.clone())
+            }
+            None => charset::Message::Rejected,
+        };
+        let mut params = Vec::new();
+        message.encode_into(&mut params);
+        reply.subnegotiate(&params);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{ReceiveLocation, SendLocation};
+    use crate::option::Opt;
+    use crate::session::{Policy, TelnetSession};
+    use crate::Parser;
+
+    #[cfg(feature = "mud")]
+    #[test]
+    fn terminal_type_cycle() {
+        use super::{OptionHandler, Reply, TerminalType};
+        use crate::mtts::Mtts;
+
+        let mut handler = TerminalType::mtts(b"MUDLET", b"XTERM", Mtts::ANSI | Mtts::UTF8);
+        let mut output = Vec::new();
+        for _ in 0..5 {
+            handler.on_subnegotiation(&[1], &mut Reply::new(Opt::TTYPE, &mut output));
+        }
+        let sent: Vec<&[u8]> = output
+            .split(|b| *b == 255)
+            .filter_map(|sub| sub.strip_prefix(&[250, 24, 0]))
+            .collect();
+        let expected: &[&[u8]] = &[b"MUDLET", b"XTERM", b"MTTS 5", b"MTTS 5", b"MUDLET"];
+        assert_eq!(sent, expected);
+    }
+
+    #[test]
+    fn send_location() {
+        let mut client = TelnetSession::new(Policy::new().accept_local(Opt::SNDLOC));
+        client.register_handler(Opt::SNDLOC, SendLocation::new(b"Lab \xff 2"));
+        let mut server = TelnetSession::new(Policy::new().accept_remote(Opt::SNDLOC))
+            .parser(Parser::builder().max_subnegotiation(64).build());
+        let location = Arc::new(Mutex::new(Vec::new()));
+        let received = location.clone();
+        server.register_handler(
+            Opt::SNDLOC,
+            ReceiveLocation::new(move |params: &[u8]| {
+                *received.lock().unwrap() = params.to_vec();
+            }),
+        );
+
+        client.feed(&[255, 253, 23]).for_each(drop);
+        let output = client.take_output();
+        assert_eq!(output, b"\xff\xfb\x17\xff\xfa\x17Lab \xff\xff 2\xff\xf0");
+        server.feed(&output).for_each(drop);
+        assert_eq!(*location.lock().unwrap(), b"Lab \xff 2");
+    }
+}
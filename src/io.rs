@@ -0,0 +1,372 @@
+//! Blocking `std::io` adapters
+//!
+//! [`TelnetReader`] strips telnet commands from a `Read`, and [`TelnetWriter`] escapes data
+//! written to a `Write`. [`NvtWriter`] also translates line endings for the network virtual
+//! terminal. None of them answers negotiation, see [`TelnetSession`] for that.
+//!
+//! [`TelnetReader`]: struct.TelnetReader.html
+//! [`TelnetWriter`]: struct.TelnetWriter.html
+//! [`NvtWriter`]: struct.NvtWriter.html
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+use std::io::{self, IoSlice, Read, Write};
+
+use crate::encode;
+use crate::event::Event;
+use crate::Parser;
+
+const READ_BUF_SIZE: usize = 4096;
+
+/// Reads application data, stripping telnet commands
+pub struct TelnetReader<R> {
+    inner: R,
+    parser: Parser,
+    read_buf: Box<[u8]>,
+    readable: Vec<u8>,
+    readable_pos: usize,
+    parsed: Vec<Event>,
+    events: Vec<Event>,
+}
+
+impl<R: Read> TelnetReader<R> {
+    pub fn new(inner: R) -> TelnetReader<R> {
+        TelnetReader::with_parser(inner, Parser::new())
+    }
+
+    /// A reader parsing with `parser`, e.g. one with a larger subnegotiation buffer
+    pub fn with_parser(inner: R, parser: Parser) -> TelnetReader<R> {
+        TelnetReader {
+            inner,
+            parser,
+            read_buf: vec![0u8; READ_BUF_SIZE].into_boxed_slice(),
+            readable: Vec::new(),
+            readable_pos: 0,
+            parsed: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Take the events read so far which are not application data, e.g. negotiation
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for TelnetReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.readable_pos < self.readable.len() {
+                let readable = &self.readable[self.readable_pos..];
+                let n = readable.len().min(buf.len());
+                buf[..n].copy_from_slice(&readable[..n]);
+                self.readable_pos += n;
+                if self.readable_pos == self.readable.len() {
+                    self.readable.clear();
+                    self.readable_pos = 0;
+                }
+                return Ok(n);
+            }
+
+            let n = self.inner.read(&mut self.read_buf)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.parser
+                .advance_bytes(&mut self.parsed, &self.read_buf[..n]);
+            self.parser.flush(&mut self.parsed);
+            for event in self.parsed.drain(..) {
+                match event {
+                    Event::Data(data) => self.readable.extend_from_slice(&data),
+                    Event::Execute(byte) => self.readable.push(byte),
+                    event => self.events.push(event),
+                }
+            }
+        }
+    }
+}
+
+/// Write `data` to `writer` with every IAC byte doubled, using vectored writes
+///
+/// The data is not copied: runs between IAC bytes are written from `data` itself, see
+/// [`encode::escape_slices`](../encode/fn.escape_slices.html).
+pub fn write_escaped<W: Write + ?Sized>(writer: &mut W, data: &[u8]) -> io::Result<()> {
+    let mut slices = Vec::new();
+    encode::escape_slices(data, &mut slices);
+    write_all_vectored(writer, &mut slices)
+}
+
+/// Write all of `slices`, continuing after partial writes
+pub fn write_all_vectored<W: Write + ?Sized>(
+    writer: &mut W,
+    mut slices: &mut [IoSlice<'_>],
+) -> io::Result<()> {
+    while !slices.is_empty() {
+        let mut n = match writer.write_vectored(slices) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        while n > 0 && n >= slices[0].len() {
+            n -= slices[0].len();
+            slices = &mut slices[1..];
+        }
+        if n > 0 {
+            // Finish the slice written in part
+            writer.write_all(&slices[0][n..])?;
+            slices = &mut slices[1..];
+        }
+    }
+    Ok(())
+}
+
+/// Writes application data, escaping IAC bytes
+pub struct TelnetWriter<W> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> TelnetWriter<W> {
+    pub fn new(inner: W) -> TelnetWriter<W> {
+        TelnetWriter {
+            inner,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Write an event, e.g. a command or negotiation, as is
+    pub fn send(&mut self, event: &Event) -> io::Result<()> {
+        self.buf.clear();
+        event.encode_into(&mut self.buf);
+        self.inner.write_all(&self.buf)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for TelnetWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write_escaped(&mut self.inner, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// How [`NvtWriter`] translates line endings
+///
+/// [`NvtWriter`]: struct.NvtWriter.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CrlfPolicy {
+    /// `\n` becomes `CR LF` and a bare `\r` becomes `CR NUL`, as RFC 854 requires
+    CrLf,
+    /// `\n` and a bare `\r` both become `CR NUL`, for peers which treat `CR LF` as two lines
+    CrNul,
+    /// No translation, once BINARY is enabled for sending
+    Binary,
+}
+
+/// Writes application data following the rules of the network virtual terminal
+///
+/// IAC bytes are doubled in every mode, as RFC 856 requires even in binary mode. Line endings
+/// are translated according to a [`CrlfPolicy`]; a `\r\n` already in the data stays one line
+/// ending. A `\r` at the end of a write waits for the next byte, or is ended by `flush`.
+///
+/// [`CrlfPolicy`]: enum.CrlfPolicy.html
+pub struct NvtWriter<W> {
+    inner: W,
+    policy: CrlfPolicy,
+    after_cr: bool,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> NvtWriter<W> {
+    pub fn new(inner: W, policy: CrlfPolicy) -> NvtWriter<W> {
+        NvtWriter {
+            inner,
+            policy,
+            after_cr: false,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Change the policy, e.g. to `Binary` once BINARY was negotiated
+    pub fn set_policy(&mut self, policy: CrlfPolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> CrlfPolicy {
+        self.policy
+    }
+
+    /// Write an event, e.g. a command or negotiation, as is
+    pub fn send(&mut self, event: &Event) -> io::Result<()> {
+        self.buf.clear();
+        self.end_cr();
+        event.encode_into(&mut self.buf);
+        self.inner.write_all(&self.buf)
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// End a pending `\r` which is not followed by `\n`
+    fn end_cr(&mut self) {
+        if self.after_cr {
+            self.buf.push(0);
+            self.after_cr = false;
+        }
+    }
+}
+
+impl<W: Write> Write for NvtWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.clear();
+        for &byte in buf {
+            match (self.policy, byte) {
+                (CrlfPolicy::Binary, _) => encode::escape_into(&[byte], &mut self.buf),
+                (_, b'\n') if self.after_cr => {
+                    let end = if self.policy == CrlfPolicy::CrLf {
+                        b'\n'
+                    } else {
+                        0
+                    };
+                    self.buf.push(end);
+                    self.after_cr = false;
+                }
+                (CrlfPolicy::CrLf, b'\n') => self.buf.extend_from_slice(b"\r\n"),
+                (CrlfPolicy::CrNul, b'\n') => self.buf.extend_from_slice(b"\r\0"),
+                (_, b'\r') => {
+                    self.end_cr();
+                    self.buf.push(b'\r');
+                    self.after_cr = true;
+                }
+                _ => {
+                    self.end_cr();
+                    encode::escape_into(&[byte], &mut self.buf);
+                }
+            }
+        }
+        self.inner.write_all(&self.buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.buf.clear();
+        self.end_cr();
+        self.inner.write_all(&self.buf)?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_escaped, CrlfPolicy, NvtWriter, TelnetReader, TelnetWriter};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::{CrHandling, Parser};
+    use std::io::{self, Read, Write};
+
+    #[test]
+    fn reader_strips_commands() {
+        let input: &[u8] = &[b'a', 255, 251, 1, b'b', 255, 255, b'\r', b'\n'];
+        let mut reader = TelnetReader::new(input);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, &[b'a', b'b', 255, b'\r', b'\n']);
+        assert_eq!(
+            reader.take_events(),
+            &[Event::Negotiate(Command::WILL, Opt::ECHO)]
+        );
+    }
+
+    #[test]
+    fn reader_with_parser() {
+        let input: &[u8] = b"a\r\0b\r\n";
+        let parser = Parser::builder().cr_handling(CrHandling::StripNul).build();
+        let mut reader = TelnetReader::with_parser(input, parser);
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"a\rb\r\n");
+    }
+
+    #[test]
+    fn writer_escapes() {
+        let mut writer = TelnetWriter::new(Vec::new());
+        writer.write_all(&[b'a', 255]).unwrap();
+        writer.send(&Event::Command(Command::GA)).unwrap();
+        assert_eq!(writer.into_inner(), &[b'a', 255, 255, 255, 249]);
+    }
+
+    #[test]
+    fn vectored_partial_writes() {
+        /// Takes at most three bytes per write
+        struct Trickle(Vec<u8>);
+
+        impl Write for Trickle {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                let n = buf.len().min(3);
+                self.0.extend_from_slice(&buf[..n]);
+                Ok(n)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut trickle = Trickle(Vec::new());
+        write_escaped(&mut trickle, b"abcd\xffef\xff").unwrap();
+        assert_eq!(trickle.0, b"abcd\xff\xffef\xff\xff");
+    }
+
+    #[test]
+    fn nvt_line_endings() {
+        let mut writer = NvtWriter::new(Vec::new(), CrlfPolicy::CrLf);
+        writer.write_all(b"a\nb\r\nc\r").unwrap();
+        writer.write_all(b"\n\rd").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(writer.get_ref(), b"a\r\nb\r\nc\r\n\r\0d");
+
+        let mut writer = NvtWriter::new(Vec::new(), CrlfPolicy::CrNul);
+        writer.write_all(b"a\nb\r\n").unwrap();
+        assert_eq!(writer.get_ref(), b"a\r\0b\r\0");
+
+        writer.set_policy(CrlfPolicy::Binary);
+        writer.write_all(&[b'\n', 255]).unwrap();
+        assert_eq!(writer.into_inner(), b"a\r\0b\r\0\n\xff\xff");
+    }
+}
@@ -0,0 +1,98 @@
+//! Keepalive with `IAC NOP`
+//!
+//! NAT devices and firewalls drop connections which are idle for too long. [`Keepalive`] sends
+//! `IAC NOP` when nothing was sent for a while, and tells when the peer has been silent too long.
+//! It doesn't keep time itself: the caller passes the time elapsed since the last tick.
+//!
+//! [`Keepalive`]: struct.Keepalive.html
+use std::time::Duration;
+
+use crate::command::Command;
+use crate::encode;
+
+/// Sends `IAC NOP` after an idle period and detects silent peers
+#[derive(Debug, Clone)]
+pub struct Keepalive {
+    idle: Duration,
+    timeout: Option<Duration>,
+    since_sent: Duration,
+    since_received: Duration,
+}
+
+impl Keepalive {
+    /// Send `IAC NOP` once nothing was sent for `idle`
+    pub fn new(idle: Duration) -> Keepalive {
+        Keepalive {
+            idle,
+            timeout: None,
+            since_sent: Duration::from_secs(0),
+            since_received: Duration::from_secs(0),
+        }
+    }
+
+    /// Consider the peer gone once nothing was received for `timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Keepalive {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Note that something was sent to the peer
+    pub fn sent(&mut self) {
+        self.since_sent = Duration::from_secs(0);
+    }
+
+    /// Note that something was received from the peer
+    pub fn received(&mut self) {
+        self.since_received = Duration::from_secs(0);
+    }
+
+    /// Advance the timers by `elapsed`, appending `IAC NOP` to `out` when the connection was idle
+    ///
+    /// Returns true if the peer has been silent for longer than the timeout.
+    pub fn tick(&mut self, elapsed: Duration, out: &mut Vec<u8>) -> bool {
+        self.since_sent += elapsed;
+        self.since_received += elapsed;
+        if self.since_sent >= self.idle {
+            encode::command_into(Command::NOP, out);
+            self.sent();
+        }
+        self.is_timed_out()
+    }
+
+    /// Returns true if the peer has been silent for longer than the timeout
+    pub fn is_timed_out(&self) -> bool {
+        matches!(self.timeout, Some(timeout) if self.since_received > timeout)
+    }
+
+    /// Time since something was received from the peer
+    pub fn silent_for(&self) -> Duration {
+        self.since_received
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Keepalive;
+
+    #[test]
+    fn nop_and_timeout() {
+        let secs = Duration::from_secs;
+        let mut keepalive = Keepalive::new(secs(30)).timeout(secs(60));
+        let mut out = Vec::new();
+
+        assert!(!keepalive.tick(secs(20), &mut out));
+        assert!(out.is_empty());
+        keepalive.sent();
+        assert!(!keepalive.tick(secs(20), &mut out));
+        assert!(out.is_empty());
+        assert!(!keepalive.tick(secs(10), &mut out));
+        assert_eq!(out, &[255, 241]);
+
+        keepalive.received();
+        assert!(!keepalive.tick(secs(60), &mut out));
+        assert!(keepalive.tick(secs(1), &mut out));
+        assert_eq!(keepalive.silent_for(), secs(61));
+    }
+}
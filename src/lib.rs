@@ -1,4 +1,4 @@
-//! Parser for implementing telnet clients
+//! Parser for implementing telnet clients and servers
 //!
 //! [`Parser`] is implemented based on [Joe Wilm's vte library] and uses a state machine which is
 //! heavily influenced by [Paul Williams' ANSI parser state machine].
@@ -8,18 +8,111 @@
 //! [Paul Williams' ANSI parser state machine]: https://vt100.net/emu/dec_ansi_parser
 extern crate log;
 
+use std::fmt;
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+pub use crate::error::Error;
+
+// Log through tracing when the feature is enabled, and through log otherwise
+macro_rules! debug {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        log::debug!($($arg)*);
+    }};
+}
+
+// Per byte logging, compiled out unless the trace-parser feature is enabled
+macro_rules! trace {
+    ($($arg:tt)*) => {{
+        #[cfg(all(feature = "trace-parser", feature = "tracing"))]
+        tracing::trace!($($arg)*);
+        #[cfg(all(feature = "trace-parser", not(feature = "tracing")))]
+        log::trace!($($arg)*);
+    }};
+}
+
+#[cfg(feature = "ansi")]
+pub mod ansi;
+pub mod audit;
+#[cfg(feature = "bridge")]
+pub mod bridge;
+#[cfg(feature = "bytes")]
+pub mod buf;
+#[cfg(feature = "capture")]
+pub mod capture;
+pub mod channel;
+pub mod charset;
+pub mod client;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod combinator;
 pub mod command;
+#[cfg(feature = "conformance")]
+pub mod conformance;
+pub mod discipline;
+pub mod dump;
+pub mod echo;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+pub mod encode;
+pub mod error;
+pub mod event;
+#[cfg(feature = "mud")]
+pub mod gmcp;
+pub mod handler;
+pub mod io;
+pub mod keepalive;
+pub mod limits;
+pub mod line;
+#[cfg(feature = "linemode")]
+pub mod linemode;
+pub mod manager;
+#[cfg(feature = "mud")]
+pub mod mcp;
+pub mod message;
+#[cfg(feature = "mud")]
+pub mod msdp;
+#[cfg(feature = "mud")]
+pub mod mssp;
+#[cfg(feature = "mud")]
+pub mod mtts;
+pub mod naws;
 pub mod option;
+pub mod profile;
+pub mod prompt;
+pub mod proxy;
 pub mod q;
-
+pub mod record;
+pub mod sanitize;
+pub mod session;
+pub mod stats;
+pub mod status;
+#[cfg(feature = "tokio")]
+pub mod stream;
+#[cfg(feature = "vte")]
+pub mod terminal;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod timing;
+#[cfg(feature = "tn3270e")]
+pub mod tn3270e;
+pub mod utf8;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 const MAX_INTERMEDIATES: usize = 1024;
-const MAX_SUBS: usize = 8;
+// Fits terminal types, NEW-ENVIRON lists and CHARSET requests, while MUD protocols need more
+const MAX_SUBS: usize = 1024;
 // const MAX_PARAMS: usize = 16;
 
 // TODO: add data to enums?
 #[allow(dead_code)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum State {
     // This isn't a real state.
     // Anywhere,
@@ -39,10 +132,15 @@ pub enum State {
     SubEntry,
     // SubIntermediate is transition to from SubEntry.
     SubIntermediate,
+    // SubIac is entered on IAC within a subnegotiation, which ends it with SE or escapes an IAC.
+    SubIac,
+    // SubNegEntry is entered from SubIac for WILL, WONT, DO and DONT.
+    SubNegEntry,
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Action {
     None,
     Clear,
@@ -55,20 +153,130 @@ pub enum Action {
     SubStart,
     SubPut,
     SubDispatch,
+    SubInterrupt,
+    SubNegStart,
+    SubAbort,
     Ignore,
 }
 
+const STATES: [State; 8] = [
+    State::Ground,
+    State::Data,
+    State::IacEntry,
+    State::NegEntry,
+    State::SubEntry,
+    State::SubIntermediate,
+    State::SubIac,
+    State::SubNegEntry,
+];
+
+/// Transitions of every state for every byte
+pub type TransitionTable = [[(State, Action); 256]; STATES.len()];
+
+/// Transition tables indexed by `binary * 2 + strict`
+static TRANSITIONS: [TransitionTable; 4] = [
+    build_table(false, false),
+    build_table(false, true),
+    build_table(true, false),
+    build_table(true, true),
+];
+
+const fn build_table(binary: bool, strict: bool) -> TransitionTable {
+    let mut table = [[(State::Ground, Action::None); 256]; STATES.len()];
+    let mut state = 0;
+    while state < STATES.len() {
+        let mut byte = 0;
+        while byte < 256 {
+            table[state][byte] = STATES[state].transition(byte as u8, binary, strict);
+            byte += 1;
+        }
+        state += 1;
+    }
+    table
+}
+
 impl State {
+    /// Next state and action for `byte`, with BINARY enabled for the other party or not and in
+    /// strict mode or not
+    ///
+    /// The NUL of `CR NUL` is the only byte depending on more than this, see
+    /// [`CrHandling`](enum.CrHandling.html).
+    pub const fn transition(self, byte: u8, binary: bool, strict: bool) -> (State, Action) {
+        match self {
+            State::Ground | State::Data => {
+                match byte {
+                    // Non-printable bytes, CR included
+                    0x00..=0x1f => (State::Data, Action::Execute),
+                    // Collect printable characters
+                    0x20..=0x7f => (State::Ground, Action::Collect),
+                    // Data in binary mode, various commands otherwise
+                    0x80..=0xfe if binary => (State::Ground, Action::Collect),
+                    0x80..=0xfe => (State::Data, Action::Execute),
+                    // Beginning of IAC sequence
+                    0xff => (State::IacEntry, Action::None),
+                }
+            }
+            State::IacEntry => {
+                match byte {
+                    // Beginning of subnegotation
+                    0xfa => (State::SubEntry, Action::None),
+                    // Beginning of negotation using WILL, WONT, DO or DONT
+                    0xfb..=0xfe => (State::NegEntry, Action::NegStart),
+                    // Not a command
+                    0x00..=0xeb if strict => (State::Ground, Action::Ignore),
+                    // Command to dispatch to interpret
+                    _ => (State::Ground, Action::IacDispatch),
+                }
+            }
+            State::NegEntry => (State::Ground, Action::NegDispatch),
+            State::SubEntry | State::SubIntermediate => {
+                match byte {
+                    // SE right after SB, without option
+                    0xf0 if matches!(self, State::SubEntry) => (State::Ground, Action::SubDispatch),
+                    // End of the parameters, or an escaped IAC. Kept to be dispatched.
+                    0xff => (State::SubIac, Action::SubPut),
+                    // Continuation of subnegotation
+                    _ => (State::SubIntermediate, Action::SubPut),
+                }
+            }
+            State::SubIac => {
+                match byte {
+                    // End of subnegotiation parameters
+                    0xf0 => (State::Ground, Action::SubDispatch),
+                    // Escaped IAC
+                    0xff => (State::SubIntermediate, Action::SubPut),
+                    // A subnegotiation without SE, replaced by a new one
+                    0xfa => (State::SubEntry, Action::SubAbort),
+                    // Negotiation within the subnegotiation
+                    0xfb..=0xfe => (State::SubNegEntry, Action::SubNegStart),
+                    // Any other command within the subnegotiation
+                    0xec..=0xf9 => (State::SubIntermediate, Action::SubInterrupt),
+                    // Not a command, kept as parameters
+                    _ => (State::SubIntermediate, Action::SubPut),
+                }
+            }
+            State::SubNegEntry => (State::SubIntermediate, Action::NegDispatch),
+        }
+    }
+
+    /// The table of [`transition`](#method.transition) for every state and byte, indexed by
+    /// `state as usize` and the byte, e.g. to dump it
+    pub fn table(binary: bool, strict: bool) -> &'static TransitionTable {
+        &TRANSITIONS[usize::from(binary) * 2 + usize::from(strict)]
+    }
+
     /// Get entry action for this state
     #[inline(always)]
     pub fn entry_action(&self) -> Action {
         match self {
             State::Ground => Action::None,
-            State::Data => Action::DataDispatch,
-            State::IacEntry => Action::DataDispatch,
+            State::Data => Action::None,
+            State::IacEntry => Action::None,
             State::NegEntry => Action::None,
             State::SubEntry => Action::SubStart,
             State::SubIntermediate => Action::None,
+            State::SubIac => Action::None,
+            State::SubNegEntry => Action::None,
         }
     }
 
@@ -76,42 +284,499 @@ impl State {
     #[inline(always)]
     pub fn exit_action(&self) -> Action {
         match self {
-            State::Ground => Action::None,
+            // Collected data is dispatched before the action of the byte ending it
+            State::Ground => Action::DataDispatch,
             State::Data => Action::Clear,
-            State::IacEntry => Action::Clear,
+            // Data before an IAC is dispatched once the command is known
+            State::IacEntry => Action::DataDispatch,
             State::NegEntry => Action::None,
             State::SubEntry => Action::None,
             State::SubIntermediate => Action::None,
+            State::SubIac => Action::None,
+            State::SubNegEntry => Action::None,
         }
     }
 }
 
+/// What the parser does when data or a subnegotiation does not fit its buffer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Overflow {
+    /// Drop the bytes which do not fit and dispatch the rest
+    Truncate,
+    /// Dispatch the buffered data and continue collecting. Subnegotiations can not be split, so
+    /// they are truncated.
+    Flush,
+    /// Discard the whole data run or subnegotiation
+    Abort,
+}
+
+/// What the parser does with a command interrupting a subnegotiation
+///
+/// Some servers send e.g. `IAC WILL ECHO` between `IAC SB` and `IAC SE`. The command is
+/// dispatched as usual and [`ParseError::InterruptedSubnegotiation`] is reported either way.
+///
+/// [`ParseError::InterruptedSubnegotiation`]: enum.ParseError.html#variant.InterruptedSubnegotiation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SubInterrupt {
+    /// Continue the subnegotiation as if the command was not there
+    Continue,
+    /// Discard the subnegotiation, up to its SE
+    Abort,
+}
+
+/// When the parser dispatches data without waiting for a control byte
+///
+/// Data is always dispatched when a control byte or an IAC ends it. A server sending a prompt
+/// without a newline or GA would leave it buffered, so the policy decides when to deliver it
+/// anyway.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlushPolicy {
+    /// Only on [`Parser::flush`](struct.Parser.html#method.flush)
+    Manual,
+    /// At the end of every [`Parser::advance_bytes`](struct.Parser.html#method.advance_bytes)
+    PerRead,
+    /// As soon as this many bytes are buffered
+    Threshold(usize),
+    /// When [`Parser::idle`](struct.Parser.html#method.idle) is told that no bytes arrived for
+    /// this long
+    Idle(Duration),
+}
+
+/// How the parser treats a carriage return
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CrHandling {
+    /// Execute every byte as received
+    Raw,
+    /// Drop the NUL of `CR NUL`, which stands for a bare carriage return
+    StripNul,
+}
+
+/// What ended data passed to [`Perform::data`](trait.Perform.html#tymethod.data)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DataEnd {
+    /// A carriage return or line feed
+    Newline,
+    /// Any other control byte, e.g. the ESC of an ANSI escape sequence
+    Control,
+    /// `IAC GA`, usually after a prompt
+    GoAhead,
+    /// `IAC EOR`, usually after a prompt
+    Eor,
+    /// The data buffer filled up with [`Overflow::Flush`](enum.Overflow.html#variant.Flush)
+    BufferFull,
+    /// The data was flushed or the input ended, see [`FlushPolicy`](enum.FlushPolicy.html)
+    Flush,
+    /// Any other IAC sequence
+    IacInterrupted,
+}
+
+impl DataEnd {
+    fn control(byte: u8) -> DataEnd {
+        match byte {
+            0x0a | 0x0d => DataEnd::Newline,
+            _ => DataEnd::Control,
+        }
+    }
+
+    fn command(byte: u8) -> DataEnd {
+        match byte {
+            249 => DataEnd::GoAhead,
+            239 => DataEnd::Eor,
+            _ => DataEnd::IacInterrupted,
+        }
+    }
+}
+
+/// Errors reported to [`Perform::error`](trait.Perform.html#method.error)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    /// Data did not fit the data buffer
+    #[error("data truncated")]
+    DataTruncated,
+    /// A subnegotiation for `option` did not fit the subnegotiation buffer
+    ///
+    /// Reported when the subnegotiation ends, right before the truncated subnegotiation is
+    /// dispatched.
+    #[error("subnegotiation of option {option} truncated")]
+    SubnegotiationTruncated { option: u8 },
+    /// IAC was followed by `byte`, which is not a command. Only reported in strict mode.
+    #[error("invalid command {byte}")]
+    InvalidCommand { byte: u8 },
+    /// A subnegotiation ended before its option byte. Only reported in strict mode.
+    #[error("subnegotiation without option")]
+    MissingOption,
+    /// A subnegotiation of `option` was interrupted by `IAC <command>`, see
+    /// [`SubInterrupt`](enum.SubInterrupt.html)
+    #[error("subnegotiation of option {option} interrupted by command {command}")]
+    InterruptedSubnegotiation { option: u8, command: u8 },
+}
+
+/// An incomplete sequence the parser is within, see [`Parser::pending`]
+///
+/// [`Parser::pending`]: struct.Parser.html#method.pending
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pending {
+    /// IAC, waiting for the command
+    Command,
+    /// `IAC <command>`, waiting for the option
+    Negotiation { command: u8 },
+    /// A subnegotiation of `option`, if received, with `len` bytes buffered
+    Subnegotiation { option: Option<u8>, len: usize },
+}
+
+impl fmt::Display for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pending::Command => write!(f, "command"),
+            Pending::Negotiation { command } => write!(f, "negotiation with command {}", command),
+            Pending::Subnegotiation {
+                option: Some(option),
+                ..
+            } => write!(f, "subnegotiation of option {}", option),
+            Pending::Subnegotiation { option: None, .. } => write!(f, "subnegotiation"),
+        }
+    }
+}
+
+/// Builder for a [`Parser`](struct.Parser.html) with non-default settings
+///
+/// ```
+/// use televerknet::{CrHandling, Parser};
+///
+/// let parser = Parser::builder()
+///     .max_subnegotiation(4096)
+///     .cr_handling(CrHandling::StripNul)
+///     .strict(true)
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParserBuilder {
+    max_data: usize,
+    max_subnegotiation: usize,
+    cr_handling: CrHandling,
+    binary_remote: bool,
+    binary_local: bool,
+    strict: bool,
+    overflow: Overflow,
+    sub_interrupt: SubInterrupt,
+    flush_policy: FlushPolicy,
+}
+
+impl Default for ParserBuilder {
+    fn default() -> ParserBuilder {
+        ParserBuilder {
+            max_data: MAX_INTERMEDIATES,
+            max_subnegotiation: MAX_SUBS,
+            cr_handling: CrHandling::Raw,
+            binary_remote: false,
+            binary_local: false,
+            strict: false,
+            overflow: Overflow::Truncate,
+            sub_interrupt: SubInterrupt::Continue,
+            flush_policy: FlushPolicy::Manual,
+        }
+    }
+}
+
+impl ParserBuilder {
+    pub fn new() -> ParserBuilder {
+        ParserBuilder::default()
+    }
+
+    /// Set the size of the data buffer, at least one byte
+    ///
+    /// With the `heapless` feature the size is capped to [`BUFFER_CAPACITY`].
+    ///
+    /// [`BUFFER_CAPACITY`]: constant.BUFFER_CAPACITY.html
+    pub fn max_data(mut self, max_data: usize) -> ParserBuilder {
+        self.max_data = max_data.max(1);
+        self
+    }
+
+    /// Set the size of the subnegotiation buffer, at least one byte and 1 KiB by default
+    ///
+    /// The buffer holds the option byte and the IAC ending the subnegotiation as well as the
+    /// parameters. With the `heapless` feature the size is capped to [`BUFFER_CAPACITY`].
+    ///
+    /// [`BUFFER_CAPACITY`]: constant.BUFFER_CAPACITY.html
+    pub fn max_subnegotiation(mut self, max_subnegotiation: usize) -> ParserBuilder {
+        self.max_subnegotiation = max_subnegotiation.max(1);
+        self
+    }
+
+    /// Set how a carriage return is handled, `Raw` by default
+    pub fn cr_handling(mut self, cr_handling: CrHandling) -> ParserBuilder {
+        self.cr_handling = cr_handling;
+        self
+    }
+
+    /// Start with TRANSMIT-BINARY enabled, see [`Parser::set_binary`]
+    ///
+    /// [`Parser::set_binary`]: struct.Parser.html#method.set_binary
+    pub fn binary(mut self, remote: bool, local: bool) -> ParserBuilder {
+        self.binary_remote = remote;
+        self.binary_local = local;
+        self
+    }
+
+    /// Report malformed sequences through [`Perform::error`] instead of dispatching them
+    ///
+    /// Lenient by default, passing `IAC` followed by a non-command byte to
+    /// [`Perform::iac_dispatch`] and a subnegotiation without option to [`Perform::sub_dispatch`].
+    ///
+    /// [`Perform::error`]: trait.Perform.html#method.error
+    /// [`Perform::iac_dispatch`]: trait.Perform.html#tymethod.iac_dispatch
+    /// [`Perform::sub_dispatch`]: trait.Perform.html#tymethod.sub_dispatch
+    pub fn strict(mut self, strict: bool) -> ParserBuilder {
+        self.strict = strict;
+        self
+    }
+
+    /// Set what to do when data or a subnegotiation overflows, truncating by default
+    pub fn overflow(mut self, overflow: Overflow) -> ParserBuilder {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Set what to do with a subnegotiation interrupted by a command, continuing by default
+    pub fn sub_interrupt(mut self, sub_interrupt: SubInterrupt) -> ParserBuilder {
+        self.sub_interrupt = sub_interrupt;
+        self
+    }
+
+    /// Set when buffered data is dispatched without a control byte ending it
+    pub fn flush_policy(mut self, flush_policy: FlushPolicy) -> ParserBuilder {
+        self.flush_policy = flush_policy;
+        self
+    }
+
+    pub fn build(self) -> Parser {
+        Parser {
+            state: State::Ground,
+            intermediates: buffer(self.max_data),
+            intermediate_idx: 0,
+            neg_command: 0,
+            subs: buffer(self.max_subnegotiation),
+            sub_idx: 0,
+            ignoring: false,
+            sub_ignoring: false,
+            sub_aborted: false,
+            overflow: self.overflow,
+            sub_interrupt: self.sub_interrupt,
+            flush_policy: self.flush_policy,
+            binary_remote: self.binary_remote,
+            binary_local: self.binary_local,
+            cr_handling: self.cr_handling,
+            after_cr: false,
+            strict: self.strict,
+        }
+    }
+}
+
+/// Capacity of each parser buffer with the `heapless` feature
+#[cfg(feature = "heapless")]
+pub const BUFFER_CAPACITY: usize = 1024;
+
+#[cfg(not(feature = "heapless"))]
+type Buffer = Box<[u8]>;
+#[cfg(feature = "heapless")]
+type Buffer = heapless::Vec<u8, BUFFER_CAPACITY>;
+
+#[cfg(not(feature = "heapless"))]
+fn buffer(size: usize) -> Buffer {
+    vec![0u8; size].into_boxed_slice()
+}
+
+#[cfg(feature = "heapless")]
+fn buffer(size: usize) -> Buffer {
+    let mut buffer = Buffer::new();
+    // Never fails, the size is capped to the capacity
+    let _ = buffer.resize(size.min(BUFFER_CAPACITY), 0);
+    buffer
+}
+
 /// Parser for raw _Telnet_ protocol which delegates actions to a [`Perform`]
 ///
+/// Buffers are allocated when the parser is built, and parsing never allocates: whatever a
+/// [`Perform`] does with the callbacks is up to it. `tests/alloc_free.rs` holds the parser to
+/// this. With the `heapless` feature the buffers are kept inside the parser instead, so building
+/// one doesn't allocate either, and their sizes are capped to [`BUFFER_CAPACITY`].
+///
+/// With the `serde` feature the whole state of a parser, including partially received
+/// sequences, can be serialized and restored, e.g. to move a connection to another process.
+///
 /// [`Perform`]: trait.Perform.html
+/// [`BUFFER_CAPACITY`]: constant.BUFFER_CAPACITY.html
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Parser {
     state: State,
-    intermediates: [u8; MAX_INTERMEDIATES],
+    intermediates: Buffer,
     intermediate_idx: usize,
     neg_command: u8,
-    subs: [u8; MAX_SUBS],
+    subs: Buffer,
     sub_idx: usize,
     ignoring: bool,
+    sub_ignoring: bool,
+    sub_aborted: bool,
+    overflow: Overflow,
+    sub_interrupt: SubInterrupt,
+    flush_policy: FlushPolicy,
+    binary_remote: bool,
+    binary_local: bool,
+    cr_handling: CrHandling,
+    after_cr: bool,
+    strict: bool,
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
+}
+
+/// Shows the state and how much is buffered rather than the buffers themselves
+impl fmt::Debug for Parser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pending_negotiation = if let State::NegEntry | State::SubNegEntry = self.state {
+            Some(self.neg_command)
+        } else {
+            None
+        };
+        f.debug_struct("Parser")
+            .field("state", &self.state)
+            .field("buffered_data", &self.intermediate_idx)
+            .field("buffered_subnegotiation", &self.sub_idx)
+            .field("pending_negotiation", &pending_negotiation)
+            .field("binary", &self.binary())
+            .finish()
+    }
 }
 
 impl Parser {
     pub fn new() -> Parser {
-        Parser {
-            state: State::Ground,
-            intermediates: [0u8; MAX_INTERMEDIATES],
-            intermediate_idx: 0,
-            neg_command: 0,
-            subs: [0u8; MAX_SUBS],
-            sub_idx: 0,
-            ignoring: false,
+        ParserBuilder::new().build()
+    }
+
+    /// Start building a parser with non-default settings
+    pub fn builder() -> ParserBuilder {
+        ParserBuilder::new()
+    }
+
+    /// Set whether TRANSMIT-BINARY is enabled for the other party and for us
+    ///
+    /// While the other party transmits binary, bytes 0x80 to 0xFE are data instead of being
+    /// executed, and the NUL of CR NUL is kept with [`CrHandling::StripNul`]. [`TelnetSession`]
+    /// keeps this in sync with negotiation of option 0.
+    ///
+    /// [`CrHandling::StripNul`]: enum.CrHandling.html#variant.StripNul
+    /// [`TelnetSession`]: session/struct.TelnetSession.html
+    pub fn set_binary(&mut self, remote: bool, local: bool) {
+        self.binary_remote = remote;
+        self.binary_local = local;
+    }
+
+    /// Whether TRANSMIT-BINARY is enabled for the other party and for us
+    pub fn binary(&self) -> (bool, bool) {
+        (self.binary_remote, self.binary_local)
+    }
+
+    /// The sequence the parser is within, if any
+    ///
+    /// Buffered data is not pending, it is delivered by [`flush`]. If the connection closes while
+    /// a sequence is pending, the other party closed it in the middle of a command, e.g. during
+    /// negotiation.
+    ///
+    /// ```
+    /// use televerknet::{Parser, Pending};
+    ///
+    /// let mut parser = Parser::new();
+    /// let mut events = Vec::new();
+    /// parser.advance_bytes(&mut events, b"\xff\xfa\x18\x00xterm");
+    /// assert_eq!(
+    ///     parser.pending(),
+    ///     Some(Pending::Subnegotiation { option: Some(24), len: 7 })
+    /// );
+    /// ```
+    ///
+    /// [`flush`]: #method.flush
+    pub fn pending(&self) -> Option<Pending> {
+        match self.state {
+            State::Ground | State::Data => None,
+            State::IacEntry => Some(Pending::Command),
+            State::NegEntry | State::SubNegEntry => Some(Pending::Negotiation {
+                command: self.neg_command,
+            }),
+            State::SubEntry => Some(Pending::Subnegotiation {
+                option: None,
+                len: 0,
+            }),
+            State::SubIntermediate | State::SubIac => Some(Pending::Subnegotiation {
+                option: self.subs().first().copied(),
+                len: self.sub_idx,
+            }),
+        }
+    }
+
+    /// Returns true between sequences, where a new command may start
+    pub(crate) fn is_ground(&self) -> bool {
+        matches!(self.state, State::Ground | State::Data)
+    }
+
+    #[inline]
+    fn is_data(&self, byte: u8) -> bool {
+        match byte {
+            0x20..=0x7f => true,
+            0x80..=0xfe => self.binary_remote,
+            _ => false,
         }
     }
 
+    /// Number of data bytes at the start of `bytes`
+    ///
+    /// Scans eight bytes at a time: plain text is nearly all of the traffic, and should not go
+    /// through the state machine byte by byte.
+    #[inline]
+    fn data_run(&self, bytes: &[u8]) -> usize {
+        const ONES: u64 = 0x0101_0101_0101_0101;
+        const HIGH: u64 = 0x8080_8080_8080_8080;
+
+        let mut run = 0;
+        for chunk in bytes.chunks_exact(8) {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            let x = u64::from_le_bytes(word);
+            // High bit of every byte below 0x20; borrows only flag bytes after a match
+            let control = x.wrapping_sub(0x20 * ONES) & !x & HIGH;
+            let other = if self.binary_remote {
+                // Bytes equal to 0xff
+                (!x).wrapping_sub(ONES) & x & HIGH
+            } else {
+                x & HIGH
+            };
+            let found = control | other;
+            if found != 0 {
+                return run + (found.trailing_zeros() / 8) as usize;
+            }
+            run += 8;
+        }
+        run + bytes[run..]
+            .iter()
+            .position(|b| !self.is_data(*b))
+            .unwrap_or(bytes.len() - run)
+    }
+
+    /// Size of the subnegotiation buffer, see [`ParserBuilder::max_subnegotiation`]
+    ///
+    /// [`ParserBuilder::max_subnegotiation`]: struct.ParserBuilder.html#method.max_subnegotiation
+    pub fn max_subnegotiation(&self) -> usize {
+        self.subs.len()
+    }
+
     #[inline]
     fn intermediates(&self) -> &[u8] {
         &self.intermediates[..self.intermediate_idx]
@@ -128,52 +793,365 @@ impl Parser {
     ///
     /// [`Perform`]: trait.Perform.html
     #[inline]
-    pub fn advance<P: Perform>(&mut self, performer: &mut P, byte: u8) {
+    pub fn advance<P: Perform + ?Sized>(&mut self, performer: &mut P, byte: u8) {
         let (state, action) = self.get_action(byte);
+        trace!("byte {:02x} in {:?}: {:?}", byte, self.state, action);
         self.perform_state_change(performer, state, action, byte);
     }
 
-    fn get_action(&mut self, byte: u8) -> (State, Action) {
-        // TODO: create lookup table for this?
-        match self.state {
-            State::Ground | State::Data => {
-                match byte {
-                    // Non-printable bytes
-                    0x00..=0x1f => (State::Data, Action::Execute),
-                    // Collect printable characters
-                    0x20..=0x7f => (State::Ground, Action::Collect),
-                    // Various commands
-                    0x80..=0xfe => (State::Data, Action::Execute),
-                    // Beginning of IAC sequence
-                    0xff => (State::IacEntry, Action::None),
+    /// Advance the parser state over a slice of bytes
+    ///
+    /// Equivalent to calling [`advance`] for each byte in order, except for data: runs of
+    /// data bytes are dispatched as slices of `bytes`, without being copied into the parser
+    /// or truncated. Data is therefore dispatched when the slice ends rather than when the next
+    /// control byte arrives, and may be split over several calls to [`Perform::data`]. Data before
+    /// an IAC ending the slice is kept until the command arrives, to tell what ended it.
+    ///
+    /// Parsing stops early when [`Perform::should_break`] returns true after an event, e.g. at a
+    /// START_TLS hand-off where the rest of the bytes are no longer telnet. Returns the number of
+    /// bytes consumed.
+    ///
+    /// [`advance`]: #method.advance
+    /// [`Perform::data`]: trait.Perform.html#tymethod.data
+    /// [`Perform::should_break`]: trait.Perform.html#method.should_break
+    #[inline]
+    pub fn advance_bytes<P: Perform + ?Sized>(&mut self, performer: &mut P, bytes: &[u8]) -> usize {
+        let mut i = 0;
+        while i < bytes.len() {
+            if let State::Ground | State::Data = self.state {
+                let run = self.data_run(&bytes[i..]);
+                if run > 0 {
+                    let end = i + run;
+                    let data_end = match bytes.get(end) {
+                        None => DataEnd::Flush,
+                        Some(0xff) => match bytes.get(end + 1) {
+                            Some(command) => DataEnd::command(*command),
+                            // Keep the data until the command after a trailing IAC arrives
+                            None if run <= self.intermediates.len() - self.intermediate_idx => {
+                                for byte in &bytes[i..end] {
+                                    self.advance(performer, *byte);
+                                }
+                                i = end;
+                                continue;
+                            }
+                            None => DataEnd::IacInterrupted,
+                        },
+                        Some(byte) => DataEnd::control(*byte),
+                    };
+                    self.perform_state_change(performer, State::Ground, Action::None, 0);
+                    self.after_cr = false;
+                    // Data collected by advance goes first, to keep the order
+                    self.dispatch_data(performer, DataEnd::Flush);
+                    performer.data(&bytes[i..end], false, data_end);
+                    i = end;
+                    if performer.should_break() {
+                        return i;
+                    }
+                    continue;
                 }
             }
-            State::IacEntry => {
-                match byte {
-                    // Beginning of subnegotation
-                    0xfa => (State::SubEntry, Action::None),
-                    // Beginning of negotation using WILL, WONT, DO or DONT
-                    0xfb..=0xfe => (State::NegEntry, Action::NegStart),
-                    // Command to dispatch to interpret
-                    _ => (State::Ground, Action::IacDispatch),
+            self.advance(performer, bytes[i]);
+            i += 1;
+            if performer.should_break() {
+                return i;
+            }
+        }
+        if self.flush_policy == FlushPolicy::PerRead {
+            self.flush(performer);
+        }
+        i
+    }
+
+    /// Advance the parser over the complete sequences at the start of `bytes`
+    ///
+    /// Like [`advance_bytes`], but an incomplete command, negotiation or subnegotiation at the end
+    /// of `bytes` is left unconsumed instead of being buffered, and data is dispatched before
+    /// returning. Returns the number of bytes consumed; the rest should be passed again with
+    /// more input, e.g. by leaving it in a ring buffer.
+    ///
+    /// The parser must not be within a sequence, so don't mix this with [`advance`].
+    ///
+    /// [`advance`]: #method.advance
+    /// [`advance_bytes`]: #method.advance_bytes
+    pub fn advance_partial<P: Perform + ?Sized>(
+        &mut self,
+        performer: &mut P,
+        bytes: &[u8],
+    ) -> usize {
+        let mut complete = 0;
+        while let Some(len) = encode::sequence_len(&bytes[complete..]) {
+            complete += len;
+        }
+        let consumed = self.advance_bytes(performer, &bytes[..complete]);
+        self.flush(performer);
+        consumed
+    }
+
+    /// Advance the parser over the complete sequences at the start of `bytes`, passing data and
+    /// subnegotiations borrowed from `bytes`
+    ///
+    /// Nothing is copied to the buffers of the parser, so subnegotiations are not limited by
+    /// [`max_subnegotiation`] and data is passed in the runs found in `bytes`. As with
+    /// [`advance_partial`], an incomplete sequence at the end of `bytes` is left unconsumed and
+    /// the number of bytes consumed is returned.
+    ///
+    /// A subnegotiation interrupted by a command is always aborted as with
+    /// [`SubInterrupt::Abort`], as the rest of its parameters would not be contiguous.
+    ///
+    /// The parser must not be within a sequence, so don't mix this with [`advance`].
+    ///
+    /// [`max_subnegotiation`]: struct.ParserBuilder.html#method.max_subnegotiation
+    /// [`advance_partial`]: #method.advance_partial
+    /// [`SubInterrupt::Abort`]: enum.SubInterrupt.html#variant.Abort
+    /// [`advance`]: #method.advance
+    pub fn advance_borrowed<'a, P>(&mut self, performer: &mut P, bytes: &'a [u8]) -> usize
+    where
+        P: PerformBorrowed<'a> + ?Sized,
+    {
+        let mut i = 0;
+        while i < bytes.len() {
+            let byte = bytes[i];
+            if byte != 0xff {
+                let run = self.data_run(&bytes[i..]);
+                if run > 0 {
+                    self.after_cr = false;
+                    performer.data(&bytes[i..i + run]);
+                    i += run;
+                    continue;
+                }
+                let nul_of_cr = byte == 0x00
+                    && self.after_cr
+                    && self.cr_handling == CrHandling::StripNul
+                    && !self.binary_remote;
+                self.after_cr = byte == 0x0d;
+                if !nul_of_cr {
+                    performer.execute(byte);
                 }
+                i += 1;
+                continue;
             }
-            State::NegEntry => (State::Ground, Action::NegDispatch),
-            State::SubEntry | State::SubIntermediate => {
-                match byte {
-                    // End of subnegotiation parameters
-                    0xf0 => (State::Ground, Action::SubDispatch),
-                    // Continuation of subnegotation
-                    _ => (State::SubIntermediate, Action::SubPut),
+
+            let len = match bytes.get(i + 1) {
+                None => break,
+                Some(0xfa) => match self.borrowed_sub(performer, &bytes[i..], false) {
+                    Some(len) => len,
+                    None => break,
+                },
+                Some(0xfb..=0xfe) if i + 2 >= bytes.len() => break,
+                Some(0xfb..=0xfe) => 3,
+                Some(_) => 2,
+            };
+            self.after_cr = false;
+            match bytes[i + 1] {
+                0xfa => {
+                    self.borrowed_sub(performer, &bytes[i..], true);
+                }
+                0xfb..=0xfe => performer.negotiate_dispatch(bytes[i + 1], bytes[i + 2]),
+                0xff => performer.data(&bytes[i + 1..i + 2]),
+                command => {
+                    let table = State::table(self.binary_remote, self.strict);
+                    match table[State::IacEntry as usize][usize::from(command)] {
+                        (_, Action::Ignore) => {
+                            performer.error(ParseError::InvalidCommand { byte: command })
+                        }
+                        _ => performer.iac_dispatch(command),
+                    }
+                }
+            }
+            i += len;
+        }
+        i
+    }
+
+    /// Length of the subnegotiation starting with the `IAC SB` at the start of `bytes`, or
+    /// `None` if it is incomplete, making its callbacks with `dispatch`
+    fn borrowed_sub<'a, P>(
+        &self,
+        performer: &mut P,
+        bytes: &'a [u8],
+        dispatch: bool,
+    ) -> Option<usize>
+    where
+        P: PerformBorrowed<'a> + ?Sized,
+    {
+        // Start of the subs, as for sub_dispatch
+        let mut start = 2;
+        let mut aborted = false;
+        let mut j = start;
+        loop {
+            let byte = *bytes.get(j)?;
+            // SE right after SB
+            if j == start && byte == 0xf0 {
+                if dispatch && self.strict {
+                    performer.error(ParseError::MissingOption);
+                }
+                return Some(j + 1);
+            }
+            if byte != 0xff {
+                j += 1;
+                continue;
+            }
+            let command = *bytes.get(j + 1)?;
+            let len = match command {
+                0xfb..=0xfe if j + 2 >= bytes.len() => return None,
+                0xfb..=0xfe => 3,
+                _ => 2,
+            };
+            if command == 0xf0 {
+                let subs = &bytes[start..=j];
+                if dispatch {
+                    if self.strict && subs == [0xff] {
+                        performer.error(ParseError::MissingOption);
+                    } else if !aborted {
+                        performer.sub_dispatch(subs);
+                    }
+                }
+                return Some(j + 2);
+            }
+            if dispatch && (0xec..=0xfe).contains(&command) {
+                let option = if j > start { bytes[start] } else { 0xff };
+                performer.error(ParseError::InterruptedSubnegotiation { option, command });
+                match command {
+                    0xfa => (),
+                    0xfb..=0xfe => performer.negotiate_dispatch(command, bytes[j + 2]),
+                    _ => performer.iac_dispatch(command),
+                }
+            }
+            match command {
+                // A new subnegotiation replaces this one
+                0xfa => {
+                    start = j + 2;
+                    aborted = false;
                 }
+                0xec..=0xf9 | 0xfb..=0xfe => aborted = true,
+                _ => (),
             }
+            j += len;
         }
     }
 
+    /// Advance the parser over a slice of bytes, awaiting each callback on `performer`
+    ///
+    /// `bytes` are parsed first, then the callbacks are made in order, so the parser is not
+    /// borrowed by a performer waiting for I/O. Data and subnegotiations are copied in between.
+    /// Callbacks not made yet when the future is dropped are lost.
+    pub async fn advance_async<P: AsyncPerform + ?Sized>(
+        &mut self,
+        performer: &mut P,
+        bytes: &[u8],
+    ) {
+        let mut callbacks = Callbacks::default();
+        self.advance_bytes(&mut callbacks, bytes);
+        callbacks.perform(performer).await;
+    }
+
+    /// Dispatch data collected so far to an [`AsyncPerform`], see [`flush`]
+    ///
+    /// [`AsyncPerform`]: trait.AsyncPerform.html
+    /// [`flush`]: #method.flush
+    pub async fn flush_async<P: AsyncPerform + ?Sized>(&mut self, performer: &mut P) {
+        let mut callbacks = Callbacks::default();
+        self.flush(&mut callbacks);
+        callbacks.perform(performer).await;
+    }
+
+    /// Advance the parser over a slice of bytes, passing each event to `f` as it is parsed
+    ///
+    /// For tools which would rather not implement [`Perform`]. Data is dispatched as with
+    /// [`advance_bytes`], so it is passed to `f` at the end of the slice at the latest, and an
+    /// escaped IAC is a `Data` event of its own. Returning `ControlFlow::Break` from `f` stops
+    /// parsing right behind the event, as [`Perform::should_break`] does. Returns the number of
+    /// bytes consumed.
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use televerknet::event::Event;
+    /// use televerknet::Parser;
+    ///
+    /// let mut commands = 0;
+    /// let consumed = Parser::new().advance_with(b"login: \xff\xf9more", |event| {
+    ///     if let Event::Command(_) = event {
+    ///         commands += 1;
+    ///         return ControlFlow::Break(());
+    ///     }
+    ///     ControlFlow::Continue(())
+    /// });
+    /// assert_eq!(commands, 1);
+    /// assert_eq!(consumed, 9);
+    /// ```
+    ///
+    /// [`Perform`]: trait.Perform.html
+    /// [`advance_bytes`]: #method.advance_bytes
+    /// [`Perform::should_break`]: trait.Perform.html#method.should_break
+    pub fn advance_with<F>(&mut self, bytes: &[u8], f: F) -> usize
+    where
+        F: FnMut(event::Event) -> ControlFlow<()>,
+    {
+        self.advance_bytes(&mut event::EachEvent::new(f), bytes)
+    }
+
+    /// [`advance`] with a performer chosen at runtime
+    ///
+    /// [`advance`]: #method.advance
+    pub fn advance_dyn(&mut self, performer: &mut dyn Perform, byte: u8) {
+        self.advance(performer, byte);
+    }
+
+    /// [`advance_bytes`] with a performer chosen at runtime
+    ///
+    /// [`advance_bytes`]: #method.advance_bytes
+    pub fn advance_bytes_dyn(&mut self, performer: &mut dyn Perform, bytes: &[u8]) -> usize {
+        self.advance_bytes(performer, bytes)
+    }
+
+    /// Tell the parser that no bytes arrived for `elapsed`, flushing with the `Idle` policy
+    pub fn idle<P: Perform + ?Sized>(&mut self, performer: &mut P, elapsed: Duration) {
+        if let FlushPolicy::Idle(timeout) = self.flush_policy {
+            if elapsed >= timeout {
+                self.flush(performer);
+            }
+        }
+    }
+
+    /// Dispatch data collected so far without waiting for a control byte
+    pub fn flush<P: Perform + ?Sized>(&mut self, performer: &mut P) {
+        if let State::Ground = self.state {
+            self.dispatch_data(performer, DataEnd::Flush);
+        }
+    }
+
+    /// Dispatch and clear the collected data
+    fn dispatch_data<P: Perform + ?Sized>(&mut self, performer: &mut P, end: DataEnd) {
+        let aborted = self.ignoring && self.overflow == Overflow::Abort;
+        if self.intermediate_idx > 0 && !aborted {
+            performer.data(self.intermediates(), self.ignoring, end);
+        }
+        self.intermediate_idx = 0;
+        self.ignoring = false;
+    }
+
+    fn get_action(&mut self, byte: u8) -> (State, Action) {
+        let after_cr = std::mem::replace(&mut self.after_cr, false);
+        if let State::Ground | State::Data = self.state {
+            match byte {
+                // NUL of CR NUL, which is data like any other in binary mode
+                0x00 if after_cr
+                    && self.cr_handling == CrHandling::StripNul
+                    && !self.binary_remote =>
+                {
+                    return (State::Data, Action::Ignore);
+                }
+                0x0d => self.after_cr = true,
+                _ => (),
+            }
+        }
+        State::table(self.binary_remote, self.strict)[self.state as usize][usize::from(byte)]
+    }
+
     #[inline]
     fn perform_state_change<P>(&mut self, performer: &mut P, state: State, action: Action, byte: u8)
     where
-        P: Perform,
+        P: Perform + ?Sized,
     {
         macro_rules! maybe_action {
             ($action:expr, $arg:expr) => {
@@ -186,83 +1164,188 @@ impl Parser {
             };
         }
 
-        match state {
-            state => {
-                // Exit action for previous state
-                let exit_action = self.state.exit_action();
-                maybe_action!(exit_action, 0);
+        if state == self.state {
+            maybe_action!(action, byte);
+            return;
+        }
 
-                // Transition action
-                maybe_action!(action, byte);
+        trace!("state transition {:?} -> {:?}", self.state, state);
 
-                // Entry action for new state
-                maybe_action!(state.entry_action(), 0);
+        // Exit action for previous state
+        let exit_action = self.state.exit_action();
+        maybe_action!(exit_action, byte);
 
-                // Assume the new state
-                self.state = state;
-            }
-        }
+        // Transition action
+        maybe_action!(action, byte);
+
+        // Entry action for new state
+        maybe_action!(state.entry_action(), 0);
+
+        // Assume the new state
+        self.state = state;
     }
 
     #[inline]
-    fn perform_action<P: Perform>(&mut self, performer: &mut P, action: Action, byte: u8) {
+    fn perform_action<P: Perform + ?Sized>(&mut self, performer: &mut P, action: Action, byte: u8) {
         match action {
             Action::Execute => performer.execute(byte),
             Action::Collect => {
-                if self.intermediate_idx == MAX_INTERMEDIATES {
-                    self.ignoring = true;
-                } else {
-                    self.intermediates[self.intermediate_idx] = byte;
-                    self.intermediate_idx += 1;
+                if self.intermediate_idx == self.intermediates.len() {
+                    if self.overflow == Overflow::Flush {
+                        self.dispatch_data(performer, DataEnd::BufferFull);
+                    } else {
+                        if !self.ignoring {
+                            performer.error(ParseError::DataTruncated);
+                        }
+                        self.ignoring = true;
+                        return;
+                    }
+                }
+                self.intermediates[self.intermediate_idx] = byte;
+                self.intermediate_idx += 1;
+                if let FlushPolicy::Threshold(threshold) = self.flush_policy {
+                    if self.intermediate_idx >= threshold {
+                        performer.data(self.intermediates(), self.ignoring, DataEnd::Flush);
+                        self.intermediate_idx = 0;
+                    }
                 }
             }
-            Action::DataDispatch => {
-                if self.intermediate_idx > 0 {
-                    performer.data(self.intermediates(), self.ignoring);
+            Action::DataDispatch => match self.state {
+                // Wait for the command after IAC
+                State::Ground if byte == 0xff => (),
+                State::IacEntry => self.dispatch_data(performer, DataEnd::command(byte)),
+                _ => self.dispatch_data(performer, DataEnd::control(byte)),
+            },
+            Action::Ignore => {
+                if self.state == State::IacEntry {
+                    performer.error(ParseError::InvalidCommand { byte });
                 }
             }
-            Action::Ignore | Action::None => (),
+            Action::None => (),
             Action::Clear => {
                 self.intermediate_idx = 0;
                 self.ignoring = false;
             }
-            Action::IacDispatch => performer.iac_dispatch(byte),
+            Action::IacDispatch => {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::trace_span!("iac", command = byte).entered();
+                performer.iac_dispatch(byte)
+            }
             Action::NegStart => {
                 self.neg_command = byte;
             }
-            Action::NegDispatch => performer.negotiate_dispatch(self.neg_command, byte),
+            Action::NegDispatch => {
+                #[cfg(feature = "tracing")]
+                let _span =
+                    tracing::trace_span!("negotiate", command = self.neg_command, option = byte)
+                        .entered();
+                performer.negotiate_dispatch(self.neg_command, byte)
+            }
             Action::SubStart => {
                 self.sub_idx = 0;
+                self.sub_ignoring = false;
+                self.sub_aborted = false;
+                performer.sub_start();
             }
             Action::SubPut => {
                 let sub_idx = self.sub_idx;
-                if sub_idx < MAX_SUBS {
+                if sub_idx < self.subs.len() {
                     self.subs[sub_idx] = byte;
                     self.sub_idx += 1;
+                } else {
+                    self.sub_ignoring = true;
                 }
             }
+            Action::SubInterrupt => {
+                self.interrupt_sub(performer, byte);
+                performer.iac_dispatch(byte);
+            }
+            Action::SubNegStart => {
+                self.interrupt_sub(performer, byte);
+                self.neg_command = byte;
+            }
+            Action::SubAbort => {
+                self.interrupt_sub(performer, byte);
+            }
             Action::SubDispatch => {
-                if self.sub_idx > 0 {
+                // SE right after SB, or after SB IAC
+                if self.strict && (self.sub_idx == 0 || self.subs() == [0xff]) {
+                    performer.error(ParseError::MissingOption);
+                    return;
+                }
+                let aborted =
+                    self.sub_aborted || self.sub_ignoring && self.overflow == Overflow::Abort;
+                if self.sub_ignoring {
+                    performer.error(ParseError::SubnegotiationTruncated {
+                        option: self.subs[0],
+                    });
+                }
+                if self.sub_idx > 0 && !aborted {
+                    #[cfg(feature = "tracing")]
+                    let _span = tracing::trace_span!(
+                        "subnegotiate",
+                        option = self.subs[0],
+                        len = self.sub_idx
+                    )
+                    .entered();
                     performer.sub_dispatch(self.subs());
                 }
             }
         }
     }
+
+    /// Take back the IAC kept by `SubPut` before `command`, and report the interruption
+    fn interrupt_sub<P: Perform + ?Sized>(&mut self, performer: &mut P, command: u8) {
+        if !self.sub_ignoring {
+            self.sub_idx -= 1;
+        }
+        if self.sub_interrupt == SubInterrupt::Abort {
+            self.sub_aborted = true;
+        }
+        let option = if self.sub_idx > 0 { self.subs[0] } else { 0xff };
+        performer.error(ParseError::InterruptedSubnegotiation { option, command });
+    }
 }
 
 pub trait Perform {
     /// Data event: for DATA and SEND events
+    ///
+    /// `end` tells what ended the data, e.g. a newline or a GA after a prompt.
     // TODO: rename to hook?
-    fn data(&mut self, intermediates: &[u8], ignore: bool);
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd);
 
     fn execute(&mut self, byte: u8);
 
-    /// WARNING and ERROR events
-    // fn error(&mut self);
+    /// Error event: data or a subnegotiation overflowed its buffer, or in strict mode a sequence
+    /// was malformed
+    ///
+    /// What happens to the overflowing bytes depends on [`ParserBuilder::overflow`].
+    ///
+    /// [`ParserBuilder::overflow`]: struct.ParserBuilder.html#method.overflow
+    fn error(&mut self, _error: ParseError) {}
+
+    /// Return true to make [`Parser::advance_bytes`] return after the current byte
+    ///
+    /// Checked after every byte and every run of data, so an event which hands the rest of the
+    /// stream to something else can stop parsing right behind it.
+    ///
+    /// [`Parser::advance_bytes`]: struct.Parser.html#method.advance_bytes
+    fn should_break(&mut self) -> bool {
+        false
+    }
 
     /// Command event: for IAC
     fn iac_dispatch(&mut self, byte: u8);
 
+    /// Start of a subnegotiation, right after `IAC SB`
+    ///
+    /// Not every subnegotiation started is dispatched, e.g. one discarded by
+    /// [`Overflow::Abort`], so state kept for the next [`sub_dispatch`] is reset here.
+    ///
+    /// [`Overflow::Abort`]: enum.Overflow.html#variant.Abort
+    /// [`sub_dispatch`]: #tymethod.sub_dispatch
+    fn sub_start(&mut self) {}
+
     /// Command event: for IAC SUB ...
     fn sub_dispatch(&mut self, subs: &[u8]);
 
@@ -286,6 +1369,182 @@ pub trait Perform {
     // TODO: mssp_dispatch
 }
 
+/// Lets a `Box<dyn Perform>` be passed where a performer is expected
+impl<P: Perform + ?Sized> Perform for Box<P> {
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        (**self).data(intermediates, ignore, end);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        (**self).execute(byte);
+    }
+
+    fn error(&mut self, error: ParseError) {
+        (**self).error(error);
+    }
+
+    fn should_break(&mut self) -> bool {
+        (**self).should_break()
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        (**self).iac_dispatch(byte);
+    }
+
+    fn sub_start(&mut self) {
+        (**self).sub_start();
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        (**self).sub_dispatch(subs);
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        (**self).negotiate_dispatch(cmd, opt);
+    }
+
+    fn subnegotiate_dispatch(&mut self, params: &[u8], opt: u8) {
+        (**self).subnegotiate_dispatch(params, opt);
+    }
+
+    fn zmp_dispatch(&mut self, params: &[&[u8]]) {
+        (**self).zmp_dispatch(params);
+    }
+
+    fn ttypes_dispatch(&mut self, cmd: u8, terminal_type: &[u8]) {
+        (**self).ttypes_dispatch(cmd, terminal_type);
+    }
+
+    fn compress_dispatch(&mut self, state: u8) {
+        (**self).compress_dispatch(state);
+    }
+}
+
+/// Like [`Perform`], with data and subnegotiations borrowed from the input for `'a`
+///
+/// Used by [`Parser::advance_borrowed`], which copies nothing: a performer may keep the slices
+/// for as long as the input lives, e.g. to collect them without allocating.
+///
+/// [`Perform`]: trait.Perform.html
+/// [`Parser::advance_borrowed`]: struct.Parser.html#method.advance_borrowed
+pub trait PerformBorrowed<'a> {
+    /// Data event: a run of data bytes, or the second byte of an escaped IAC
+    fn data(&mut self, data: &'a [u8]);
+
+    fn execute(&mut self, byte: u8);
+
+    /// Error event: in strict mode a sequence was malformed, or a subnegotiation was interrupted
+    fn error(&mut self, _error: ParseError) {}
+
+    /// Command event: for IAC
+    fn iac_dispatch(&mut self, byte: u8);
+
+    /// Negotiate event: WILL, WONT, DO, DONT
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8);
+
+    /// Command event: for IAC SUB ...
+    ///
+    /// `subs` is laid out as for [`Perform::sub_dispatch`]: the option, the parameters with IAC
+    /// bytes still doubled, and the IAC before SE.
+    ///
+    /// [`Perform::sub_dispatch`]: trait.Perform.html#tymethod.sub_dispatch
+    fn sub_dispatch(&mut self, subs: &'a [u8]);
+}
+
+/// Like [`Perform`], with callbacks which may await, e.g. I/O done to handle an event
+///
+/// Used by [`Parser::advance_async`] and [`Parser::flush_async`].
+///
+/// [`Perform`]: trait.Perform.html
+/// [`Parser::advance_async`]: struct.Parser.html#method.advance_async
+/// [`Parser::flush_async`]: struct.Parser.html#method.flush_async
+#[allow(async_fn_in_trait)]
+pub trait AsyncPerform {
+    /// Data event, see [`Perform::data`](trait.Perform.html#tymethod.data)
+    async fn data(&mut self, data: &[u8], ignore: bool, end: DataEnd);
+
+    async fn execute(&mut self, byte: u8);
+
+    /// Error event, see [`Perform::error`](trait.Perform.html#method.error)
+    async fn error(&mut self, _error: ParseError) {}
+
+    /// Command event: for IAC
+    async fn iac_dispatch(&mut self, byte: u8);
+
+    /// Negotiate event: WILL, WONT, DO, DONT
+    async fn negotiate_dispatch(&mut self, cmd: u8, opt: u8);
+
+    /// Command event: for IAC SUB ...
+    async fn sub_dispatch(&mut self, subs: &[u8]);
+}
+
+/// A callback recorded to be made on an `AsyncPerform`
+enum Callback {
+    Data(Vec<u8>, bool, DataEnd),
+    Execute(u8),
+    Error(ParseError),
+    Iac(u8),
+    Negotiate(u8, u8),
+    Sub(Vec<u8>),
+}
+
+impl Callback {
+    async fn perform<P: AsyncPerform + ?Sized>(self, performer: &mut P) {
+        match self {
+            Callback::Data(data, ignore, end) => performer.data(&data, ignore, end).await,
+            Callback::Execute(byte) => performer.execute(byte).await,
+            Callback::Error(error) => performer.error(error).await,
+            Callback::Iac(byte) => performer.iac_dispatch(byte).await,
+            Callback::Negotiate(cmd, opt) => performer.negotiate_dispatch(cmd, opt).await,
+            Callback::Sub(subs) => performer.sub_dispatch(&subs).await,
+        }
+    }
+}
+
+/// Records callbacks to replay them on an `AsyncPerform`
+#[derive(Default)]
+struct Callbacks(Vec<Callback>);
+
+impl Callbacks {
+    async fn perform<P: AsyncPerform + ?Sized>(self, performer: &mut P) {
+        for callback in self.0 {
+            callback.perform(performer).await;
+        }
+    }
+}
+
+impl Perform for Callbacks {
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        self.0
+            .push(Callback::Data(intermediates.to_vec(), ignore, end));
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.0.push(Callback::Execute(byte));
+    }
+
+    fn error(&mut self, error: ParseError) {
+        self.0.push(Callback::Error(error));
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        self.0.push(Callback::Iac(byte));
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        self.0.push(Callback::Sub(subs.to_vec()));
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        self.0.push(Callback::Negotiate(cmd, opt));
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+    fn compress_dispatch(&mut self, _state: u8) {}
+}
+
 #[cfg(test)]
 #[macro_use]
 extern crate std;
@@ -294,8 +1553,16 @@ extern crate std;
 extern crate env_logger;
 
 #[cfg(test)]
+// The oldest tests predate these lints
+#[allow(clippy::redundant_static_lifetimes, clippy::byte_char_slices)]
 mod tests {
-    use super::{Parser, Perform};
+    use super::{
+        Action, AsyncPerform, CrHandling, DataEnd, FlushPolicy, Overflow, ParseError, Parser,
+        Pending, Perform, PerformBorrowed, State, SubInterrupt, MAX_INTERMEDIATES, MAX_SUBS,
+        STATES,
+    };
+    use crate::event::Event;
+    use std::time::Duration;
     // use core::i64;
     use std::vec::Vec;
 
@@ -311,17 +1578,20 @@ mod tests {
     struct IacDispatcher {
         intermediates: Vec<Vec<u8>>,
         ignoring: Vec<bool>,
+        ends: Vec<DataEnd>,
         execute: Vec<u8>,
         iac: Vec<u8>,
         negs: Vec<(u8, u8)>,
         subs: Vec<Vec<u8>>,
+        errors: Vec<ParseError>,
     }
 
     // All empty bodies except iac_dispatch
     impl Perform for IacDispatcher {
-        fn data(&mut self, intermediates: &[u8], ignoring: bool) {
+        fn data(&mut self, intermediates: &[u8], ignoring: bool, end: DataEnd) {
             self.intermediates.push(intermediates.to_vec());
             self.ignoring.push(ignoring);
+            self.ends.push(end);
         }
         fn execute(&mut self, byte: u8) {
             self.execute.push(byte);
@@ -329,6 +1599,9 @@ mod tests {
         fn iac_dispatch(&mut self, byte: u8) {
             self.iac.push(byte);
         }
+        fn error(&mut self, error: ParseError) {
+            self.errors.push(error);
+        }
         fn sub_dispatch(&mut self, subs: &[u8]) {
             self.subs.push(subs.to_vec());
         }
@@ -428,6 +1701,86 @@ mod tests {
         assert_eq!(dispatcher.subs[0], &BYTES[2..(BYTES.len() - 1)]);
     }
 
+    #[test]
+    fn parse_iac_sb_interrupted() {
+        init_test_logging();
+
+        static BYTES: &[u8] = &[
+            255, 250, 24, 0, b'x', // IAC SB TERMINAL-TYPE IS x
+            240,  // SE without IAC is a parameter
+            255, 251, 1, // IAC WILL ECHO
+            b'y', 255, 241, // y IAC NOP
+            255, 240, // IAC SE
+            255, 250, 24, 1, // IAC SB TERMINAL-TYPE SEND
+            255, 250, 31, 0, 255, 240, // IAC SB NAWS 0 IAC SE
+        ];
+
+        let run = |sub_interrupt| {
+            let mut dispatcher = IacDispatcher::default();
+            let mut parser = Parser::builder().sub_interrupt(sub_interrupt).build();
+            parser.advance_bytes(&mut dispatcher, BYTES);
+            assert_eq!(dispatcher.negs, &[(251, 1)]);
+            assert_eq!(dispatcher.iac, &[241]);
+            assert_eq!(
+                dispatcher.errors,
+                &[
+                    ParseError::InterruptedSubnegotiation {
+                        option: 24,
+                        command: 251
+                    },
+                    ParseError::InterruptedSubnegotiation {
+                        option: 24,
+                        command: 241
+                    },
+                    ParseError::InterruptedSubnegotiation {
+                        option: 24,
+                        command: 250
+                    },
+                ]
+            );
+            dispatcher.subs
+        };
+
+        assert_eq!(
+            run(SubInterrupt::Continue),
+            &[vec![24, 0, b'x', 240, b'y', 255], vec![31, 0, 255]]
+        );
+        assert_eq!(run(SubInterrupt::Abort), &[vec![31, 0, 255]]);
+    }
+
+    #[test]
+    fn pending() {
+        let mut dispatcher = IacDispatcher::default();
+        let mut parser = Parser::new();
+        parser.advance_bytes(&mut dispatcher, b"data");
+        assert_eq!(parser.pending(), None);
+        parser.advance(&mut dispatcher, 255);
+        assert_eq!(parser.pending(), Some(Pending::Command));
+        parser.advance(&mut dispatcher, 253);
+        assert_eq!(
+            parser.pending(),
+            Some(Pending::Negotiation { command: 253 })
+        );
+        parser.advance_bytes(&mut dispatcher, &[1, 255, 250]);
+        assert_eq!(
+            parser.pending(),
+            Some(Pending::Subnegotiation {
+                option: None,
+                len: 0
+            })
+        );
+        parser.advance_bytes(&mut dispatcher, &[31, 0, 80, 255]);
+        assert_eq!(
+            parser.pending(),
+            Some(Pending::Subnegotiation {
+                option: Some(31),
+                len: 4
+            })
+        );
+        parser.advance(&mut dispatcher, 240);
+        assert_eq!(parser.pending(), None);
+    }
+
     #[test]
     fn parse_crlf() {
         init_test_logging();
@@ -463,4 +1816,450 @@ mod tests {
         assert_eq!(dispatcher.intermediates[0], &[b'r']);
         assert_eq!(dispatcher.intermediates[1], &[b's']);
     }
+
+    #[test]
+    fn advance_bytes_large_data() {
+        init_test_logging();
+
+        let mut bytes = vec![b'x'; 4000];
+        bytes.extend_from_slice(&[0x0d, b'y']);
+
+        let mut dispatcher = IacDispatcher::default();
+        let mut parser = Parser::new();
+        parser.advance(&mut dispatcher, b'w');
+        parser.advance_bytes(&mut dispatcher, &bytes);
+
+        // Nothing is truncated, and data collected byte by byte comes first
+        assert_eq!(dispatcher.intermediates.len(), 3);
+        assert_eq!(dispatcher.intermediates[0], b"w");
+        assert_eq!(dispatcher.intermediates[1].len(), 4000);
+        assert_eq!(dispatcher.intermediates[2], b"y");
+        assert_eq!(dispatcher.ignoring, &[false, false, false]);
+        assert_eq!(dispatcher.execute, &[0x0d]);
+    }
+
+    #[test]
+    fn transition_table() {
+        let table = State::table(false, false);
+        assert_eq!(
+            table[State::IacEntry as usize][0xfa],
+            (State::SubEntry, Action::None)
+        );
+        assert_eq!(
+            table[State::Ground as usize][0x80],
+            (State::Data, Action::Execute)
+        );
+        assert_eq!(
+            State::table(true, false)[State::Ground as usize][0x80],
+            (State::Ground, Action::Collect)
+        );
+        assert_eq!(
+            State::table(false, true)[State::IacEntry as usize][0x41],
+            (State::Ground, Action::Ignore)
+        );
+        for (state, row) in STATES.iter().zip(table.iter()) {
+            for (byte, transition) in row.iter().enumerate() {
+                assert_eq!(*transition, state.transition(byte as u8, false, false));
+            }
+        }
+    }
+
+    #[test]
+    fn advance_borrowed() {
+        init_test_logging();
+
+        static INPUTS: &[&[u8]] = &[
+            b"ab\r\0c\r\n\x80",
+            b"\xff\xffa\xff\xf9\xff\x41\xff\xfb\x18",
+            b"\xff\xfa\x18\x00a longer terminal type\xff\xff\xff\xf0",
+            b"\xff\xfa\xff\xf0\xff\xfa\xf0\xff\xfa\x1f\xff\xfd\x01\x00\xff\xf0",
+            b"\xff\xfa\x1f\x00\xff\xfa\x18\x01\xff\xf0",
+        ];
+        for strict in &[false, true] {
+            for input in INPUTS {
+                let build = || {
+                    Parser::builder()
+                        .max_subnegotiation(64)
+                        .cr_handling(CrHandling::StripNul)
+                        .sub_interrupt(SubInterrupt::Abort)
+                        .strict(*strict)
+                        .build()
+                };
+                let mut expected: Vec<Event> = Vec::new();
+                build().advance_partial(&mut expected, input);
+                let mut events: Vec<Event> = Vec::new();
+                let consumed = build().advance_borrowed(&mut events, input);
+                assert_eq!(consumed, input.len());
+                assert_eq!(events, expected, "{:?}", input);
+            }
+        }
+
+        struct Slices<'a>(Vec<&'a [u8]>);
+        impl<'a> PerformBorrowed<'a> for Slices<'a> {
+            fn data(&mut self, data: &'a [u8]) {
+                self.0.push(data);
+            }
+            fn execute(&mut self, _byte: u8) {}
+            fn iac_dispatch(&mut self, _byte: u8) {}
+            fn negotiate_dispatch(&mut self, _cmd: u8, _opt: u8) {}
+            fn sub_dispatch(&mut self, subs: &'a [u8]) {
+                self.0.push(subs);
+            }
+        }
+        let input = b"hi\xff\xfa\x18\x00xterm\xff\xf0\xff\xfa\x1f";
+        let mut slices = Slices(Vec::new());
+        let consumed = Parser::new().advance_borrowed(&mut slices, input);
+        assert_eq!(consumed, 13);
+        assert_eq!(slices.0, &[&b"hi"[..], &b"\x18\x00xterm\xff"[..]]);
+        assert_eq!(slices.0[1].as_ptr(), input[4..].as_ptr());
+    }
+
+    #[test]
+    fn advance_async() {
+        use futures::{SinkExt, StreamExt};
+
+        init_test_logging();
+
+        // Forwards events to a channel, awaiting room in it
+        struct Forward(futures::channel::mpsc::Sender<Vec<u8>>);
+        impl AsyncPerform for Forward {
+            async fn data(&mut self, data: &[u8], _ignore: bool, _end: DataEnd) {
+                self.0.send(data.to_vec()).await.unwrap();
+            }
+            async fn execute(&mut self, byte: u8) {
+                self.0.send(vec![byte]).await.unwrap();
+            }
+            async fn iac_dispatch(&mut self, byte: u8) {
+                self.0.send(vec![255, byte]).await.unwrap();
+            }
+            async fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+                self.0.send(vec![255, cmd, opt]).await.unwrap();
+            }
+            async fn sub_dispatch(&mut self, subs: &[u8]) {
+                self.0.send(subs.to_vec()).await.unwrap();
+            }
+        }
+
+        let (sender, receiver) = futures::channel::mpsc::channel(0);
+        let mut parser = Parser::new();
+        let parse = async move {
+            let mut forward = Forward(sender);
+            parser
+                .advance_async(&mut forward, b"hi\n\xff\xfb\x18\xff\xfa\x18\x01\xff\xf0ok")
+                .await;
+            parser.flush_async(&mut forward).await;
+        };
+        let (_, received) =
+            futures::executor::block_on(futures::future::join(parse, receiver.collect::<Vec<_>>()));
+        assert_eq!(
+            received,
+            &[
+                b"hi".to_vec(),
+                vec![b'\n'],
+                vec![255, 251, 24],
+                vec![24, 1, 255],
+                b"ok".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn data_run_scan() {
+        for binary in &[false, true] {
+            let mut parser = Parser::new();
+            parser.set_binary(*binary, false);
+            for special in &[0x00, 0x0d, 0x1f, 0x20, 0x7f, 0x80, 0xfe, 0xff] {
+                for pos in 0..20 {
+                    let mut bytes = vec![b'a'; 20];
+                    bytes[pos] = *special;
+                    let expected = bytes
+                        .iter()
+                        .position(|b| !parser.is_data(*b))
+                        .unwrap_or(bytes.len());
+                    assert_eq!(
+                        parser.data_run(&bytes),
+                        expected,
+                        "{:02x} at {}",
+                        special,
+                        pos
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn advance_partial() {
+        init_test_logging();
+
+        let mut dispatcher = IacDispatcher::default();
+        let mut parser = Parser::new();
+        let mut buffer = vec![b'a', 255, 251];
+        assert_eq!(parser.advance_partial(&mut dispatcher, &buffer), 1);
+        buffer.drain(..1);
+        buffer.extend_from_slice(&[1, 255, 250, 24, 0]);
+        assert_eq!(parser.advance_partial(&mut dispatcher, &buffer), 3);
+        buffer.drain(..3);
+        buffer.extend_from_slice(&[255, 240, b'b']);
+        assert_eq!(parser.advance_partial(&mut dispatcher, &buffer), 7);
+
+        assert_eq!(dispatcher.intermediates, &[b"a", b"b"]);
+        assert_eq!(dispatcher.negs, &[(251, 1)]);
+        assert_eq!(dispatcher.subs, &[vec![24, 0, 255]]);
+    }
+
+    #[test]
+    fn overflow() {
+        init_test_logging();
+
+        #[derive(Default)]
+        struct Errors {
+            data: Vec<usize>,
+            errors: Vec<ParseError>,
+        }
+
+        impl Perform for Errors {
+            fn data(&mut self, intermediates: &[u8], _ignore: bool, _end: DataEnd) {
+                self.data.push(intermediates.len());
+            }
+            fn execute(&mut self, _byte: u8) {}
+            fn iac_dispatch(&mut self, _byte: u8) {}
+            fn sub_dispatch(&mut self, subs: &[u8]) {
+                self.data.push(subs.len());
+            }
+            fn negotiate_dispatch(&mut self, _cmd: u8, _opt: u8) {}
+            fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+            fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+            fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+            fn compress_dispatch(&mut self, _state: u8) {}
+            fn error(&mut self, error: ParseError) {
+                self.errors.push(error);
+            }
+        }
+
+        let mut bytes = vec![b'x'; MAX_INTERMEDIATES + 10];
+        bytes.extend_from_slice(&[0x0d, 255, 250, 201]);
+        bytes.extend_from_slice(&[b'y'; MAX_SUBS]);
+        bytes.extend_from_slice(&[255, 240]);
+
+        let run = |overflow| {
+            let mut performer = Errors::default();
+            let mut parser = Parser::builder().overflow(overflow).build();
+            for byte in &bytes {
+                parser.advance(&mut performer, *byte);
+            }
+            performer
+        };
+
+        let truncated = run(Overflow::Truncate);
+        assert_eq!(truncated.data, &[MAX_INTERMEDIATES, MAX_SUBS]);
+        assert_eq!(
+            truncated.errors,
+            &[
+                ParseError::DataTruncated,
+                ParseError::SubnegotiationTruncated { option: 201 },
+            ]
+        );
+
+        let flushed = run(Overflow::Flush);
+        assert_eq!(flushed.data, &[MAX_INTERMEDIATES, 10, MAX_SUBS]);
+        assert_eq!(flushed.errors.len(), 1);
+
+        let aborted = run(Overflow::Abort);
+        assert!(aborted.data.is_empty());
+        assert_eq!(aborted.errors.len(), 2);
+    }
+
+    #[test]
+    fn flush_policies() {
+        init_test_logging();
+
+        let prompt = |policy, idle| {
+            let mut dispatcher = IacDispatcher::default();
+            let mut parser = Parser::builder().flush_policy(policy).build();
+            for byte in b"> " {
+                parser.advance(&mut dispatcher, *byte);
+            }
+            // A read with nothing to collect
+            parser.advance_bytes(&mut dispatcher, &[]);
+            parser.idle(&mut dispatcher, idle);
+            dispatcher.intermediates
+        };
+
+        let second = Duration::from_secs(1);
+        assert!(prompt(FlushPolicy::Manual, second).is_empty());
+        assert_eq!(prompt(FlushPolicy::PerRead, second), &[b"> "]);
+        assert_eq!(prompt(FlushPolicy::Threshold(1), second), &[b">", b" "]);
+        assert!(prompt(FlushPolicy::Idle(second * 2), second).is_empty());
+        assert_eq!(prompt(FlushPolicy::Idle(second), second), &[b"> "]);
+    }
+
+    #[test]
+    fn data_end() {
+        init_test_logging();
+
+        static BYTES: &[u8] = &[
+            b'a', b'\r', b'\n', b'>', 255, 249, b'b', 0x1b, b'c', 255, 239, b'd', 255, 241, b'e',
+        ];
+        let ends = [
+            DataEnd::Newline,
+            DataEnd::GoAhead,
+            DataEnd::Control,
+            DataEnd::Eor,
+            DataEnd::IacInterrupted,
+            DataEnd::Flush,
+        ];
+
+        let mut dispatcher = IacDispatcher::default();
+        let mut parser = Parser::new();
+        for byte in BYTES {
+            parser.advance(&mut dispatcher, *byte);
+        }
+        parser.flush(&mut dispatcher);
+        assert_eq!(dispatcher.ends, ends);
+
+        // A trailing IAC keeps the data until the command arrives
+        let mut dispatcher = IacDispatcher::default();
+        let mut parser = Parser::new();
+        parser.advance_bytes(&mut dispatcher, &BYTES[..5]);
+        parser.advance_bytes(&mut dispatcher, &BYTES[5..]);
+        assert_eq!(dispatcher.ends, ends);
+        assert_eq!(dispatcher.intermediates[1], b">");
+    }
+
+    #[test]
+    fn builder() {
+        init_test_logging();
+
+        static BYTES: &[u8] = &[
+            b'a', 0x0d, 0x00, 255, 0x41, 255, 250, 255, 240, 255, 250, 24, 1, 2, 3, 255, 240,
+        ];
+
+        let mut lenient = IacDispatcher::default();
+        Parser::new().advance_bytes(&mut lenient, BYTES);
+        assert_eq!(lenient.execute, &[0x0d, 0x00]);
+        assert_eq!(lenient.iac, &[0x41]);
+        assert_eq!(lenient.subs, &[vec![255], vec![24, 1, 2, 3, 255]]);
+        assert!(lenient.errors.is_empty());
+
+        let mut strict = IacDispatcher::default();
+        let mut parser = Parser::builder()
+            .max_subnegotiation(64)
+            .cr_handling(CrHandling::StripNul)
+            .binary(false, true)
+            .strict(true)
+            .build();
+        assert_eq!(parser.binary(), (false, true));
+        parser.advance_bytes(&mut strict, BYTES);
+        assert_eq!(strict.execute, &[0x0d]);
+        assert!(strict.iac.is_empty());
+        assert_eq!(strict.subs, &[vec![24, 1, 2, 3, 255]]);
+        assert_eq!(
+            strict.errors,
+            &[
+                ParseError::InvalidCommand { byte: 0x41 },
+                ParseError::MissingOption
+            ]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot() {
+        init_test_logging();
+
+        static BYTES: &[u8] = &[b'a', b'b', 255, 250, 24, 0, 255, 240, b'c'];
+
+        let mut whole = IacDispatcher::default();
+        Parser::new().advance_bytes(&mut whole, BYTES);
+
+        let mut restored = IacDispatcher::default();
+        let mut parser = Parser::new();
+        parser.advance_bytes(&mut restored, &BYTES[..5]);
+        let snapshot = serde_json::to_string(&parser).unwrap();
+        let mut parser: Parser = serde_json::from_str(&snapshot).unwrap();
+        parser.advance_bytes(&mut restored, &BYTES[5..]);
+
+        assert_eq!(restored.subs, whole.subs);
+        assert_eq!(restored.intermediates.concat(), b"abc");
+    }
+
+    #[test]
+    fn default_debug_and_clone() {
+        assert_eq!(
+            format!("{:?}", Parser::default()),
+            format!("{:?}", Parser::new())
+        );
+
+        let mut parser = Parser::new();
+        parser.advance_bytes(&mut IacDispatcher::default(), &[b'a', 0x7f, 255, 253]);
+        let clone = parser.clone();
+        assert_eq!(
+            format!("{:?}", clone),
+            "Parser { state: NegEntry, buffered_data: 0, buffered_subnegotiation: 0, \
+             pending_negotiation: Some(253), binary: (false, false) }"
+        );
+
+        // Parsers and negotiators can be moved to and shared between threads
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Parser>();
+        assert_send_sync::<crate::q::Negotiator>();
+    }
+
+    #[test]
+    fn dynamic_performers() {
+        init_test_logging();
+
+        let mut performers: Vec<Box<dyn Perform>> = vec![
+            Box::new(IacDispatcher::default()),
+            Box::new(Vec::<crate::event::Event>::new()),
+        ];
+        let mut parser = Parser::new();
+        for performer in performers.iter_mut() {
+            parser.advance_dyn(performer.as_mut(), b'a');
+            assert_eq!(parser.advance_bytes_dyn(performer.as_mut(), &[255, 241]), 2);
+            // Boxes are performers themselves
+            parser.advance_bytes(performer, &[255, 241]);
+        }
+    }
+
+    #[test]
+    fn break_from_callback() {
+        init_test_logging();
+
+        #[derive(Default)]
+        struct StartTls {
+            dispatcher: IacDispatcher,
+            follows: bool,
+        }
+
+        impl Perform for StartTls {
+            fn data(&mut self, intermediates: &[u8], ignoring: bool, end: DataEnd) {
+                self.dispatcher.data(intermediates, ignoring, end);
+            }
+            fn execute(&mut self, _byte: u8) {}
+            fn iac_dispatch(&mut self, _byte: u8) {}
+            fn sub_dispatch(&mut self, subs: &[u8]) {
+                self.follows = subs.starts_with(&[46, 1]);
+            }
+            fn negotiate_dispatch(&mut self, _cmd: u8, _opt: u8) {}
+            fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+            fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+            fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+            fn compress_dispatch(&mut self, _state: u8) {}
+            fn should_break(&mut self) -> bool {
+                self.follows
+            }
+        }
+
+        static BYTES: &[u8] = &[b'o', b'k', 255, 250, 46, 1, 255, 240, 0x16, 0x03, b'x'];
+
+        let mut performer = StartTls::default();
+        let mut parser = Parser::new();
+        assert_eq!(parser.advance_bytes(&mut performer, BYTES), 8);
+        assert_eq!(performer.dispatcher.intermediates, &[b"ok"]);
+
+        let mut dispatcher = IacDispatcher::default();
+        assert_eq!(parser.advance_bytes(&mut dispatcher, BYTES), BYTES.len());
+    }
 }
@@ -0,0 +1,273 @@
+//! Limits against abusive peers
+//!
+//! A hostile peer can keep a server busy with a flood of commands or subnegotiations, each of
+//! which is answered by the negotiator or passed to a handler. [`Limits`] sets thresholds on
+//! them. A [`TelnetSession`] with limits replaces the event exceeding one with
+//! [`Event::ProtocolAbuse`], and drops the events exceeding it after that instead of acting on
+//! them.
+//!
+//! Commands are limited in a row, until data arrives. Subnegotiations are limited per window of
+//! time, which the caller advances with [`TelnetSession::tick`] as for the timing marks.
+//!
+//! ```
+//! use televerknet::event::Event;
+//! use televerknet::limits::{Abuse, Limits};
+//! use televerknet::session::{Role, TelnetSession};
+//!
+//! let limits = Limits::new().max_commands(2);
+//! let mut session = TelnetSession::with_role(Role::Server).limits(limits);
+//! let events: Vec<Event> = session.feed(b"\xff\xf1\xff\xf1\xff\xf6\xff\xf6").collect();
+//! assert_eq!(events.last(), Some(&Event::ProtocolAbuse(Abuse::Commands)));
+//! ```
+//!
+//! [`Limits`]: struct.Limits.html
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+//! [`TelnetSession::tick`]: ../session/struct.TelnetSession.html#method.tick
+//! [`Event::ProtocolAbuse`]: ../event/enum.Event.html#variant.ProtocolAbuse
+use std::time::Duration;
+
+use crate::event::Event;
+
+/// A limit the other party exceeded
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Abuse {
+    /// Too many IAC sequences without data in between
+    Commands,
+    /// Too many subnegotiations within the window
+    Subnegotiations,
+    /// Too many bytes of subnegotiation parameters within the window
+    SubnegotiationBytes,
+}
+
+/// Thresholds for [`Limiter`], none by default
+///
+/// [`Limiter`]: struct.Limiter.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Limits {
+    window: Duration,
+    max_commands: Option<usize>,
+    max_subnegotiations: Option<usize>,
+    max_subnegotiation_bytes: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            window: Duration::from_secs(1),
+            max_commands: None,
+            max_subnegotiations: None,
+            max_subnegotiation_bytes: None,
+        }
+    }
+}
+
+impl Limits {
+    pub fn new() -> Limits {
+        Limits::default()
+    }
+
+    /// Set the window the subnegotiation limits apply to, a second by default
+    pub fn window(mut self, window: Duration) -> Limits {
+        self.window = window;
+        self
+    }
+
+    /// Allow at most `max` commands, negotiations and subnegotiations in a row without data
+    pub fn max_commands(mut self, max: usize) -> Limits {
+        self.max_commands = Some(max);
+        self
+    }
+
+    /// Allow at most `max` subnegotiations per window
+    pub fn max_subnegotiations(mut self, max: usize) -> Limits {
+        self.max_subnegotiations = Some(max);
+        self
+    }
+
+    /// Allow at most `max` bytes of subnegotiation parameters per window
+    pub fn max_subnegotiation_bytes(mut self, max: usize) -> Limits {
+        self.max_subnegotiation_bytes = Some(max);
+        self
+    }
+}
+
+/// What to do with an event, see [`Limiter::check`]
+///
+/// [`Limiter::check`]: struct.Limiter.html#method.check
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The event is within the limits
+    Allow,
+    /// The event exceeds a limit for the first time, report the abuse instead
+    Report(Abuse),
+    /// The event exceeds a limit which was already reported, drop it
+    Drop,
+}
+
+/// Counts events against [`Limits`]
+///
+/// [`Limits`]: struct.Limits.html
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    limits: Limits,
+    elapsed: Duration,
+    commands: usize,
+    subnegotiations: usize,
+    subnegotiation_bytes: usize,
+    reported: [bool; 3],
+}
+
+impl Limiter {
+    pub fn new(limits: Limits) -> Limiter {
+        Limiter {
+            limits,
+            elapsed: Duration::from_secs(0),
+            commands: 0,
+            subnegotiations: 0,
+            subnegotiation_bytes: 0,
+            reported: [false; 3],
+        }
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Advance time by `elapsed`, starting a new window once the current one is over
+    pub fn tick(&mut self, elapsed: Duration) {
+        self.elapsed += elapsed;
+        if self.elapsed >= self.limits.window {
+            self.elapsed = Duration::from_secs(0);
+            self.subnegotiations = 0;
+            self.subnegotiation_bytes = 0;
+            self.reported[Abuse::Subnegotiations as usize] = false;
+            self.reported[Abuse::SubnegotiationBytes as usize] = false;
+        }
+    }
+
+    /// Count `event` and decide what to do with it
+    pub fn check(&mut self, event: &Event) -> Verdict {
+        let mut subnegotiation = false;
+        match event {
+            Event::Data(_) | Event::Execute(_) => {
+                self.commands = 0;
+                self.reported[Abuse::Commands as usize] = false;
+                return Verdict::Allow;
+            }
+            Event::Command(_) | Event::Negotiate(..) => (),
+            Event::Subnegotiate(_, params) => {
+                self.subnegotiations += 1;
+                self.subnegotiation_bytes += params.len();
+                subnegotiation = true;
+            }
+            Event::LogoutRequested
+            | Event::EndOfTurn
+            | Event::ProtocolAbuse(_)
+            | Event::StatusMismatch(_)
+            | Event::EchoChanged(_) => return Verdict::Allow,
+        }
+        self.commands += 1;
+
+        let exceeds = |count: usize, max: Option<usize>| max.is_some_and(|max| count > max);
+        let abuse = if exceeds(self.commands, self.limits.max_commands) {
+            Abuse::Commands
+        } else if !subnegotiation {
+            return Verdict::Allow;
+        } else if exceeds(self.subnegotiations, self.limits.max_subnegotiations) {
+            Abuse::Subnegotiations
+        } else if exceeds(
+            self.subnegotiation_bytes,
+            self.limits.max_subnegotiation_bytes,
+        ) {
+            Abuse::SubnegotiationBytes
+        } else {
+            return Verdict::Allow;
+        };
+        if self.reported[abuse as usize] {
+            return Verdict::Drop;
+        }
+        self.reported[abuse as usize] = true;
+        Verdict::Report(abuse)
+    }
+
+    /// Check the events from index `from` on, replacing or removing those exceeding a limit
+    pub fn filter(&mut self, events: &mut Vec<Event>, from: usize) {
+        let checked = events.split_off(from);
+        for event in checked {
+            match self.check(&event) {
+                Verdict::Allow => events.push(event),
+                Verdict::Report(abuse) => events.push(Event::ProtocolAbuse(abuse)),
+                Verdict::Drop => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Abuse, Limiter, Limits, Verdict};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::session::{Role, TelnetSession};
+
+    #[test]
+    fn limiter() {
+        let mut limiter = Limiter::new(
+            Limits::new()
+                .max_subnegotiations(2)
+                .max_subnegotiation_bytes(5),
+        );
+        let sub = |len| Event::Subnegotiate(Opt::GMCP, vec![b'x'; len]);
+        assert_eq!(limiter.check(&sub(1)), Verdict::Allow);
+        assert_eq!(limiter.check(&sub(1)), Verdict::Allow);
+        assert_eq!(
+            limiter.check(&sub(1)),
+            Verdict::Report(Abuse::Subnegotiations)
+        );
+        assert_eq!(limiter.check(&sub(1)), Verdict::Drop);
+        assert_eq!(limiter.check(&Event::Command(Command::NOP)), Verdict::Allow);
+
+        limiter.tick(Duration::from_millis(600));
+        assert_eq!(limiter.check(&sub(1)), Verdict::Drop);
+        limiter.tick(Duration::from_millis(600));
+        assert_eq!(limiter.check(&sub(3)), Verdict::Allow);
+        assert_eq!(
+            limiter.check(&sub(3)),
+            Verdict::Report(Abuse::SubnegotiationBytes)
+        );
+    }
+
+    #[test]
+    fn session() {
+        let mut session =
+            TelnetSession::with_role(Role::Server).limits(Limits::new().max_commands(3));
+        session.take_output();
+
+        // The refusals of the negotiations dropped are not sent
+        let events: Vec<Event> = session
+            .feed(b"\xff\xfb\x20\xff\xfb\x21\xff\xfb\x22\xff\xfb\x23\xff\xfb\x24")
+            .collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[3], Event::ProtocolAbuse(Abuse::Commands));
+        assert_eq!(
+            session.take_output(),
+            b"\xff\xfe\x20\xff\xfe\x21\xff\xfe\x22"
+        );
+
+        // Data starts a new run of commands
+        let events: Vec<Event> = session.feed(b"hi\xff\xfb\x25").collect();
+        assert_eq!(
+            events,
+            &[
+                Event::Data(b"hi".to_vec()),
+                Event::Negotiate(Command::WILL, Opt::from_u8(0x25)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Assembly of data into lines
+//!
+//! [`LineAssembler`] is a performer which collects data into complete lines, whatever the reads
+//! and commands in between, and passes them on to a [`LinePerform`]. Lines end with `CR LF`,
+//! `CR NUL` or a bare `LF`. Text followed by `IAC GA` or `IAC EOR` instead is a prompt.
+//!
+//! [`LineAssembler`]: struct.LineAssembler.html
+//! [`LinePerform`]: trait.LinePerform.html
+use crate::command::Command;
+use crate::event::Event;
+use crate::{DataEnd, ParseError, Perform};
+
+const CR: u8 = b'\r';
+const LF: u8 = b'\n';
+const NUL: u8 = 0;
+
+/// Receives the lines and prompts of a [`LineAssembler`]
+///
+/// [`LineAssembler`]: struct.LineAssembler.html
+pub trait LinePerform {
+    /// A complete line, without its line ending
+    ///
+    /// Invalid UTF-8 is replaced with U+FFFD.
+    fn line(&mut self, line: &str);
+
+    /// Text followed by GA or EOR
+    fn prompt(&mut self, prompt: &str);
+
+    /// Any event which is not data, GA or EOR
+    fn event(&mut self, _event: Event) {}
+
+    /// See [`Perform::error`](../trait.Perform.html#method.error)
+    fn error(&mut self, _error: ParseError) {}
+}
+
+/// Collects data into lines for a [`LinePerform`]
+///
+/// Control bytes other than line endings, e.g. the ESC of ANSI sequences, are kept in the line.
+///
+/// [`LinePerform`]: trait.LinePerform.html
+#[derive(Debug, Default)]
+pub struct LineAssembler<P> {
+    inner: P,
+    line: Vec<u8>,
+    after_cr: bool,
+}
+
+impl<P: LinePerform> LineAssembler<P> {
+    pub fn new(inner: P) -> LineAssembler<P> {
+        LineAssembler {
+            inner,
+            line: Vec::new(),
+            after_cr: false,
+        }
+    }
+
+    /// The start of a line received so far
+    pub fn pending(&self) -> &[u8] {
+        &self.line
+    }
+
+    pub fn get_ref(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.after_cr {
+                self.after_cr = false;
+                self.end_line();
+                if byte == LF || byte == NUL {
+                    continue;
+                }
+            }
+            match byte {
+                CR => self.after_cr = true,
+                LF => self.end_line(),
+                byte => self.line.push(byte),
+            }
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.inner.line(&String::from_utf8_lossy(&self.line));
+        self.line.clear();
+    }
+
+    fn end_prompt(&mut self) {
+        if self.after_cr {
+            self.after_cr = false;
+            self.end_line();
+        }
+        if !self.line.is_empty() {
+            self.inner.prompt(&String::from_utf8_lossy(&self.line));
+            self.line.clear();
+        }
+    }
+
+    /// Pass on events collected from a callback, with data added to the line
+    fn pass_on(&mut self, events: Vec<Event>) {
+        for event in events {
+            match event {
+                Event::Data(data) => self.push(&data),
+                Event::Command(Command::GA) | Event::Command(Command::EOR) => self.end_prompt(),
+                event => self.inner.event(event),
+            }
+        }
+    }
+}
+
+impl<P: LinePerform> Perform for LineAssembler<P> {
+    fn data(&mut self, intermediates: &[u8], _ignore: bool, _end: DataEnd) {
+        self.push(intermediates);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.push(&[byte]);
+    }
+
+    fn error(&mut self, error: ParseError) {
+        self.inner.error(error);
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        let mut events: Vec<Event> = Vec::with_capacity(1);
+        events.iac_dispatch(byte);
+        self.pass_on(events);
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        let mut events: Vec<Event> = Vec::with_capacity(1);
+        events.sub_dispatch(subs);
+        self.pass_on(events);
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        let mut events: Vec<Event> = Vec::with_capacity(1);
+        events.negotiate_dispatch(cmd, opt);
+        self.pass_on(events);
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+    fn compress_dispatch(&mut self, _state: u8) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LineAssembler, LinePerform};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::Parser;
+
+    #[derive(Default)]
+    struct Lines(Vec<String>);
+
+    impl LinePerform for Lines {
+        fn line(&mut self, line: &str) {
+            self.0.push(format!("line {}", line));
+        }
+
+        fn prompt(&mut self, prompt: &str) {
+            self.0.push(format!("prompt {}", prompt));
+        }
+
+        fn event(&mut self, event: Event) {
+            self.0.push(format!("{:?}", event));
+        }
+    }
+
+    #[test]
+    fn lines_and_prompts() {
+        let mut parser = Parser::new();
+        let mut assembler = LineAssembler::new(Lines::default());
+        parser.advance_bytes(&mut assembler, b"Wel\xff\xfb\x01come\r");
+        assert_eq!(assembler.pending(), b"Welcome");
+        parser.advance_bytes(
+            &mut assembler,
+            b"\n\x1b[1mA\xff\xffB\x1b[0m\r\0\nLogin: \xff\xf9",
+        );
+        parser.advance_bytes(&mut assembler, b"x\r\xff\xef");
+
+        assert_eq!(
+            assembler.into_inner().0,
+            &[
+                format!("{:?}", Event::Negotiate(Command::WILL, Opt::ECHO)),
+                "line Welcome".to_owned(),
+                "line \x1b[1mA\u{fffd}B\x1b[0m".to_owned(),
+                "line ".to_owned(),
+                "prompt Login: ".to_owned(),
+                "line x".to_owned(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,569 @@
+//! Local line editing with LINEMODE, as described in [RFC 1184]
+//!
+//! A client performing LINEMODE edits lines itself as long as the server sets the EDIT bit of the
+//! [`Mode`]. The server also sets the special characters for editing and signals with SLC, and
+//! with FORWARDMASK the characters which send the line before it is complete, e.g. function
+//! keys.
+//!
+//! [`LineEditor`] keeps that state and the line being typed. Events from the server go to
+//! [`LineEditor::observe`], which returns the answer to send back, and typed bytes to
+//! [`LineEditor::key`], which returns what to send once there is something:
+//!
+//! ```
+//! use televerknet::event::Event;
+//! use televerknet::linemode::{Input, LineEditor};
+//! use televerknet::option::Opt;
+//!
+//! let mut editor = LineEditor::new();
+//! // MODE EDIT
+//! let answer = editor.observe(&Event::Subnegotiate(Opt::LINEMODE, vec![1, 1]));
+//! assert_eq!(answer, Some(Event::Subnegotiate(Opt::LINEMODE, vec![1, 5])));
+//!
+//! let typed: Vec<Input> = b"lsx\x7f -l\r".iter().filter_map(|b| editor.key(*b)).collect();
+//! assert_eq!(typed, &[Input::Line(b"ls -l".to_vec())]);
+//! ```
+//!
+//! [RFC 1184]: https://www.rfc-editor.org/rfc/rfc1184.html
+//! [`Mode`]: struct.Mode.html
+//! [`LineEditor`]: struct.LineEditor.html
+//! [`LineEditor::observe`]: struct.LineEditor.html#method.observe
+//! [`LineEditor::key`]: struct.LineEditor.html#method.key
+use std::ops::BitOr;
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::option::Opt;
+
+const LINEMODE_MODE: u8 = 1;
+const FORWARDMASK: u8 = 2;
+const LINEMODE_SLC: u8 = 3;
+
+const WILL: u8 = 251;
+const WONT: u8 = 252;
+const DO: u8 = 253;
+const DONT: u8 = 254;
+
+/// One bit for each of the 256 characters
+const FORWARDMASK_LEN: usize = 32;
+
+const SLC_LEVELBITS: u8 = 0x03;
+const SLC_NOSUPPORT: u8 = 0;
+const SLC_VALUE: u8 = 2;
+const SLC_DEFAULT: u8 = 3;
+const SLC_ACK: u8 = 0x80;
+
+const CR: u8 = b'\r';
+const LF: u8 = b'\n';
+const TAB: u8 = b'\t';
+const TAB_WIDTH: usize = 8;
+
+/// Bits of the LINEMODE MODE
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Mode(u8);
+
+impl Mode {
+    /// The client edits lines locally
+    pub const EDIT: Mode = Mode(1);
+    /// The client turns signal characters into telnet commands, e.g. `^C` into IP
+    pub const TRAPSIG: Mode = Mode(2);
+    /// Set by the client when it acknowledges a mode
+    pub const MODE_ACK: Mode = Mode(4);
+    /// The client expands tabs into spaces
+    pub const SOFT_TAB: Mode = Mode(8);
+    /// The client echoes non-printable characters literally
+    pub const LIT_ECHO: Mode = Mode(16);
+
+    pub const fn from_bits(bits: u8) -> Mode {
+        Mode(bits)
+    }
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Returns true if all flags of `other` are set
+    pub const fn contains(self, other: Mode) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Mode {
+    type Output = Mode;
+
+    fn bitor(self, other: Mode) -> Mode {
+        Mode(self.0 | other.0)
+    }
+}
+
+/// Functions of the special characters set with SLC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Slc {
+    Synch = 1,
+    Brk = 2,
+    Ip = 3,
+    Ao = 4,
+    Ayt = 5,
+    Eor = 6,
+    Abort = 7,
+    Eof = 8,
+    Susp = 9,
+    /// Erase the last character
+    Ec = 10,
+    /// Erase the line
+    El = 11,
+    /// Erase the last word
+    Ew = 12,
+    Rp = 13,
+    /// Take the next character literally
+    Lnext = 14,
+    Xon = 15,
+    Xoff = 16,
+    /// Forward the line, as a character in the forward mask does
+    Forw1 = 17,
+    Forw2 = 18,
+}
+
+const SLC_FUNCTIONS: [Slc; 18] = [
+    Slc::Synch,
+    Slc::Brk,
+    Slc::Ip,
+    Slc::Ao,
+    Slc::Ayt,
+    Slc::Eor,
+    Slc::Abort,
+    Slc::Eof,
+    Slc::Susp,
+    Slc::Ec,
+    Slc::El,
+    Slc::Ew,
+    Slc::Rp,
+    Slc::Lnext,
+    Slc::Xon,
+    Slc::Xoff,
+    Slc::Forw1,
+    Slc::Forw2,
+];
+
+/// Signals sent as a command with TRAPSIG
+const SIGNALS: [(Slc, Command); 7] = [
+    (Slc::Ip, Command::IP),
+    (Slc::Ao, Command::AO),
+    (Slc::Ayt, Command::AYT),
+    (Slc::Brk, Command::BREAK),
+    (Slc::Abort, Command::ABORT),
+    (Slc::Eof, Command::EOF),
+    (Slc::Susp, Command::SUSP),
+];
+
+impl Slc {
+    pub fn from_u8(function: u8) -> Option<Slc> {
+        SLC_FUNCTIONS
+            .iter()
+            .copied()
+            .find(|slc| *slc as u8 == function)
+    }
+
+    /// The character of a client by default, the one of most Unix terminals
+    pub fn default_value(self) -> Option<u8> {
+        match self {
+            Slc::Ip => Some(0x03),
+            Slc::Ao => Some(0x0f),
+            Slc::Ayt => Some(0x14),
+            Slc::Abort => Some(0x1c),
+            Slc::Eof => Some(0x04),
+            Slc::Susp => Some(0x1a),
+            Slc::Ec => Some(0x7f),
+            Slc::El => Some(0x15),
+            Slc::Ew => Some(0x17),
+            Slc::Rp => Some(0x12),
+            Slc::Lnext => Some(0x16),
+            Slc::Xon => Some(0x11),
+            Slc::Xoff => Some(0x13),
+            Slc::Synch | Slc::Brk | Slc::Eor | Slc::Forw1 | Slc::Forw2 => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize - 1
+    }
+}
+
+/// What to send for typed input, see [`LineEditor::key`]
+///
+/// [`LineEditor::key`]: struct.LineEditor.html#method.key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    /// A complete line, without the CR or LF which ended it
+    Line(Vec<u8>),
+    /// The line so far followed by a character of the forward mask or FORW1 and FORW2, to send
+    /// as it is
+    Forward(Vec<u8>),
+    /// A character typed while the server edits, to send as it is
+    Character(u8),
+    /// A signal character typed with TRAPSIG, to send as a command
+    Command(Command),
+}
+
+/// Edits lines as negotiated with LINEMODE
+///
+/// Without a MODE from the server the editor passes every character through, as the server
+/// edits. Special characters start out with the defaults of [`Slc::default_value`].
+///
+/// [`Slc::default_value`]: enum.Slc.html#method.default_value
+#[derive(Debug, Clone)]
+pub struct LineEditor {
+    mode: Mode,
+    slc: [Option<u8>; 18],
+    forward_mask: Option<[u8; FORWARDMASK_LEN]>,
+    line: Vec<u8>,
+    literal_next: bool,
+    after_cr: bool,
+}
+
+impl Default for LineEditor {
+    fn default() -> LineEditor {
+        LineEditor::new()
+    }
+}
+
+impl LineEditor {
+    pub fn new() -> LineEditor {
+        LineEditor {
+            mode: Mode::default(),
+            slc: SLC_FUNCTIONS.map(Slc::default_value),
+            forward_mask: None,
+            line: Vec::new(),
+            literal_next: false,
+            after_cr: false,
+        }
+    }
+
+    /// The mode set by the server
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The character of `function`, if it is supported
+    pub fn special_char(&self, function: Slc) -> Option<u8> {
+        self.slc[function.index()]
+    }
+
+    /// The line typed so far, to show it
+    pub fn pending(&self) -> &[u8] {
+        &self.line
+    }
+
+    /// Observe an event from the server
+    ///
+    /// Returns the answer to send, if any. Disabling LINEMODE with DONT drops the line typed so
+    /// far and goes back to passing characters through.
+    ///
+    /// A forward mask of more than 32 bytes, or none at all, is refused with `WONT FORWARDMASK`.
+    /// A shorter one leaves the characters past its end out. Events should come from a
+    /// [`TelnetSession`], which leaves out subnegotiations cut short by the parser.
+    ///
+    /// [`TelnetSession`]: ../session/struct.TelnetSession.html
+    pub fn observe(&mut self, event: &Event) -> Option<Event> {
+        let params = match event {
+            Event::Negotiate(Command::DONT, Opt::LINEMODE) => {
+                *self = LineEditor::new();
+                return None;
+            }
+            Event::Subnegotiate(Opt::LINEMODE, params) => params,
+            _ => return None,
+        };
+        let answer = match params.as_slice() {
+            [LINEMODE_MODE, mask] => {
+                let mode = Mode(*mask);
+                if mode.contains(Mode::MODE_ACK) {
+                    return None;
+                }
+                self.mode = mode;
+                vec![LINEMODE_MODE, (mode | Mode::MODE_ACK).bits()]
+            }
+            [DO, FORWARDMASK, mask @ ..] => {
+                let mut forward_mask = [0; FORWARDMASK_LEN];
+                if mask.is_empty() || mask.len() > forward_mask.len() {
+                    self.forward_mask = None;
+                    return Some(Event::Subnegotiate(Opt::LINEMODE, vec![WONT, FORWARDMASK]));
+                }
+                forward_mask[..mask.len()].copy_from_slice(mask);
+                self.forward_mask = Some(forward_mask);
+                vec![WILL, FORWARDMASK]
+            }
+            [DONT, FORWARDMASK] => {
+                self.forward_mask = None;
+                vec![WONT, FORWARDMASK]
+            }
+            [LINEMODE_SLC, triplets @ ..] => self.slc(triplets)?,
+            _ => return None,
+        };
+        Some(Event::Subnegotiate(Opt::LINEMODE, answer))
+    }
+
+    /// Take the special characters of the server, answering those not acknowledged yet
+    fn slc(&mut self, triplets: &[u8]) -> Option<Vec<u8>> {
+        let mut answer = vec![LINEMODE_SLC];
+        for triplet in triplets.chunks_exact(3) {
+            let (function, modifiers, value) = (triplet[0], triplet[1], triplet[2]);
+            // SLC 0 DEFAULT asks for our whole table
+            if function == 0 && modifiers & SLC_LEVELBITS == SLC_DEFAULT {
+                self.slc = SLC_FUNCTIONS.map(Slc::default_value);
+                for slc in SLC_FUNCTIONS {
+                    self.push_slc(slc, &mut answer);
+                }
+                continue;
+            }
+            let slc = match Slc::from_u8(function) {
+                Some(slc) => slc,
+                None => continue,
+            };
+            match modifiers & SLC_LEVELBITS {
+                SLC_DEFAULT => {
+                    self.slc[slc.index()] = slc.default_value();
+                    self.push_slc(slc, &mut answer);
+                }
+                SLC_NOSUPPORT => {
+                    self.slc[slc.index()] = None;
+                    if modifiers & SLC_ACK == 0 {
+                        answer.extend_from_slice(&[function, SLC_NOSUPPORT | SLC_ACK, 0]);
+                    }
+                }
+                _ => {
+                    self.slc[slc.index()] = Some(value);
+                    if modifiers & SLC_ACK == 0 {
+                        answer.extend_from_slice(&[function, modifiers | SLC_ACK, value]);
+                    }
+                }
+            }
+        }
+        if answer.len() == 1 {
+            None
+        } else {
+            Some(answer)
+        }
+    }
+
+    fn push_slc(&self, slc: Slc, answer: &mut Vec<u8>) {
+        match self.special_char(slc) {
+            Some(value) => answer.extend_from_slice(&[slc as u8, SLC_VALUE, value]),
+            None => answer.extend_from_slice(&[slc as u8, SLC_NOSUPPORT, 0]),
+        }
+    }
+
+    fn is_forwarding(&self, byte: u8) -> bool {
+        let forward = |slc| self.special_char(slc) == Some(byte);
+        let masked = self
+            .forward_mask
+            .is_some_and(|mask| mask[usize::from(byte / 8)] & (0x80 >> (byte % 8)) != 0);
+        masked || forward(Slc::Forw1) || forward(Slc::Forw2)
+    }
+
+    /// Handle a typed byte
+    ///
+    /// Returns what to send to the server, if anything.
+    pub fn key(&mut self, byte: u8) -> Option<Input> {
+        let after_cr = std::mem::replace(&mut self.after_cr, false);
+        let is = |slc| self.special_char(slc) == Some(byte);
+        if self.literal_next {
+            self.literal_next = false;
+        } else {
+            if self.mode.contains(Mode::TRAPSIG) {
+                if let Some((_, command)) = SIGNALS.iter().find(|(slc, _)| is(*slc)) {
+                    self.line.clear();
+                    return Some(Input::Command(*command));
+                }
+            }
+            if !self.mode.contains(Mode::EDIT) {
+                return Some(Input::Character(byte));
+            }
+            if is(Slc::Lnext) {
+                self.literal_next = true;
+                return None;
+            }
+            if is(Slc::Ec) {
+                self.line.pop();
+                return None;
+            }
+            if is(Slc::El) {
+                self.line.clear();
+                return None;
+            }
+            if is(Slc::Ew) {
+                while self.line.last() == Some(&b' ') {
+                    self.line.pop();
+                }
+                while self.line.last().is_some_and(|b| *b != b' ') {
+                    self.line.pop();
+                }
+                return None;
+            }
+            match byte {
+                LF if after_cr => return None,
+                CR | LF => {
+                    self.after_cr = byte == CR;
+                    return Some(Input::Line(std::mem::take(&mut self.line)));
+                }
+                _ => (),
+            }
+            if self.is_forwarding(byte) {
+                self.line.push(byte);
+                return Some(Input::Forward(std::mem::take(&mut self.line)));
+            }
+        }
+        if byte == TAB && self.mode.contains(Mode::SOFT_TAB) {
+            let spaces = TAB_WIDTH - self.line.len() % TAB_WIDTH;
+            self.line.resize(self.line.len() + spaces, b' ');
+        } else {
+            self.line.push(byte);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Input, LineEditor, Mode, Slc};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::session::{Policy, TelnetSession};
+
+    fn keys(editor: &mut LineEditor, typed: &[u8]) -> Vec<Input> {
+        typed.iter().filter_map(|b| editor.key(*b)).collect()
+    }
+
+    #[test]
+    fn editing() {
+        let mut editor = LineEditor::new();
+        assert_eq!(
+            keys(&mut editor, b"ab"),
+            &[Input::Character(b'a'), Input::Character(b'b')]
+        );
+
+        let mode = Event::Subnegotiate(Opt::LINEMODE, vec![1, 1 | 2 | 8]);
+        assert_eq!(
+            editor.observe(&mode),
+            Some(Event::Subnegotiate(Opt::LINEMODE, vec![1, 15]))
+        );
+        assert!(editor.mode().contains(Mode::EDIT | Mode::TRAPSIG));
+        // An acknowledged mode is not answered again
+        assert_eq!(
+            editor.observe(&Event::Subnegotiate(Opt::LINEMODE, vec![1, 15])),
+            None
+        );
+
+        assert_eq!(
+            keys(&mut editor, b"rm foo bar\x17baz\r\n"),
+            &[Input::Line(b"rm foo baz".to_vec())]
+        );
+        assert_eq!(
+            keys(&mut editor, b"oops\x15a\x16\x7fb\tc\n"),
+            &[Input::Line(b"a\x7fb     c".to_vec())]
+        );
+        assert_eq!(
+            keys(&mut editor, b"sleep\x03"),
+            &[Input::Command(Command::IP)]
+        );
+        assert!(editor.pending().is_empty());
+
+        editor.observe(&Event::Negotiate(Command::DONT, Opt::LINEMODE));
+        assert_eq!(keys(&mut editor, b"\x03"), &[Input::Character(3)]);
+    }
+
+    #[test]
+    fn special_characters() {
+        let mut editor = LineEditor::new();
+        // EC set to ^H, EL not supported, EW already acknowledged, AYT back to the default
+        let slc = Event::Subnegotiate(
+            Opt::LINEMODE,
+            vec![3, 10, 2, 8, 11, 0, 0, 12, 0x82, 0x17, 5, 3, 0],
+        );
+        assert_eq!(
+            editor.observe(&slc),
+            Some(Event::Subnegotiate(
+                Opt::LINEMODE,
+                vec![3, 10, 0x82, 8, 11, 0x80, 0, 5, 2, 0x14]
+            ))
+        );
+        assert_eq!(editor.special_char(Slc::Ec), Some(8));
+        assert_eq!(editor.special_char(Slc::El), None);
+
+        editor.observe(&Event::Subnegotiate(Opt::LINEMODE, vec![1, 1]));
+        assert_eq!(
+            keys(&mut editor, b"ab\x08c\x15\r"),
+            &[Input::Line(b"ac\x15".to_vec())]
+        );
+    }
+
+    #[test]
+    fn forward_mask() {
+        let mut editor = LineEditor::new();
+        editor.observe(&Event::Subnegotiate(Opt::LINEMODE, vec![1, 1]));
+        // ESC, the start of function keys
+        let mut mask = vec![253, 2, 0, 0, 0, 0x10];
+        assert_eq!(
+            editor.observe(&Event::Subnegotiate(Opt::LINEMODE, mask.clone())),
+            Some(Event::Subnegotiate(Opt::LINEMODE, vec![251, 2]))
+        );
+        assert_eq!(
+            keys(&mut editor, b"go\x1b[A"),
+            &[Input::Forward(b"go\x1b".to_vec())]
+        );
+        assert_eq!(editor.pending(), b"[A");
+
+        mask.truncate(2);
+        mask[0] = 254;
+        assert_eq!(
+            editor.observe(&Event::Subnegotiate(Opt::LINEMODE, mask)),
+            Some(Event::Subnegotiate(Opt::LINEMODE, vec![252, 2]))
+        );
+        assert!(keys(&mut editor, b"\x1b").is_empty());
+
+        // Longer than a mask can be
+        let mut mask = vec![253, 2];
+        mask.resize(35, 0xff);
+        assert_eq!(
+            editor.observe(&Event::Subnegotiate(Opt::LINEMODE, mask)),
+            Some(Event::Subnegotiate(Opt::LINEMODE, vec![252, 2]))
+        );
+        assert!(keys(&mut editor, b"\xfa").is_empty());
+    }
+
+    #[test]
+    fn session() {
+        let mut session = TelnetSession::new(Policy::new().accept_local(Opt::LINEMODE));
+        let mut editor = LineEditor::new();
+
+        // DO LINEMODE, MODE EDIT, a value for every SLC function with ^H erasing a character,
+        // and a full forward mask of the last eight characters
+        let mut from_server = b"\xff\xfd\x22\xff\xfa\x22\x01\x01\xff\xf0\xff\xfa\x22\x03".to_vec();
+        for function in 1..=18 {
+            let value = if function == Slc::Ec as u8 {
+                8
+            } else {
+                0x80 + function
+            };
+            from_server.extend_from_slice(&[function, 2, value]);
+        }
+        from_server.extend_from_slice(b"\xff\xf0\xff\xfa\x22\xfd\x02");
+        from_server.extend_from_slice(&[0; 31]);
+        from_server.extend_from_slice(b"\xff\xff\xff\xf0");
+
+        let events: Vec<Event> = session.feed(&from_server).collect();
+        for event in &events {
+            if let Some(answer) = editor.observe(event) {
+                session.send(&answer);
+            }
+        }
+        assert!(session
+            .take_output()
+            .ends_with(b"\xff\xfa\x22\xfb\x02\xff\xf0"));
+        assert_eq!(editor.special_char(Slc::Ec), Some(8));
+        assert_eq!(editor.special_char(Slc::Forw2), Some(0x92));
+        assert_eq!(
+            keys(&mut editor, b"ab\x08c\xfa"),
+            &[Input::Forward(b"ac\xfa".to_vec())]
+        );
+    }
+}
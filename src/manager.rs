@@ -0,0 +1,175 @@
+//! Many sessions of a server
+//!
+//! [`SessionManager`] owns a [`TelnetSession`] per connection, keyed by any connection id, e.g. a
+//! mio `Token` or a socket address. New sessions share the policy of the manager, and
+//! [`SessionManager::stats`] totals the statistics of all sessions, closed ones included.
+//!
+//! [`SessionManager`]: struct.SessionManager.html
+//! [`SessionManager::stats`]: struct.SessionManager.html#method.stats
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::vec;
+
+use crate::event::Event;
+use crate::session::{Policy, Role, TelnetSession};
+use crate::stats::Stats;
+
+/// Sessions keyed by connection id
+pub struct SessionManager<K> {
+    policy: Policy,
+    role: Option<Role>,
+    sessions: HashMap<K, TelnetSession>,
+    closed: Stats,
+}
+
+impl<K: Eq + Hash> SessionManager<K> {
+    /// Manage sessions which answer negotiation according to `policy`
+    pub fn new(policy: Policy) -> SessionManager<K> {
+        SessionManager {
+            policy,
+            role: None,
+            sessions: HashMap::new(),
+            closed: Stats::new(),
+        }
+    }
+
+    /// Manage sessions created with [`TelnetSession::with_role`]
+    ///
+    /// [`TelnetSession::with_role`]: ../session/struct.TelnetSession.html#method.with_role
+    pub fn with_role(role: Role) -> SessionManager<K> {
+        let mut manager = SessionManager::new(Policy::for_role(role));
+        manager.role = Some(role);
+        manager
+    }
+
+    /// The policy of new sessions
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Start a session for the connection `key`, replacing any previous one
+    ///
+    /// A server session has its initial offers queued as output right away.
+    pub fn open(&mut self, key: K) -> &mut TelnetSession {
+        let session = match self.role {
+            Some(role) => TelnetSession::with_role(role),
+            None => TelnetSession::new(self.policy.clone()),
+        };
+        self.insert(key, session)
+    }
+
+    /// Add a session created by the caller, e.g. one with its own handlers
+    pub fn insert(&mut self, key: K, session: TelnetSession) -> &mut TelnetSession {
+        if let Some(previous) = self.sessions.remove(&key) {
+            self.closed.merge(&previous.stats());
+        }
+        self.sessions.entry(key).or_insert(session)
+    }
+
+    /// End the session of `key`, keeping its statistics in the totals
+    pub fn close(&mut self, key: &K) -> Option<TelnetSession> {
+        let session = self.sessions.remove(key)?;
+        self.closed.merge(&session.stats());
+        Some(session)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&TelnetSession> {
+        self.sessions.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut TelnetSession> {
+        self.sessions.get_mut(key)
+    }
+
+    /// Feed bytes received on the connection `key` to its session
+    ///
+    /// Returns `None` if there is no such session. See [`TelnetSession::feed`].
+    ///
+    /// [`TelnetSession::feed`]: ../session/struct.TelnetSession.html#method.feed
+    pub fn feed(&mut self, key: &K, bytes: &[u8]) -> Option<vec::Drain<'_, Event>> {
+        Some(self.sessions.get_mut(key)?.feed(bytes))
+    }
+
+    /// Take the bytes waiting to be sent on the connection `key`
+    pub fn take_output(&mut self, key: &K) -> Option<Vec<u8>> {
+        Some(self.sessions.get_mut(key)?.take_output())
+    }
+
+    /// Queue `data` for every session, e.g. a server wide announcement
+    pub fn broadcast(&mut self, data: &[u8]) {
+        for session in self.sessions.values_mut() {
+            session.send_data(data);
+        }
+    }
+
+    /// The connections with output waiting to be sent
+    pub fn wants_write(&self) -> impl Iterator<Item = &K> {
+        self.sessions
+            .iter()
+            .filter(|(_, session)| session.wants_write())
+            .map(|(key, _)| key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &TelnetSession)> {
+        self.sessions.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut TelnetSession)> {
+        self.sessions.iter_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Statistics of all sessions, open and closed
+    pub fn stats(&self) -> Stats {
+        let mut stats = self.closed.clone();
+        for session in self.sessions.values() {
+            stats.merge(&session.stats());
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SessionManager;
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::session::{Policy, Role};
+
+    #[test]
+    fn sessions() {
+        let mut manager = SessionManager::new(Policy::new().accept_local(Opt::TTYPE));
+        manager.open(1);
+        manager.open(2);
+        assert_eq!(manager.len(), 2);
+
+        let events: Vec<Event> = manager.feed(&1, &[255, 253, 24, b'a']).unwrap().collect();
+        assert_eq!(events[0], Event::Negotiate(Command::DO, Opt::TTYPE));
+        assert!(manager.feed(&3, b"x").is_none());
+        assert_eq!(manager.wants_write().collect::<Vec<_>>(), &[&1]);
+        assert_eq!(manager.take_output(&1).unwrap(), &[255, 251, 24]);
+
+        manager.broadcast(b"bye\xff");
+        assert_eq!(manager.take_output(&2).unwrap(), b"bye\xff\xff");
+        manager.feed(&2, b"ok").unwrap().for_each(drop);
+        manager.close(&2).unwrap();
+        assert!(manager.get(&2).is_none());
+
+        let stats = manager.stats();
+        assert_eq!(stats.bytes_in(), 6);
+        assert_eq!(stats.bytes_out(), 8);
+        assert_eq!(stats.commands(Command::DO), 1);
+
+        let mut servers = SessionManager::with_role(Role::Server);
+        assert!(servers.open("a").wants_write());
+    }
+}
@@ -0,0 +1,402 @@
+//! MUD Client Protocol
+//!
+//! MOO servers send [MCP 2.1] messages in band, as lines of data starting with `#$#`, instead of
+//! subnegotiations. [`McpLayer`] takes the events of the parser, picks out those lines and returns
+//! them as [`McpMessage`]s, passing everything else on. Lines starting with `#$"` are quoted data
+//! and passed on without that prefix. Multiline values, announced by a key ending with `*` and
+//! sent in `#$#*` continuation lines, are collected until their `#$#:` end line.
+//!
+//! [MCP 2.1]: https://www.moo.mud.org/mcp/mcp2.html
+//! [`McpLayer`]: struct.McpLayer.html
+//! [`McpMessage`]: struct.McpMessage.html
+use std::collections::{BTreeMap, HashMap};
+
+use crate::event::Event;
+
+const MESSAGE: &[u8] = b"#$#";
+const QUOTE: &[u8] = b"#$\"";
+
+/// An MCP message
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct McpMessage {
+    /// The message name, in lower case
+    pub name: String,
+    /// The authentication key, missing for the `mcp` message which sets it up
+    pub auth_key: Option<String>,
+    /// Values by key, keys in lower case
+    pub keyvals: BTreeMap<String, String>,
+    /// Lines of multiline values by key, keys in lower case without the `*`
+    pub multiline: BTreeMap<String, Vec<String>>,
+}
+
+impl McpMessage {
+    pub fn new(name: &str, auth_key: Option<&str>) -> McpMessage {
+        McpMessage {
+            name: name.to_ascii_lowercase(),
+            auth_key: auth_key.map(str::to_owned),
+            ..McpMessage::default()
+        }
+    }
+
+    /// Add a value
+    pub fn keyval(mut self, key: &str, value: &str) -> McpMessage {
+        self.keyvals
+            .insert(key.to_ascii_lowercase(), value.to_owned());
+        self
+    }
+
+    /// Add a multiline value
+    pub fn lines(mut self, key: &str, lines: Vec<String>) -> McpMessage {
+        self.multiline.insert(key.to_ascii_lowercase(), lines);
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.keyvals.get(key).map(String::as_str)
+    }
+
+    /// Append the message as lines ending with CR LF to `dst`
+    ///
+    /// Multiline values are sent in continuation lines with `data_tag`, which should be unique
+    /// among the messages in progress.
+    pub fn encode_into(&self, data_tag: &str, dst: &mut Vec<u8>) {
+        dst.extend_from_slice(MESSAGE);
+        dst.extend_from_slice(self.name.as_bytes());
+        if let Some(auth_key) = &self.auth_key {
+            dst.push(b' ');
+            dst.extend_from_slice(auth_key.as_bytes());
+        }
+        for (key, value) in &self.keyvals {
+            if key == "_data-tag" {
+                continue;
+            }
+            dst.push(b' ');
+            dst.extend_from_slice(key.as_bytes());
+            dst.extend_from_slice(b": ");
+            quote_into(value, dst);
+        }
+        for key in self.multiline.keys() {
+            dst.push(b' ');
+            dst.extend_from_slice(key.as_bytes());
+            dst.extend_from_slice(b"*: \"\"");
+        }
+        if self.multiline.is_empty() {
+            dst.extend_from_slice(b"\r\n");
+            return;
+        }
+        dst.extend_from_slice(b" _data-tag: ");
+        dst.extend_from_slice(data_tag.as_bytes());
+        dst.extend_from_slice(b"\r\n");
+        for (key, lines) in &self.multiline {
+            for line in lines {
+                dst.extend_from_slice(
+                    format!("#$#* {} {}: {}\r\n", data_tag, key, line).as_bytes(),
+                );
+            }
+        }
+        dst.extend_from_slice(format!("#$#: {}\r\n", data_tag).as_bytes());
+    }
+}
+
+/// Append `value` to `dst`, quoted unless it is a simple token
+fn quote_into(value: &str, dst: &mut Vec<u8>) {
+    let simple = !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && !matches!(b, b'"' | b'\\' | b':' | b'*'));
+    if simple {
+        dst.extend_from_slice(value.as_bytes());
+        return;
+    }
+    dst.push(b'"');
+    for b in value.bytes() {
+        if matches!(b, b'"' | b'\\') {
+            dst.push(b'\\');
+        }
+        dst.push(b);
+    }
+    dst.push(b'"');
+}
+
+/// What [`McpLayer`] found in the events passed to it
+///
+/// [`McpLayer`]: struct.McpLayer.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum McpEvent {
+    /// Data which is not MCP, including the line endings
+    Data(Vec<u8>),
+    /// A complete message
+    Message(McpMessage),
+    /// Any event other than data
+    Other(Event),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum LineState {
+    /// At the start of a line, with up to two bytes of a possible prefix buffered
+    Start,
+    /// Within an MCP line, buffered until its end
+    Message,
+    /// Within a line of data
+    Data,
+}
+
+/// Separates MCP messages from data
+#[derive(Debug)]
+pub struct McpLayer {
+    state: LineState,
+    line: Vec<u8>,
+    auth_key: Option<String>,
+    pending: HashMap<String, McpMessage>,
+}
+
+impl Default for McpLayer {
+    fn default() -> McpLayer {
+        McpLayer::new()
+    }
+}
+
+impl McpLayer {
+    pub fn new() -> McpLayer {
+        McpLayer {
+            state: LineState::Start,
+            line: Vec::new(),
+            auth_key: None,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Drop messages which don't carry `auth_key`, once it was sent to the server
+    pub fn set_auth_key(&mut self, auth_key: Option<String>) {
+        self.auth_key = auth_key;
+    }
+
+    /// Pass an event through the layer, appending what was found to `out`
+    ///
+    /// Data and control bytes are checked for MCP lines, with line feeds ending lines. Data which
+    /// could still start an MCP line is held back until it can be told apart.
+    pub fn push(&mut self, event: &Event, out: &mut Vec<McpEvent>) {
+        match event {
+            Event::Data(data) => self.push_bytes(data, out),
+            Event::Execute(byte) => self.push_bytes(&[*byte], out),
+            event => out.push(McpEvent::Other(event.clone())),
+        }
+    }
+
+    /// Pass on data held back, e.g. when the connection closes
+    pub fn flush(&mut self, out: &mut Vec<McpEvent>) {
+        if self.state == LineState::Start && !self.line.is_empty() {
+            push_data(&self.line, out);
+            self.line.clear();
+        }
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8], out: &mut Vec<McpEvent>) {
+        for &byte in bytes {
+            match self.state {
+                LineState::Start => {
+                    self.line.push(byte);
+                    if self.line == MESSAGE {
+                        self.line.clear();
+                        self.state = LineState::Message;
+                    } else if self.line == QUOTE {
+                        self.line.clear();
+                        self.state = LineState::Data;
+                    } else if !MESSAGE.starts_with(&self.line) && !QUOTE.starts_with(&self.line) {
+                        push_data(&self.line, out);
+                        self.line.clear();
+                        self.state = if byte == b'\n' {
+                            LineState::Start
+                        } else {
+                            LineState::Data
+                        };
+                    }
+                }
+                LineState::Message => {
+                    if byte == b'\n' {
+                        let line = std::mem::take(&mut self.line);
+                        let line = String::from_utf8_lossy(&line);
+                        self.message_line(line.trim_end_matches('\r'), out);
+                        self.state = LineState::Start;
+                    } else {
+                        self.line.push(byte);
+                    }
+                }
+                LineState::Data => {
+                    push_data(&[byte], out);
+                    if byte == b'\n' {
+                        self.state = LineState::Start;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Handle an MCP line without its `#$#` prefix
+    fn message_line(&mut self, line: &str, out: &mut Vec<McpEvent>) {
+        if let Some(rest) = line.strip_prefix("* ") {
+            // #$#* <data-tag> <key>: <line>
+            let (tag, rest) = split_word(rest);
+            let (key, value) = split_word(rest);
+            let key = key.trim_end_matches(':').to_ascii_lowercase();
+            if let Some(lines) = self
+                .pending
+                .get_mut(tag)
+                .and_then(|message| message.multiline.get_mut(&key))
+            {
+                lines.push(value.to_owned());
+            }
+        } else if let Some(rest) = line.strip_prefix(": ") {
+            if let Some(message) = self.pending.remove(rest.trim()) {
+                out.push(McpEvent::Message(message));
+            }
+        } else if let Some(message) = parse_message(line) {
+            if message.name != "mcp" && self.auth_key.is_some() && message.auth_key != self.auth_key
+            {
+                return;
+            }
+            if message.multiline.is_empty() {
+                out.push(McpEvent::Message(message));
+            } else if let Some(tag) = message.keyvals.get("_data-tag").cloned() {
+                self.pending.insert(tag, message);
+            }
+        }
+    }
+}
+
+/// Append `bytes` to the data at the end of `out`
+fn push_data(bytes: &[u8], out: &mut Vec<McpEvent>) {
+    match out.last_mut() {
+        Some(McpEvent::Data(data)) => data.extend_from_slice(bytes),
+        _ => out.push(McpEvent::Data(bytes.to_vec())),
+    }
+}
+
+/// Split off the first word of `s` and the spaces after it
+fn split_word(s: &str) -> (&str, &str) {
+    match s.find(' ') {
+        Some(end) => (&s[..end], s[end..].trim_start_matches(' ')),
+        None => (s, ""),
+    }
+}
+
+/// Parse `<name> [<auth-key>] <key>: <value> ...`
+fn parse_message(line: &str) -> Option<McpMessage> {
+    let (name, mut rest) = split_word(line);
+    if name.is_empty() {
+        return None;
+    }
+    let mut message = McpMessage::new(name, None);
+    if message.name != "mcp" {
+        let (auth_key, after) = split_word(rest);
+        message.auth_key = Some(auth_key.to_owned());
+        rest = after;
+    }
+    while !rest.is_empty() {
+        let (key, after) = split_word(rest);
+        let key = key.strip_suffix(':')?.to_ascii_lowercase();
+        let (value, after) = parse_value(after)?;
+        rest = after.trim_start_matches(' ');
+        match key.strip_suffix('*') {
+            Some(key) => {
+                message.multiline.insert(key.to_owned(), Vec::new());
+            }
+            None => {
+                message.keyvals.insert(key, value);
+            }
+        }
+    }
+    Some(message)
+}
+
+/// Parse a value, quoted or not, returning it and the rest of the line
+fn parse_value(s: &str) -> Option<(String, &str)> {
+    let quoted = match s.strip_prefix('"') {
+        Some(quoted) => quoted,
+        None => {
+            let (value, rest) = split_word(s);
+            return Some((value.to_owned(), rest));
+        }
+    };
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => value.push(chars.next()?.1),
+            '"' => return Some((value, &quoted[i + 1..])),
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{McpEvent, McpLayer, McpMessage};
+    use crate::event::Event;
+    use crate::Parser;
+
+    #[test]
+    fn messages_and_data() {
+        let input: &[u8] = b"hello\r\n#$#mcp version: 2.1 to: 2.1\r\n#$\"#$#not\r\n\
+            #$#dns-org-mud-moo-simpleedit-content 3k name: \"a \\\"b\\\"\" content*: \"\" \
+            _data-tag: 7\r\n#$#* 7 content: line one\r\n#$#* 7 content: line two\r\n#$#: 7\r\n\
+            #$#other wrongkey\r\n#$";
+        let mut parser = Parser::new();
+        let mut events: Vec<Event> = Vec::new();
+        // Fed in two parts, splitting a line
+        parser.advance_bytes(&mut events, &input[..20]);
+        parser.flush(&mut events);
+        parser.advance_bytes(&mut events, &input[20..]);
+        parser.flush(&mut events);
+
+        let mut layer = McpLayer::new();
+        let mut out = Vec::new();
+        for event in &events[..] {
+            if let Event::Data(data) = event {
+                if data.starts_with(b"#$#other") {
+                    layer.set_auth_key(Some("3k".to_owned()));
+                }
+            }
+            layer.push(event, &mut out);
+        }
+        layer.flush(&mut out);
+
+        let data: Vec<u8> = out
+            .iter()
+            .filter_map(|event| match event {
+                McpEvent::Data(data) => Some(data.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        assert_eq!(data, b"hello\r\n#$#not\r\n#$");
+
+        let messages: Vec<&McpMessage> = out
+            .iter()
+            .filter_map(|event| match event {
+                McpEvent::Message(message) => Some(message),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            &[
+                &McpMessage::new("mcp", None)
+                    .keyval("version", "2.1")
+                    .keyval("to", "2.1"),
+                &McpMessage::new("dns-org-mud-moo-simpleedit-content", Some("3k"))
+                    .keyval("name", "a \"b\"")
+                    .keyval("_data-tag", "7")
+                    .lines("content", vec!["line one".into(), "line two".into()]),
+            ]
+        );
+
+        let mut encoded = Vec::new();
+        messages[1].encode_into("7", &mut encoded);
+        let mut layer = McpLayer::new();
+        let mut out = Vec::new();
+        layer.push(&Event::Data(encoded), &mut out);
+        assert_eq!(out, &[McpEvent::Message(messages[1].clone())]);
+    }
+}
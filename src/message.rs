@@ -0,0 +1,176 @@
+//! Typed messages to send
+//!
+//! [`OutMessage`] covers what an application sends to the other party, from data to common
+//! subnegotiations, and encodes it with the proper IAC escaping. [`OutMessage::parse`] is the
+//! inverse, so that encoding most messages and parsing them gives the messages back; the
+//! exceptions are listed there.
+//!
+//! [`OutMessage`]: enum.OutMessage.html
+//! [`OutMessage::parse`]: enum.OutMessage.html#method.parse
+use crate::command::Command;
+use crate::encode;
+use crate::event::Event;
+#[cfg(feature = "mud")]
+use crate::gmcp;
+use crate::option::Opt;
+use crate::ParserBuilder;
+
+const MAX_SUBNEGOTIATION: usize = 64 * 1024;
+
+/// A message to send to the other party
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutMessage {
+    /// Data, escaped when encoded
+    Data(Vec<u8>),
+    /// `IAC <command>`
+    Command(Command),
+    /// `IAC <WILL|WONT|DO|DONT> <option>`
+    Negotiate(Command, Opt),
+    /// `IAC SB <option> <params> IAC SE`, parameters escaped when encoded
+    Subnegotiate(Opt, Vec<u8>),
+    /// A GMCP message name and its JSON data, see [`gmcp`](../gmcp/index.html)
+    #[cfg(feature = "mud")]
+    Gmcp(String, String),
+    /// The window size, width and height
+    Naws(u16, u16),
+}
+
+impl OutMessage {
+    /// Append the wire representation of the message to `dst`, e.g. a `Vec<u8>` or a `BytesMut`
+    pub fn encode_into<D: Extend<u8>>(&self, dst: &mut D) {
+        match self {
+            OutMessage::Data(data) => encode::escape_into(data, dst),
+            OutMessage::Command(command) => encode::command_into(*command, dst),
+            OutMessage::Negotiate(command, option) => {
+                encode::negotiate_into(*command, *option, dst)
+            }
+            OutMessage::Subnegotiate(option, params) => {
+                encode::subnegotiate_into(*option, params, dst)
+            }
+            #[cfg(feature = "mud")]
+            OutMessage::Gmcp(name, data) => gmcp::encode_into(name, data, dst),
+            OutMessage::Naws(width, height) => {
+                let mut params = Vec::with_capacity(4);
+                params.extend_from_slice(&width.to_be_bytes());
+                params.extend_from_slice(&height.to_be_bytes());
+                encode::subnegotiate_into(Opt::NAWS, &params, dst);
+            }
+        }
+    }
+
+    /// The wire representation of the message
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.encode_into(&mut bytes);
+        bytes
+    }
+
+    /// Parse `bytes` into messages
+    ///
+    /// Data is joined with the control bytes in it, GMCP subnegotiations become `Gmcp` messages
+    /// and NAWS subnegotiations of four bytes become `Naws` messages.
+    ///
+    /// Encoding messages and parsing them gives them back, except for:
+    ///
+    /// - empty `Data`, which encodes to nothing
+    /// - adjacent `Data`, which is merged into one message
+    /// - `Command` with IAC, which is data, or with SB, SE, WILL, WONT, DO or DONT, which start
+    ///   or end other sequences
+    /// - `Negotiate` with any other command than WILL, WONT, DO or DONT
+    /// - `Subnegotiate` with SE or IAC as the option, or with GMCP or NAWS, which become `Gmcp`
+    ///   and `Naws` messages
+    /// - subnegotiations longer than 64 KiB, which are truncated
+    pub fn parse(bytes: &[u8]) -> Vec<OutMessage> {
+        let mut parser = ParserBuilder::new()
+            .max_data(bytes.len())
+            .max_subnegotiation(MAX_SUBNEGOTIATION)
+            .build();
+        let mut events: Vec<Event> = Vec::new();
+        parser.advance_bytes(&mut events, bytes);
+        parser.flush(&mut events);
+
+        let mut messages = Vec::new();
+        for event in events {
+            let message = match event {
+                Event::Data(data) => OutMessage::Data(data),
+                Event::Execute(byte) => OutMessage::Data(vec![byte]),
+                Event::Command(command) => OutMessage::Command(command),
+                Event::Negotiate(command, option) => OutMessage::Negotiate(command, option),
+                Event::Subnegotiate(option, params) => {
+                    OutMessage::from_subnegotiation(option, params)
+                }
+                Event::LogoutRequested
+                | Event::EndOfTurn
+                | Event::ProtocolAbuse(_)
+                | Event::StatusMismatch(_)
+                | Event::EchoChanged(_) => continue,
+            };
+            match (messages.last_mut(), message) {
+                (Some(OutMessage::Data(data)), OutMessage::Data(more)) => {
+                    data.extend_from_slice(&more)
+                }
+                (_, message) => messages.push(message),
+            }
+        }
+        messages
+    }
+
+    fn from_subnegotiation(option: Opt, params: Vec<u8>) -> OutMessage {
+        match (option, params.as_slice()) {
+            (Opt::NAWS, [w0, w1, h0, h1]) => OutMessage::Naws(
+                u16::from_be_bytes([*w0, *w1]),
+                u16::from_be_bytes([*h0, *h1]),
+            ),
+            #[cfg(feature = "mud")]
+            (Opt::GMCP, _) => match gmcp::decode(&params) {
+                Some((name, data)) => OutMessage::Gmcp(name.to_owned(), data.to_owned()),
+                None => OutMessage::Subnegotiate(option, params),
+            },
+            _ => OutMessage::Subnegotiate(option, params),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use super::OutMessage;
+    use crate::command::Command;
+    use crate::option::Opt;
+    use crate::testing::out_message;
+
+    #[test]
+    fn round_trip() {
+        let messages = vec![
+            OutMessage::Data(b"look\r\n\xff".to_vec()),
+            OutMessage::Negotiate(Command::WILL, Opt::NAWS),
+            OutMessage::Naws(80, 255),
+            #[cfg(feature = "mud")]
+            OutMessage::Gmcp("Core.Hello".to_owned(), "{\"client\": \"tvk\"}".to_owned()),
+            OutMessage::Command(Command::GA),
+            OutMessage::Subnegotiate(Opt::TTYPE, vec![0, b'X']),
+        ];
+        let bytes: Vec<u8> = messages.iter().flat_map(OutMessage::encode).collect();
+        assert_eq!(OutMessage::parse(&bytes), messages);
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips(messages in vec(out_message(), 0..16)) {
+            let bytes: Vec<u8> = messages.iter().flat_map(OutMessage::encode).collect();
+            // Adjacent data is merged
+            let mut expected: Vec<OutMessage> = Vec::new();
+            for message in messages {
+                match (expected.last_mut(), message) {
+                    (Some(OutMessage::Data(data)), OutMessage::Data(more)) => {
+                        data.extend_from_slice(&more)
+                    }
+                    (_, message) => expected.push(message),
+                }
+            }
+            prop_assert_eq!(OutMessage::parse(&bytes), expected);
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! MUD Server Data Protocol
+//!
+//! MSDP sends variables as subnegotiations of `MSDP_VAR` name `MSDP_VAL` value pairs, where a
+//! value is a string, an array or a table. [`decode`] and [`encode_into`] convert between the
+//! parameters and [`MsdpValue`]s, and [`Msdp`] requests and caches reported variables for a
+//! client.
+//!
+//! [`decode`]: fn.decode.html
+//! [`encode_into`]: fn.encode_into.html
+//! [`MsdpValue`]: enum.MsdpValue.html
+//! [`Msdp`]: struct.Msdp.html
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::command::Command;
+use crate::encode;
+use crate::event::Event;
+use crate::option::Opt;
+
+const MSDP_VAR: u8 = 1;
+const MSDP_VAL: u8 = 2;
+const MSDP_TABLE_OPEN: u8 = 3;
+const MSDP_TABLE_CLOSE: u8 = 4;
+const MSDP_ARRAY_OPEN: u8 = 5;
+const MSDP_ARRAY_CLOSE: u8 = 6;
+
+/// Arrays and tables nested deeper are dropped, as the peer controls the nesting
+const MAX_DEPTH: usize = 32;
+
+/// Value of an MSDP variable
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MsdpValue {
+    String(String),
+    Array(Vec<MsdpValue>),
+    Table(BTreeMap<String, MsdpValue>),
+}
+
+impl MsdpValue {
+    /// The string, if the value is one
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            MsdpValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for MsdpValue {
+    fn from(s: &str) -> MsdpValue {
+        MsdpValue::String(s.to_owned())
+    }
+}
+
+/// Split MSDP parameters, without the option byte, into variables and their values
+///
+/// A variable with several values gets them as an array. Bytes before the first variable are
+/// ignored, and so are variables with arrays or tables nested more than 32 levels deep.
+pub fn decode(params: &[u8]) -> Vec<(String, MsdpValue)> {
+    let mut reader = Reader { bytes: params };
+    let mut variables = Vec::new();
+    reader.skip_until(MSDP_VAR);
+    while reader.next() == Some(MSDP_VAR) {
+        let name = reader.string();
+        if let Some(value) = reader.values(0) {
+            variables.push((name, value));
+        }
+        reader.skip_until(MSDP_VAR);
+    }
+    variables
+}
+
+/// Append MSDP parameters, without the option byte, setting `var` to `value` to `dst`
+pub fn encode_into(var: &str, value: &MsdpValue, dst: &mut Vec<u8>) {
+    dst.push(MSDP_VAR);
+    dst.extend_from_slice(var.as_bytes());
+    encode_value(value, dst);
+}
+
+fn encode_value(value: &MsdpValue, dst: &mut Vec<u8>) {
+    dst.push(MSDP_VAL);
+    match value {
+        MsdpValue::String(s) => dst.extend_from_slice(s.as_bytes()),
+        MsdpValue::Array(values) => {
+            dst.push(MSDP_ARRAY_OPEN);
+            for value in values {
+                encode_value(value, dst);
+            }
+            dst.push(MSDP_ARRAY_CLOSE);
+        }
+        MsdpValue::Table(table) => {
+            dst.push(MSDP_TABLE_OPEN);
+            for (var, value) in table {
+                encode_into(var, value, dst);
+            }
+            dst.push(MSDP_TABLE_CLOSE);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.first().copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.bytes = &self.bytes[1..];
+        Some(byte)
+    }
+
+    fn skip_until(&mut self, byte: u8) {
+        while self.peek().is_some() && self.peek() != Some(byte) {
+            self.next();
+        }
+    }
+
+    /// Bytes up to the next MSDP control byte
+    fn string(&mut self) -> String {
+        let len = (self.bytes.iter())
+            .position(|b| (MSDP_VAR..=MSDP_ARRAY_CLOSE).contains(b))
+            .unwrap_or(self.bytes.len());
+        let s = String::from_utf8_lossy(&self.bytes[..len]).into_owned();
+        self.bytes = &self.bytes[len..];
+        s
+    }
+
+    /// Skip the rest of `depth` nested arrays and tables
+    fn skip_nested(&mut self, mut depth: usize) {
+        while depth > 0 {
+            match self.next() {
+                Some(MSDP_ARRAY_OPEN) | Some(MSDP_TABLE_OPEN) => depth += 1,
+                Some(MSDP_ARRAY_CLOSE) | Some(MSDP_TABLE_CLOSE) => depth -= 1,
+                Some(_) => (),
+                None => break,
+            }
+        }
+    }
+
+    /// The values following a variable within `depth` arrays and tables, as an array if there
+    /// are several
+    ///
+    /// Returns `None` without values, or when they are nested too deep. The rest of the
+    /// outermost array or table is skipped then.
+    fn values(&mut self, depth: usize) -> Option<MsdpValue> {
+        let mut values = Vec::new();
+        while self.peek() == Some(MSDP_VAL) {
+            self.next();
+            values.push(self.value(depth)?);
+        }
+        match values.len() {
+            0 => None,
+            1 => values.pop(),
+            _ => Some(MsdpValue::Array(values)),
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Option<MsdpValue> {
+        match self.peek() {
+            Some(MSDP_ARRAY_OPEN) | Some(MSDP_TABLE_OPEN) if depth == MAX_DEPTH => {
+                self.next();
+                self.skip_nested(depth + 1);
+                None
+            }
+            Some(MSDP_ARRAY_OPEN) => {
+                self.next();
+                let mut values = Vec::new();
+                loop {
+                    match self.next() {
+                        Some(MSDP_VAL) => values.push(self.value(depth + 1)?),
+                        Some(MSDP_ARRAY_CLOSE) | None => break,
+                        Some(_) => (),
+                    }
+                }
+                Some(MsdpValue::Array(values))
+            }
+            Some(MSDP_TABLE_OPEN) => {
+                self.next();
+                let mut table = BTreeMap::new();
+                loop {
+                    match self.next() {
+                        Some(MSDP_VAR) => {
+                            let name = self.string();
+                            let value = match self.peek() {
+                                Some(MSDP_VAL) => self.values(depth + 1)?,
+                                _ => "".into(),
+                            };
+                            table.insert(name, value);
+                        }
+                        Some(MSDP_TABLE_CLOSE) | None => break,
+                        Some(_) => (),
+                    }
+                }
+                Some(MsdpValue::Table(table))
+            }
+            _ => Some(MsdpValue::String(self.string())),
+        }
+    }
+}
+
+/// Lists a server can be asked for with [`Msdp::list`](struct.Msdp.html#method.list)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MsdpList {
+    Commands,
+    Lists,
+    ConfigurableVariables,
+    ReportableVariables,
+    ReportedVariables,
+    SendableVariables,
+}
+
+impl MsdpList {
+    pub fn name(self) -> &'static str {
+        match self {
+            MsdpList::Commands => "COMMANDS",
+            MsdpList::Lists => "LISTS",
+            MsdpList::ConfigurableVariables => "CONFIGURABLE_VARIABLES",
+            MsdpList::ReportableVariables => "REPORTABLE_VARIABLES",
+            MsdpList::ReportedVariables => "REPORTED_VARIABLES",
+            MsdpList::SendableVariables => "SENDABLE_VARIABLES",
+        }
+    }
+}
+
+/// Requests MSDP variables and caches their values
+///
+/// Reports requested before the server offers MSDP are sent once it does. Output is appended to
+/// a buffer passed to each method, to be sent to the server.
+#[derive(Debug, Default, Clone)]
+pub struct Msdp {
+    enabled: bool,
+    reported: BTreeSet<String>,
+    values: BTreeMap<String, MsdpValue>,
+}
+
+impl Msdp {
+    pub fn new() -> Msdp {
+        Msdp::default()
+    }
+
+    /// Ask the server for a list, e.g. of reportable variables
+    ///
+    /// The answer arrives as a variable named after the list. Nothing is sent before the server
+    /// offers MSDP.
+    pub fn list(&mut self, list: MsdpList, out: &mut Vec<u8>) {
+        self.command("LIST", list.name(), out);
+    }
+
+    /// Ask the server to send `var` whenever it changes
+    pub fn report(&mut self, var: &str, out: &mut Vec<u8>) {
+        if self.reported.insert(var.to_owned()) {
+            self.command("REPORT", var, out);
+        }
+    }
+
+    /// Ask the server to stop reporting `var`
+    pub fn unreport(&mut self, var: &str, out: &mut Vec<u8>) {
+        if self.reported.remove(var) {
+            self.command("UNREPORT", var, out);
+        }
+    }
+
+    /// Ask the server to send `var` once
+    pub fn send(&mut self, var: &str, out: &mut Vec<u8>) {
+        self.command("SEND", var, out);
+    }
+
+    /// Observe an event from the server, answering MSDP negotiation and caching variables
+    ///
+    /// Returns the names of the variables whose value changed.
+    pub fn observe(&mut self, event: &Event, out: &mut Vec<u8>) -> Vec<String> {
+        match event {
+            Event::Negotiate(Command::WILL, Opt::MSDP) if !self.enabled => {
+                encode::negotiate_into(Command::DO, Opt::MSDP, out);
+                self.enabled = true;
+                for var in &self.reported {
+                    subnegotiate("REPORT", var, out);
+                }
+                Vec::new()
+            }
+            Event::Negotiate(Command::WONT, Opt::MSDP) if self.enabled => {
+                encode::negotiate_into(Command::DONT, Opt::MSDP, out);
+                self.enabled = false;
+                Vec::new()
+            }
+            Event::Subnegotiate(Opt::MSDP, params) => self.update(params),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Cache the variables of MSDP parameters, without the option byte
+    ///
+    /// Returns the names of the variables whose value changed.
+    pub fn update(&mut self, params: &[u8]) -> Vec<String> {
+        let mut changed = Vec::new();
+        for (var, value) in decode(params) {
+            if self.values.get(&var) != Some(&value) {
+                changed.push(var.clone());
+                self.values.insert(var, value);
+            }
+        }
+        changed
+    }
+
+    /// The last value received for `var`
+    pub fn get(&self, var: &str) -> Option<&MsdpValue> {
+        self.values.get(var)
+    }
+
+    /// Variables the server was asked to report
+    pub fn reported(&self) -> &BTreeSet<String> {
+        &self.reported
+    }
+
+    /// Returns true once the server offered MSDP
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn command(&self, command: &str, arg: &str, out: &mut Vec<u8>) {
+        if self.enabled {
+            subnegotiate(command, arg, out);
+        }
+    }
+}
+
+fn subnegotiate(command: &str, arg: &str, out: &mut Vec<u8>) {
+    let mut params = Vec::new();
+    encode_into(command, &arg.into(), &mut params);
+    encode::subnegotiate_into(Opt::MSDP, &params, out);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::{decode, encode_into, Msdp, MsdpList, MsdpValue};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |depth| {
+            let mut params = b"\x01DEEP".to_vec();
+            for _ in 0..depth {
+                params.extend_from_slice(&[2, 5]);
+            }
+            params.extend_from_slice(&[2, b'x']);
+            params.resize(params.len() + depth, 6);
+            params.extend_from_slice(b"\x01NEXT\x02y");
+            params
+        };
+        assert_eq!(decode(&nested(32)).len(), 2);
+        assert_eq!(
+            decode(&nested(33)),
+            &[("NEXT".to_owned(), MsdpValue::from("y"))]
+        );
+
+        // Never closed, and too deep to recurse into
+        let mut params = vec![1, b'X', 2];
+        for _ in 0..32000 {
+            params.extend_from_slice(&[5, 2]);
+        }
+        assert!(decode(&params).is_empty());
+    }
+
+    #[test]
+    fn report_and_cache() {
+        let mut table = BTreeMap::new();
+        table.insert("HP".to_owned(), "10".into());
+        table.insert(
+            "EXITS".to_owned(),
+            MsdpValue::Array(vec!["n".into(), "s".into()]),
+        );
+        let room = MsdpValue::Table(table);
+        let mut params = Vec::new();
+        encode_into("ROOM", &room, &mut params);
+        assert_eq!(decode(&params), &[("ROOM".to_owned(), room.clone())]);
+        assert_eq!(
+            decode(b"\x01LIST\x02A\x02B"),
+            &[(
+                "LIST".to_owned(),
+                MsdpValue::Array(vec!["A".into(), "B".into()])
+            )]
+        );
+
+        let mut msdp = Msdp::new();
+        let mut out = Vec::new();
+        msdp.report("HEALTH", &mut out);
+        msdp.list(MsdpList::ReportableVariables, &mut out);
+        assert!(out.is_empty());
+
+        msdp.observe(&Event::Negotiate(Command::WILL, Opt::MSDP), &mut out);
+        let mut expected = vec![255, 253, 69, 255, 250, 69];
+        expected.extend_from_slice(b"\x01REPORT\x02HEALTH");
+        expected.extend_from_slice(&[255, 240]);
+        assert_eq!(out, expected);
+        out.clear();
+
+        let health = Event::Subnegotiate(Opt::MSDP, b"\x01HEALTH\x0250".to_vec());
+        assert_eq!(msdp.observe(&health, &mut out), &["HEALTH"]);
+        assert_eq!(msdp.observe(&health, &mut out), Vec::<String>::new());
+        assert_eq!(msdp.get("HEALTH").and_then(MsdpValue::as_str), Some("50"));
+
+        msdp.unreport("HEALTH", &mut out);
+        assert!(out.ends_with(b"\x01UNREPORT\x02HEALTH\xff\xf0"));
+        assert!(msdp.reported().is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+//! MUD Server Status Protocol
+//!
+//! A server answers `DO MSSP` with a subnegotiation of variables, each with one or more values.
+//! [`decode`] splits the parameters into name and value pairs, and [`MsspInfo`] gives typed
+//! access to the variables defined by [MSSP].
+//!
+//! [`decode`]: fn.decode.html
+//! [`MsspInfo`]: struct.MsspInfo.html
+//! [MSSP]: https://tintin.mudhalla.net/protocols/mssp/
+use std::collections::BTreeMap;
+
+const MSSP_VAR: u8 = 1;
+const MSSP_VAL: u8 = 2;
+
+/// Variables defined by MSSP, kept out of [`MsspInfo::nonstandard`]
+///
+/// [`MsspInfo::nonstandard`]: struct.MsspInfo.html#method.nonstandard
+const STANDARD: &[&str] = &[
+    "NAME",
+    "PLAYERS",
+    "UPTIME",
+    "CODEBASE",
+    "CONTACT",
+    "CRAWL DELAY",
+    "CREATED",
+    "DISCORD",
+    "HOSTNAME",
+    "ICON",
+    "IP",
+    "IPV6",
+    "LANGUAGE",
+    "LOCATION",
+    "MINIMUM AGE",
+    "PORT",
+    "REFERRAL",
+    "SSL",
+    "WEBSITE",
+    "FAMILY",
+    "GENRE",
+    "GAMEPLAY",
+    "STATUS",
+    "GAMESYSTEM",
+    "INTERMUD",
+    "SUBGENRE",
+    "AREAS",
+    "HELPFILES",
+    "MOBILES",
+    "OBJECTS",
+    "ROOMS",
+    "CLASSES",
+    "LEVELS",
+    "RACES",
+    "SKILLS",
+    "ANSI",
+    "GMCP",
+    "MCCP",
+    "MCP",
+    "MSDP",
+    "MSP",
+    "MXP",
+    "PUEBLO",
+    "UTF-8",
+    "VT100",
+    "XTERM 256 COLORS",
+    "XTERM TRUE COLORS",
+    "PAY TO PLAY",
+    "PAY FOR PERKS",
+    "HIRING BUILDERS",
+    "HIRING CODERS",
+];
+
+/// Split MSSP parameters, without the option byte, into `(variable, value)` pairs
+///
+/// A variable with several values appears once for each value. Bytes before the first variable
+/// are ignored.
+pub fn decode(params: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut pairs = Vec::new();
+    let mut var: Option<&[u8]> = None;
+    let mut rest = params;
+    while let Some(start) = rest.iter().position(|b| *b == MSSP_VAR || *b == MSSP_VAL) {
+        let kind = rest[start];
+        let field = &rest[start + 1..];
+        let len = field
+            .iter()
+            .position(|b| *b == MSSP_VAR || *b == MSSP_VAL)
+            .unwrap_or(field.len());
+        match (kind, var) {
+            (MSSP_VAR, _) => var = Some(&field[..len]),
+            (_, Some(var)) => pairs.push((var.to_vec(), field[..len].to_vec())),
+            (_, None) => (),
+        }
+        rest = &field[len..];
+    }
+    pairs
+}
+
+/// Append MSSP parameters, without the option byte, for `(variable, value)` pairs to `dst`
+pub fn encode_into<V: AsRef<[u8]>>(pairs: &[(V, V)], dst: &mut Vec<u8>) {
+    for (var, val) in pairs {
+        dst.push(MSSP_VAR);
+        dst.extend_from_slice(var.as_ref());
+        dst.push(MSSP_VAL);
+        dst.extend_from_slice(val.as_ref());
+    }
+}
+
+/// MSSP variables of a server
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MsspInfo {
+    variables: BTreeMap<String, Vec<String>>,
+    nonstandard: BTreeMap<String, Vec<String>>,
+}
+
+impl MsspInfo {
+    /// Parse MSSP parameters, without the option byte
+    ///
+    /// Variable names are matched case-insensitively, and kept upper case.
+    pub fn parse(params: &[u8]) -> MsspInfo {
+        let mut info = MsspInfo::default();
+        for (var, val) in decode(params) {
+            let var = String::from_utf8_lossy(&var).to_uppercase();
+            let val = String::from_utf8_lossy(&val).into_owned();
+            let map = if STANDARD.contains(&var.as_str()) {
+                &mut info.variables
+            } else {
+                &mut info.nonstandard
+            };
+            map.entry(var).or_default().push(val);
+        }
+        info
+    }
+
+    /// All values of a standard variable
+    pub fn values(&self, var: &str) -> &[String] {
+        self.variables.get(var).map_or(&[], Vec::as_slice)
+    }
+
+    /// First value of a standard variable
+    pub fn get(&self, var: &str) -> Option<&str> {
+        self.values(var).first().map(String::as_str)
+    }
+
+    /// Variables not defined by MSSP
+    pub fn nonstandard(&self) -> &BTreeMap<String, Vec<String>> {
+        &self.nonstandard
+    }
+
+    /// A variable set to `1` or `0`, e.g. `ANSI` or `PAY TO PLAY`
+    pub fn flag(&self, var: &str) -> Option<bool> {
+        match self.get(var)? {
+            "1" => Some(true),
+            "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.get("NAME")
+    }
+
+    /// Number of players online
+    pub fn players(&self) -> Option<u32> {
+        self.number("PLAYERS")
+    }
+
+    /// Unix time at which the server started
+    pub fn uptime(&self) -> Option<u64> {
+        self.number("UPTIME")
+    }
+
+    pub fn codebase(&self) -> Option<&str> {
+        self.get("CODEBASE")
+    }
+
+    pub fn contact(&self) -> Option<&str> {
+        self.get("CONTACT")
+    }
+
+    /// Preferred minimum number of hours between crawls, `None` if the server doesn't care
+    pub fn crawl_delay(&self) -> Option<u32> {
+        self.number("CRAWL DELAY")
+    }
+
+    /// Year the server was created
+    pub fn created(&self) -> Option<u32> {
+        self.number("CREATED")
+    }
+
+    pub fn hostname(&self) -> Option<&str> {
+        self.get("HOSTNAME")
+    }
+
+    pub fn ip(&self) -> Option<&str> {
+        self.get("IP")
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.get("LANGUAGE")
+    }
+
+    pub fn location(&self) -> Option<&str> {
+        self.get("LOCATION")
+    }
+
+    pub fn minimum_age(&self) -> Option<u32> {
+        self.number("MINIMUM AGE")
+    }
+
+    /// All ports the server listens on
+    pub fn ports(&self) -> Vec<u16> {
+        self.values("PORT")
+            .iter()
+            .filter_map(|port| port.trim().parse().ok())
+            .collect()
+    }
+
+    pub fn website(&self) -> Option<&str> {
+        self.get("WEBSITE")
+    }
+
+    pub fn genre(&self) -> Option<&str> {
+        self.get("GENRE")
+    }
+
+    /// Gameplay styles, e.g. `Roleplaying` and `Player versus Player`
+    pub fn gameplay(&self) -> &[String] {
+        self.values("GAMEPLAY")
+    }
+
+    /// Development status, e.g. `Live` or `Alpha`
+    pub fn status(&self) -> Option<&str> {
+        self.get("STATUS")
+    }
+
+    /// A number, with `-1` standing for unknown as MSSP allows
+    fn number<T: std::str::FromStr>(&self, var: &str) -> Option<T> {
+        self.get(var)?.trim().parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode_into, MsspInfo};
+
+    #[test]
+    fn info() {
+        let mut params = Vec::new();
+        encode_into(
+            &[
+                ("NAME", "Example MUD"),
+                ("PLAYERS", "52"),
+                ("UPTIME", "1234567890"),
+                ("PORT", "4000"),
+                ("PORT", "4001"),
+                ("crawl delay", "-1"),
+                ("ANSI", "1"),
+                ("HOUSES", "yes"),
+            ],
+            &mut params,
+        );
+        assert_eq!(
+            decode(&params[..13]),
+            &[(b"NAME".to_vec(), b"Example".to_vec())]
+        );
+
+        let info = MsspInfo::parse(&params);
+        assert_eq!(info.name(), Some("Example MUD"));
+        assert_eq!(info.players(), Some(52));
+        assert_eq!(info.uptime(), Some(1_234_567_890));
+        assert_eq!(info.ports(), &[4000, 4001]);
+        assert_eq!(info.crawl_delay(), None);
+        assert_eq!(info.flag("ANSI"), Some(true));
+        assert_eq!(info.nonstandard()["HOUSES"], &["yes"]);
+    }
+}
@@ -0,0 +1,160 @@
+//! MUD Terminal Type Standard
+//!
+//! MUD clients report their capabilities as the third terminal type, `MTTS <n>`, where `n` is
+//! a sum of the flags defined by [MTTS]. [`Mtts`] holds those flags and converts from and to the
+//! terminal type.
+//!
+//! [`Mtts`]: struct.Mtts.html
+//! [MTTS]: https://tintin.mudhalla.net/protocols/mtts/
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+const PREFIX: &[u8] = b"MTTS ";
+
+/// Set of MTTS capability flags
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub struct Mtts(u32);
+
+macro_rules! mtts_flags {
+    (
+        $(
+            $(#[$docs:meta])*
+            ($bit:expr, $konst:ident, $name:expr);
+        )+
+    ) => {
+        impl Mtts {
+        $(
+            $(#[$docs])*
+            pub const $konst: Mtts = Mtts($bit);
+        )+
+        }
+
+        const NAMES: &[(Mtts, &str)] = &[
+            $(
+            (Mtts::$konst, $name),
+            )+
+        ];
+    }
+}
+
+mtts_flags! {
+    /// Client supports all common ANSI color codes
+    (1, ANSI, "ANSI");
+    /// Client supports all common VT100 codes
+    (2, VT100, "VT100");
+    /// Client is using UTF-8 character encoding
+    (4, UTF8, "UTF-8");
+    /// Client supports all 256 color codes
+    (8, COLORS_256, "256 COLORS");
+    /// Client supports xterm mouse tracking
+    (16, MOUSE_TRACKING, "MOUSE TRACKING");
+    /// Client supports the OSC color palette
+    (32, OSC_COLOR_PALETTE, "OSC COLOR PALETTE");
+    /// Client is using a screen reader
+    (64, SCREEN_READER, "SCREEN READER");
+    /// Client is a proxy allowing different users to connect from the same IP address
+    (128, PROXY, "PROXY");
+    /// Client supports truecolor codes using semicolon notation
+    (256, TRUECOLOR, "TRUECOLOR");
+    /// Client supports the Mud New Environment Standard
+    (512, MNES, "MNES");
+    /// Client supports the Mud Server Link Protocol
+    (1024, MSLP, "MSLP");
+    /// Client supports SSL for data encryption
+    (2048, SSL, "SSL");
+}
+
+impl Mtts {
+    /// No flags
+    pub const fn empty() -> Mtts {
+        Mtts(0)
+    }
+
+    /// Flags from their sum, keeping bits without a name
+    pub const fn from_bits(bits: u32) -> Mtts {
+        Mtts(bits)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns true if all flags of `other` are set
+    pub const fn contains(self, other: Mtts) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Mtts) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Mtts) {
+        self.0 &= !other.0;
+    }
+
+    /// Parse a terminal type of the form `MTTS <n>`
+    pub fn from_terminal_type(terminal_type: &[u8]) -> Option<Mtts> {
+        let digits = terminal_type.strip_prefix(PREFIX)?;
+        if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        std::str::from_utf8(digits).ok()?.parse().ok().map(Mtts)
+    }
+
+    /// The terminal type reporting the flags, `MTTS <n>`
+    pub fn to_terminal_type(self) -> Vec<u8> {
+        format!("MTTS {}", self.0).into_bytes()
+    }
+}
+
+impl BitOr for Mtts {
+    type Output = Mtts;
+
+    fn bitor(self, other: Mtts) -> Mtts {
+        Mtts(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Mtts {
+    fn bitor_assign(&mut self, other: Mtts) {
+        self.0 |= other.0;
+    }
+}
+
+/// Lists the names of the flags, e.g. `Mtts(ANSI | UTF-8)`
+impl fmt::Debug for Mtts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Mtts(")?;
+        let mut first = true;
+        for (flag, name) in NAMES {
+            if self.contains(*flag) {
+                if !first {
+                    write!(f, " | ")?;
+                }
+                write!(f, "{}", name)?;
+                first = false;
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mtts;
+
+    #[test]
+    fn terminal_type() {
+        let mtts = Mtts::ANSI | Mtts::UTF8 | Mtts::COLORS_256 | Mtts::TRUECOLOR;
+        assert_eq!(mtts.to_terminal_type(), b"MTTS 269");
+        assert_eq!(Mtts::from_terminal_type(b"MTTS 269"), Some(mtts));
+        assert_eq!(Mtts::from_terminal_type(b"MTTS +1"), None);
+        assert_eq!(Mtts::from_terminal_type(b"XTERM"), None);
+        assert_eq!(
+            format!("{:?}", mtts),
+            "Mtts(ANSI | UTF-8 | 256 COLORS | TRUECOLOR)"
+        );
+        assert!(mtts.contains(Mtts::ANSI | Mtts::UTF8));
+        assert!(!mtts.contains(Mtts::SCREEN_READER));
+    }
+}
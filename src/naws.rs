@@ -0,0 +1,167 @@
+//! NAWS, window size, as described in [RFC 1073]
+//!
+//! [`Naws`] answers the server for a client which is not using a [`TelnetSession`]: it agrees
+//! to `DO NAWS`, sends the window size, and sends it again on every [`Naws::resize`]. Terminals
+//! resized by dragging report many sizes in a row, so updates can be debounced.
+//!
+//! [`Naws`]: struct.Naws.html
+//! [`Naws::resize`]: struct.Naws.html#method.resize
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+//! [RFC 1073]: https://www.rfc-editor.org/rfc/rfc1073.html
+use std::time::{Duration, Instant};
+
+use crate::command::Command;
+use crate::encode;
+use crate::event::Event;
+use crate::option::Opt;
+
+/// Answers NAWS negotiation and sends window size updates
+///
+/// Output is appended to a buffer passed to each method, to be sent to the server.
+#[derive(Debug, Clone)]
+pub struct Naws {
+    width: u16,
+    height: u16,
+    offered: bool,
+    enabled: bool,
+    debounce: Option<Duration>,
+    last_sent: Option<Instant>,
+    pending: bool,
+}
+
+impl Naws {
+    pub fn new(width: u16, height: u16) -> Naws {
+        Naws {
+            width,
+            height,
+            offered: false,
+            enabled: false,
+            debounce: None,
+            last_sent: None,
+            pending: false,
+        }
+    }
+
+    /// Send at most one update per `interval`, see [`poll`](#method.poll)
+    pub fn debounce(mut self, interval: Duration) -> Naws {
+        self.debounce = Some(interval);
+        self
+    }
+
+    /// Offer NAWS before the server asks for it
+    pub fn offer(&mut self, out: &mut Vec<u8>) {
+        if !self.offered && !self.enabled {
+            encode::negotiate_into(Command::WILL, Opt::NAWS, out);
+            self.offered = true;
+        }
+    }
+
+    /// Observe an event from the server, answering NAWS negotiation
+    pub fn observe(&mut self, event: &Event, out: &mut Vec<u8>) {
+        match event {
+            Event::Negotiate(Command::DO, Opt::NAWS) if !self.enabled => {
+                if !self.offered {
+                    encode::negotiate_into(Command::WILL, Opt::NAWS, out);
+                }
+                self.offered = false;
+                self.enabled = true;
+                self.send(Instant::now(), out);
+            }
+            Event::Negotiate(Command::DONT, Opt::NAWS) if self.enabled || self.offered => {
+                if self.enabled {
+                    encode::negotiate_into(Command::WONT, Opt::NAWS, out);
+                }
+                self.offered = false;
+                self.enabled = false;
+                self.pending = false;
+            }
+            _ => (),
+        }
+    }
+
+    /// Change the window size, sending it unless an update was sent within the debounce
+    /// interval
+    pub fn resize(&mut self, width: u16, height: u16, now: Instant, out: &mut Vec<u8>) {
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        if !self.enabled {
+            return;
+        }
+        match (self.debounce, self.last_sent) {
+            (Some(interval), Some(last_sent)) if now.duration_since(last_sent) < interval => {
+                self.pending = true;
+            }
+            _ => self.send(now, out),
+        }
+    }
+
+    /// Send a size held back by debouncing once the interval has passed
+    pub fn poll(&mut self, now: Instant, out: &mut Vec<u8>) {
+        if let (true, Some(interval), Some(last_sent)) =
+            (self.pending, self.debounce, self.last_sent)
+        {
+            if now.duration_since(last_sent) >= interval {
+                self.send(now, out);
+            }
+        }
+    }
+
+    /// The current window size
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// Returns true once the server agreed to NAWS
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn send(&mut self, now: Instant, out: &mut Vec<u8>) {
+        let mut params = Vec::with_capacity(4);
+        params.extend_from_slice(&self.width.to_be_bytes());
+        params.extend_from_slice(&self.height.to_be_bytes());
+        encode::subnegotiate_into(Opt::NAWS, &params, out);
+        self.last_sent = Some(now);
+        self.pending = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Naws;
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    #[test]
+    fn resize_debounced() {
+        let mut naws = Naws::new(80, 24).debounce(Duration::from_millis(100));
+        let mut out = Vec::new();
+        let start = Instant::now();
+
+        // Not sent before the server asks for it
+        naws.resize(100, 30, start, &mut out);
+        assert!(out.is_empty());
+
+        naws.observe(&Event::Negotiate(Command::DO, Opt::NAWS), &mut out);
+        assert_eq!(out, &[255, 251, 31, 255, 250, 31, 0, 100, 0, 30, 255, 240]);
+        out.clear();
+
+        // Held back right after the first size
+        let later = Instant::now();
+        naws.resize(120, 40, later, &mut out);
+        assert!(out.is_empty());
+        naws.poll(later + Duration::from_millis(100), &mut out);
+        assert_eq!(out, &[255, 250, 31, 0, 120, 0, 40, 255, 240]);
+        out.clear();
+
+        naws.observe(&Event::Negotiate(Command::DONT, Opt::NAWS), &mut out);
+        assert_eq!(out, &[255, 252, 31]);
+        assert!(!naws.is_enabled());
+    }
+}
@@ -1,46 +1,178 @@
 use std::convert::From;
-use std::error::Error;
 use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
 
 /// A telnet option value.
 #[derive(Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
 pub struct Opt(u8);
 
-/// A possible error value when converting a `Option` from a `u8`.
-#[derive(Debug)]
-pub struct InvalidOption {
-    invalid_src: u8,
+/// Classification of an option value.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Kind {
+    /// The option is defined in the option table of this crate.
+    Known,
+    /// The option has been given a name in an [`OptionNames`] table.
+    ///
+    /// [`OptionNames`]: struct.OptionNames.html
+    Custom,
+    /// The option is not defined, but may still be negotiated (and usually refused).
+    Unknown,
+}
+
+/// Names of options which are not defined by this crate
+///
+/// An application keeps its own table, filled by [`register`] or [`define_telnet_options!`], and
+/// uses it to show and parse options where it wants its names. Options of the option table always
+/// have their own names.
+///
+/// [`register`]: #method.register
+/// [`define_telnet_options!`]: ../macro.define_telnet_options.html
+#[derive(Clone)]
+pub struct OptionNames {
+    custom: [Option<&'static str>; 256],
+}
+
+impl OptionNames {
+    /// A table without custom names
+    pub fn new() -> OptionNames {
+        OptionNames {
+            custom: [None; 256],
+        }
+    }
+
+    /// Name an option that is not defined by this crate.
+    ///
+    /// Returns false if the option is already known or registered with a different name.
+    pub fn register(&mut self, option: Opt, name: &'static str) -> bool {
+        if canonical_reason(option.0).is_some() {
+            return false;
+        }
+        match self.custom[usize::from(option.0)] {
+            Some(existing) => existing == name,
+            None => {
+                self.custom[usize::from(option.0)] = Some(name);
+                true
+            }
+        }
+    }
+
+    /// Name of the option, from the option table or registered.
+    pub fn name(&self, option: Opt) -> Option<&'static str> {
+        option
+            .canonical_reason()
+            .or(self.custom[usize::from(option.0)])
+    }
+
+    /// Classify the option, telling registered options apart from unknown ones.
+    pub fn kind(&self, option: Opt) -> Kind {
+        match option.kind() {
+            Kind::Unknown if self.custom[usize::from(option.0)].is_some() => Kind::Custom,
+            kind => kind,
+        }
+    }
+
+    /// Parse an option from its name as `FromStr` does, or from a registered name.
+    pub fn parse(&self, name: &str) -> Result<Opt, Error> {
+        name.parse().or_else(|err| {
+            (0..=255u8)
+                .find(|num| match self.custom[usize::from(*num)] {
+                    Some(custom) => names_eq(custom, name),
+                    None => false,
+                })
+                .map(Opt)
+                .ok_or(err)
+        })
+    }
+
+    /// Show the option as its `Display` does, with registered names.
+    pub fn display(&self, option: Opt) -> impl fmt::Display {
+        Named(option, self.name(option))
+    }
 }
 
-impl fmt::Display for InvalidOption {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "invalid option")
+impl Default for OptionNames {
+    fn default() -> OptionNames {
+        OptionNames::new()
     }
 }
 
-impl Error for InvalidOption {
-    fn description(&self) -> &str {
-        "invalid option"
+/// Lists the registered names
+impl fmt::Debug for OptionNames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                (0..=255u8).filter_map(|num| self.custom[usize::from(num)].map(|name| (num, name))),
+            )
+            .finish()
     }
 }
 
-impl Opt {
-    // TODO: return ParseError?
-    pub fn from_u8(src: u8) -> Result<Opt, InvalidOption> {
-        match src {
-            1..=39 => Ok(Opt(src)),
-            70 | 85 | 86 | 93 | 255 => Ok(Opt(src)),
-            _ => Err(InvalidOption { invalid_src: src }),
+struct Named(Opt, Option<&'static str>);
+
+impl fmt::Display for Named {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.0 .0, self.1.unwrap_or("<unknown option>"))
+    }
+}
+
+/// Compare option or command names ignoring case, and whether words are separated by `-`, `_`
+/// or a space.
+pub(crate) fn names_eq(a: &str, b: &str) -> bool {
+    fn fold(byte: u8) -> u8 {
+        match byte {
+            b'_' | b' ' => b'-',
+            byte => byte.to_ascii_uppercase(),
         }
     }
+    a.len() == b.len() && a.bytes().zip(b.bytes()).all(|(a, b)| fold(a) == fold(b))
+}
+
+impl Opt {
+    /// Create an option from any value; every byte is a valid option on the wire.
+    pub const fn from_u8(src: u8) -> Opt {
+        Opt(src)
+    }
 
     pub fn as_u8(&self) -> u8 {
         (*self).into()
     }
 
+    /// Name of the option in the option table, see [`OptionNames`] for other names.
+    ///
+    /// [`OptionNames`]: struct.OptionNames.html
     pub fn canonical_reason(&self) -> Option<&'static str> {
         canonical_reason(self.0)
     }
+
+    /// The RFC defining the option, if it is specified by one.
+    pub fn rfc(&self) -> Option<u16> {
+        rfc(self.0)
+    }
+
+    /// Returns true if the option uses subnegotiation (`IAC SB <option> ... IAC SE`).
+    ///
+    /// Unknown options are assumed not to.
+    pub fn supports_subnegotiation(&self) -> bool {
+        supports_subnegotiation(self.0)
+    }
+
+    /// Returns true if the option is defined in the option table.
+    pub fn is_known(&self) -> bool {
+        self.kind() == Kind::Known
+    }
+
+    /// Classify the option, see [`Kind`]. Never `Custom`, which only [`OptionNames::kind`] knows.
+    ///
+    /// [`Kind`]: enum.Kind.html
+    /// [`OptionNames::kind`]: struct.OptionNames.html#method.kind
+    pub fn kind(&self) -> Kind {
+        match canonical_reason(self.0) {
+            Some(_) => Kind::Known,
+            None => Kind::Unknown,
+        }
+    }
 }
 
 impl PartialEq<u8> for Opt {
@@ -62,9 +194,28 @@ impl From<Opt> for u8 {
     }
 }
 
+impl From<u8> for Opt {
+    #[inline]
+    fn from(src: u8) -> Opt {
+        Opt(src)
+    }
+}
+
+/// Parses an option from its canonical name, e.g. `"NAWS"` or `"NEW-ENVIRON"`. Matching is
+/// case-insensitive, and words may be separated by `-` or `_`, e.g. `"new_environ"`.
+impl FromStr for Opt {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Opt, Error> {
+        from_name(s)
+            .map(Opt)
+            .ok_or_else(|| Error::UnknownOption(s.to_owned()))
+    }
+}
+
 impl<'a> From<&'a Opt> for Opt {
     fn from(t: &'a Opt) -> Self {
-        t.clone()
+        *t
     }
 }
 
@@ -85,11 +236,123 @@ impl fmt::Display for Opt {
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for Opt {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "{=u8} {=str}",
+            self.0,
+            self.canonical_reason().unwrap_or("<unknown option>")
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Opt {
+    /// Serializes as the option name when known, otherwise as the numeric value.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.canonical_reason() {
+            Some(name) => serializer.serialize_str(name),
+            None => serializer.serialize_u8(self.0),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Opt {
+    /// Deserializes from either an option name or a numeric value.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Opt, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Opt;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a telnet option name or value")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Opt, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Opt, E> {
+                use std::convert::TryFrom;
+                u8::try_from(v).map(Opt).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Opt {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Opt> {
+        u8::arbitrary(u).map(Opt)
+    }
+}
+
+/// Declare application specific options.
+///
+/// Expands to a unit struct with an associated `Opt` constant per option, and a `register`
+/// function which adds the names to an [`OptionNames`] table. `register` returns false if any of
+/// the options could not be registered.
+///
+/// ```
+/// use televerknet::option::OptionNames;
+///
+/// televerknet::define_telnet_options! {
+///     pub struct MyOptions {
+///         /// Our private status channel
+///         (180, STATUS_CHANNEL, "STATUS-CHANNEL");
+///     }
+/// }
+///
+/// let mut names = OptionNames::new();
+/// assert!(MyOptions::register(&mut names));
+/// assert_eq!(names.display(MyOptions::STATUS_CHANNEL).to_string(), "180 STATUS-CHANNEL");
+/// assert_eq!(names.parse("status-channel").unwrap(), MyOptions::STATUS_CHANNEL);
+/// ```
+///
+/// [`OptionNames`]: option/struct.OptionNames.html
+#[macro_export]
+macro_rules! define_telnet_options {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$docs:meta])*
+                ($num:expr, $konst:ident, $phrase:expr);
+            )+
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name;
+
+        impl $name {
+        $(
+            $(#[$docs])*
+            pub const $konst: $crate::option::Opt = $crate::option::Opt::from_u8($num);
+        )+
+
+            /// Add the option names to `names`, see `televerknet::option::OptionNames`.
+            pub fn register(names: &mut $crate::option::OptionNames) -> bool {
+                let mut registered = true;
+                $(
+                registered &= names.register(Self::$konst, $phrase);
+                )+
+                registered
+            }
+        }
+    };
+}
+
 macro_rules! telnet_options {
     (
         $(
             $(#[$docs:meta])*
-            ($num:expr, $konst:ident, $phrase:expr);
+            ($num:expr, $konst:ident, $phrase:expr, $rfc:expr, $sub:expr);
         )+
     ) => {
         impl Opt {
@@ -100,6 +363,47 @@ macro_rules! telnet_options {
 
         }
 
+        /// The options defined in the option table as an enum, to `match` with exhaustiveness
+        /// checking.
+        ///
+        /// Variants have the names of the [`Opt`] constants. Other options, registered ones
+        /// included, are `Other`.
+        ///
+        /// [`Opt`]: struct.Opt.html
+        #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+        #[non_exhaustive]
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+        pub enum OptKind {
+        $(
+            $(#[$docs])*
+            $konst,
+        )+
+            /// An option which is not in the option table
+            Other(u8),
+        }
+
+        impl From<Opt> for OptKind {
+            fn from(option: Opt) -> OptKind {
+                match option.0 {
+                    $(
+                    $num => OptKind::$konst,
+                    )+
+                    other => OptKind::Other(other),
+                }
+            }
+        }
+
+        impl From<OptKind> for Opt {
+            fn from(kind: OptKind) -> Opt {
+                match kind {
+                    $(
+                    OptKind::$konst => Opt::$konst,
+                    )+
+                    OptKind::Other(other) => Opt(other),
+                }
+            }
+        }
+
         fn canonical_reason(num: u8) -> Option<&'static str> {
             match num {
                 $(
@@ -108,68 +412,217 @@ macro_rules! telnet_options {
                 _ => None
             }
         }
+
+        fn rfc(num: u8) -> Option<u16> {
+            match num {
+                $(
+                $num => $rfc,
+                )+
+                _ => None
+            }
+        }
+
+        fn supports_subnegotiation(num: u8) -> bool {
+            match num {
+                $(
+                $num => $sub,
+                )+
+                _ => false
+            }
+        }
+
+        fn from_name(name: &str) -> Option<u8> {
+            $(
+            if names_eq(name, $phrase) {
+                return Some($num);
+            }
+            )+
+            None
+        }
     }
 }
 
 telnet_options! {
-    (0, BINARY, "BINARY");
-    (1, ECHO, "ECHO");
-    (2, RCP, "RCP");
-    (3, SGA, "SGA");
-    (4, NAMS, "NAMS");
-    (5, STATUS, "STATUS");
-    (6, TM, "TM");
-    (7, RCTE, "RCTE");
-    (8, NAOL, "NAOL");
-    (9, NAOP, "NAOP");
-    (10, NAOCRD, "NAOCRD");
-    (11, NAOHTS, "NAOHTS");
-    (12, NAOHTD, "NAOHTD");
-    (13, NAOFFD, "NAOFFD");
-    (14, NAOVTS, "NAOVTS");
-    (15, NAOVTD, "NAOVTD");
-    (16, NAOLFD, "NAOLFD");
-    (17, XASCII, "XASCII");
-    (18, LOGOUT, "LOGOUT");
-    (19, BM, "BM");
-    (20, DET, "DET");
-    (21, SUPDUP, "SUPDUP");
-    (22, SUPDUPOUTPUT, "SUPDUPOUTPUT");
-    (23, SNDLOC, "SNDLOC");
-    (24, TTYPE, "TTYPE");
-    (25, EOR, "EOR");
-    (26, TUID, "TUID");
-    (27, OUTMRK, "OUTMRK");
-    (28, TTYLOC, "TTYLOC");
-    (29, _3270REGIME, "3270REGIME");
-    (30, X3PAD, "X3PAD");
-    (31, NAWS, "NAWS");
-    (32, TSPEED, "TSPEED");
-    (33, LFLOW, "LFLOW");
-    (34, LINEMODE, "LINEMODE");
-    (35, XDISPLOC, "XDISPLOC");
-    (36, ENVIRON, "ENVIRON");
-    (37, AUTHENTICATION, "AUTHENTICATION");
-    (38, ENCRYPT, "ENCRYPT");
-    (39, NEW_ENVIRON, "NEW_ENVIRON");
-    (70, MSSP, "MSSP");
-    (85, COMPRESS, "COMPRESS");
+    (0, BINARY, "BINARY", Some(856), false);
+    (1, ECHO, "ECHO", Some(857), false);
+    (2, RCP, "RCP", None, false);
+    (3, SGA, "SGA", Some(858), false);
+    (4, NAMS, "NAMS", None, false);
+    (5, STATUS, "STATUS", Some(859), true);
+    (6, TM, "TM", Some(860), false);
+    (7, RCTE, "RCTE", Some(726), true);
+    (8, NAOL, "NAOL", None, true);
+    (9, NAOP, "NAOP", None, true);
+    (10, NAOCRD, "NAOCRD", Some(652), true);
+    (11, NAOHTS, "NAOHTS", Some(653), true);
+    (12, NAOHTD, "NAOHTD", Some(654), true);
+    (13, NAOFFD, "NAOFFD", Some(655), true);
+    (14, NAOVTS, "NAOVTS", Some(656), true);
+    (15, NAOVTD, "NAOVTD", Some(657), true);
+    (16, NAOLFD, "NAOLFD", Some(658), true);
+    (17, XASCII, "XASCII", Some(698), false);
+    (18, LOGOUT, "LOGOUT", Some(727), false);
+    (19, BM, "BM", Some(735), true);
+    (20, DET, "DET", Some(1043), true);
+    (21, SUPDUP, "SUPDUP", Some(736), false);
+    (22, SUPDUPOUTPUT, "SUPDUPOUTPUT", Some(749), true);
+    (23, SNDLOC, "SNDLOC", Some(779), true);
+    (24, TTYPE, "TTYPE", Some(1091), true);
+    /// End of record, records are terminated by the `EOR` command
+    (25, EOR, "EOR", Some(885), false);
+    (26, TUID, "TUID", Some(927), true);
+    (27, OUTMRK, "OUTMRK", Some(933), true);
+    (28, TTYLOC, "TTYLOC", Some(946), true);
+    (29, _3270REGIME, "3270REGIME", Some(1041), true);
+    (30, X3PAD, "X3PAD", Some(1053), true);
+    (31, NAWS, "NAWS", Some(1073), true);
+    (32, TSPEED, "TSPEED", Some(1079), true);
+    (33, LFLOW, "LFLOW", Some(1372), true);
+    (34, LINEMODE, "LINEMODE", Some(1184), true);
+    (35, XDISPLOC, "XDISPLOC", Some(1096), true);
+    (36, ENVIRON, "ENVIRON", Some(1408), true);
+    (37, AUTHENTICATION, "AUTHENTICATION", Some(2941), true);
+    (38, ENCRYPT, "ENCRYPT", Some(2946), true);
+    (39, NEW_ENVIRON, "NEW-ENVIRON", Some(1572), true);
+    /// TN3270 enhancements
+    (40, TN3270E, "TN3270E", Some(2355), true);
+    /// Character set negotiation
+    (42, CHARSET, "CHARSET", Some(2066), true);
+    /// Com port control
+    (44, COM_PORT, "COM-PORT", Some(2217), true);
+    /// TLS negotiation, draft-altman-telnet-starttls
+    (46, START_TLS, "START-TLS", None, true);
+    /// MUD Server Data Protocol
+    /// https://tintin.sourceforge.io/protocols/msdp/
+    (69, MSDP, "MSDP", None, true);
+    /// MUD Server Status Protocol
+    /// https://tintin.sourceforge.io/protocols/mssp/
+    (70, MSSP, "MSSP", None, true);
+    /// Also known as MCCP 1
+    (85, COMPRESS, "COMPRESS", None, true);
     /// Also known as MCCP 2
     /// https://tintin.sourceforge.io/protocols/mccp/
-    (86, COMPRESS2, "COMPRESS2");
-    (93, ZMP, "ZMP");
-    (255, EXOPL, "EXOPL");
+    (86, COMPRESS2, "COMPRESS2", None, true);
+    /// MUD Client Compression Protocol v3, compression of client to server data
+    /// https://tintin.sourceforge.io/protocols/mccp/
+    (87, MCCP3, "MCCP3", None, true);
+    /// MUD Sound Protocol
+    (90, MSP, "MSP", None, false);
+    /// MUD eXtension Protocol
+    (91, MXP, "MXP", None, true);
+    (93, ZMP, "ZMP", None, true);
+    /// Aardwolf channel data
+    (102, AARD102, "AARD102", None, true);
+    /// Achaea Telnet Client Protocol
+    (200, ATCP, "ATCP", None, true);
+    /// Generic MUD Communication Protocol
+    /// https://tintin.sourceforge.io/protocols/gmcp/
+    (201, GMCP, "GMCP", None, true);
+    (255, EXOPL, "EXOPL", Some(861), true);
 }
 
 #[cfg(test)]
 mod test {
-    use super::Opt;
+    use super::{Kind, Opt, OptKind, OptionNames};
 
     #[test]
     fn option_from_u8() {
-        assert_eq!(Opt::from_u8(86).unwrap(), Opt::COMPRESS2);
+        assert_eq!(Opt::from_u8(86), Opt::COMPRESS2);
         assert_eq!(Opt::COMPRESS2, 86);
         // assert_eq!(Opt::MCCP2, 86);
-        assert_eq!(Opt::from_u8(254).unwrap_err().invalid_src, 254);
+        assert_eq!(Opt::from_u8(0), Opt::BINARY);
+        assert_eq!(Opt::from_u8(201), Opt::GMCP);
+        assert_eq!(Opt::from_u8(254), 254);
+    }
+
+    #[test]
+    fn option_from() {
+        assert_eq!(Opt::from(31), Opt::NAWS);
+        assert_eq!(Opt::from(254), 254);
+    }
+
+    #[test]
+    fn option_kind() {
+        assert!(Opt::BINARY.is_known());
+        assert_eq!(Opt::TTYPE.kind(), Kind::Known);
+        assert!(!Opt::from_u8(254).is_known());
+        assert_eq!(Opt::from_u8(254).kind(), Kind::Unknown);
+    }
+
+    #[test]
+    fn option_typed() {
+        assert_eq!(OptKind::from(Opt::NAWS), OptKind::NAWS);
+        assert_eq!(OptKind::from(Opt::from_u8(254)), OptKind::Other(254));
+        assert_eq!(Opt::from(OptKind::_3270REGIME), Opt::_3270REGIME);
+        assert_eq!(Opt::from(OptKind::Other(31)), Opt::NAWS);
+    }
+
+    #[test]
+    fn option_from_str() {
+        assert_eq!("NAWS".parse::<Opt>().unwrap(), Opt::NAWS);
+        assert_eq!("ttype".parse::<Opt>().unwrap(), Opt::TTYPE);
+        assert_eq!("3270REGIME".parse::<Opt>().unwrap(), Opt::_3270REGIME);
+        assert_eq!("com-port".parse::<Opt>().unwrap(), Opt::COM_PORT);
+        assert_eq!("COM_PORT".parse::<Opt>().unwrap(), Opt::COM_PORT);
+        assert_eq!("new_environ".parse::<Opt>().unwrap(), Opt::NEW_ENVIRON);
+        assert_eq!("START-TLS".parse::<Opt>().unwrap(), Opt::START_TLS);
+        assert_eq!(Opt::NEW_ENVIRON.to_string(), "39 NEW-ENVIRON");
+        assert!("WILL".parse::<Opt>().is_err());
+    }
+
+    #[test]
+    fn option_metadata() {
+        assert_eq!(Opt::NAWS.rfc(), Some(1073));
+        assert!(Opt::NAWS.supports_subnegotiation());
+        assert_eq!(Opt::ECHO.rfc(), Some(857));
+        assert!(!Opt::ECHO.supports_subnegotiation());
+        assert_eq!(Opt::GMCP.rfc(), None);
+        assert!(Opt::GMCP.supports_subnegotiation());
+        assert!(!Opt::from_u8(254).supports_subnegotiation());
+    }
+
+    #[test]
+    fn option_register() {
+        define_telnet_options! {
+            struct TestOptions {
+                (250, TEST, "TEST-OPTION");
+            }
+        }
+
+        let mut names = OptionNames::new();
+        assert_eq!(names.kind(TestOptions::TEST), Kind::Unknown);
+        assert!(TestOptions::register(&mut names));
+        assert_eq!(names.kind(TestOptions::TEST), Kind::Custom);
+        assert_eq!(names.kind(Opt::NAWS), Kind::Known);
+        assert_eq!(names.name(TestOptions::TEST), Some("TEST-OPTION"));
+        assert_eq!(names.parse("test-option").unwrap(), TestOptions::TEST);
+        assert_eq!(names.parse("naws").unwrap(), Opt::NAWS);
+        assert!(names.parse("other").is_err());
+        assert_eq!(
+            names.display(TestOptions::TEST).to_string(),
+            "250 TEST-OPTION"
+        );
+        assert_eq!(format!("{:?}", names), r#"{250: "TEST-OPTION"}"#);
+
+        // the names stay in the table
+        assert_eq!(TestOptions::TEST.kind(), Kind::Unknown);
+        assert_eq!(TestOptions::TEST.to_string(), "250 <unknown option>");
+        assert!("test-option".parse::<Opt>().is_err());
+        assert_eq!(OptionNames::new().name(TestOptions::TEST), None);
+
+        // builtin options can't be renamed, nor can custom ones
+        assert!(!names.register(Opt::NAWS, "WINDOW-SIZE"));
+        assert!(!names.register(TestOptions::TEST, "OTHER"));
+        assert!(names.register(TestOptions::TEST, "TEST-OPTION"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn option_serde() {
+        assert_eq!(serde_json::to_string(&Opt::NAWS).unwrap(), "\"NAWS\"");
+        assert_eq!(serde_json::from_str::<Opt>("\"naws\"").unwrap(), Opt::NAWS);
+        assert_eq!(serde_json::from_str::<Opt>("24").unwrap(), Opt::TTYPE);
+        assert_eq!(serde_json::to_string(&Opt::from_u8(254)).unwrap(), "254");
+        assert!(serde_json::from_str::<Opt>("\"NOPE\"").is_err());
     }
 }
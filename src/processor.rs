@@ -1,12 +0,0 @@
-enum State {
-    Data,
-    Eol,
-    Iac,
-    Will,
-    Wont,
-    Do,
-    Dont,
-    Sb,
-    SbData,
-    SbDataCallback, // TODO ?
-}
@@ -0,0 +1,196 @@
+//! Presets for common kinds of connections
+//!
+//! A [`Profile`] picks the parser settings, the negotiation policy and the options negotiated
+//! right away for one kind of peer, so that a MUD client doesn't need to know about every knob:
+//!
+//! ```
+//! use televerknet::option::Opt;
+//! use televerknet::profile::Profile;
+//! use televerknet::session::{Role, TelnetSession};
+//!
+//! let mut session = TelnetSession::with_profile(Profile::Mud, Role::Client);
+//! session.feed(b"\xff\xfb\xc9");
+//! assert!(session.is_remote_enabled(Opt::GMCP));
+//! ```
+//!
+//! Each part is available on its own, e.g. to start from the parser of a profile and change one
+//! setting.
+//!
+//! [`Profile`]: enum.Profile.html
+use crate::option::Opt;
+use crate::session::{Policy, Role};
+use crate::{CrHandling, FlushPolicy, ParserBuilder, SubInterrupt};
+
+const MUD_MAX_SUBNEGOTIATION: usize = 64 * 1024;
+
+/// A preset of settings for a kind of peer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Profile {
+    /// A plain network virtual terminal as in RFC 854: no options, malformed input is reported
+    StrictRfc854,
+    /// MUDs and their clients, with EOR prompts and GMCP, MSDP and MSSP
+    Mud,
+    /// Bulletin board systems, sending 8-bit ANSI art with TRANSMIT-BINARY
+    Bbs,
+    /// Serial port servers as in RFC 2217, passing binary data through untouched
+    SerialGateway,
+}
+
+impl Profile {
+    /// Settings of the parser
+    pub fn parser(self) -> ParserBuilder {
+        match self {
+            Profile::StrictRfc854 => ParserBuilder::new()
+                .strict(true)
+                .cr_handling(CrHandling::StripNul)
+                .sub_interrupt(SubInterrupt::Abort),
+            // GMCP and MSDP messages don't fit the default subnegotiation buffer, and prompts
+            // often end without GA or EOR
+            Profile::Mud => ParserBuilder::new()
+                .max_subnegotiation(MUD_MAX_SUBNEGOTIATION)
+                .cr_handling(CrHandling::StripNul)
+                .flush_policy(FlushPolicy::PerRead),
+            Profile::Bbs => ParserBuilder::new()
+                .cr_handling(CrHandling::StripNul)
+                .flush_policy(FlushPolicy::PerRead),
+            Profile::SerialGateway => ParserBuilder::new().flush_policy(FlushPolicy::PerRead),
+        }
+    }
+
+    /// Which options `role` accepts
+    ///
+    /// Builds on [`Policy::for_role`], except for a strict or serial gateway profile.
+    ///
+    /// [`Policy::for_role`]: ../session/struct.Policy.html#method.for_role
+    pub fn policy(self, role: Role) -> Policy {
+        match (self, role) {
+            (Profile::StrictRfc854, _) => Policy::new(),
+            (Profile::Mud, Role::Client) => Policy::for_role(role)
+                .accept_local(Opt::NAWS)
+                .accept_remote(Opt::EOR)
+                .accept_remote(Opt::GMCP)
+                .accept_remote(Opt::MSDP)
+                .accept_remote(Opt::MSSP),
+            (Profile::Mud, Role::Server) => Policy::for_role(role)
+                .accept_local(Opt::EOR)
+                .accept_local(Opt::GMCP)
+                .accept_local(Opt::MSDP)
+                .accept_local(Opt::MSSP),
+            (Profile::Bbs, Role::Client) => Policy::for_role(role)
+                .accept_local(Opt::NAWS)
+                .accept_local(Opt::BINARY)
+                .accept_remote(Opt::BINARY),
+            (Profile::Bbs, Role::Server) => Policy::for_role(role)
+                .accept_local(Opt::BINARY)
+                .accept_remote(Opt::BINARY),
+            (Profile::SerialGateway, Role::Client) => Policy::new()
+                .accept_local(Opt::BINARY)
+                .accept_local(Opt::SGA)
+                .accept_local(Opt::COM_PORT)
+                .accept_remote(Opt::BINARY)
+                .accept_remote(Opt::SGA),
+            (Profile::SerialGateway, Role::Server) => Policy::new()
+                .accept_local(Opt::BINARY)
+                .accept_local(Opt::SGA)
+                .accept_remote(Opt::BINARY)
+                .accept_remote(Opt::SGA)
+                .accept_remote(Opt::COM_PORT),
+        }
+    }
+
+    /// Options a server negotiates as soon as the session is created
+    ///
+    /// Each is offered if the policy of the server accepts it locally, and requested if the
+    /// policy accepts it remotely. The server asks for the terminal type once TTYPE is enabled.
+    pub fn options(self) -> &'static [Opt] {
+        match self {
+            Profile::StrictRfc854 => &[],
+            Profile::Mud => &[
+                Opt::TTYPE,
+                Opt::NAWS,
+                Opt::EOR,
+                Opt::GMCP,
+                Opt::MSDP,
+                Opt::MSSP,
+            ],
+            Profile::Bbs => &[Opt::ECHO, Opt::SGA, Opt::BINARY, Opt::TTYPE, Opt::NAWS],
+            Profile::SerialGateway => &[Opt::BINARY, Opt::SGA, Opt::COM_PORT],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Profile;
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::session::{Role, TelnetSession};
+
+    #[test]
+    fn mud() {
+        let mut server = TelnetSession::with_profile(Profile::Mud, Role::Server);
+        let mut client = TelnetSession::with_profile(Profile::Mud, Role::Client);
+        let offers = server.take_output();
+        assert_eq!(
+            offers,
+            b"\xff\xfd\x18\xff\xfd\x1f\xff\xfb\x19\xff\xfb\xc9\xff\xfb\x45\xff\xfb\x46"
+        );
+        client.feed(&offers);
+        for _ in 0..3 {
+            let output = client.take_output();
+            server.feed(&output);
+            let output = server.take_output();
+            client.feed(&output);
+        }
+        assert!(client.is_remote_enabled(Opt::GMCP));
+        assert!(client.is_remote_enabled(Opt::MSDP));
+        assert!(server.is_remote_enabled(Opt::NAWS));
+        assert!(server.is_local_enabled(Opt::EOR));
+
+        // The whole message fits the buffer, unless it is capped by the heapless feature
+        #[cfg(not(feature = "heapless"))]
+        {
+            let mut message = b"\xff\xfa\xc9Room.Info ".to_vec();
+            message.resize(8000, b'x');
+            message.extend_from_slice(b"\xff\xf0");
+            let events: Vec<Event> = client.feed(&message).collect();
+            assert!(
+                matches!(&events[..], [Event::Subnegotiate(Opt::GMCP, params)] if params.len() == 7997)
+            );
+        }
+    }
+
+    #[test]
+    fn strict() {
+        let mut session = TelnetSession::with_profile(Profile::StrictRfc854, Role::Server);
+        assert!(session.take_output().is_empty());
+        let events: Vec<Event> = session.feed(b"\xff\x41\xff\xfb\x01a\r\0").collect();
+        assert_eq!(
+            events,
+            &[
+                Event::Negotiate(Command::WILL, Opt::ECHO),
+                Event::Data(b"a".to_vec()),
+                Event::Execute(b'\r'),
+            ]
+        );
+        assert_eq!(session.take_output(), b"\xff\xfe\x01");
+    }
+
+    #[test]
+    fn serial_gateway() {
+        let mut client = TelnetSession::with_profile(Profile::SerialGateway, Role::Client);
+        let mut server = TelnetSession::with_profile(Profile::SerialGateway, Role::Server);
+        assert!(client.take_output().is_empty());
+        for _ in 0..3 {
+            let output = server.take_output();
+            client.feed(&output);
+            let output = client.take_output();
+            server.feed(&output);
+        }
+        assert!(server.is_remote_enabled(Opt::COM_PORT));
+        assert_eq!(server.binary(), (true, true));
+        assert!(!server.is_local_enabled(Opt::ECHO));
+    }
+}
@@ -0,0 +1,210 @@
+//! Prompt detection
+//!
+//! Servers mark prompts with `IAC GA` or `IAC EOR`, unless go ahead is suppressed, and many don't
+//! mark them at all. [`PromptDetector`] combines those marks with hints from the text after the
+//! last line ending: whether it looks like a prompt, and how long the server has been quiet since
+//! sending it. The result is a [`Confidence`], so each client can pick its own threshold.
+//!
+//! Like [`Keepalive`], the detector doesn't keep time itself: the caller passes the time elapsed
+//! since the last tick.
+//!
+//! [`PromptDetector`]: struct.PromptDetector.html
+//! [`Confidence`]: enum.Confidence.html
+//! [`Keepalive`]: ../keepalive/struct.Keepalive.html
+use std::time::Duration;
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::option::Opt;
+
+const DEFAULT_IDLE: Duration = Duration::from_millis(250);
+const DEFAULT_SUFFIXES: [&str; 5] = ["> ", ": ", "? ", "$ ", "# "];
+
+/// How likely the text after the last line ending is a prompt
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// No text, or nothing suggests a prompt
+    None,
+    /// The text ends like a prompt, or the server went quiet after it
+    Low,
+    /// The text ends like a prompt and the server went quiet after it
+    Medium,
+    /// The server marked the text with GA or EOR
+    High,
+}
+
+/// A detected prompt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    pub text: String,
+    pub confidence: Confidence,
+}
+
+/// Classifies unterminated text as a prompt
+#[derive(Debug, Clone)]
+pub struct PromptDetector {
+    idle: Duration,
+    suffixes: Vec<String>,
+    pending: Vec<u8>,
+    marked: bool,
+    marks_prompts: bool,
+    quiet_for: Duration,
+}
+
+impl Default for PromptDetector {
+    fn default() -> PromptDetector {
+        PromptDetector::new()
+    }
+}
+
+impl PromptDetector {
+    /// A detector which considers text followed by 250 ms of silence and text ending like
+    /// `"> "`, `": "`, `"? "`, `"$ "` or `"# "`
+    pub fn new() -> PromptDetector {
+        PromptDetector {
+            idle: DEFAULT_IDLE,
+            suffixes: DEFAULT_SUFFIXES.iter().map(|s| (*s).to_owned()).collect(),
+            pending: Vec::new(),
+            marked: false,
+            marks_prompts: false,
+            quiet_for: Duration::from_secs(0),
+        }
+    }
+
+    /// Consider text a prompt once the server was quiet for `idle` after it
+    pub fn idle(mut self, idle: Duration) -> PromptDetector {
+        self.idle = idle;
+        self
+    }
+
+    /// Consider text ending with one of `suffixes` a prompt, replacing the defaults
+    pub fn suffixes(mut self, suffixes: &[&str]) -> PromptDetector {
+        self.suffixes = suffixes.iter().map(|s| (*s).to_owned()).collect();
+        self
+    }
+
+    /// Follow an event received from the server
+    ///
+    /// Once the server has marked a prompt with GA or EOR, unmarked text is only considered a
+    /// prompt with low confidence. Suppressing go ahead resets that.
+    pub fn observe(&mut self, event: &Event) {
+        match event {
+            Event::Data(data) => self.push(data),
+            Event::Execute(byte) => self.push(&[*byte]),
+            Event::Command(Command::GA) | Event::Command(Command::EOR)
+                if !self.pending.is_empty() =>
+            {
+                self.marked = true;
+                self.marks_prompts = true;
+            }
+            Event::Negotiate(Command::WILL, Opt::SGA) => self.marks_prompts = false,
+            _ => (),
+        }
+    }
+
+    /// Advance the silence of the server by `elapsed`, returning the confidence of the pending
+    /// text
+    pub fn tick(&mut self, elapsed: Duration) -> Confidence {
+        self.quiet_for += elapsed;
+        self.confidence()
+    }
+
+    /// The confidence that the text after the last line ending is a prompt
+    pub fn confidence(&self) -> Confidence {
+        if self.pending.is_empty() {
+            return Confidence::None;
+        }
+        if self.marked {
+            return Confidence::High;
+        }
+        let text = String::from_utf8_lossy(&self.pending);
+        let suffix = self.suffixes.iter().any(|s| text.ends_with(s.as_str()));
+        let quiet = self.quiet_for >= self.idle;
+        match (suffix, quiet) {
+            (true, true) if !self.marks_prompts => Confidence::Medium,
+            (true, _) | (_, true) => Confidence::Low,
+            (false, false) => Confidence::None,
+        }
+    }
+
+    /// The text after the last line ending
+    pub fn pending(&self) -> &[u8] {
+        &self.pending
+    }
+
+    /// Take the pending text as a prompt if its confidence is at least `min`
+    pub fn take_prompt(&mut self, min: Confidence) -> Option<Prompt> {
+        let confidence = self.confidence();
+        if confidence == Confidence::None || confidence < min {
+            return None;
+        }
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        self.marked = false;
+        Some(Prompt { text, confidence })
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.quiet_for = Duration::from_secs(0);
+        for &byte in bytes {
+            match byte {
+                b'\r' | b'\n' | 0 => {
+                    self.pending.clear();
+                    self.marked = false;
+                }
+                byte => {
+                    // Text after a marked prompt is a new line, e.g. the echo of a command
+                    if self.marked {
+                        self.pending.clear();
+                        self.marked = false;
+                    }
+                    self.pending.push(byte);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Confidence, Prompt, PromptDetector};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    #[test]
+    fn confidence() {
+        let ms = Duration::from_millis;
+        let mut detector = PromptDetector::new().idle(ms(100));
+        detector.observe(&Event::Data(b"Welcome".to_vec()));
+        assert_eq!(detector.tick(ms(50)), Confidence::None);
+        assert_eq!(detector.tick(ms(50)), Confidence::Low);
+        detector.observe(&Event::Execute(b'\n'));
+        assert_eq!(detector.confidence(), Confidence::None);
+
+        detector.observe(&Event::Data(b"Login: ".to_vec()));
+        assert_eq!(detector.confidence(), Confidence::Low);
+        assert_eq!(detector.tick(ms(100)), Confidence::Medium);
+        assert_eq!(detector.take_prompt(Confidence::High), None);
+        assert_eq!(
+            detector.take_prompt(Confidence::Medium),
+            Some(Prompt {
+                text: "Login: ".to_owned(),
+                confidence: Confidence::Medium,
+            })
+        );
+
+        detector.observe(&Event::Data(b"HP 10>".to_vec()));
+        detector.observe(&Event::Command(Command::GA));
+        assert_eq!(detector.confidence(), Confidence::High);
+        detector.take_prompt(Confidence::High).unwrap();
+
+        // Unmarked text from a server which marks prompts
+        detector.observe(&Event::Data(b"More? ".to_vec()));
+        assert_eq!(detector.tick(ms(100)), Confidence::Low);
+        detector.observe(&Event::Negotiate(Command::WILL, Opt::SGA));
+        assert_eq!(detector.confidence(), Confidence::Medium);
+    }
+}
@@ -0,0 +1,123 @@
+//! Transparent parsing for proxies and loggers
+//!
+//! [`Proxy`] parses a stream like [`Parser`] does, but pairs every event with the exact bytes it
+//! was parsed from. Forwarding the raw bytes of all segments reproduces the stream byte for byte,
+//! escaping included, while the events tell what is being forwarded. A sequence split between
+//! two reads is held back until it is complete, so segments never cut a sequence in half.
+//!
+//! [`Proxy`]: struct.Proxy.html
+//! [`Parser`]: ../struct.Parser.html
+use crate::encode;
+use crate::event::Event;
+use crate::Parser;
+
+/// Raw bytes of one sequence, or one run of printable data, and the event parsed from them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub raw: Vec<u8>,
+    /// `None` for sequences which produce no event, e.g. an unknown command
+    pub event: Option<Event>,
+}
+
+/// Parser which keeps the raw bytes of every event
+#[derive(Default)]
+pub struct Proxy {
+    parser: Parser,
+    events: Vec<Event>,
+    pending: Vec<u8>,
+}
+
+impl Proxy {
+    pub fn new() -> Proxy {
+        Proxy::default()
+    }
+
+    /// Parse `bytes`, returning the segments completed by them
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Segment> {
+        self.pending.extend_from_slice(bytes);
+
+        let mut segments = Vec::new();
+        let mut pos = 0;
+        while let Some(n) = segment_len(&self.pending[pos..]) {
+            let raw = &self.pending[pos..pos + n];
+            self.parser.advance_bytes(&mut self.events, raw);
+            self.parser.flush(&mut self.events);
+            // A segment is a single sequence or only printable data, so it yields one event
+            let event = self.events.drain(..).next();
+            segments.push(Segment {
+                raw: raw.to_vec(),
+                event,
+            });
+            pos += n;
+        }
+        self.pending.drain(..pos);
+        segments
+    }
+
+    /// Bytes of an incomplete sequence, held back until the rest arrives
+    pub fn pending(&self) -> &[u8] {
+        &self.pending
+    }
+}
+
+/// Length of the run of printable data, or of the sequence, at the start of `bytes`
+fn segment_len(bytes: &[u8]) -> Option<usize> {
+    match bytes.iter().position(|b| !(0x20..=0x7f).contains(b)) {
+        Some(0) => encode::sequence_len(bytes),
+        Some(n) => Some(n),
+        None if bytes.is_empty() => None,
+        None => Some(bytes.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Proxy, Segment};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    #[test]
+    fn byte_exact() {
+        let stream: &[u8] = &[
+            b'h', b'i', 255, 255, b'\r', b'\n', 255, 253, 31, 255, 250, 24, 1, 255, 240, 255, 1,
+        ];
+        // Every split point must give the same bytes back
+        for split in 0..stream.len() {
+            let mut proxy = Proxy::new();
+            let mut segments = proxy.feed(&stream[..split]);
+            segments.extend(proxy.feed(&stream[split..]));
+            assert!(proxy.pending().is_empty());
+
+            let raw: Vec<u8> = segments.iter().flat_map(|s| s.raw.clone()).collect();
+            assert_eq!(raw, stream);
+        }
+    }
+
+    #[test]
+    fn segments() {
+        let mut proxy = Proxy::new();
+        let segments = proxy.feed(&[b'o', b'k', 255, 255, 255, 253]);
+        assert_eq!(
+            segments,
+            &[
+                Segment {
+                    raw: b"ok".to_vec(),
+                    event: Some(Event::Data(b"ok".to_vec())),
+                },
+                Segment {
+                    raw: vec![255, 255],
+                    event: Some(Event::Data(vec![255])),
+                },
+            ]
+        );
+        assert_eq!(proxy.pending(), &[255, 253]);
+
+        let segments = proxy.feed(&[1]);
+        assert_eq!(
+            segments[0].event,
+            Some(Event::Negotiate(Command::DO, Opt::ECHO))
+        );
+        assert_eq!(segments[0].raw, &[255, 253, 1]);
+    }
+}
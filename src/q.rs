@@ -21,52 +21,213 @@ pub enum QueueBit {
     Opposite,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, thiserror::Error)]
 pub enum NegotiatorError {
+    #[error("option already enabled")]
     AlreadyEnabled,
+    #[error("option change already queued")]
     AlreadyQueued,
+    #[error("option already disabled")]
     AlreadyDisabled,
+    #[error("option already being negotiated")]
     AlreadyNegotiating,
+    #[error("DONT answered by WILL")]
     DontAnsweredByWill,
+    #[error("WONT answered by DO")]
     WontAnsweredByDo,
+    #[error("not a negotiation command")]
     UnknownCommand,
+    /// The option is not below the number of options tracked by the negotiator
+    #[error("option not tracked by the negotiator")]
+    UntrackedOption,
+    /// The option was refused with [`Negotiator::refuse`](struct.Negotiator.html#method.refuse)
+    #[error("option refused")]
+    Refused,
+}
+
+/// What to do about a received negotiation, see [`Negotiator::decide`]
+///
+/// [`Negotiator::decide`]: struct.Negotiator.html#method.decide
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NegotiationAction {
+    command: Command,
+    option: u8,
+    /// The new state and queue bit, on the side `command` is about
+    update: Option<(OptionState, QueueBit)>,
+    send: Option<Command>,
+    request: bool,
+    error: Option<NegotiatorError>,
+}
+
+impl NegotiationAction {
+    /// The command received
+    pub fn command(&self) -> Command {
+        self.command
+    }
+
+    pub fn option(&self) -> u8 {
+        self.option
+    }
+
+    /// The answer to send for the option, if any
+    pub fn send(&self) -> Option<Command> {
+        self.send
+    }
+
+    /// The state of the option once applied, if it changes
+    pub fn state(&self) -> Option<OptionState> {
+        self.update.map(|(state, _)| state)
+    }
+
+    /// Returns true if the other party asks to enable an option which is off
+    ///
+    /// The request is refused unless [`accept`] was called.
+    ///
+    /// [`accept`]: #method.accept
+    pub fn is_request(&self) -> bool {
+        self.request
+    }
+
+    /// Agree to a request, enabling the option and answering with WILL or DO
+    ///
+    /// Other actions are returned unchanged, as they are required by the protocol.
+    pub fn accept(mut self) -> NegotiationAction {
+        if self.request {
+            let yes = match self.command {
+                Command::WILL => Command::DO,
+                _ => Command::WILL,
+            };
+            self.update = Some((OptionState::Yes, QueueBit::Empty));
+            self.send = Some(yes);
+        }
+        self
+    }
+
+    /// The error to report for the command received, if any
+    pub fn error(&self) -> Option<NegotiatorError> {
+        self.error
+    }
 }
 
 // There are two queues implemented as described by Daniel J. Bernstein in RFC 1143.
 //
 // If the value is true, we know that once the outstanding request is finished we will direct
 // change this option again.
-pub struct Negotiator {
-    local: [OptionState; MAX_OPTIONS],
-    localq: [QueueBit; MAX_OPTIONS],
-    remote: [OptionState; MAX_OPTIONS],
-    remoteq: [QueueBit; MAX_OPTIONS],
+//
+// All state is held in fixed size arrays, so negotiation never allocates. Only the `N` first
+// options are tracked, the others are always off and requests to enable them are refused.
+pub struct Negotiator<const N: usize = MAX_OPTIONS> {
+    local: [OptionState; N],
+    localq: [QueueBit; N],
+    remote: [OptionState; N],
+    remoteq: [QueueBit; N],
+    /// Options never to enable, one bit per option
+    refused: [u64; MAX_OPTIONS / 64],
+}
+
+impl<const N: usize> Default for Negotiator<N> {
+    fn default() -> Negotiator<N> {
+        Negotiator::bounded()
+    }
 }
 
 impl Negotiator {
+    /// A negotiator tracking every option
     pub fn new() -> Negotiator {
+        Negotiator::bounded()
+    }
+}
+
+impl<const N: usize> Negotiator<N> {
+    /// A negotiator tracking the options below `N`, e.g. `Negotiator::<32>::bounded()`
+    ///
+    /// # Panics
+    ///
+    /// If `N` is larger than 256.
+    pub fn bounded() -> Negotiator<N> {
+        assert!(N <= MAX_OPTIONS, "there are only {} options", MAX_OPTIONS);
         Negotiator {
-            local: [OptionState::No; MAX_OPTIONS],
-            localq: [QueueBit::Empty; MAX_OPTIONS],
-            remote: [OptionState::No; MAX_OPTIONS],
-            remoteq: [QueueBit::Empty; MAX_OPTIONS],
+            local: [OptionState::No; N],
+            localq: [QueueBit::Empty; N],
+            remote: [OptionState::No; N],
+            remoteq: [QueueBit::Empty; N],
+            refused: [0; MAX_OPTIONS / 64],
         }
     }
 
+    /// Returns true if `option` is tracked
+    pub fn tracks(&self, option: u8) -> bool {
+        usize::from(option) < N
+    }
+
+    /// Never enable `option` again, on either side
+    ///
+    /// Requests to enable it are answered with DONT or WONT right away, without asking the
+    /// performer, and [`enable`] and [`offer`] fail. If the option is enabled, or being enabled,
+    /// it is disabled.
+    ///
+    /// [`enable`]: #method.enable
+    /// [`offer`]: #method.offer
+    pub fn refuse<P: Perform>(&mut self, performer: &mut P, option: u8) {
+        let u = usize::from(option);
+        self.refused[u / 64] |= 1 << (u % 64);
+        if !self.tracks(option) {
+            return;
+        }
+        // Failing only if already on the way to being disabled
+        if let OptionState::Yes | OptionState::WantYes = self.remote[u] {
+            let _ = self.disable(performer, option);
+        }
+        if let OptionState::Yes | OptionState::WantYes = self.local[u] {
+            let _ = self.withdraw(performer, option);
+        }
+    }
+
+    /// Returns true if `option` was refused
+    pub fn is_refused(&self, option: u8) -> bool {
+        let u = usize::from(option);
+        self.refused[u / 64] & (1 << (u % 64)) != 0
+    }
+
+    /// State of `option` as performed by us
+    pub fn local_state(&self, option: u8) -> OptionState {
+        self.local
+            .get(usize::from(option))
+            .copied()
+            .unwrap_or(OptionState::No)
+    }
+
+    /// State of `option` as performed by the other party
+    pub fn remote_state(&self, option: u8) -> OptionState {
+        self.remote
+            .get(usize::from(option))
+            .copied()
+            .unwrap_or(OptionState::No)
+    }
+
+    /// Handle a received `command` for `option`, sending the answer with `performer`
     #[inline]
     pub fn recv<P: Perform>(
         &mut self,
         performer: &mut P,
         command: Command,
         option: u8,
-    ) -> Option<NegotiatorError> {
-        match command {
-            Command::WILL => self.recv_will(performer, option),
-            Command::WONT => self.recv_wont(performer, option),
-            Command::DO => self.recv_do(performer, option),
-            Command::DONT => self.recv_dont(performer, option),
-            _ => Some(NegotiatorError::UnknownCommand),
+    ) -> Result<(), NegotiatorError> {
+        let mut action = self.decide(command, option);
+        if action.is_request() {
+            let accepted = match command {
+                Command::WILL => performer.accept_remote(option),
+                _ => performer.accept_local(option),
+            };
+            if accepted {
+                action = action.accept();
+            }
+        }
+        self.apply(action);
+        if let Some(answer) = action.send() {
+            performer.send(answer, option);
         }
+        action.error().map_or(Ok(()), Err)
     }
 
     #[inline]
@@ -74,188 +235,234 @@ impl Negotiator {
         &mut self,
         performer: &mut P,
         option: u8,
-    ) -> Option<NegotiatorError> {
+    ) -> Result<(), NegotiatorError> {
+        self.recv(performer, Command::WILL, option)
+    }
+
+    /// What to do about a received `command` for `option`, without changing any state
+    ///
+    /// A request to enable an option which is off is refused unless the caller calls
+    /// [`NegotiationAction::accept`], e.g. after asking the user. The action takes effect with
+    /// [`apply`], after which its answer must be sent. An action should be applied before
+    /// deciding on another command for the same option.
+    ///
+    /// [`NegotiationAction::accept`]: struct.NegotiationAction.html#method.accept
+    /// [`apply`]: #method.apply
+    pub fn decide(&self, command: Command, option: u8) -> NegotiationAction {
         let u = usize::from(option);
-        match (self.remote[u], self.remoteq[u]) {
-            (OptionState::No, _) => {
-                if performer.want_enabled(option) {
-                    self.remote[u] = OptionState::Yes;
-                    performer.send(Command::DO, option);
-                } else {
-                    performer.send(Command::DONT, option);
-                }
-                None
+        let mut action = NegotiationAction {
+            command,
+            option,
+            update: None,
+            send: None,
+            request: false,
+            error: None,
+        };
+        let tracked = self.tracks(option);
+        let (state, queue, yes, no, refused) = match command {
+            Command::WILL | Command::WONT => (
+                self.remote_state(option),
+                self.remoteq.get(u).copied().unwrap_or(QueueBit::Empty),
+                Command::DO,
+                Command::DONT,
+                NegotiatorError::DontAnsweredByWill,
+            ),
+            Command::DO | Command::DONT => (
+                self.local_state(option),
+                self.localq.get(u).copied().unwrap_or(QueueBit::Empty),
+                Command::WILL,
+                Command::WONT,
+                NegotiatorError::WontAnsweredByDo,
+            ),
+            _ => {
+                action.error = Some(NegotiatorError::UnknownCommand);
+                return action;
             }
-            (OptionState::Yes, _) => None,
-            (OptionState::WantNo, QueueBit::Empty) => {
-                self.remote[u] = OptionState::No;
-                Some(NegotiatorError::DontAnsweredByWill)
+        };
+        let enable = command == Command::WILL || command == Command::DO;
+        match (enable, state, queue) {
+            (true, OptionState::No, _) => {
+                action.request = tracked && !self.is_refused(option);
+                action.send = Some(no);
             }
-            (OptionState::WantNo, QueueBit::Opposite) => {
-                self.remote[u] = OptionState::Yes;
-                self.remoteq[u] = QueueBit::Empty;
-                Some(NegotiatorError::DontAnsweredByWill)
+            (true, OptionState::Yes, _) | (false, OptionState::No, _) => (),
+            (true, OptionState::WantNo, QueueBit::Empty) => {
+                action.update = Some((OptionState::No, QueueBit::Empty));
+                action.error = Some(refused);
             }
-            (OptionState::WantYes, QueueBit::Empty) => {
-                self.remote[u] = OptionState::Yes;
-                None
+            (true, OptionState::WantNo, QueueBit::Opposite) => {
+                action.update = Some((OptionState::Yes, QueueBit::Empty));
+                action.error = Some(refused);
             }
-            (OptionState::WantYes, QueueBit::Opposite) => {
-                self.remote[u] = OptionState::WantNo;
-                self.remoteq[u] = QueueBit::Empty;
-                performer.send(Command::DONT, option);
-                None
+            (true, OptionState::WantYes, QueueBit::Empty) => {
+                action.update = Some((OptionState::Yes, QueueBit::Empty));
             }
-        }
-    }
-
-    #[inline]
-    fn recv_wont<P: Perform>(&mut self, performer: &mut P, option: u8) -> Option<NegotiatorError> {
-        let u = usize::from(option);
-        match (self.remote[u], self.remoteq[u]) {
-            (OptionState::No, _) => None,
-            (OptionState::Yes, _) => {
-                self.remote[u] = OptionState::No;
-                performer.send(Command::DONT, option);
-                None
+            (true, OptionState::WantYes, QueueBit::Opposite) => {
+                action.update = Some((OptionState::WantNo, QueueBit::Empty));
+                action.send = Some(no);
             }
-            (OptionState::WantNo, QueueBit::Empty) => {
-                self.remote[u] = OptionState::No;
-                None
+            (false, OptionState::Yes, _) => {
+                action.update = Some((OptionState::No, queue));
+                action.send = Some(no);
             }
-            (OptionState::WantNo, QueueBit::Opposite) => {
-                self.remote[u] = OptionState::WantYes;
-                self.remoteq[u] = QueueBit::Empty;
-                performer.send(Command::DO, option);
-                None
+            (false, OptionState::WantNo, QueueBit::Empty) | (false, OptionState::WantYes, _) => {
+                action.update = Some((OptionState::No, QueueBit::Empty));
             }
-            (OptionState::WantYes, QueueBit::Empty) => {
-                self.remote[u] = OptionState::No;
-                None
+            (false, OptionState::WantNo, QueueBit::Opposite) => {
+                action.update = Some((OptionState::WantYes, QueueBit::Empty));
+                action.send = Some(yes);
             }
-            (OptionState::WantYes, QueueBit::Opposite) => {
-                self.remote[u] = OptionState::No;
-                self.remoteq[u] = QueueBit::Empty;
-                None
+        }
+        action
+    }
+
+    /// Change the state of the option as decided by `action`
+    ///
+    /// The answer of the action, if any, is left to the caller to send.
+    pub fn apply(&mut self, action: NegotiationAction) {
+        let u = usize::from(action.option);
+        if u >= N {
+            return;
+        }
+        if let Some((state, queue)) = action.update {
+            match action.command {
+                Command::WILL | Command::WONT => {
+                    self.remote[u] = state;
+                    self.remoteq[u] = queue;
+                }
+                _ => {
+                    self.local[u] = state;
+                    self.localq[u] = queue;
+                }
             }
         }
     }
 
     #[inline]
-    fn recv_do<P: Perform>(&mut self, performer: &mut P, option: u8) -> Option<NegotiatorError> {
+    pub fn enable<P: Perform>(
+        &mut self,
+        performer: &mut P,
+        option: u8,
+    ) -> Result<(), NegotiatorError> {
         let u = usize::from(option);
-        match (self.local[u], self.localq[u]) {
+        if u >= N {
+            return Err(NegotiatorError::UntrackedOption);
+        }
+        if self.is_refused(option) {
+            return Err(NegotiatorError::Refused);
+        }
+        match (self.remote[u], self.remoteq[u]) {
             (OptionState::No, _) => {
-                if performer.want_enabled(option) {
-                    self.local[u] = OptionState::Yes;
-                    performer.send(Command::WILL, option);
-                } else {
-                    performer.send(Command::WONT, option);
-                }
-                None
+                self.remote[u] = OptionState::WantYes;
+                performer.send(Command::DO, option);
+                Ok(())
             }
-            (OptionState::Yes, _) => None,
+            (OptionState::Yes, _) => Err(NegotiatorError::AlreadyEnabled),
             (OptionState::WantNo, QueueBit::Empty) => {
-                self.local[u] = OptionState::No;
-                Some(NegotiatorError::WontAnsweredByDo)
-            }
-            (OptionState::WantNo, QueueBit::Opposite) => {
-                self.local[u] = OptionState::Yes;
-                self.localq[u] = QueueBit::Empty;
-                Some(NegotiatorError::WontAnsweredByDo)
-            }
-            (OptionState::WantYes, QueueBit::Empty) => {
-                self.local[u] = OptionState::Yes;
-                None
+                self.remoteq[u] = QueueBit::Opposite;
+                Ok(())
             }
+            (OptionState::WantNo, QueueBit::Opposite) => Err(NegotiatorError::AlreadyQueued),
+            (OptionState::WantYes, QueueBit::Empty) => Err(NegotiatorError::AlreadyNegotiating),
             (OptionState::WantYes, QueueBit::Opposite) => {
-                self.local[u] = OptionState::WantNo;
-                self.localq[u] = QueueBit::Empty;
-                performer.send(Command::WONT, option);
-                None
+                self.remoteq[u] = QueueBit::Empty;
+                Ok(())
             }
         }
     }
 
     #[inline]
-    fn recv_dont<P: Perform>(&mut self, performer: &mut P, option: u8) -> Option<NegotiatorError> {
+    pub fn disable<P: Perform>(
+        &mut self,
+        performer: &mut P,
+        option: u8,
+    ) -> Result<(), NegotiatorError> {
         let u = usize::from(option);
-        match (self.local[u], self.localq[u]) {
-            (OptionState::No, _) => None,
+        if u >= N {
+            return Err(NegotiatorError::UntrackedOption);
+        }
+        match (self.remote[u], self.remoteq[u]) {
+            (OptionState::No, _) => Err(NegotiatorError::AlreadyDisabled),
             (OptionState::Yes, _) => {
-                self.local[u] = OptionState::No;
-                performer.send(Command::WONT, option);
-                None
-            }
-            (OptionState::WantNo, QueueBit::Empty) => {
-                self.local[u] = OptionState::No;
-                None
+                self.remote[u] = OptionState::WantNo;
+                performer.send(Command::DONT, option);
+                Ok(())
             }
+            (OptionState::WantNo, QueueBit::Empty) => Err(NegotiatorError::AlreadyNegotiating),
             (OptionState::WantNo, QueueBit::Opposite) => {
-                self.local[u] = OptionState::WantYes;
-                self.localq[u] = QueueBit::Empty;
-                performer.send(Command::WILL, option);
-                None
+                self.remoteq[u] = QueueBit::Empty;
+                Ok(())
             }
             (OptionState::WantYes, QueueBit::Empty) => {
-                self.local[u] = OptionState::No;
-                None
-            }
-            (OptionState::WantYes, QueueBit::Opposite) => {
-                self.local[u] = OptionState::No;
-                self.localq[u] = QueueBit::Empty;
-                None
+                self.remoteq[u] = QueueBit::Opposite;
+                Ok(())
             }
+            (OptionState::WantYes, QueueBit::Opposite) => Err(NegotiatorError::AlreadyQueued),
         }
     }
 
+    /// Offer to perform `option` ourselves
     #[inline]
-    pub fn enable<P: Perform>(&mut self, performer: &mut P, option: u8) -> Option<NegotiatorError> {
+    pub fn offer<P: Perform>(
+        &mut self,
+        performer: &mut P,
+        option: u8,
+    ) -> Result<(), NegotiatorError> {
         let u = usize::from(option);
-        match (self.remote[u], self.remoteq[u]) {
+        if u >= N {
+            return Err(NegotiatorError::UntrackedOption);
+        }
+        if self.is_refused(option) {
+            return Err(NegotiatorError::Refused);
+        }
+        match (self.local[u], self.localq[u]) {
             (OptionState::No, _) => {
-                self.remote[u] = OptionState::WantYes;
-                performer.send(Command::DO, option);
-                None
+                self.local[u] = OptionState::WantYes;
+                performer.send(Command::WILL, option);
+                Ok(())
             }
-            (OptionState::Yes, _) => Some(NegotiatorError::AlreadyEnabled),
+            (OptionState::Yes, _) => Err(NegotiatorError::AlreadyEnabled),
             (OptionState::WantNo, QueueBit::Empty) => {
-                self.remoteq[u] = QueueBit::Opposite;
-                None
+                self.localq[u] = QueueBit::Opposite;
+                Ok(())
             }
-            (OptionState::WantNo, QueueBit::Opposite) => Some(NegotiatorError::AlreadyQueued),
-            (OptionState::WantYes, QueueBit::Empty) => Some(NegotiatorError::AlreadyNegotiating),
+            (OptionState::WantNo, QueueBit::Opposite) => Err(NegotiatorError::AlreadyQueued),
+            (OptionState::WantYes, QueueBit::Empty) => Err(NegotiatorError::AlreadyNegotiating),
             (OptionState::WantYes, QueueBit::Opposite) => {
-                self.remoteq[u] = QueueBit::Empty;
-                None
+                self.localq[u] = QueueBit::Empty;
+                Ok(())
             }
         }
     }
 
+    /// Stop performing `option` ourselves
     #[inline]
-    pub fn disable<P: Perform>(
+    pub fn withdraw<P: Perform>(
         &mut self,
         performer: &mut P,
         option: u8,
-    ) -> Option<NegotiatorError> {
+    ) -> Result<(), NegotiatorError> {
         let u = usize::from(option);
-        match (self.remote[u], self.remoteq[u]) {
-            (OptionState::No, _) => Some(NegotiatorError::AlreadyDisabled),
+        if u >= N {
+            return Err(NegotiatorError::UntrackedOption);
+        }
+        match (self.local[u], self.localq[u]) {
+            (OptionState::No, _) => Err(NegotiatorError::AlreadyDisabled),
             (OptionState::Yes, _) => {
-                self.remote[u] = OptionState::WantNo;
-                performer.send(Command::DONT, option);
-                None
+                self.local[u] = OptionState::WantNo;
+                performer.send(Command::WONT, option);
+                Ok(())
             }
-            (OptionState::WantNo, QueueBit::Empty) => Some(NegotiatorError::AlreadyNegotiating),
+            (OptionState::WantNo, QueueBit::Empty) => Err(NegotiatorError::AlreadyNegotiating),
             (OptionState::WantNo, QueueBit::Opposite) => {
-                self.remoteq[u] = QueueBit::Empty;
-                None
+                self.localq[u] = QueueBit::Empty;
+                Ok(())
             }
             (OptionState::WantYes, QueueBit::Empty) => {
-                self.remoteq[u] = QueueBit::Opposite;
-                None
+                self.localq[u] = QueueBit::Opposite;
+                Ok(())
             }
-            (OptionState::WantYes, QueueBit::Opposite) => Some(NegotiatorError::AlreadyQueued),
+            (OptionState::WantYes, QueueBit::Opposite) => Err(NegotiatorError::AlreadyQueued),
         }
     }
 }
@@ -263,24 +470,31 @@ impl Negotiator {
 pub trait Perform {
     fn send(&mut self, command: Command, option: u8);
 
-    // called to see if we want a specific option enabled
-    fn want_enabled(&mut self, option: u8) -> bool;
+    /// Called on an incoming DO to see if we are willing to perform `option` ourselves
+    fn accept_local(&mut self, option: u8) -> bool;
+
+    /// Called on an incoming WILL to see if we want the other party to perform `option`
+    fn accept_remote(&mut self, option: u8) -> bool;
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Command, Negotiator, OptionState, Perform, QueueBit, MAX_OPTIONS};
+    use super::{
+        Command, Negotiator, NegotiatorError, OptionState, Perform, QueueBit, MAX_OPTIONS,
+    };
 
     struct TestDispatcher {
         commands: Vec<(Command, u8)>,
-        enabled: [bool; MAX_OPTIONS],
+        local: [bool; MAX_OPTIONS],
+        remote: [bool; MAX_OPTIONS],
     }
 
     impl Default for TestDispatcher {
         fn default() -> Self {
             TestDispatcher {
                 commands: Default::default(),
-                enabled: [false; MAX_OPTIONS],
+                local: [false; MAX_OPTIONS],
+                remote: [false; MAX_OPTIONS],
             }
         }
     }
@@ -289,8 +503,11 @@ mod tests {
         fn send(&mut self, command: Command, option: u8) {
             self.commands.push((command, option));
         }
-        fn want_enabled(&mut self, option: u8) -> bool {
-            self.enabled[usize::from(option)]
+        fn accept_local(&mut self, option: u8) -> bool {
+            self.local[usize::from(option)]
+        }
+        fn accept_remote(&mut self, option: u8) -> bool {
+            self.remote[usize::from(option)]
         }
     }
 
@@ -308,31 +525,31 @@ mod tests {
         let mut dispatcher = TestDispatcher::default();
 
         // 1. it decides to disable
-        it.disable(&mut dispatcher, 200);
+        it.disable(&mut dispatcher, 200).unwrap();
         assert_eq!(dispatcher.commands.len(), 1);
         assert_eq!(dispatcher.commands.pop().unwrap(), (Command::DONT, 200));
         assert_eq!(it.remote[200], OptionState::WantNo);
         assert_eq!(it.remoteq[200], QueueBit::Empty);
 
         // 2. it decides to reenable (command is queued)
-        it.enable(&mut dispatcher, 200);
+        it.enable(&mut dispatcher, 200).unwrap();
         assert_eq!(dispatcher.commands.len(), 0);
         assert_eq!(it.remote[200], OptionState::WantNo);
         assert_eq!(it.remoteq[200], QueueBit::Opposite);
 
         // 3. we receive DONT
-        we.recv(&mut dispatcher, Command::DONT, 200);
+        we.recv(&mut dispatcher, Command::DONT, 200).unwrap();
         assert_eq!(dispatcher.commands.len(), 1);
         assert_eq!(dispatcher.commands.pop().unwrap(), (Command::WONT, 200));
         assert_eq!(we.local[200], OptionState::No);
 
         // 4. we receive DO but disagree
-        we.recv(&mut dispatcher, Command::DO, 200);
+        we.recv(&mut dispatcher, Command::DO, 200).unwrap();
         assert_eq!(dispatcher.commands.len(), 1);
         assert_eq!(dispatcher.commands.pop().unwrap(), (Command::WONT, 200));
 
         // 5. it receieve WONT but automatically tries to reenable
-        it.recv(&mut dispatcher, Command::WONT, 200);
+        it.recv(&mut dispatcher, Command::WONT, 200).unwrap();
         assert_eq!(it.remote[200], OptionState::WantYes);
         assert_eq!(it.remoteq[200], QueueBit::Empty);
         // 6. it pushes to reenable again
@@ -340,15 +557,16 @@ mod tests {
         assert_eq!(dispatcher.commands.pop().unwrap(), (Command::DO, 200));
 
         // 7. it receives wont and gives up
-        it.recv(&mut dispatcher, Command::WONT, 200);
+        it.recv(&mut dispatcher, Command::WONT, 200).unwrap();
         assert_eq!(dispatcher.commands.len(), 0);
         assert_eq!(it.remote[200], OptionState::No);
 
         // for whatever reason, they decides to agree with future requests
-        dispatcher.enabled[200] = true;
+        dispatcher.local[200] = true;
+        dispatcher.remote[200] = true;
 
         // 8. we receive DO and decide to agree
-        we.recv(&mut dispatcher, Command::DO, 200);
+        we.recv(&mut dispatcher, Command::DO, 200).unwrap();
         assert_eq!(dispatcher.commands.len(), 1);
         assert_eq!(dispatcher.commands.pop().unwrap(), (Command::WILL, 200));
         assert_eq!(we.local[200], OptionState::Yes);
@@ -357,21 +575,21 @@ mod tests {
         assert_eq!(we.remoteq[200], QueueBit::Empty);
 
         // 9. we decide to disable. we send WONT and disable the option
-        we.disable(&mut dispatcher, 200);
+        we.disable(&mut dispatcher, 200).unwrap();
         assert_eq!(dispatcher.commands.len(), 1);
         assert_eq!(dispatcher.commands.pop().unwrap(), (Command::DONT, 200));
         assert_eq!(we.remote[200], OptionState::WantNo);
         assert_eq!(we.remoteq[200], QueueBit::Empty);
 
         // 10. it receives WILL and agrees
-        it.recv(&mut dispatcher, Command::WILL, 200);
+        it.recv(&mut dispatcher, Command::WILL, 200).unwrap();
         assert_eq!(dispatcher.commands.len(), 1);
         assert_eq!(dispatcher.commands.pop().unwrap(), (Command::DO, 200));
         assert_eq!(it.remote[200], OptionState::Yes);
         assert_eq!(it.remoteq[200], QueueBit::Empty);
 
         // 11. it receives WONT and agrees
-        it.recv(&mut dispatcher, Command::WONT, 200);
+        it.recv(&mut dispatcher, Command::WONT, 200).unwrap();
         assert_eq!(dispatcher.commands.len(), 1);
         assert_eq!(dispatcher.commands.pop().unwrap(), (Command::DONT, 200));
         assert_eq!(it.local[200], OptionState::Yes);
@@ -380,13 +598,150 @@ mod tests {
         assert_eq!(it.remoteq[200], QueueBit::Empty);
 
         // 12. we receives DO and agrees
-        we.recv(&mut dispatcher, Command::DO, 200);
+        we.recv(&mut dispatcher, Command::DO, 200).unwrap();
         assert_eq!(we.remote[200], OptionState::WantNo);
         assert_eq!(we.remoteq[200], QueueBit::Empty);
 
         // 13. we receives DONT and gives up
-        we.recv(&mut dispatcher, Command::DONT, 200);
+        we.recv(&mut dispatcher, Command::DONT, 200).unwrap();
         assert_eq!(we.local[200], OptionState::No);
         assert_eq!(we.localq[200], QueueBit::Empty);
     }
+
+    #[test]
+    fn asymmetric_policy() {
+        let mut neg = Negotiator::new();
+        let mut dispatcher = TestDispatcher::default();
+
+        // we are willing to echo ourselves, but don't want the other party to echo
+        dispatcher.local[1] = true;
+
+        neg.recv(&mut dispatcher, Command::DO, 1).unwrap();
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::WILL, 1));
+        assert_eq!(neg.local[1], OptionState::Yes);
+
+        neg.recv(&mut dispatcher, Command::WILL, 1).unwrap();
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::DONT, 1));
+        assert_eq!(neg.remote[1], OptionState::No);
+    }
+
+    #[test]
+    fn decide_and_apply() {
+        let mut neg = Negotiator::new();
+
+        // A request is refused unless accepted, and deciding changes nothing
+        let action = neg.decide(Command::DO, 1);
+        assert!(action.is_request());
+        assert_eq!(action.send(), Some(Command::WONT));
+        assert_eq!(action.state(), None);
+        assert_eq!(neg.local[1], OptionState::No);
+
+        let action = action.accept();
+        assert_eq!(action.send(), Some(Command::WILL));
+        neg.apply(action);
+        assert_eq!(neg.local[1], OptionState::Yes);
+
+        // Disabling is not up to us
+        let action = neg.decide(Command::DONT, 1);
+        assert!(!action.is_request());
+        assert_eq!(action.accept(), action);
+        assert_eq!(action.send(), Some(Command::WONT));
+        neg.apply(action);
+        assert_eq!(neg.local[1], OptionState::No);
+
+        let mut dispatcher = TestDispatcher::default();
+        neg.enable(&mut dispatcher, 3).unwrap();
+        let action = neg.decide(Command::WILL, 3);
+        assert!(!action.is_request());
+        assert_eq!(action.send(), None);
+        assert_eq!(action.state(), Some(OptionState::Yes));
+
+        let action = neg.decide(Command::SE, 3);
+        assert_eq!(action.error(), Some(NegotiatorError::UnknownCommand));
+    }
+
+    #[test]
+    fn bounded() {
+        let mut neg = Negotiator::<32>::bounded();
+        let mut dispatcher = TestDispatcher {
+            local: [true; MAX_OPTIONS],
+            ..Default::default()
+        };
+
+        neg.recv(&mut dispatcher, Command::DO, 31).unwrap();
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::WILL, 31));
+        assert_eq!(neg.local_state(31), OptionState::Yes);
+
+        // Untracked options are refused whatever the performer says
+        assert!(!neg.tracks(32));
+        neg.recv(&mut dispatcher, Command::DO, 32).unwrap();
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::WONT, 32));
+        assert!(!neg.decide(Command::DO, 32).accept().is_request());
+        assert_eq!(neg.local_state(32), OptionState::No);
+        neg.recv(&mut dispatcher, Command::DONT, 200).unwrap();
+        assert!(dispatcher.commands.is_empty());
+        assert_eq!(
+            neg.offer(&mut dispatcher, 200),
+            Err(NegotiatorError::UntrackedOption)
+        );
+        assert!(dispatcher.commands.is_empty());
+    }
+
+    #[test]
+    fn refuse() {
+        let mut neg = Negotiator::new();
+        let mut dispatcher = TestDispatcher {
+            local: [true; MAX_OPTIONS],
+            remote: [true; MAX_OPTIONS],
+            ..Default::default()
+        };
+
+        neg.recv(&mut dispatcher, Command::WILL, 38).unwrap();
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::DO, 38));
+        neg.refuse(&mut dispatcher, 38);
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::DONT, 38));
+        neg.recv(&mut dispatcher, Command::WONT, 38).unwrap();
+        assert!(dispatcher.commands.is_empty());
+
+        // Requests are refused although the performer accepts them
+        neg.recv(&mut dispatcher, Command::WILL, 38).unwrap();
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::DONT, 38));
+        neg.recv(&mut dispatcher, Command::DO, 38).unwrap();
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::WONT, 38));
+        assert_eq!(neg.remote_state(38), OptionState::No);
+        assert_eq!(neg.local_state(38), OptionState::No);
+
+        assert_eq!(
+            neg.enable(&mut dispatcher, 38),
+            Err(NegotiatorError::Refused)
+        );
+        assert_eq!(
+            neg.offer(&mut dispatcher, 38),
+            Err(NegotiatorError::Refused)
+        );
+        assert!(dispatcher.commands.is_empty());
+        assert!(neg.is_refused(38));
+        assert!(!neg.is_refused(37));
+    }
+
+    #[test]
+    fn offer_and_withdraw() {
+        let mut neg = Negotiator::new();
+        let mut dispatcher = TestDispatcher::default();
+
+        assert!(neg.offer(&mut dispatcher, 3).is_ok());
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::WILL, 3));
+        assert_eq!(neg.local[3], OptionState::WantYes);
+
+        // DO answers our offer, so it is not answered again
+        neg.recv(&mut dispatcher, Command::DO, 3).unwrap();
+        assert!(dispatcher.commands.is_empty());
+        assert_eq!(neg.local[3], OptionState::Yes);
+
+        assert!(neg.withdraw(&mut dispatcher, 3).is_ok());
+        assert_eq!(dispatcher.commands.pop().unwrap(), (Command::WONT, 3));
+        neg.recv(&mut dispatcher, Command::DONT, 3).unwrap();
+        assert!(dispatcher.commands.is_empty());
+        assert_eq!(neg.local[3], OptionState::No);
+    }
 }
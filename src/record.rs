@@ -0,0 +1,268 @@
+//! Recording and replay of sessions
+//!
+//! [`Recorder`] writes the raw bytes received from a server, with the time they arrived, so that
+//! parser bugs seen on a live connection can be reproduced later with [`Replayer`].
+//!
+//! A recording is a sequence of entries, each made of the time since the start of the recording
+//! in microseconds as a little endian `u64`, the length of the bytes as a little endian `u32`, and
+//! the bytes themselves.
+//!
+//! A replay either feeds the entries as fast as possible or keeps their original spacing, possibly
+//! sped up, see [`Pacing`]. [`Replayer::timed_events`] gives the events of a recording with the
+//! time their bytes arrived.
+//!
+//! [`Recorder`]: struct.Recorder.html
+//! [`Replayer`]: struct.Replayer.html
+//! [`Pacing`]: enum.Pacing.html
+//! [`Replayer::timed_events`]: struct.Replayer.html#method.timed_events
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::event::Event;
+use crate::{Parser, Perform};
+
+/// One read of a recorded session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Time since the start of the recording
+    pub offset: Duration,
+    pub bytes: Vec<u8>,
+}
+
+/// An event with the time its bytes arrived
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedEvent {
+    /// Time since the start of the recording
+    pub offset: Duration,
+    pub event: Event,
+}
+
+/// How fast a replay feeds the entries of a recording
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pacing {
+    /// Without waiting
+    Fast,
+    /// At the offsets they were recorded at
+    Original,
+    /// At their offsets divided by the factor, e.g. twice as fast with 2.0
+    Scaled(f64),
+}
+
+impl Pacing {
+    /// When an entry recorded at `offset` is due, from the start of the replay
+    pub fn due(self, offset: Duration) -> Duration {
+        match self {
+            Pacing::Fast => Duration::from_secs(0),
+            Pacing::Original => offset,
+            Pacing::Scaled(factor) => offset.div_f64(factor),
+        }
+    }
+}
+
+/// Writes timestamped bytes to a recording
+pub struct Recorder<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start a recording, offsets are measured from now
+    pub fn new(writer: W) -> Recorder<W> {
+        Recorder {
+            writer,
+            start: Instant::now(),
+        }
+    }
+
+    /// Record bytes received now
+    pub fn record(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let offset = self.start.elapsed();
+        self.write_entry(offset, bytes)
+    }
+
+    /// Record bytes with an explicit offset, e.g. when converting other captures
+    pub fn write_entry(&mut self, offset: Duration, bytes: &[u8]) -> io::Result<()> {
+        let micros = u64::try_from(offset.as_micros()).unwrap_or(u64::MAX);
+        let len = u32::try_from(bytes.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "entry too large"))?;
+        self.writer.write_all(&micros.to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(bytes)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads a recording back
+pub struct Replayer<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> Replayer<R> {
+    pub fn new(reader: R) -> Replayer<R> {
+        Replayer { reader }
+    }
+
+    /// Read the next entry, or `None` at the end of the recording
+    pub fn next_entry(&mut self) -> io::Result<Option<Entry>> {
+        let mut micros = [0u8; 8];
+        match self.reader.read_exact(&mut micros) {
+            Ok(()) => (),
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(Some(Entry {
+            offset: Duration::from_micros(u64::from_le_bytes(micros)),
+            bytes,
+        }))
+    }
+
+    /// Feed every entry through `parser` to `performer`
+    ///
+    /// With `realtime` set, waits before each entry so it is fed at its original offset from the
+    /// start of the replay.
+    pub fn replay<P: Perform>(
+        &mut self,
+        parser: &mut Parser,
+        performer: &mut P,
+        realtime: bool,
+    ) -> io::Result<()> {
+        let pacing = if realtime {
+            Pacing::Original
+        } else {
+            Pacing::Fast
+        };
+        self.replay_paced(parser, performer, pacing)
+    }
+
+    /// Feed every entry through `parser` to `performer`, waiting before each entry as `pacing`
+    /// requires
+    ///
+    /// Waits are measured from the start of the replay, so time spent by the performer doesn't
+    /// add up over the entries.
+    pub fn replay_paced<P: Perform>(
+        &mut self,
+        parser: &mut Parser,
+        performer: &mut P,
+        pacing: Pacing,
+    ) -> io::Result<()> {
+        let start = Instant::now();
+        while let Some(entry) = self.next_entry()? {
+            if let Some(wait) = pacing.due(entry.offset).checked_sub(start.elapsed()) {
+                thread::sleep(wait);
+            }
+            parser.advance_bytes(performer, &entry.bytes);
+            parser.flush(performer);
+        }
+        Ok(())
+    }
+
+    /// Parse every entry with `parser`, returning the events with the offset of the entry their
+    /// bytes arrived in
+    ///
+    /// Doesn't wait, the offsets tell when each event happened.
+    pub fn timed_events(&mut self, parser: &mut Parser) -> io::Result<Vec<TimedEvent>> {
+        let mut timed = Vec::new();
+        let mut events: Vec<Event> = Vec::new();
+        while let Some(entry) = self.next_entry()? {
+            parser.advance_bytes(&mut events, &entry.bytes);
+            parser.flush(&mut events);
+            timed.extend(events.drain(..).map(|event| TimedEvent {
+                offset: entry.offset,
+                event,
+            }));
+        }
+        Ok(timed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, Pacing, Recorder, Replayer, TimedEvent};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::Parser;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn record_and_replay() {
+        let mut recorder = Recorder::new(Vec::new());
+        recorder.record(&[b'h', b'i', 255]).unwrap();
+        recorder
+            .write_entry(Duration::from_millis(5), &[251, 1])
+            .unwrap();
+        let recording = recorder.into_inner();
+
+        let mut replayer = Replayer::new(&recording[..]);
+        replayer.next_entry().unwrap().unwrap();
+        assert_eq!(
+            replayer.next_entry().unwrap(),
+            Some(Entry {
+                offset: Duration::from_millis(5),
+                bytes: vec![251, 1],
+            })
+        );
+        assert_eq!(replayer.next_entry().unwrap(), None);
+
+        let mut events: Vec<Event> = Vec::new();
+        Replayer::new(&recording[..])
+            .replay(&mut Parser::new(), &mut events, true)
+            .unwrap();
+        assert_eq!(
+            events,
+            &[
+                Event::Data(b"hi".to_vec()),
+                Event::Negotiate(Command::WILL, Opt::ECHO),
+            ]
+        );
+    }
+
+    #[test]
+    fn paced_replay() {
+        let mut recorder = Recorder::new(Vec::new());
+        recorder
+            .write_entry(Duration::from_millis(0), b"a")
+            .unwrap();
+        recorder
+            .write_entry(Duration::from_millis(40), &[255, 249])
+            .unwrap();
+        let recording = recorder.into_inner();
+
+        assert_eq!(
+            Replayer::new(&recording[..])
+                .timed_events(&mut Parser::new())
+                .unwrap(),
+            &[
+                TimedEvent {
+                    offset: Duration::from_millis(0),
+                    event: Event::Data(b"a".to_vec()),
+                },
+                TimedEvent {
+                    offset: Duration::from_millis(40),
+                    event: Event::Command(Command::GA),
+                },
+            ]
+        );
+
+        let start = Instant::now();
+        let mut events: Vec<Event> = Vec::new();
+        Replayer::new(&recording[..])
+            .replay_paced(&mut Parser::new(), &mut events, Pacing::Scaled(2.0))
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            Pacing::Fast.due(Duration::from_secs(1)),
+            Duration::from_secs(0)
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Redaction of credentials for logging
+//!
+//! Some subnegotiations carry secrets: authentication data, encryption keys, and environment
+//! variables such as passwords sent with NEW-ENVIRON. [`Sanitizer`] replaces those parts of
+//! events, or of a raw byte stream, with a mask before they are logged or recorded. Everything
+//! else passes through unchanged.
+//!
+//! [`Sanitizer`]: struct.Sanitizer.html
+use std::collections::HashMap;
+
+use crate::event::Event;
+use crate::option::Opt;
+use crate::{Parser, ParserBuilder};
+
+const MAX_SUBNEGOTIATION: usize = 64 * 1024;
+const DEFAULT_MASK: &[u8] = b"***";
+
+const ENVIRON_VAR: u8 = 0;
+const ENVIRON_VALUE: u8 = 1;
+const ENVIRON_ESC: u8 = 2;
+const ENVIRON_USERVAR: u8 = 3;
+
+/// What [`Sanitizer`] hides of the subnegotiations of an option
+///
+/// [`Sanitizer`]: struct.Sanitizer.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Redaction {
+    /// Nothing
+    Keep,
+    /// Everything after the first byte, which usually tells the kind of subnegotiation
+    Payload,
+    /// The values of NEW-ENVIRON variables, keeping their names
+    Values,
+}
+
+/// Redacts sensitive subnegotiation parameters
+#[derive(Clone)]
+pub struct Sanitizer {
+    rules: HashMap<Opt, Redaction>,
+    mask: Vec<u8>,
+    parser: Parser,
+}
+
+impl Default for Sanitizer {
+    fn default() -> Sanitizer {
+        Sanitizer::new()
+    }
+}
+
+impl Sanitizer {
+    /// Redact the payload of AUTHENTICATION and ENCRYPT, and the values of NEW-ENVIRON
+    pub fn new() -> Sanitizer {
+        Sanitizer {
+            rules: HashMap::new(),
+            mask: DEFAULT_MASK.to_vec(),
+            parser: ParserBuilder::new()
+                .max_subnegotiation(MAX_SUBNEGOTIATION)
+                .build(),
+        }
+        .redact(Opt::AUTHENTICATION, Redaction::Payload)
+        .redact(Opt::ENCRYPT, Redaction::Payload)
+        .redact(Opt::NEW_ENVIRON, Redaction::Values)
+    }
+
+    /// Redact `redaction` of the subnegotiations of `option`
+    pub fn redact(mut self, option: Opt, redaction: Redaction) -> Sanitizer {
+        self.rules.insert(option, redaction);
+        self
+    }
+
+    /// Replace redacted bytes with `mask` instead of `***`
+    pub fn mask(mut self, mask: &[u8]) -> Sanitizer {
+        self.mask = mask.to_vec();
+        self
+    }
+
+    /// The redaction of `option`
+    pub fn redaction(&self, option: Opt) -> Redaction {
+        self.rules.get(&option).copied().unwrap_or(Redaction::Keep)
+    }
+
+    /// Redact the parameters of a subnegotiation of `option`
+    pub fn params(&self, option: Opt, params: &[u8]) -> Vec<u8> {
+        match self.redaction(option) {
+            Redaction::Keep => params.to_vec(),
+            Redaction::Payload if params.len() <= 1 => params.to_vec(),
+            Redaction::Payload => {
+                let mut redacted = vec![params[0]];
+                redacted.extend_from_slice(&self.mask);
+                redacted
+            }
+            Redaction::Values => self.environ_values(params),
+        }
+    }
+
+    /// Redact an event
+    pub fn event(&self, event: Event) -> Event {
+        match event {
+            Event::Subnegotiate(option, params) => {
+                let params = self.params(option, &params);
+                Event::Subnegotiate(option, params)
+            }
+            event => event,
+        }
+    }
+
+    /// Redact raw bytes received or sent, appending the result to `out`
+    ///
+    /// The bytes are parsed to find the subnegotiations, which are kept until complete, and
+    /// encoded again.
+    pub fn bytes(&mut self, bytes: &[u8], out: &mut Vec<u8>) {
+        let mut events: Vec<Event> = Vec::new();
+        self.parser.advance_bytes(&mut events, bytes);
+        self.parser.flush(&mut events);
+        for event in events {
+            self.event(event).encode_into(out);
+        }
+    }
+
+    /// Replace every value of `IS` or `INFO` parameters with the mask
+    fn environ_values(&self, params: &[u8]) -> Vec<u8> {
+        let mut redacted = Vec::with_capacity(params.len());
+        let mut in_value = false;
+        let mut escaped = false;
+        for &byte in params {
+            if escaped {
+                escaped = false;
+                if !in_value {
+                    redacted.extend_from_slice(&[ENVIRON_ESC, byte]);
+                }
+                continue;
+            }
+            match byte {
+                ENVIRON_ESC => escaped = true,
+                ENVIRON_VAR | ENVIRON_USERVAR => {
+                    in_value = false;
+                    redacted.push(byte);
+                }
+                ENVIRON_VALUE => {
+                    in_value = true;
+                    redacted.push(byte);
+                    redacted.extend_from_slice(&self.mask);
+                }
+                byte if !in_value => redacted.push(byte),
+                _ => (),
+            }
+        }
+        redacted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Redaction, Sanitizer};
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    #[test]
+    fn redacts() {
+        let mut sanitizer = Sanitizer::new();
+        // NEW-ENVIRON IS VAR "USER" VALUE "joe" USERVAR "PASS" VALUE "s\x02\x01cret"
+        let environ = b"\x00\x00USER\x01joe\x03PASS\x01s\x02\x01cret".to_vec();
+        assert_eq!(
+            sanitizer.event(Event::Subnegotiate(Opt::NEW_ENVIRON, environ)),
+            Event::Subnegotiate(
+                Opt::NEW_ENVIRON,
+                b"\x00\x00USER\x01***\x03PASS\x01***".to_vec()
+            )
+        );
+        assert_eq!(
+            sanitizer.params(Opt::AUTHENTICATION, b"\x00\x05\x00key"),
+            b"\x00***"
+        );
+        assert_eq!(sanitizer.params(Opt::TTYPE, b"\x00xterm"), b"\x00xterm");
+
+        let mut out = Vec::new();
+        sanitizer.bytes(b"hi\xff\xfa\x26\x00\x01se", &mut out);
+        sanitizer.bytes(b"cret\xff\xff\xff\xf0\xff\xff", &mut out);
+        assert_eq!(out, b"hi\xff\xfa\x26\x00***\xff\xf0\xff\xff");
+
+        let sanitizer = Sanitizer::new()
+            .redact(Opt::ENCRYPT, Redaction::Keep)
+            .mask(b"");
+        assert_eq!(sanitizer.params(Opt::ENCRYPT, b"\x00\x01"), b"\x00\x01");
+        assert_eq!(sanitizer.params(Opt::AUTHENTICATION, b"\x00\x01"), b"\x00");
+    }
+}
@@ -0,0 +1,1307 @@
+//! A telnet session combining the parser and the negotiator
+//!
+//! [`TelnetSession`] does no I/O by itself. Bytes read from the peer are passed to
+//! [`TelnetSession::feed`], which answers option negotiation according to a [`Policy`], and
+//! everything that should be sent to the peer is buffered until taken with
+//! [`TelnetSession::take_output`].
+//!
+//! Sessions created with [`TelnetSession::with_role`] use the defaults of a client or a server.
+//! A server offers to echo and to suppress go ahead, asks for the terminal type and requests it
+//! once the client agrees. A client accepts those and answers the request with the terminal type
+//! set with [`TelnetSession::terminal_type`]. [`TelnetSession::with_profile`] goes further and
+//! sets up the parser and options for a kind of peer, e.g. a MUD.
+//!
+//! A START_TLS handshake is negotiated by the session, see [`StartTls`] for the point where the
+//! caller takes over to wrap the connection in TLS.
+//!
+//! For readiness based event loops, e.g. with mio, [`TelnetSession::read_from`] and
+//! [`TelnetSession::write_to`] perform a single read or write on a non-blocking socket, keeping
+//! all buffering inside the session.
+//!
+//! [`TelnetSession`]: struct.TelnetSession.html
+//! [`TelnetSession::feed`]: struct.TelnetSession.html#method.feed
+//! [`TelnetSession::take_output`]: struct.TelnetSession.html#method.take_output
+//! [`TelnetSession::with_role`]: struct.TelnetSession.html#method.with_role
+//! [`TelnetSession::terminal_type`]: struct.TelnetSession.html#method.terminal_type
+//! [`TelnetSession::with_profile`]: struct.TelnetSession.html#method.with_profile
+//! [`TelnetSession::read_from`]: struct.TelnetSession.html#method.read_from
+//! [`TelnetSession::write_to`]: struct.TelnetSession.html#method.write_to
+//! [`Policy`]: struct.Policy.html
+//! [`StartTls`]: enum.StartTls.html
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use std::vec;
+
+use crate::audit::{AuditEvent, AuditSink, Side};
+use crate::command::Command;
+use crate::echo::EchoHint;
+use crate::encode;
+use crate::event::Event;
+use crate::handler::{OptionHandler, Reply};
+use crate::limits::{Limiter, Limits};
+use crate::option::Opt;
+use crate::profile::Profile;
+use crate::q::{self, Negotiator, OptionState};
+use crate::stats::Stats;
+use crate::status;
+use crate::timing::{RoundTrip, TimingMark};
+use crate::{DataEnd, ParseError, Parser, Pending, Perform};
+
+const MAX_OPTIONS: usize = 256;
+/// Fits a STATUS report of every option enabled on both sides, escaped IAC included
+const MAX_SUBNEGOTIATION: usize = 2 * 2 * MAX_OPTIONS + 8;
+const READ_BUF_SIZE: usize = 4096;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+const START_TLS_FOLLOWS: u8 = 1;
+
+/// Progress of a START_TLS handshake
+///
+/// The server requests START_TLS with [`TelnetSession::request_remote`], and a client accepts it
+/// with its policy. Once both sides have sent `IAC SB START_TLS FOLLOWS IAC SE` the session is
+/// `Ready`: it stops parsing right after the FOLLOWS of the other party, and keeps everything
+/// received after it for the TLS layer. The caller writes the remaining output, takes those
+/// bytes with [`TelnetSession::take_tls_input`] as the start of the TLS handshake, and feeds
+/// decrypted bytes from then on. The session is then `Done`, and ignores any further FOLLOWS.
+///
+/// [`TelnetSession::request_remote`]: struct.TelnetSession.html#method.request_remote
+/// [`TelnetSession::take_tls_input`]: struct.TelnetSession.html#method.take_tls_input
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StartTls {
+    Idle,
+    /// We sent FOLLOWS and wait for the other party
+    Follows,
+    /// Both sides sent FOLLOWS, the connection must be wrapped in TLS
+    Ready,
+    /// The TLS input was taken, the session parses the decrypted stream
+    Done,
+}
+
+/// Returns true for events which change how the bytes following them are parsed
+fn is_stop_event(event: &Event) -> bool {
+    match event {
+        Event::Negotiate(_, option) => *option == Opt::BINARY,
+        Event::Subnegotiate(Opt::START_TLS, params) => params.as_slice() == [START_TLS_FOLLOWS],
+        _ => false,
+    }
+}
+
+/// Pass `event` to the audit sink, if there is one
+fn audit(sink: &mut Option<Box<dyn AuditSink>>, event: AuditEvent) {
+    if let Some(sink) = sink {
+        sink.record(&event);
+    }
+}
+
+/// Collects the events of the parser, leaving out subnegotiations which did not fit its buffer
+///
+/// The parser reports the truncation right before dispatching the subnegotiation, and what is
+/// left of it can't be trusted, e.g. a STATUS report missing options. A truncated subnegotiation
+/// is not always dispatched, e.g. with `Overflow::Abort`, so the flag is reset when the next one
+/// starts.
+struct Collector<'a> {
+    events: &'a mut Vec<Event>,
+    truncated: bool,
+}
+
+impl<'a> Collector<'a> {
+    fn new(events: &'a mut Vec<Event>) -> Collector<'a> {
+        Collector {
+            events,
+            truncated: false,
+        }
+    }
+}
+
+impl<'a> Perform for Collector<'a> {
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        self.events.data(intermediates, ignore, end);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.events.execute(byte);
+    }
+
+    fn error(&mut self, error: ParseError) {
+        self.truncated = matches!(error, ParseError::SubnegotiationTruncated { .. });
+        self.events.error(error);
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        self.events.iac_dispatch(byte);
+    }
+
+    fn sub_start(&mut self) {
+        self.truncated = false;
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        if self.truncated {
+            self.truncated = false;
+            debug!("dropping truncated subnegotiation of option {}", subs[0]);
+            return;
+        }
+        self.events.sub_dispatch(subs);
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        self.events.negotiate_dispatch(cmd, opt);
+    }
+
+    fn subnegotiate_dispatch(&mut self, params: &[u8], opt: u8) {
+        self.events.subnegotiate_dispatch(params, opt);
+    }
+
+    fn zmp_dispatch(&mut self, params: &[&[u8]]) {
+        self.events.zmp_dispatch(params);
+    }
+
+    fn ttypes_dispatch(&mut self, cmd: u8, terminal_type: &[u8]) {
+        self.events.ttypes_dispatch(cmd, terminal_type);
+    }
+
+    fn compress_dispatch(&mut self, state: u8) {
+        self.events.compress_dispatch(state);
+    }
+}
+
+/// Which end of the connection a session is
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+/// Which options to accept when the other party asks for them
+///
+/// The default policy refuses every option.
+#[derive(Clone)]
+pub struct Policy {
+    local: [bool; MAX_OPTIONS],
+    remote: [bool; MAX_OPTIONS],
+    refused: [bool; MAX_OPTIONS],
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy::new()
+    }
+}
+
+impl Policy {
+    pub fn new() -> Policy {
+        Policy {
+            local: [false; MAX_OPTIONS],
+            remote: [false; MAX_OPTIONS],
+            refused: [false; MAX_OPTIONS],
+        }
+    }
+
+    /// Accept performing `option` ourselves when asked with DO
+    pub fn accept_local(mut self, option: Opt) -> Policy {
+        self.local[usize::from(option.as_u8())] = true;
+        self
+    }
+
+    /// Accept the other party performing `option` when offered with WILL
+    pub fn accept_remote(mut self, option: Opt) -> Policy {
+        self.remote[usize::from(option.as_u8())] = true;
+        self
+    }
+
+    /// Never enable `option`, on either side
+    ///
+    /// Requests are refused even if the option was accepted, and the session does not offer or
+    /// request it. Useful to make sure e.g. ENCRYPT or AUTHENTICATION are never used.
+    pub fn refuse(mut self, option: Opt) -> Policy {
+        self.refused[usize::from(option.as_u8())] = true;
+        self
+    }
+
+    /// The default policy of `role`
+    ///
+    /// A client accepts the server echoing and suppressing go ahead, and sends its terminal type.
+    /// A server accepts echoing and suppressing go ahead, and the client sending its terminal
+    /// type and window size. Both acknowledge LOGOUT.
+    pub fn for_role(role: Role) -> Policy {
+        match role {
+            Role::Client => Policy::new()
+                .accept_local(Opt::TTYPE)
+                .accept_remote(Opt::ECHO)
+                .accept_remote(Opt::SGA)
+                .accept_remote(Opt::LOGOUT),
+            Role::Server => Policy::new()
+                .accept_local(Opt::ECHO)
+                .accept_local(Opt::SGA)
+                .accept_remote(Opt::TTYPE)
+                .accept_remote(Opt::NAWS)
+                .accept_local(Opt::LOGOUT),
+        }
+    }
+
+    pub fn is_local_accepted(&self, option: Opt) -> bool {
+        self.local[usize::from(option.as_u8())] && !self.is_refused(option)
+    }
+
+    pub fn is_remote_accepted(&self, option: Opt) -> bool {
+        self.remote[usize::from(option.as_u8())] && !self.is_refused(option)
+    }
+
+    pub fn is_refused(&self, option: Opt) -> bool {
+        self.refused[usize::from(option.as_u8())]
+    }
+}
+
+/// Answers negotiation by writing to the session output
+struct Responder<'a> {
+    policy: &'a Policy,
+    output: &'a mut Vec<u8>,
+}
+
+impl<'a> q::Perform for Responder<'a> {
+    fn send(&mut self, command: Command, option: u8) {
+        encode::negotiate_into(command, Opt::from_u8(option), self.output);
+    }
+
+    fn accept_local(&mut self, option: u8) -> bool {
+        self.policy.is_local_accepted(Opt::from_u8(option))
+    }
+
+    fn accept_remote(&mut self, option: u8) -> bool {
+        self.policy.is_remote_accepted(Opt::from_u8(option))
+    }
+}
+
+/// Parser, negotiator and output buffer for one connection
+pub struct TelnetSession {
+    parser: Parser,
+    negotiator: Negotiator,
+    policy: Policy,
+    role: Option<Role>,
+    terminal_type: Option<Vec<u8>>,
+    ayt_answer: Option<Vec<u8>>,
+    start_tls: StartTls,
+    tls_input: Vec<u8>,
+    stats: Stats,
+    timing: TimingMark,
+    round_trips: Vec<RoundTrip>,
+    handlers: HashMap<Opt, Box<dyn OptionHandler>>,
+    limiter: Option<Limiter>,
+    audit: Option<Box<dyn AuditSink>>,
+    events: Vec<Event>,
+    output: Vec<u8>,
+    read_buf: Box<[u8]>,
+    closed: bool,
+}
+
+impl Default for TelnetSession {
+    fn default() -> TelnetSession {
+        TelnetSession::new(Policy::new())
+    }
+}
+
+impl TelnetSession {
+    pub fn new(policy: Policy) -> TelnetSession {
+        let mut negotiator = Negotiator::new();
+        let mut output = Vec::new();
+        for option in 0..=u8::MAX {
+            if policy.is_refused(Opt::from_u8(option)) {
+                let mut responder = Responder {
+                    policy: &policy,
+                    output: &mut output,
+                };
+                negotiator.refuse(&mut responder, option);
+            }
+        }
+        TelnetSession {
+            parser: Parser::builder()
+                .max_subnegotiation(MAX_SUBNEGOTIATION)
+                .build(),
+            negotiator,
+            policy,
+            role: None,
+            terminal_type: None,
+            ayt_answer: None,
+            start_tls: StartTls::Idle,
+            tls_input: Vec::new(),
+            stats: Stats::new(),
+            timing: TimingMark::new(),
+            round_trips: Vec::new(),
+            handlers: HashMap::new(),
+            limiter: None,
+            audit: None,
+            events: Vec::new(),
+            output,
+            read_buf: vec![0u8; READ_BUF_SIZE].into_boxed_slice(),
+            closed: false,
+        }
+    }
+
+    /// Create a session with the default policy of `role`
+    ///
+    /// A server session queues its initial offers and requests as output right away.
+    pub fn with_role(role: Role) -> TelnetSession {
+        let mut session = TelnetSession::new(Policy::for_role(role));
+        session.role = Some(role);
+        if role == Role::Server {
+            // Nothing is negotiated yet, nor refused by the policy
+            let _ = session.offer_local(Opt::ECHO);
+            let _ = session.offer_local(Opt::SGA);
+            let _ = session.request_remote(Opt::TTYPE);
+        }
+        session
+    }
+
+    /// Create a session of `role` with the parser and policy of `profile`
+    ///
+    /// A server session queues its initial offers and requests as output right away, see
+    /// [`Profile::options`].
+    ///
+    /// [`Profile::options`]: ../profile/enum.Profile.html#method.options
+    pub fn with_profile(profile: Profile, role: Role) -> TelnetSession {
+        let mut session = TelnetSession::new(profile.policy(role)).parser(profile.parser().build());
+        session.role = Some(role);
+        if role == Role::Server {
+            for option in profile.options() {
+                // Options not accepted are skipped, so nothing is refused or negotiating yet
+                if session.policy.is_local_accepted(*option) {
+                    let _ = session.offer_local(*option);
+                }
+                if session.policy.is_remote_accepted(*option) {
+                    let _ = session.request_remote(*option);
+                }
+            }
+        }
+        session
+    }
+
+    /// Set the terminal type a client sends when the server asks for it
+    pub fn terminal_type(mut self, name: &[u8]) -> TelnetSession {
+        self.terminal_type = Some(name.to_vec());
+        self
+    }
+
+    /// Answer `IAC AYT` with `message`
+    ///
+    /// The message is followed by `IAC GA`, or by `IAC NOP` once go ahead is suppressed, so the
+    /// other party sees where the answer ends. Without an answer AYT is only returned as an event.
+    pub fn answer_ayt(mut self, message: &[u8]) -> TelnetSession {
+        self.ayt_answer = Some(message.to_vec());
+        self
+    }
+
+    /// Parse with `parser`, e.g. one built with a larger subnegotiation buffer
+    pub fn parser(mut self, parser: Parser) -> TelnetSession {
+        self.parser = parser;
+        self
+    }
+
+    /// Check received events against `limits`, see [`limits`](../limits/index.html)
+    pub fn limits(mut self, limits: Limits) -> TelnetSession {
+        self.limiter = Some(Limiter::new(limits));
+        self
+    }
+
+    /// Let `handler` handle `option` from now on, replacing any previous handler
+    ///
+    /// A handler replaces what the session does with subnegotiations of its option, e.g.
+    /// answering TTYPE with the terminal type of the session. It only gets the subnegotiations
+    /// while the option is enabled on either side.
+    pub fn register_handler<H>(&mut self, option: Opt, handler: H)
+    where
+        H: OptionHandler + 'static,
+    {
+        self.handlers.insert(option, Box::new(handler));
+    }
+
+    /// Record security relevant actions with `sink`, replacing any previous sink
+    ///
+    /// See [`audit`](../audit/index.html) for what is recorded.
+    pub fn register_audit_sink<S>(&mut self, sink: S)
+    where
+        S: AuditSink + 'static,
+    {
+        self.audit = Some(Box::new(sink));
+    }
+
+    pub fn role(&self) -> Option<Role> {
+        self.role
+    }
+
+    /// Feed bytes received from the other party
+    ///
+    /// Negotiation is answered according to the policy, with the answers buffered as output.
+    /// Returns all events found in `bytes`, negotiation included. Subnegotiations which did not
+    /// fit the subnegotiation buffer of the parser are left out.
+    ///
+    /// Once a START_TLS handshake is [`Ready`], bytes are kept for the TLS layer instead.
+    ///
+    /// [`Ready`]: enum.StartTls.html#variant.Ready
+    pub fn feed(&mut self, bytes: &[u8]) -> vec::Drain<'_, Event> {
+        if self.start_tls == StartTls::Ready {
+            self.tls_input.extend_from_slice(bytes);
+            return self.events.drain(..);
+        }
+
+        self.stats.count_in(bytes.len());
+        let mut rest = bytes;
+        let mut handled = 0;
+        loop {
+            let parsed = self.advance(rest);
+            rest = &rest[parsed..];
+            if rest.is_empty() {
+                self.parser.flush(&mut self.events);
+            }
+            self.handle_events(handled);
+            handled = self.events.len();
+            if rest.is_empty() || self.start_tls == StartTls::Ready {
+                break;
+            }
+        }
+        self.tls_input.extend_from_slice(rest);
+        self.events.drain(..)
+    }
+
+    /// Act on the events from index `from` on
+    ///
+    /// An [`Event::LogoutRequested`] is added after the events when the other party asks to log
+    /// out with `DO LOGOUT`, or announces it will with `WILL LOGOUT`. An [`Event::EndOfTurn`] is
+    /// added after each GA while the other party does not suppress go ahead. With limits, events
+    /// exceeding them are replaced by an [`Event::ProtocolAbuse`] or dropped first. A STATUS
+    /// report is followed by an [`Event::StatusMismatch`] per option it disagrees on.
+    ///
+    /// [`Event::StatusMismatch`]: ../event/enum.Event.html#variant.StatusMismatch
+    /// [`Event::ProtocolAbuse`]: ../event/enum.Event.html#variant.ProtocolAbuse
+    /// [`Event::LogoutRequested`]: ../event/enum.Event.html#variant.LogoutRequested
+    /// [`Event::EndOfTurn`]: ../event/enum.Event.html#variant.EndOfTurn
+    fn handle_events(&mut self, from: usize) {
+        if let Some(limiter) = &mut self.limiter {
+            limiter.filter(&mut self.events, from);
+        }
+        let mut logout = false;
+        let mut inserts = Vec::new();
+        for (i, event) in self.events.iter().enumerate().skip(from) {
+            self.stats.observe(event);
+            if let Event::Subnegotiate(option, params) = event {
+                if let Some(event) = AuditEvent::from_subnegotiation(*option, params) {
+                    audit(&mut self.audit, event);
+                }
+            }
+            match event {
+                Event::Negotiate(command, option) => {
+                    if let Some(round_trip) = self.timing.answer(*command, *option) {
+                        self.stats.count_round_trip(round_trip.rtt);
+                        self.round_trips.push(round_trip);
+                        continue;
+                    }
+                    let state = match *command {
+                        Command::DO => Some(self.negotiator.local_state(option.as_u8())),
+                        Command::WILL => Some(self.negotiator.remote_state(option.as_u8())),
+                        _ => None,
+                    };
+                    // Not an answer to our own offer or request
+                    logout |= *option == Opt::LOGOUT && state == Some(OptionState::No);
+                    let echo_hint = self.echo_hint();
+                    let was_enabled = self.is_remote_enabled(*option);
+                    let was_local_enabled = self.is_local_enabled(*option);
+                    let mut responder = Responder {
+                        policy: &self.policy,
+                        output: &mut self.output,
+                    };
+                    if let Err(err) = self
+                        .negotiator
+                        .recv(&mut responder, *command, option.as_u8())
+                    {
+                        debug!("negotiation of {} failed: {:?}", option, err);
+                    }
+                    if *option == Opt::BINARY {
+                        self.parser.set_binary(
+                            self.is_remote_enabled(Opt::BINARY),
+                            self.is_local_enabled(Opt::BINARY),
+                        );
+                    }
+                    let local_enabled =
+                        self.negotiator.local_state(option.as_u8()) == OptionState::Yes;
+                    let remote_enabled =
+                        self.negotiator.remote_state(option.as_u8()) == OptionState::Yes;
+                    for (was, is, side) in [
+                        (was_local_enabled, local_enabled, Side::Local),
+                        (was_enabled, remote_enabled, Side::Remote),
+                    ] {
+                        match (was, is) {
+                            (false, true) => {
+                                audit(&mut self.audit, AuditEvent::Enabled(*option, side))
+                            }
+                            (true, false) => {
+                                audit(&mut self.audit, AuditEvent::Disabled(*option, side))
+                            }
+                            _ => (),
+                        }
+                    }
+                    if self.echo_hint() != echo_hint {
+                        inserts.push((i + 1, Event::EchoChanged(self.echo_hint())));
+                    }
+                    if let Some(handler) = self.handlers.get_mut(option) {
+                        let mut reply = Reply::new(*option, &mut self.output);
+                        match (was_local_enabled, local_enabled) {
+                            (false, true) => handler.on_local_enable(&mut reply),
+                            (true, false) => handler.on_local_disable(&mut reply),
+                            _ => (),
+                        }
+                        match (was_enabled, remote_enabled) {
+                            (false, true) => handler.on_remote_enable(&mut reply),
+                            (true, false) => handler.on_remote_disable(&mut reply),
+                            _ => (),
+                        }
+                    }
+                    if was_enabled || !self.is_remote_enabled(*option) {
+                        continue;
+                    }
+                    if self.role == Some(Role::Server) && *option == Opt::TTYPE {
+                        encode::subnegotiate_into(Opt::TTYPE, &[TTYPE_SEND], &mut self.output);
+                    }
+                    if *option == Opt::START_TLS && self.start_tls == StartTls::Idle {
+                        encode::subnegotiate_into(
+                            Opt::START_TLS,
+                            &[START_TLS_FOLLOWS],
+                            &mut self.output,
+                        );
+                        self.start_tls = StartTls::Follows;
+                        audit(&mut self.audit, AuditEvent::StartTls(StartTls::Follows));
+                    }
+                }
+                Event::Command(Command::GA) if !self.is_remote_enabled(Opt::SGA) => {
+                    inserts.push((i + 1, Event::EndOfTurn));
+                }
+                Event::Command(Command::AYT) => {
+                    if let Some(message) = &self.ayt_answer {
+                        encode::escape_into(message, &mut self.output);
+                        let end = if self.is_local_enabled(Opt::SGA) {
+                            Command::NOP
+                        } else {
+                            Command::GA
+                        };
+                        encode::command_into(end, &mut self.output);
+                    }
+                }
+                Event::Subnegotiate(option, params) if self.handlers.contains_key(option) => {
+                    // A refused option has nothing to answer
+                    if !self.is_local_enabled(*option) && !self.is_remote_enabled(*option) {
+                        debug!("ignoring subnegotiation of disabled option {}", option);
+                    } else if let Some(handler) = self.handlers.get_mut(option) {
+                        handler
+                            .on_subnegotiation(params, &mut Reply::new(*option, &mut self.output));
+                    }
+                }
+                Event::Subnegotiate(Opt::STATUS, params) if self.is_remote_enabled(Opt::STATUS) => {
+                    if let Some(negotiations) = status::decode(params) {
+                        for discrepancy in status::verify(&self.negotiator, &negotiations) {
+                            inserts.push((i + 1, Event::StatusMismatch(discrepancy)));
+                        }
+                    }
+                }
+                Event::Subnegotiate(Opt::TTYPE, params)
+                    if params.as_slice() == [TTYPE_SEND] && self.is_local_enabled(Opt::TTYPE) =>
+                {
+                    if let Some(name) = &self.terminal_type {
+                        let mut params = vec![TTYPE_IS];
+                        params.extend_from_slice(name);
+                        encode::subnegotiate_into(Opt::TTYPE, &params, &mut self.output);
+                    }
+                }
+                Event::Subnegotiate(Opt::START_TLS, params)
+                    if params.as_slice() == [START_TLS_FOLLOWS]
+                        && (self.start_tls == StartTls::Follows
+                            || self.start_tls == StartTls::Idle
+                                && self.is_local_enabled(Opt::START_TLS)) =>
+                {
+                    if self.start_tls == StartTls::Idle {
+                        encode::subnegotiate_into(
+                            Opt::START_TLS,
+                            &[START_TLS_FOLLOWS],
+                            &mut self.output,
+                        );
+                    }
+                    self.start_tls = StartTls::Ready;
+                    audit(&mut self.audit, AuditEvent::StartTls(StartTls::Ready));
+                }
+                _ => (),
+            }
+        }
+        for (i, event) in inserts.into_iter().rev() {
+            self.events.insert(i, event);
+        }
+        if logout {
+            self.events.push(Event::LogoutRequested);
+            audit(&mut self.audit, AuditEvent::LogoutRequested);
+        }
+    }
+
+    /// Parse `bytes` up to the first event which must be handled before parsing on, returning
+    /// how many bytes were parsed
+    ///
+    /// Negotiation of BINARY changes how data is parsed. While START_TLS is negotiated, a FOLLOWS
+    /// from the other party ends the telnet stream, as what follows belongs to the TLS handshake.
+    fn advance(&mut self, bytes: &[u8]) -> usize {
+        let negotiating = match self.start_tls {
+            StartTls::Idle => self.is_local_enabled(Opt::START_TLS),
+            StartTls::Follows | StartTls::Ready => true,
+            StartTls::Done => false,
+        };
+        // A sequence split between reads is finished byte by byte, as its start is not in bytes
+        let mut i = 0;
+        while i < bytes.len() && (negotiating || !self.parser.is_ground()) {
+            let before = self.events.len();
+            self.parser
+                .advance(&mut Collector::new(&mut self.events), bytes[i]);
+            i += 1;
+            if self.events.len() > before && is_stop_event(&self.events[before]) {
+                return i;
+            }
+            if !negotiating && self.parser.is_ground() {
+                break;
+            }
+        }
+
+        let end = bytes[i..]
+            .windows(3)
+            .position(|w| w[0] == 255 && (251..=254).contains(&w[1]) && w[2] == 0)
+            .map_or(bytes.len(), |pos| i + pos + 3);
+        self.parser
+            .advance_bytes(&mut Collector::new(&mut self.events), &bytes[i..end]);
+        end
+    }
+
+    /// Progress of the START_TLS handshake
+    pub fn start_tls(&self) -> StartTls {
+        self.start_tls
+    }
+
+    /// Take the bytes received after the START_TLS hand-off and resume parsing
+    ///
+    /// The returned bytes are the start of the TLS handshake. Bytes fed afterwards must be the
+    /// decrypted stream, in which START_TLS is no longer negotiated.
+    pub fn take_tls_input(&mut self) -> Vec<u8> {
+        if self.start_tls == StartTls::Ready {
+            self.start_tls = StartTls::Done;
+        }
+        std::mem::take(&mut self.tls_input)
+    }
+
+    /// Offer to perform `option` ourselves
+    pub fn offer_local(&mut self, option: Opt) -> Result<(), q::NegotiatorError> {
+        let mut responder = Responder {
+            policy: &self.policy,
+            output: &mut self.output,
+        };
+        self.negotiator.offer(&mut responder, option.as_u8())
+    }
+
+    /// Stop performing `option` ourselves
+    pub fn withdraw_local(&mut self, option: Opt) -> Result<(), q::NegotiatorError> {
+        let mut responder = Responder {
+            policy: &self.policy,
+            output: &mut self.output,
+        };
+        self.negotiator.withdraw(&mut responder, option.as_u8())
+    }
+
+    /// Ask the other party to perform `option`
+    pub fn request_remote(&mut self, option: Opt) -> Result<(), q::NegotiatorError> {
+        let mut responder = Responder {
+            policy: &self.policy,
+            output: &mut self.output,
+        };
+        self.negotiator.enable(&mut responder, option.as_u8())
+    }
+
+    /// Ask the other party to stop performing `option`
+    pub fn refuse_remote(&mut self, option: Opt) -> Result<(), q::NegotiatorError> {
+        let mut responder = Responder {
+            policy: &self.policy,
+            output: &mut self.output,
+        };
+        self.negotiator.disable(&mut responder, option.as_u8())
+    }
+
+    /// Returns true if we are performing `option`
+    pub fn is_local_enabled(&self, option: Opt) -> bool {
+        self.negotiator.local_state(option.as_u8()) == OptionState::Yes
+    }
+
+    /// Returns true if the other party is performing `option`
+    pub fn is_remote_enabled(&self, option: Opt) -> bool {
+        self.negotiator.remote_state(option.as_u8()) == OptionState::Yes
+    }
+
+    /// Queue data for the other party, escaping IAC bytes
+    pub fn send_data(&mut self, data: &[u8]) {
+        encode::escape_into(data, &mut self.output);
+    }
+
+    /// Queue text for the other party, with the line endings of the network virtual terminal
+    /// unless we transmit binary
+    ///
+    /// See [`encode::text_into`](../encode/fn.text_into.html).
+    pub fn send_text(&mut self, text: &[u8]) {
+        encode::text_into(text, self.is_local_enabled(Opt::BINARY), &mut self.output);
+    }
+
+    /// Whether TRANSMIT-BINARY is enabled for the other party and for us
+    pub fn binary(&self) -> (bool, bool) {
+        (
+            self.is_remote_enabled(Opt::BINARY),
+            self.is_local_enabled(Opt::BINARY),
+        )
+    }
+
+    /// Whether the connection is half-duplex, go ahead not being suppressed in both directions
+    ///
+    /// The other party then ends its turns with GA, see [`Event::EndOfTurn`], and expects us to
+    /// end ours with [`end_of_turn`].
+    ///
+    /// [`Event::EndOfTurn`]: ../event/enum.Event.html#variant.EndOfTurn
+    /// [`end_of_turn`]: #method.end_of_turn
+    pub fn is_half_duplex(&self) -> bool {
+        !(self.is_local_enabled(Opt::SGA) && self.is_remote_enabled(Opt::SGA))
+    }
+
+    /// How a client should show what the user types, following ECHO and SGA of the server
+    ///
+    /// Each change is also added to the events as [`Event::EchoChanged`], right after the
+    /// negotiation causing it, so data received before it was meant for the previous hint.
+    ///
+    /// [`Event::EchoChanged`]: ../event/enum.Event.html#variant.EchoChanged
+    pub fn echo_hint(&self) -> EchoHint {
+        EchoHint::new(
+            self.is_remote_enabled(Opt::ECHO),
+            self.is_remote_enabled(Opt::SGA),
+        )
+    }
+
+    /// Hand the turn to the other party with `IAC GA`, unless we suppress go ahead
+    pub fn end_of_turn(&mut self) {
+        if !self.is_local_enabled(Opt::SGA) {
+            encode::end_of_turn_into(&mut self.output);
+        }
+    }
+
+    /// Queue an event for the other party
+    pub fn send(&mut self, event: &Event) {
+        event.encode_into(&mut self.output);
+    }
+
+    /// Bytes waiting to be sent to the other party
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Remove the first `n` bytes of output, after they have been sent
+    ///
+    /// `n` is capped to the length of the output, so consuming more than is waiting empties it.
+    pub fn consume_output(&mut self, n: usize) {
+        let n = n.min(self.output.len());
+        self.stats.count_out(n);
+        self.output.drain(..n);
+    }
+
+    /// Take all bytes waiting to be sent to the other party
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.stats.count_out(self.output.len());
+        std::mem::take(&mut self.output)
+    }
+
+    /// Snapshot of the statistics of the session
+    ///
+    /// Output is counted once it is taken or consumed.
+    pub fn stats(&self) -> Stats {
+        self.stats.clone()
+    }
+
+    /// Perform a single read from `reader` and return the events found
+    ///
+    /// Errors from the reader are returned as is, including `WouldBlock`. When the reader reaches
+    /// end of file no events are returned and [`is_closed`] returns true.
+    ///
+    /// [`is_closed`]: #method.is_closed
+    pub fn read_from<R: Read>(&mut self, reader: &mut R) -> io::Result<Vec<Event>> {
+        let n = reader.read(&mut self.read_buf)?;
+        if n == 0 {
+            self.closed = true;
+            return Ok(Vec::new());
+        }
+        // The read buffer is moved out while feeding, as feed borrows the whole session
+        let read_buf = std::mem::take(&mut self.read_buf);
+        let events = self.feed(&read_buf[..n]).collect();
+        self.read_buf = read_buf;
+        Ok(events)
+    }
+
+    /// Perform a single write of pending output to `writer`, returning the number of bytes
+    /// written
+    pub fn write_to<W: Write>(&mut self, writer: &mut W) -> io::Result<usize> {
+        if self.output.is_empty() {
+            return Ok(0);
+        }
+        let n = writer.write(&self.output)?;
+        self.consume_output(n);
+        Ok(n)
+    }
+
+    /// Returns true if there is output waiting to be written
+    pub fn wants_write(&self) -> bool {
+        !self.output.is_empty()
+    }
+
+    /// Ask the other party for its view of the options with `IAC SB STATUS SEND IAC SE`
+    ///
+    /// Only answered once the other party performs STATUS. Options it sees differently are added
+    /// to the events after its report as [`Event::StatusMismatch`].
+    ///
+    /// [`Event::StatusMismatch`]: ../event/enum.Event.html#variant.StatusMismatch
+    pub fn request_status(&mut self) {
+        status::send_into(&mut self.output);
+    }
+
+    /// Send `IAC DO TIMING-MARK` to measure the round-trip time
+    ///
+    /// The answer is not negotiated, it is taken by [`take_round_trips`] instead. Time is
+    /// measured with [`tick`].
+    ///
+    /// [`take_round_trips`]: #method.take_round_trips
+    /// [`tick`]: #method.tick
+    pub fn request_timing_mark(&mut self) {
+        self.timing.request(&mut self.output);
+    }
+
+    /// Tell the session that `elapsed` passed, for the timing marks and the limits
+    pub fn tick(&mut self, elapsed: Duration) {
+        self.timing.tick(elapsed);
+        if let Some(limiter) = &mut self.limiter {
+            limiter.tick(elapsed);
+        }
+    }
+
+    /// Take the timing marks answered so far
+    pub fn take_round_trips(&mut self) -> Vec<RoundTrip> {
+        std::mem::take(&mut self.round_trips)
+    }
+
+    /// The incomplete sequence received last, if any, e.g. to tell that the connection closed
+    /// during negotiation
+    pub fn pending(&self) -> Option<Pending> {
+        self.parser.pending()
+    }
+
+    /// Returns true once a read has reached end of file
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Policy, Role, StartTls, TelnetSession};
+    use crate::command::Command;
+    use crate::echo::EchoHint;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::timing::RoundTrip;
+    use crate::{CrHandling, Overflow, Parser, ParserBuilder};
+
+    #[test]
+    fn answers_negotiation() {
+        let policy = Policy::new().accept_local(Opt::TTYPE);
+        let mut session = TelnetSession::new(policy);
+
+        let events: Vec<Event> = session
+            .feed(&[255, 253, 24, 255, 253, 31, b'h', b'i'])
+            .collect();
+        assert_eq!(
+            events,
+            &[
+                Event::Negotiate(Command::DO, Opt::TTYPE),
+                Event::Negotiate(Command::DO, Opt::NAWS),
+                Event::Data(b"hi".to_vec()),
+            ]
+        );
+        assert_eq!(session.take_output(), &[255, 251, 24, 255, 252, 31]);
+        assert!(session.is_local_enabled(Opt::TTYPE));
+        assert!(!session.is_local_enabled(Opt::NAWS));
+
+        let stats = session.stats();
+        assert_eq!(stats.bytes_in(), 8);
+        assert_eq!(stats.bytes_out(), 6);
+        assert_eq!(stats.commands(Command::DO), 2);
+    }
+
+    #[test]
+    fn aborted_subnegotiation_is_forgotten() {
+        let parser = ParserBuilder::new()
+            .max_subnegotiation(4)
+            .overflow(Overflow::Abort)
+            .build();
+        let mut session = TelnetSession::new(Policy::new()).parser(parser);
+        let events: Vec<Event> = session
+            .feed(&[
+                255, 250, 24, 0, 1, 2, 3, 4, 255, 240, 255, 250, 24, 0, b'x', 255, 240,
+            ])
+            .collect();
+        assert_eq!(events, &[Event::Subnegotiate(Opt::TTYPE, vec![0, b'x'])]);
+    }
+
+    #[test]
+    fn consume_more_output_than_waiting() {
+        let mut session = TelnetSession::new(Policy::new());
+        session.send(&Event::Command(Command::NOP));
+        session.consume_output(1);
+        assert_eq!(session.output(), &[241]);
+        session.consume_output(10);
+        assert!(session.output().is_empty());
+        assert_eq!(session.stats().bytes_out(), 2);
+    }
+
+    #[test]
+    fn refused_options() {
+        let policy = Policy::new()
+            .accept_local(Opt::ENCRYPT)
+            .accept_remote(Opt::ENCRYPT)
+            .refuse(Opt::ENCRYPT);
+        let mut session = TelnetSession::new(policy);
+        assert!(session.output().is_empty());
+
+        session.feed(&[255, 251, 38, 255, 253, 38]).for_each(drop);
+        assert_eq!(session.take_output(), &[255, 254, 38, 255, 252, 38]);
+        assert!(session.request_remote(Opt::ENCRYPT).is_err());
+        assert!(session.offer_local(Opt::ENCRYPT).is_err());
+        assert!(session.output().is_empty());
+    }
+
+    #[test]
+    fn timing_mark() {
+        let mut session = TelnetSession::default();
+        session.request_timing_mark();
+        assert_eq!(session.take_output(), &[255, 253, 6]);
+        session.tick(Duration::from_millis(80));
+
+        // The answer is not taken for an offer
+        session.feed(&[255, 251, 6]).for_each(drop);
+        assert!(session.output().is_empty());
+        assert!(!session.is_remote_enabled(Opt::TM));
+        assert_eq!(
+            session.take_round_trips(),
+            &[RoundTrip {
+                rtt: Duration::from_millis(80),
+                acknowledged: true
+            }]
+        );
+
+        session.feed(&[255, 251, 6]).for_each(drop);
+        assert_eq!(session.take_output(), &[255, 254, 6]);
+        assert!(session.take_round_trips().is_empty());
+    }
+
+    #[test]
+    fn answers_ayt() {
+        let mut session = TelnetSession::default();
+        session.feed(&[255, 246]).for_each(drop);
+        assert!(session.output().is_empty());
+
+        let policy = Policy::new().accept_local(Opt::SGA);
+        let mut session = TelnetSession::new(policy).answer_ayt(b"[yes]");
+        let events: Vec<Event> = session.feed(&[255, 246]).collect();
+        assert_eq!(events, &[Event::Command(Command::AYT)]);
+        assert_eq!(session.take_output(), b"[yes]\xff\xf9");
+
+        session.feed(&[255, 253, 3, 255, 246]).for_each(drop);
+        assert_eq!(session.take_output(), b"\xff\xfb\x03[yes]\xff\xf1");
+    }
+
+    #[test]
+    fn logout() {
+        let mut server = TelnetSession::with_role(Role::Server);
+        server.take_output();
+        let events: Vec<Event> = server.feed(&[255, 253, 18]).collect();
+        assert_eq!(
+            events,
+            &[
+                Event::Negotiate(Command::DO, Opt::LOGOUT),
+                Event::LogoutRequested,
+            ]
+        );
+        assert_eq!(server.take_output(), &[255, 251, 18]);
+
+        // The answer to our own request is not a request
+        let mut client = TelnetSession::with_role(Role::Client);
+        client.request_remote(Opt::LOGOUT).unwrap();
+        let events: Vec<Event> = client.feed(&[255, 251, 18]).collect();
+        assert_eq!(events, &[Event::Negotiate(Command::WILL, Opt::LOGOUT)]);
+    }
+
+    #[test]
+    fn read_and_write() {
+        let mut session = TelnetSession::default();
+        let mut input: &[u8] = &[255, 253, 31, b'>'];
+
+        let events = session.read_from(&mut input).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(session.wants_write());
+        assert!(!session.is_closed());
+
+        let mut output = Vec::new();
+        assert_eq!(session.write_to(&mut output).unwrap(), 3);
+        assert_eq!(output, &[255, 252, 31]);
+        assert!(!session.wants_write());
+
+        assert!(session.read_from(&mut input).unwrap().is_empty());
+        assert!(session.is_closed());
+    }
+
+    #[test]
+    fn request_remote() {
+        let mut session = TelnetSession::default();
+        assert!(session.request_remote(Opt::ECHO).is_ok());
+        assert_eq!(session.take_output(), &[255, 253, 1]);
+
+        session.feed(&[255, 251, 1]).for_each(drop);
+        assert!(session.output().is_empty());
+        assert!(session.is_remote_enabled(Opt::ECHO));
+    }
+
+    #[test]
+    fn client_against_server() {
+        let mut server = TelnetSession::with_role(Role::Server);
+        let mut client = TelnetSession::with_role(Role::Client).terminal_type(b"xterm-256color");
+
+        // Pass output back and forth until both sides are quiet
+        let mut terminal_type = None;
+        while server.wants_write() || client.wants_write() {
+            let to_client = server.take_output();
+            client.feed(&to_client).for_each(drop);
+            let to_server = client.take_output();
+            for event in server.feed(&to_server) {
+                if let Event::Subnegotiate(Opt::TTYPE, params) = event {
+                    terminal_type = Some(params);
+                }
+            }
+        }
+
+        assert!(server.is_local_enabled(Opt::ECHO));
+        assert!(server.is_local_enabled(Opt::SGA));
+        assert!(server.is_remote_enabled(Opt::TTYPE));
+        assert!(client.is_remote_enabled(Opt::ECHO));
+        assert!(client.is_remote_enabled(Opt::SGA));
+        assert!(client.is_local_enabled(Opt::TTYPE));
+        assert_eq!(terminal_type.unwrap(), b"\0xterm-256color");
+    }
+
+    #[test]
+    fn start_tls_handoff() {
+        let mut server = TelnetSession::default();
+        let mut client = TelnetSession::new(Policy::new().accept_local(Opt::START_TLS));
+
+        server.request_remote(Opt::START_TLS).unwrap();
+        client.feed(&server.take_output()).for_each(drop);
+        assert_eq!(client.output(), &[255, 251, 46]);
+
+        server.feed(&client.take_output()).for_each(drop);
+        assert_eq!(server.start_tls(), StartTls::Follows);
+        assert_eq!(server.output(), &[255, 250, 46, 1, 255, 240]);
+
+        client.feed(&server.take_output()).for_each(drop);
+        assert_eq!(client.start_tls(), StartTls::Ready);
+        assert_eq!(client.take_output(), &[255, 250, 46, 1, 255, 240]);
+
+        // The client hello arrives right behind the FOLLOWS and must not be parsed
+        let mut input = vec![255, 250, 46, 1, 255, 240];
+        input.extend_from_slice(&[0x16, 0x03, 0xff, 0xff]);
+        assert_eq!(server.feed(&input).count(), 1);
+        assert_eq!(server.start_tls(), StartTls::Ready);
+        assert!(server.output().is_empty());
+        assert_eq!(server.take_tls_input(), &[0x16, 0x03, 0xff, 0xff]);
+        assert_eq!(server.start_tls(), StartTls::Done);
+
+        assert_eq!(
+            server.feed(b"hi").collect::<Vec<_>>(),
+            &[Event::Data(b"hi".to_vec())]
+        );
+
+        // Another FOLLOWS within the encrypted stream doesn't start a second hand-off
+        assert!(client.take_tls_input().is_empty());
+        let mut input = vec![255, 250, 46, 1, 255, 240];
+        input.extend_from_slice(b"hi");
+        assert_eq!(
+            client.feed(&input).collect::<Vec<_>>(),
+            &[
+                Event::Subnegotiate(Opt::START_TLS, vec![1]),
+                Event::Data(b"hi".to_vec())
+            ]
+        );
+        assert_eq!(client.start_tls(), StartTls::Done);
+        assert!(client.output().is_empty());
+        assert!(client.take_tls_input().is_empty());
+    }
+
+    #[test]
+    fn binary_data() {
+        let mut session = TelnetSession::new(Policy::new().accept_remote(Opt::BINARY));
+
+        // High bytes right behind WILL BINARY are data, also when split between reads
+        let mut events: Vec<Event> = session.feed(&[0xe4, 255, 251]).collect();
+        events.extend(session.feed(&[0, 0xc3, 0xa4, b'!']));
+        assert_eq!(
+            events,
+            &[
+                Event::Execute(0xe4),
+                Event::Negotiate(Command::WILL, Opt::BINARY),
+                Event::Data(vec![0xc3, 0xa4, b'!']),
+            ]
+        );
+
+        let events: Vec<Event> = session.feed(&[255, 252, 0, 0xe4]).collect();
+        assert_eq!(events[1], Event::Execute(0xe4));
+    }
+
+    #[test]
+    fn turns() {
+        let policy = Policy::new().accept_local(Opt::SGA).accept_remote(Opt::SGA);
+        let mut session = TelnetSession::new(policy);
+        assert!(session.is_half_duplex());
+        let events: Vec<Event> = session.feed(b"a\xff\xf9b\xff\xf9").collect();
+        assert_eq!(
+            events,
+            &[
+                Event::Data(b"a".to_vec()),
+                Event::Command(Command::GA),
+                Event::EndOfTurn,
+                Event::Data(b"b".to_vec()),
+                Event::Command(Command::GA),
+                Event::EndOfTurn,
+            ]
+        );
+        session.end_of_turn();
+        assert_eq!(session.take_output(), &[255, 249]);
+
+        // Go ahead suppressed by the other party only
+        session.feed(&[255, 251, 3]).for_each(drop);
+        session.take_output();
+        assert!(session.is_half_duplex());
+        let events: Vec<Event> = session.feed(&[255, 249]).collect();
+        assert_eq!(events, &[Event::Command(Command::GA)]);
+        session.end_of_turn();
+        assert_eq!(session.take_output(), &[255, 249]);
+
+        // And by us
+        session.feed(&[255, 253, 3]).for_each(drop);
+        session.take_output();
+        assert!(!session.is_half_duplex());
+        session.end_of_turn();
+        assert!(session.take_output().is_empty());
+    }
+
+    #[test]
+    fn echo_hint() {
+        let mut session = TelnetSession::with_role(Role::Client);
+        assert_eq!(session.echo_hint(), EchoHint::Local);
+
+        // The prompt and the text after the password are shown as usual
+        let events: Vec<Event> = session
+            .feed(b"Password: \xff\xfb\x01\r\n\xff\xfc\x01Welcome")
+            .collect();
+        assert_eq!(
+            events,
+            &[
+                Event::Data(b"Password: ".to_vec()),
+                Event::Negotiate(Command::WILL, Opt::ECHO),
+                Event::EchoChanged(EchoHint::Masked),
+                Event::Execute(b'\r'),
+                Event::Execute(b'\n'),
+                Event::Negotiate(Command::WONT, Opt::ECHO),
+                Event::EchoChanged(EchoHint::Local),
+                Event::Data(b"Welcome".to_vec()),
+            ]
+        );
+        assert_eq!(session.echo_hint(), EchoHint::Local);
+
+        // Repeated negotiations change nothing
+        session.feed(b"\xff\xfb\x03\xff\xfb\x01").for_each(drop);
+        assert_eq!(session.echo_hint(), EchoHint::Remote);
+        let events: Vec<Event> = session.feed(b"\xff\xfb\x01").collect();
+        assert_eq!(events, &[Event::Negotiate(Command::WILL, Opt::ECHO)]);
+    }
+
+    #[test]
+    fn binary_per_direction() {
+        let policy = Policy::new()
+            .accept_local(Opt::BINARY)
+            .accept_remote(Opt::BINARY);
+        let parser = Parser::builder().cr_handling(CrHandling::StripNul).build();
+        let mut session = TelnetSession::new(policy).parser(parser);
+
+        // We transmit binary, the other party doesn't
+        session.feed(&[255, 253, 0]).for_each(drop);
+        session.take_output();
+        assert_eq!(session.binary(), (false, true));
+        session.send_text(b"a\nb");
+        assert_eq!(session.take_output(), b"a\nb");
+        let events: Vec<Event> = session.feed(b"\r\0\xe4").collect();
+        assert_eq!(events, &[Event::Execute(b'\r'), Event::Execute(0xe4)]);
+
+        // And the other way around
+        session.feed(&[255, 254, 0, 255, 251, 0]).for_each(drop);
+        session.take_output();
+        assert_eq!(session.binary(), (true, false));
+        session.send_text(b"a\nb");
+        assert_eq!(session.take_output(), b"a\r\nb");
+        let events: Vec<Event> = session.feed(b"\r\0\xe4").collect();
+        assert_eq!(
+            events,
+            &[
+                Event::Execute(b'\r'),
+                Event::Execute(0),
+                Event::Data(vec![0xe4])
+            ]
+        );
+    }
+
+    #[test]
+    fn option_handlers() {
+        use crate::handler::{TerminalType, WindowSize};
+
+        let policy = Policy::new()
+            .accept_local(Opt::TTYPE)
+            .accept_local(Opt::NAWS);
+        let mut session = TelnetSession::new(policy).terminal_type(b"IGNORED");
+        session.register_handler(Opt::TTYPE, TerminalType::new(&[b"XTERM"]));
+        session.register_handler(Opt::NAWS, WindowSize::new(80, 24));
+
+        session
+            .feed(&[255, 253, 31, 255, 253, 24, 255, 250, 24, 1, 255, 240])
+            .for_each(drop);
+        let mut expected = vec![255, 251, 31];
+        expected.extend_from_slice(&[255, 250, 31, 0, 80, 0, 24, 255, 240]);
+        expected.extend_from_slice(&[255, 251, 24]);
+        expected.extend_from_slice(&[255, 250, 24, 0, b'X', b'T', b'E', b'R', b'M', 255, 240]);
+        assert_eq!(session.take_output(), expected);
+    }
+
+    #[test]
+    fn option_handlers_need_enabled_option() {
+        use crate::handler::TerminalType;
+
+        let mut session = TelnetSession::new(Policy::new());
+        session.register_handler(Opt::TTYPE, TerminalType::new(&[b"XTERM"]));
+
+        // TTYPE is refused, so the request isn't answered
+        session
+            .feed(&[255, 253, 24, 255, 250, 24, 1, 255, 240])
+            .for_each(drop);
+        assert_eq!(session.take_output(), &[255, 252, 24]);
+    }
+}
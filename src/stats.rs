@@ -0,0 +1,342 @@
+//! Session statistics
+//!
+//! [`Stats`] counts bytes and events of a connection. [`TelnetSession`] keeps one up to date, and
+//! [`TelnetSession::stats`] returns a snapshot of it. Other consumers can feed a `Stats` with
+//! [`Stats::observe`].
+//!
+//! With the `metrics` feature, every count is also emitted through the [`metrics`] facade, for
+//! whichever recorder the application installed:
+//!
+//! - `televerknet_bytes_received_total` and `televerknet_bytes_sent_total`, counters
+//! - `televerknet_events_total`, a counter labelled with the `kind` of event, e.g. `negotiate`
+//! - `televerknet_negotiations_total`, a counter labelled with the `command` and `option`
+//! - `televerknet_subnegotiation_bytes`, a histogram of parameter sizes labelled with the
+//!   `option`
+//! - `televerknet_round_trip_seconds`, a histogram of TIMING-MARK round trips
+//!
+//! [`Stats`]: struct.Stats.html
+//! [`metrics`]: https://docs.rs/metrics
+//! [`Stats::observe`]: struct.Stats.html#method.observe
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+//! [`TelnetSession::stats`]: ../session/struct.TelnetSession.html#method.stats
+use std::time::Duration;
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::option::Opt;
+
+/// Counters for one connection
+#[derive(Clone)]
+pub struct Stats {
+    bytes_in: u64,
+    bytes_out: u64,
+    commands: [u64; 256],
+    negotiations: [u64; 256],
+    subnegotiations: u64,
+    subnegotiation_bytes: u64,
+    largest_subnegotiation: usize,
+    round_trips: u64,
+    round_trip_time: Duration,
+    compressed: u64,
+    decompressed: u64,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats::new()
+    }
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            bytes_in: 0,
+            bytes_out: 0,
+            commands: [0; 256],
+            negotiations: [0; 256],
+            subnegotiations: 0,
+            subnegotiation_bytes: 0,
+            largest_subnegotiation: 0,
+            round_trips: 0,
+            round_trip_time: Duration::from_secs(0),
+            compressed: 0,
+            decompressed: 0,
+        }
+    }
+
+    /// Count `n` bytes received
+    pub fn count_in(&mut self, n: usize) {
+        self.bytes_in += n as u64;
+        #[cfg(feature = "metrics")]
+        metrics::counter!("televerknet_bytes_received_total").increment(n as u64);
+    }
+
+    /// Count `n` bytes sent
+    pub fn count_out(&mut self, n: usize) {
+        self.bytes_out += n as u64;
+        #[cfg(feature = "metrics")]
+        metrics::counter!("televerknet_bytes_sent_total").increment(n as u64);
+    }
+
+    /// Count `compressed` bytes received which inflated to `decompressed` bytes, e.g. with MCCP
+    pub fn count_compressed(&mut self, compressed: usize, decompressed: usize) {
+        self.compressed += compressed as u64;
+        self.decompressed += decompressed as u64;
+    }
+
+    /// Count an answered TIMING-MARK, see [`timing`](../timing/index.html)
+    pub fn count_round_trip(&mut self, rtt: Duration) {
+        self.round_trips += 1;
+        self.round_trip_time += rtt;
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("televerknet_round_trip_seconds").record(rtt.as_secs_f64());
+    }
+
+    /// Count a received event
+    pub fn observe(&mut self, event: &Event) {
+        #[cfg(feature = "metrics")]
+        emit(event);
+        match event {
+            Event::Command(command) => self.commands[usize::from(command.as_u8())] += 1,
+            Event::Negotiate(command, option) => {
+                self.commands[usize::from(command.as_u8())] += 1;
+                self.negotiations[usize::from(option.as_u8())] += 1;
+            }
+            Event::Subnegotiate(_, params) => {
+                self.commands[usize::from(Command::SB.as_u8())] += 1;
+                self.subnegotiations += 1;
+                self.subnegotiation_bytes += params.len() as u64;
+                self.largest_subnegotiation = self.largest_subnegotiation.max(params.len());
+            }
+            Event::Data(_)
+            | Event::Execute(_)
+            | Event::LogoutRequested
+            | Event::EndOfTurn
+            | Event::ProtocolAbuse(_)
+            | Event::StatusMismatch(_)
+            | Event::EchoChanged(_) => (),
+        }
+    }
+
+    /// Add the counters of `other`, e.g. to total the statistics of many sessions
+    pub fn merge(&mut self, other: &Stats) {
+        self.bytes_in += other.bytes_in;
+        self.bytes_out += other.bytes_out;
+        for (count, other) in self.commands.iter_mut().zip(other.commands.iter()) {
+            *count += other;
+        }
+        for (count, other) in self.negotiations.iter_mut().zip(other.negotiations.iter()) {
+            *count += other;
+        }
+        self.subnegotiations += other.subnegotiations;
+        self.subnegotiation_bytes += other.subnegotiation_bytes;
+        self.largest_subnegotiation = self
+            .largest_subnegotiation
+            .max(other.largest_subnegotiation);
+        self.round_trips += other.round_trips;
+        self.round_trip_time += other.round_trip_time;
+        self.compressed += other.compressed;
+        self.decompressed += other.decompressed;
+    }
+
+    pub fn bytes_in(&self) -> u64 {
+        self.bytes_in
+    }
+
+    pub fn bytes_out(&self) -> u64 {
+        self.bytes_out
+    }
+
+    /// Number of `command` received, negotiation and subnegotiation included
+    pub fn commands(&self, command: Command) -> u64 {
+        self.commands[usize::from(command.as_u8())]
+    }
+
+    /// Number of WILL, WONT, DO and DONT received for `option`
+    pub fn negotiations(&self, option: Opt) -> u64 {
+        self.negotiations[usize::from(option.as_u8())]
+    }
+
+    pub fn subnegotiations(&self) -> u64 {
+        self.subnegotiations
+    }
+
+    /// Total size of the parameters of all subnegotiations
+    pub fn subnegotiation_bytes(&self) -> u64 {
+        self.subnegotiation_bytes
+    }
+
+    pub fn largest_subnegotiation(&self) -> usize {
+        self.largest_subnegotiation
+    }
+
+    /// Number of answered TIMING-MARKs
+    pub fn round_trips(&self) -> u64 {
+        self.round_trips
+    }
+
+    /// Mean time of the answered TIMING-MARKs, or `None` if none was answered
+    pub fn mean_round_trip(&self) -> Option<Duration> {
+        if self.round_trips == 0 {
+            None
+        } else {
+            Some(self.round_trip_time / self.round_trips as u32)
+        }
+    }
+
+    /// Decompressed size divided by compressed size, or `None` if nothing was compressed
+    pub fn compression_ratio(&self) -> Option<f64> {
+        if self.compressed == 0 {
+            None
+        } else {
+            Some(self.decompressed as f64 / self.compressed as f64)
+        }
+    }
+}
+
+/// Emit the metrics of a received event
+#[cfg(feature = "metrics")]
+fn emit(event: &Event) {
+    fn option_label(option: Opt) -> String {
+        match option.canonical_reason() {
+            Some(name) => name.to_string(),
+            None => option.as_u8().to_string(),
+        }
+    }
+
+    let kind = match event {
+        Event::Data(_) => "data",
+        Event::Execute(_) => "execute",
+        Event::Command(_) => "command",
+        Event::Negotiate(command, option) => {
+            metrics::counter!(
+                "televerknet_negotiations_total",
+                "command" => command.name(),
+                "option" => option_label(*option)
+            )
+            .increment(1);
+            "negotiate"
+        }
+        Event::Subnegotiate(option, params) => {
+            metrics::histogram!(
+                "televerknet_subnegotiation_bytes",
+                "option" => option_label(*option)
+            )
+            .record(params.len() as f64);
+            "subnegotiate"
+        }
+        Event::LogoutRequested => "logout_requested",
+        Event::EndOfTurn => "end_of_turn",
+        Event::ProtocolAbuse(_) => "protocol_abuse",
+        Event::StatusMismatch(_) => "status_mismatch",
+        Event::EchoChanged(_) => "echo_changed",
+    };
+    metrics::counter!("televerknet_events_total", "kind" => kind).increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Stats;
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+
+    #[test]
+    fn counts() {
+        let mut stats = Stats::new();
+        stats.observe(&Event::Negotiate(Command::DO, Opt::NAWS));
+        stats.observe(&Event::Negotiate(Command::DONT, Opt::NAWS));
+        stats.observe(&Event::Subnegotiate(Opt::TTYPE, vec![1]));
+        stats.observe(&Event::Command(Command::GA));
+        stats.count_compressed(10, 40);
+
+        assert_eq!(stats.negotiations(Opt::NAWS), 2);
+        assert_eq!(stats.commands(Command::DO), 1);
+        assert_eq!(stats.commands(Command::GA), 1);
+        assert_eq!(stats.subnegotiations(), 1);
+        assert_eq!(stats.largest_subnegotiation(), 1);
+        assert_eq!(stats.compression_ratio(), Some(4.0));
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn metrics() {
+        use metrics::{
+            Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+            SharedString, Unit,
+        };
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        type Recorded = Arc<Mutex<Vec<(String, f64)>>>;
+
+        struct Handle(String, Recorded);
+        impl CounterFn for Handle {
+            fn increment(&self, value: u64) {
+                self.1.lock().unwrap().push((self.0.clone(), value as f64));
+            }
+            fn absolute(&self, _value: u64) {}
+        }
+        impl HistogramFn for Handle {
+            fn record(&self, value: f64) {
+                self.1.lock().unwrap().push((self.0.clone(), value));
+            }
+        }
+
+        struct TestRecorder(Recorded);
+        impl TestRecorder {
+            fn handle(&self, key: &Key) -> Arc<Handle> {
+                let labels: Vec<String> = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                let name = format!("{}{{{}}}", key.name(), labels.join(","));
+                Arc::new(Handle(name, self.0.clone()))
+            }
+        }
+        impl Recorder for TestRecorder {
+            fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+            fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+                Counter::from_arc(self.handle(key))
+            }
+            fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+                Gauge::noop()
+            }
+            fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+                Histogram::from_arc(self.handle(key))
+            }
+        }
+
+        let recorder = TestRecorder(Recorded::default());
+        metrics::with_local_recorder(&recorder, || {
+            let mut stats = Stats::new();
+            stats.count_in(3);
+            stats.observe(&Event::Negotiate(Command::DO, Opt::NAWS));
+            stats.observe(&Event::Subnegotiate(Opt::from_u8(230), vec![1, 2]));
+            stats.count_round_trip(Duration::from_millis(250));
+            assert_eq!(stats.round_trips(), 1);
+            assert_eq!(stats.mean_round_trip(), Some(Duration::from_millis(250)));
+        });
+        let recorded = recorder.0.lock().unwrap();
+        let recorded: Vec<(&str, f64)> = recorded
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect();
+        assert_eq!(
+            recorded,
+            &[
+                ("televerknet_bytes_received_total{}", 3.0),
+                (
+                    "televerknet_negotiations_total{command=DO,option=NAWS}",
+                    1.0
+                ),
+                ("televerknet_events_total{kind=negotiate}", 1.0),
+                ("televerknet_subnegotiation_bytes{option=230}", 2.0),
+                ("televerknet_events_total{kind=subnegotiate}", 1.0),
+                ("televerknet_round_trip_seconds{}", 0.25),
+            ]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! STATUS as described in [RFC 859]
+//!
+//! The party performing STATUS answers `IAC SB STATUS SEND IAC SE` with its view of the options:
+//! `WILL <option>` for every option it performs and `DO <option>` for every option it agreed the
+//! other party performs. [`decode`] reads that list, and [`verify`] compares it to a
+//! [`Negotiator`] to find options the two parties disagree on. A [`TelnetSession`] does so for
+//! every report it receives, adding an [`Event::StatusMismatch`] per discrepancy.
+//!
+//! [RFC 859]: https://www.rfc-editor.org/rfc/rfc859.html
+//! [`decode`]: fn.decode.html
+//! [`verify`]: fn.verify.html
+//! [`Negotiator`]: ../q/struct.Negotiator.html
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+//! [`Event::StatusMismatch`]: ../event/enum.Event.html#variant.StatusMismatch
+use crate::audit::Side;
+use crate::command::Command;
+use crate::encode;
+use crate::option::Opt;
+use crate::q::{Negotiator, OptionState};
+
+const IS: u8 = 0;
+const SEND: u8 = 1;
+const SE: u8 = 240;
+const SB: u8 = 250;
+const WILL: u8 = 251;
+const DO: u8 = 253;
+
+/// An option the other party sees differently from us
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Discrepancy {
+    pub option: Opt,
+    /// Who performs the option
+    pub side: Side,
+    /// Whether the other party reported the option as enabled, which we don't agree with
+    pub reported: bool,
+}
+
+/// Append `IAC SB STATUS SEND IAC SE` to `dst`
+pub fn send_into(dst: &mut Vec<u8>) {
+    encode::subnegotiate_into(Opt::STATUS, &[SEND], dst);
+}
+
+/// Read the negotiations of a `STATUS IS` report, given the parameters without the option byte
+///
+/// Returns `WILL` and `DO` pairs in the order reported. The states of subnegotiations in the
+/// report are skipped. Returns `None` if the parameters are not an `IS` report or are malformed.
+pub fn decode(params: &[u8]) -> Option<Vec<(Command, Opt)>> {
+    let (&is, mut rest) = params.split_first()?;
+    if is != IS {
+        return None;
+    }
+    let mut negotiations = Vec::new();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match (byte, tail) {
+            (WILL, [option, tail @ ..]) | (DO, [option, tail @ ..]) => {
+                let command = if byte == WILL {
+                    Command::WILL
+                } else {
+                    Command::DO
+                };
+                negotiations.push((command, Opt::from_u8(*option)));
+                rest = tail;
+            }
+            (SB, [_option, tail @ ..]) => {
+                // SE bytes in the parameters are doubled
+                let mut i = 0;
+                loop {
+                    match tail.get(i..i + 2) {
+                        Some([SE, SE]) => i += 2,
+                        _ if tail.get(i) == Some(&SE) => break,
+                        _ if i < tail.len() => i += 1,
+                        _ => return None,
+                    }
+                }
+                rest = &tail[i + 1..];
+            }
+            _ => return None,
+        }
+    }
+    Some(negotiations)
+}
+
+/// Compare the `negotiations` reported by the other party to the view of `negotiator`
+///
+/// Options still being negotiated are skipped, as the report may predate the answer.
+pub fn verify<const N: usize>(
+    negotiator: &Negotiator<N>,
+    negotiations: &[(Command, Opt)],
+) -> Vec<Discrepancy> {
+    let mut discrepancies = Vec::new();
+    for option in (0..=u8::MAX).take(N) {
+        // The other party's WILL is our remote side, its DO our local side
+        let checks = [
+            (Command::WILL, Side::Remote, negotiator.remote_state(option)),
+            (Command::DO, Side::Local, negotiator.local_state(option)),
+        ];
+        for (command, side, state) in checks {
+            let enabled = match state {
+                OptionState::Yes => true,
+                OptionState::No => false,
+                OptionState::WantYes | OptionState::WantNo => continue,
+            };
+            let reported = negotiations.contains(&(command, Opt::from_u8(option)));
+            if reported != enabled {
+                discrepancies.push(Discrepancy {
+                    option: Opt::from_u8(option),
+                    side,
+                    reported,
+                });
+            }
+        }
+    }
+    discrepancies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, Discrepancy};
+    use crate::audit::Side;
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::session::{Policy, TelnetSession};
+    use crate::Parser;
+
+    #[test]
+    fn decode_report() {
+        assert_eq!(
+            decode(b"\x00\xfb\x01\xfa\x18\x01\xf0\xf0\x02\xf0\xfd\x1f"),
+            Some(vec![(Command::WILL, Opt::ECHO), (Command::DO, Opt::NAWS)])
+        );
+        assert_eq!(decode(b"\x00"), Some(Vec::new()));
+        assert_eq!(decode(b"\x01"), None);
+        assert_eq!(decode(b"\x00\xfb"), None);
+        assert_eq!(decode(b"\x00\xfa\x18\x01"), None);
+    }
+
+    #[test]
+    fn verify_session() {
+        let policy = Policy::new()
+            .accept_remote(Opt::STATUS)
+            .accept_remote(Opt::ECHO)
+            .accept_local(Opt::NAWS);
+        let mut session = TelnetSession::new(policy);
+        session.feed(b"\xff\xfb\x05\xff\xfb\x01").for_each(drop);
+        session.request_status();
+        assert!(session.take_output().ends_with(b"\xff\xfa\x05\x01\xff\xf0"));
+
+        // Reports NAWS as performed by us, and leaves out its own ECHO
+        let events: Vec<Event> = session
+            .feed(b"\xff\xfa\x05\x00\xfb\x05\xfd\x1f\xff\xf0")
+            .collect();
+        assert_eq!(
+            events[1..],
+            [
+                Event::StatusMismatch(Discrepancy {
+                    option: Opt::ECHO,
+                    side: Side::Remote,
+                    reported: false,
+                }),
+                Event::StatusMismatch(Discrepancy {
+                    option: Opt::NAWS,
+                    side: Side::Local,
+                    reported: true,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_full_report() {
+        let policy = Policy::new()
+            .accept_remote(Opt::STATUS)
+            .accept_remote(Opt::ECHO)
+            .accept_remote(Opt::SGA)
+            .accept_local(Opt::NAWS)
+            .accept_local(Opt::TTYPE);
+        let negotiations = b"\xff\xfb\x05\xff\xfb\x01\xff\xfb\x03\xff\xfd\x1f\xff\xfd\x18";
+        let report = b"\xff\xfa\x05\x00\xfb\x05\xfb\x01\xfb\x03\xfd\x1f\xfd\x18\xff\xf0";
+
+        let mut session = TelnetSession::new(policy.clone());
+        session.feed(negotiations).for_each(drop);
+        let events: Vec<Event> = session.feed(report).collect();
+        assert_eq!(
+            events,
+            &[Event::Subnegotiate(
+                Opt::STATUS,
+                b"\x00\xfb\x05\xfb\x01\xfb\x03\xfd\x1f\xfd\x18".to_vec()
+            )]
+        );
+
+        // A truncated report is not verified
+        let parser = Parser::builder().max_subnegotiation(8).build();
+        let mut session = TelnetSession::new(policy).parser(parser);
+        session.feed(negotiations).for_each(drop);
+        assert_eq!(session.feed(report).count(), 0);
+    }
+}
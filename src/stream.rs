@@ -0,0 +1,210 @@
+//! Async telnet stream
+//!
+//! [`TelnetStream`] wraps an `AsyncRead + AsyncWrite` transport and exposes the application data
+//! as a plain `AsyncRead + AsyncWrite`. Telnet commands are stripped on read, option negotiation
+//! is answered through an embedded [`TelnetSession`], and IAC bytes are escaped on write.
+//!
+//! [`TelnetStream`]: struct.TelnetStream.html
+//! [`TelnetSession`]: ../session/struct.TelnetSession.html
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::event::Event;
+use crate::session::{Policy, TelnetSession};
+
+const READ_BUF_SIZE: usize = 4096;
+
+macro_rules! ready {
+    ($e:expr) => {
+        match $e {
+            Poll::Ready(t) => t,
+            Poll::Pending => return Poll::Pending,
+        }
+    };
+}
+
+/// A telnet transport exposing only application data
+pub struct TelnetStream<T> {
+    inner: T,
+    session: TelnetSession,
+    read_buf: Box<[u8]>,
+    readable: Vec<u8>,
+    readable_pos: usize,
+    events: Vec<Event>,
+}
+
+impl<T> TelnetStream<T> {
+    pub fn new(inner: T, policy: Policy) -> TelnetStream<T> {
+        TelnetStream::with_session(inner, TelnetSession::new(policy))
+    }
+
+    /// A stream driven by `session`, e.g. one given its own parser with
+    /// [`TelnetSession::parser`]
+    ///
+    /// [`TelnetSession::parser`]: ../session/struct.TelnetSession.html#method.parser
+    pub fn with_session(inner: T, session: TelnetSession) -> TelnetStream<T> {
+        TelnetStream {
+            inner,
+            session,
+            read_buf: vec![0u8; READ_BUF_SIZE].into_boxed_slice(),
+            readable: Vec::new(),
+            readable_pos: 0,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn session(&self) -> &TelnetSession {
+        &self.session
+    }
+
+    /// The session, e.g. to request options. Output queued on the session is sent with the next
+    /// read, write or flush.
+    pub fn session_mut(&mut self) -> &mut TelnetSession {
+        &mut self.session
+    }
+
+    /// Take the events received so far which are not application data, e.g. commands and
+    /// subnegotiations
+    pub fn take_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncWrite + Unpin> TelnetStream<T> {
+    /// Write buffered session output to the transport
+    fn poll_write_output(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.session.output().is_empty() {
+            let n = ready!(Pin::new(&mut self.inner).poll_write(cx, self.session.output()))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.session.consume_output(n);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncRead for TelnetStream<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            // Negotiation answers go out as soon as possible, without blocking the read
+            if let Poll::Ready(Err(err)) = this.poll_write_output(cx) {
+                return Poll::Ready(Err(err));
+            }
+
+            if this.readable_pos < this.readable.len() {
+                let readable = &this.readable[this.readable_pos..];
+                let n = readable.len().min(buf.remaining());
+                buf.put_slice(&readable[..n]);
+                this.readable_pos += n;
+                if this.readable_pos == this.readable.len() {
+                    this.readable.clear();
+                    this.readable_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            let mut read_buf = ReadBuf::new(&mut this.read_buf);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut read_buf))?;
+            let filled = read_buf.filled();
+            if filled.is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+
+            for event in this.session.feed(filled) {
+                match event {
+                    Event::Data(data) => this.readable.extend_from_slice(&data),
+                    Event::Execute(byte) => this.readable.push(byte),
+                    event => this.events.push(event),
+                }
+            }
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for TelnetStream<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_output(cx))?;
+        this.session.send_data(buf);
+        if let Poll::Ready(Err(err)) = this.poll_write_output(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_output(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_output(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TelnetStream;
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::session::Policy;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn strips_and_escapes() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let mut stream = TelnetStream::new(client, Policy::new());
+
+        server
+            .write_all(&[255, 253, 31, b'h', b'i', 255, 249])
+            .await
+            .unwrap();
+        let mut buf = [0u8; 16];
+        let n = stream.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"hi");
+
+        stream.write_all(&[b'a', 255]).await.unwrap();
+        stream.flush().await.unwrap();
+        let n = server.read(&mut buf).await.unwrap();
+        // the refusal of NAWS is sent before the data
+        assert_eq!(&buf[..n], &[255, 252, 31, b'a', 255, 255]);
+
+        assert_eq!(
+            stream.take_events(),
+            &[
+                Event::Negotiate(Command::DO, Opt::NAWS),
+                Event::Command(Command::GA),
+                Event::EndOfTurn,
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Terminal control sequences with `vte`
+//!
+//! [`VteChain`] is a performer which feeds the data of the telnet stream into a `vte::Parser`, so
+//! one pass over the bytes received gives both the telnet events and the terminal actions, e.g.
+//! printed characters and CSI sequences, in the order they were sent. The `vte` parser keeps its
+//! state across telnet commands, so an escape sequence interrupted by `IAC NOP` or a negotiation
+//! still parses, with the telnet event delivered in between.
+//!
+//! ```
+//! use televerknet::event::Event;
+//! use televerknet::terminal::{TerminalPerform, VteChain};
+//! use televerknet::Parser;
+//!
+//! #[derive(Default)]
+//! struct Screen(String);
+//!
+//! impl vte::Perform for Screen {
+//!     fn print(&mut self, c: char) {
+//!         self.0.push(c);
+//!     }
+//!
+//!     fn csi_dispatch(&mut self, _: &vte::Params, _: &[u8], _: bool, action: char) {
+//!         self.0.push_str(&format!("<{}>", action));
+//!     }
+//! }
+//!
+//! impl TerminalPerform for Screen {
+//!     fn telnet(&mut self, event: Event) {
+//!         if let Event::Command(command) = event {
+//!             self.0.push_str(&format!("[{}]", command));
+//!         }
+//!     }
+//! }
+//!
+//! let mut chain = VteChain::new(Screen::default());
+//! let mut parser = Parser::new();
+//! parser.advance_bytes(&mut chain, b"\x1b[1\xff\xf1m!");
+//! parser.flush(&mut chain);
+//! assert_eq!(chain.get_ref().0, "[241 NOP]<m>!");
+//! ```
+//!
+//! [`VteChain`]: struct.VteChain.html
+use crate::event::Event;
+use crate::{DataEnd, ParseError, Perform};
+
+/// Receives the terminal actions and telnet events of a [`VteChain`]
+///
+/// [`VteChain`]: struct.VteChain.html
+pub trait TerminalPerform: vte::Perform {
+    /// A telnet event which is not data
+    fn telnet(&mut self, event: Event);
+
+    /// See [`Perform::error`](../trait.Perform.html#method.error)
+    fn error(&mut self, _error: ParseError) {}
+}
+
+/// Passes telnet data through a `vte::Parser`
+pub struct VteChain<P> {
+    vte: vte::Parser,
+    inner: P,
+}
+
+impl<P: TerminalPerform> VteChain<P> {
+    pub fn new(inner: P) -> VteChain<P> {
+        VteChain {
+            vte: vte::Parser::new(),
+            inner,
+        }
+    }
+
+    pub fn get_ref(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut P {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+
+    /// Pass on events collected from a callback, with data fed to the terminal parser
+    fn pass_on(&mut self, events: Vec<Event>) {
+        for event in events {
+            match event {
+                Event::Data(data) => self.vte.advance(&mut self.inner, &data),
+                event => self.inner.telnet(event),
+            }
+        }
+    }
+}
+
+impl<P: TerminalPerform> Perform for VteChain<P> {
+    fn data(&mut self, intermediates: &[u8], _ignore: bool, _end: DataEnd) {
+        self.vte.advance(&mut self.inner, intermediates);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.vte.advance(&mut self.inner, &[byte]);
+    }
+
+    fn error(&mut self, error: ParseError) {
+        TerminalPerform::error(&mut self.inner, error);
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        let mut events: Vec<Event> = Vec::with_capacity(1);
+        events.iac_dispatch(byte);
+        self.pass_on(events);
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        let mut events: Vec<Event> = Vec::with_capacity(1);
+        events.sub_dispatch(subs);
+        self.pass_on(events);
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        let mut events: Vec<Event> = Vec::with_capacity(1);
+        events.negotiate_dispatch(cmd, opt);
+        self.pass_on(events);
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+    fn compress_dispatch(&mut self, _state: u8) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TerminalPerform, VteChain};
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::Parser;
+
+    #[derive(Debug, PartialEq)]
+    enum Action {
+        Print(char),
+        Execute(u8),
+        Csi(Vec<u16>, char),
+        Telnet(Event),
+    }
+
+    #[derive(Default)]
+    struct Actions(Vec<Action>);
+
+    impl vte::Perform for Actions {
+        fn print(&mut self, c: char) {
+            self.0.push(Action::Print(c));
+        }
+
+        fn execute(&mut self, byte: u8) {
+            self.0.push(Action::Execute(byte));
+        }
+
+        fn csi_dispatch(&mut self, params: &vte::Params, _: &[u8], _: bool, action: char) {
+            let params = params.iter().flatten().copied().collect();
+            self.0.push(Action::Csi(params, action));
+        }
+    }
+
+    impl TerminalPerform for Actions {
+        fn telnet(&mut self, event: Event) {
+            self.0.push(Action::Telnet(event));
+        }
+    }
+
+    #[test]
+    fn ordering_across_commands() {
+        let mut parser = Parser::new();
+        let mut chain = VteChain::new(Actions::default());
+        // A CSI sequence split by a negotiation and by reads
+        parser.advance_bytes(&mut chain, b"a\x1b[3");
+        parser.flush(&mut chain);
+        parser.advance_bytes(&mut chain, b"1\xff\xfb\x01;4");
+        parser.advance_bytes(&mut chain, b"m\xc3\xa4\r\n");
+        parser.flush(&mut chain);
+
+        assert_eq!(
+            chain.into_inner().0,
+            &[
+                Action::Print('a'),
+                Action::Telnet(Event::Negotiate(Command::WILL, Opt::ECHO)),
+                Action::Csi(vec![31, 4], 'm'),
+                Action::Print('ä'),
+                Action::Execute(b'\r'),
+                Action::Execute(b'\n'),
+            ]
+        );
+    }
+}
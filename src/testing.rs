@@ -0,0 +1,380 @@
+//! Helpers for testing code built on the parser
+//!
+//! Streams read from a socket are split at arbitrary points, and a consumer must produce the same
+//! events however a stream was split. [`assert_chunking_invariant`] checks that for a stream,
+//! and the [proptest] strategies generate valid streams and ways to split them.
+//! [`RecordingPerform`] records every callback as it was made, for tests of the parser itself.
+//! [`loopback`] connects a client and a server session to each other, so that whole negotiations
+//! can be tested as they happen between two parties.
+//!
+//! [`assert_chunking_invariant`]: fn.assert_chunking_invariant.html
+//! [`RecordingPerform`]: struct.RecordingPerform.html
+//! [`loopback`]: fn.loopback.html
+//! [proptest]: https://docs.rs/proptest
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use crate::command::Command;
+use crate::event::Event;
+use crate::message::OutMessage;
+use crate::option::Opt;
+use crate::session::{Role, TelnetSession};
+use crate::{DataEnd, ParseError, Parser, Perform};
+
+/// A performer recording the arguments of every callback
+///
+/// Unlike collecting [`Event`]s, nothing is merged or unescaped: data split over several calls
+/// stays split, and subnegotiations keep the option byte and the IAC preceding SE.
+///
+/// [`Event`]: ../event/enum.Event.html
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RecordingPerform {
+    pub data: Vec<Vec<u8>>,
+    pub ignoring: Vec<bool>,
+    pub data_ends: Vec<DataEnd>,
+    pub execute: Vec<u8>,
+    pub iac: Vec<u8>,
+    pub negotiations: Vec<(u8, u8)>,
+    pub subs: Vec<Vec<u8>>,
+    pub subnegotiations: Vec<(Vec<u8>, u8)>,
+    pub zmp: Vec<Vec<Vec<u8>>>,
+    pub ttypes: Vec<(u8, Vec<u8>)>,
+    pub compress: Vec<u8>,
+    pub errors: Vec<ParseError>,
+}
+
+impl RecordingPerform {
+    pub fn new() -> RecordingPerform {
+        RecordingPerform::default()
+    }
+
+    /// All data joined together
+    pub fn joined_data(&self) -> Vec<u8> {
+        self.data.concat()
+    }
+
+    /// Assert that the data joined together is `expected`
+    pub fn assert_data(&self, expected: &[u8]) {
+        assert_eq!(self.joined_data(), expected, "data");
+    }
+
+    /// Assert that `command` was received for `option`
+    pub fn assert_negotiated(&self, command: Command, option: Opt) {
+        assert!(
+            self.negotiations
+                .contains(&(command.as_u8(), option.as_u8())),
+            "{} {} not in {:?}",
+            command.name(),
+            option,
+            self.negotiations
+        );
+    }
+
+    /// Assert that no errors were reported
+    pub fn assert_no_errors(&self) {
+        assert!(self.errors.is_empty(), "errors: {:?}", self.errors);
+    }
+}
+
+impl Perform for RecordingPerform {
+    fn data(&mut self, intermediates: &[u8], ignore: bool, end: DataEnd) {
+        self.data.push(intermediates.to_vec());
+        self.ignoring.push(ignore);
+        self.data_ends.push(end);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        self.execute.push(byte);
+    }
+
+    fn error(&mut self, error: ParseError) {
+        self.errors.push(error);
+    }
+
+    fn iac_dispatch(&mut self, byte: u8) {
+        self.iac.push(byte);
+    }
+
+    fn sub_dispatch(&mut self, subs: &[u8]) {
+        self.subs.push(subs.to_vec());
+    }
+
+    fn negotiate_dispatch(&mut self, cmd: u8, opt: u8) {
+        self.negotiations.push((cmd, opt));
+    }
+
+    fn subnegotiate_dispatch(&mut self, params: &[u8], opt: u8) {
+        self.subnegotiations.push((params.to_vec(), opt));
+    }
+
+    fn zmp_dispatch(&mut self, params: &[&[u8]]) {
+        self.zmp
+            .push(params.iter().map(|param| param.to_vec()).collect());
+    }
+
+    fn ttypes_dispatch(&mut self, cmd: u8, terminal_type: &[u8]) {
+        self.ttypes.push((cmd, terminal_type.to_vec()));
+    }
+
+    fn compress_dispatch(&mut self, state: u8) {
+        self.compress.push(state);
+    }
+}
+
+/// Parse `bytes` split into chunks of the given sizes, the rest of the stream being the last
+/// chunk
+///
+/// Data is dispatched at the end of every chunk, as a consumer reading from a socket would. Data
+/// split between chunks is merged, so only differences in meaning show up in the events.
+pub fn parse_chunked(bytes: &[u8], chunk_sizes: &[usize]) -> Vec<Event> {
+    let mut parser = Parser::new();
+    let mut events: Vec<Event> = Vec::new();
+    let mut rest = bytes;
+    for size in chunk_sizes {
+        let (chunk, tail) = rest.split_at((*size).min(rest.len()));
+        parser.advance_bytes(&mut events, chunk);
+        parser.flush(&mut events);
+        rest = tail;
+    }
+    parser.advance_bytes(&mut events, rest);
+    parser.flush(&mut events);
+    events
+}
+
+/// Assert that `bytes` parses to the same events whole, one byte at a time, and split in two at
+/// every position
+pub fn assert_chunking_invariant(bytes: &[u8]) {
+    let whole = parse_chunked(bytes, &[]);
+    assert_eq!(parse_chunked(bytes, &vec![1; bytes.len()]), whole);
+    for split in 1..bytes.len() {
+        assert_eq!(
+            parse_chunked(bytes, &[split]),
+            whole,
+            "split at {} of {:?}",
+            split,
+            bytes
+        );
+    }
+}
+
+/// Rounds of [`Loopback::run`] before giving up on the sessions ever falling silent
+///
+/// [`Loopback::run`]: struct.Loopback.html#method.run
+const MAX_ROUNDS: usize = 64;
+
+/// A client and a server session connected to each other, see [`loopback`]
+///
+/// [`loopback`]: fn.loopback.html
+pub struct Loopback {
+    pub client: TelnetSession,
+    pub server: TelnetSession,
+    /// Events received by the client, in order
+    pub client_events: Vec<Event>,
+    /// Events received by the server, in order
+    pub server_events: Vec<Event>,
+}
+
+impl Loopback {
+    /// Connect `client` and `server`
+    pub fn new(client: TelnetSession, server: TelnetSession) -> Loopback {
+        Loopback {
+            client,
+            server,
+            client_events: Vec::new(),
+            server_events: Vec::new(),
+        }
+    }
+
+    /// Deliver the output of each session to the other one, returning true if there was any
+    ///
+    /// The client's output is delivered first.
+    pub fn round(&mut self) -> bool {
+        let to_server = self.client.take_output();
+        self.server_events.extend(self.server.feed(&to_server));
+        let to_client = self.server.take_output();
+        self.client_events.extend(self.client.feed(&to_client));
+        !to_server.is_empty() || !to_client.is_empty()
+    }
+
+    /// Deliver output back and forth until both sessions are silent, returning the number of
+    /// rounds it took
+    ///
+    /// Panics if the sessions keep answering each other, as in a negotiation loop.
+    pub fn run(&mut self) -> usize {
+        for rounds in 0..MAX_ROUNDS {
+            if !self.round() {
+                return rounds;
+            }
+        }
+        panic!("sessions still talking after {} rounds", MAX_ROUNDS);
+    }
+}
+
+/// A client and a server session with the defaults of their role, connected to each other
+///
+/// Nothing is delivered until [`Loopback::run`] or [`Loopback::round`] is called, so the
+/// sessions can be set up first.
+///
+/// ```
+/// use televerknet::option::Opt;
+/// use televerknet::testing::loopback;
+///
+/// let mut pair = loopback();
+/// pair.run();
+/// assert!(pair.client.is_remote_enabled(Opt::ECHO));
+/// assert!(pair.server.is_remote_enabled(Opt::TTYPE));
+/// ```
+///
+/// [`Loopback::run`]: struct.Loopback.html#method.run
+/// [`Loopback::round`]: struct.Loopback.html#method.round
+pub fn loopback() -> Loopback {
+    Loopback::new(
+        TelnetSession::with_role(Role::Client),
+        TelnetSession::with_role(Role::Server),
+    )
+}
+
+/// Strategy for a single valid sequence: data, a command, a negotiation or a subnegotiation
+pub fn sequence() -> impl Strategy<Value = Vec<u8>> {
+    let data = vec(any::<u8>(), 1..32).prop_map(Event::Data);
+    let command = (241u8..=249).prop_map(|c| vec![255, c]);
+    let negotiation = (251u8..=254, any::<u8>()).prop_map(|(c, o)| vec![255, c, o]);
+    // Parameters are kept short and without SE, within what the parser handles
+    let subnegotiation = (any::<u8>(), vec(0u8..240, 0..4))
+        .prop_filter("option must not be SE", |(o, _)| *o != 240)
+        .prop_map(|(o, params)| Event::Subnegotiate(Opt::from_u8(o), params));
+    prop_oneof![
+        data.prop_map(|e| encode(&e)),
+        command,
+        negotiation,
+        subnegotiation.prop_map(|e| encode(&e)),
+    ]
+}
+
+/// Strategy for an [`OutMessage`] which [`OutMessage::parse`] gives back
+///
+/// Messages which don't survive the round trip, as listed for [`OutMessage::parse`], are left
+/// out: data is never empty, commands don't start or end other sequences, and subnegotiations
+/// are short and don't use GMCP or NAWS.
+///
+/// [`OutMessage`]: ../message/enum.OutMessage.html
+/// [`OutMessage::parse`]: ../message/enum.OutMessage.html#method.parse
+pub fn out_message() -> impl Strategy<Value = OutMessage> {
+    let data = vec(any::<u8>(), 1..32).prop_map(OutMessage::Data);
+    let command = (236u8..=249)
+        .prop_filter("command must not be SE", |c| *c != 240)
+        .prop_map(|c| OutMessage::Command(Command::from_u8(c).unwrap()));
+    let negotiation = (251u8..=254, any::<u8>())
+        .prop_map(|(c, o)| OutMessage::Negotiate(Command::from_u8(c).unwrap(), Opt::from_u8(o)));
+    let subnegotiation = (any::<u8>(), vec(any::<u8>(), 0..64))
+        .prop_filter("option must not be SE, IAC, GMCP or NAWS", |(o, _)| {
+            ![240, 255, Opt::GMCP.as_u8(), Opt::NAWS.as_u8()].contains(o)
+        })
+        .prop_map(|(o, params)| OutMessage::Subnegotiate(Opt::from_u8(o), params));
+    let naws = (any::<u16>(), any::<u16>()).prop_map(|(w, h)| OutMessage::Naws(w, h));
+    #[cfg(feature = "mud")]
+    {
+        let gmcp = ("[A-Z][a-z]{0,8}(\\.[A-Z][a-z]{0,8}){0,2}", "[ -~]{0,16}")
+            .prop_map(|(name, data)| OutMessage::Gmcp(name, data.trim().to_owned()));
+        prop_oneof![data, command, negotiation, subnegotiation, gmcp, naws]
+    }
+    #[cfg(not(feature = "mud"))]
+    prop_oneof![data, command, negotiation, subnegotiation, naws]
+}
+
+/// Strategy for a stream made of up to `max` valid sequences
+pub fn stream(max: usize) -> impl Strategy<Value = Vec<u8>> {
+    vec(sequence(), 0..max).prop_map(|sequences| sequences.concat())
+}
+
+/// Strategy for chunk sizes to pass to [`parse_chunked`](fn.parse_chunked.html)
+pub fn chunk_sizes() -> impl Strategy<Value = Vec<usize>> {
+    vec(1usize..16, 0..16)
+}
+
+fn encode(event: &Event) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    event.encode_into(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        assert_chunking_invariant, chunk_sizes, loopback, parse_chunked, stream, Loopback,
+        RecordingPerform,
+    };
+    use crate::command::Command;
+    use crate::event::Event;
+    use crate::option::Opt;
+    use crate::session::{Policy, TelnetSession};
+    use crate::Parser;
+    use proptest::prelude::*;
+
+    #[test]
+    fn recording() {
+        let mut recording = RecordingPerform::new();
+        let mut parser = Parser::new();
+        parser.advance_bytes(&mut recording, &[b'a', 255, 253, 31, b'b', b'\r']);
+        parser.advance_bytes(&mut recording, &[255, 250, 31, 0, 80, 255, 240]);
+
+        recording.assert_data(b"ab");
+        recording.assert_negotiated(Command::DO, Opt::NAWS);
+        recording.assert_no_errors();
+        assert_eq!(recording.execute, b"\r");
+        assert_eq!(recording.subs, &[vec![31, 0, 80, 255]]);
+    }
+
+    #[test]
+    fn iac_split_between_reads() {
+        assert_chunking_invariant(&[b'a', 255, 255, b'b', 255, 251, 1, b'c']);
+    }
+
+    #[test]
+    fn loopback_roles() {
+        let mut pair = loopback();
+        pair.client = pair.client.terminal_type(b"XTERM");
+        assert_eq!(pair.run(), 3);
+        assert!(pair.client.is_remote_enabled(Opt::ECHO));
+        assert!(pair.client.is_remote_enabled(Opt::SGA));
+        assert!(pair.server.is_remote_enabled(Opt::TTYPE));
+        assert_eq!(
+            pair.server_events.last(),
+            Some(&Event::Subnegotiate(Opt::TTYPE, b"\0XTERM".to_vec()))
+        );
+    }
+
+    #[test]
+    fn loopback_simultaneous_requests() {
+        // Both parties ask for the other to perform the option at once, RFC 1143 section 7
+        let policy = Policy::new()
+            .accept_local(Opt::BINARY)
+            .accept_remote(Opt::BINARY);
+        let mut pair = Loopback::new(
+            TelnetSession::new(policy.clone()),
+            TelnetSession::new(policy),
+        );
+        pair.client.request_remote(Opt::BINARY).unwrap();
+        pair.server.request_remote(Opt::BINARY).unwrap();
+        pair.run();
+        for session in [&pair.client, &pair.server] {
+            assert!(session.is_local_enabled(Opt::BINARY));
+            assert!(session.is_remote_enabled(Opt::BINARY));
+        }
+
+        pair.client.withdraw_local(Opt::BINARY).unwrap();
+        pair.run();
+        assert!(!pair.server.is_remote_enabled(Opt::BINARY));
+        assert_eq!(
+            pair.client_events.last(),
+            Some(&Event::Negotiate(Command::DONT, Opt::BINARY))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn chunking_invariance(bytes in stream(16), sizes in chunk_sizes()) {
+            prop_assert_eq!(parse_chunked(&bytes, &sizes), parse_chunked(&bytes, &[]));
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Round-trip time with TIMING-MARK
+//!
+//! `IAC DO TIMING-MARK` ([RFC 860]) is answered with WILL or WONT once the other party has
+//! processed everything sent before it, which makes it the telnet level ping. [`TimingMark`]
+//! sends marks and matches the answers to them, in order. Like [`Keepalive`] it doesn't keep time
+//! itself: the caller passes the time elapsed since the last tick.
+//!
+//! The answers must not reach the negotiator, which would take `WILL TIMING-MARK` for an offer.
+//! [`TelnetSession::request_timing_mark`] takes care of this.
+//!
+//! [RFC 860]: https://www.rfc-editor.org/rfc/rfc860.html
+//! [`TimingMark`]: struct.TimingMark.html
+//! [`Keepalive`]: ../keepalive/struct.Keepalive.html
+//! [`TelnetSession::request_timing_mark`]: ../session/struct.TelnetSession.html#method.request_timing_mark
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::command::Command;
+use crate::encode;
+use crate::option::Opt;
+
+/// An answered timing mark
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RoundTrip {
+    /// Time from sending the mark to receiving the answer
+    pub rtt: Duration,
+    /// True if answered with WILL, false with WONT
+    pub acknowledged: bool,
+}
+
+/// Sends timing marks and measures the time until they are answered
+#[derive(Debug, Clone, Default)]
+pub struct TimingMark {
+    /// Time since each unanswered mark was sent, oldest first
+    outstanding: VecDeque<Duration>,
+}
+
+impl TimingMark {
+    pub fn new() -> TimingMark {
+        TimingMark::default()
+    }
+
+    /// Append `IAC DO TIMING-MARK` to `out`
+    pub fn request(&mut self, out: &mut Vec<u8>) {
+        encode::negotiate_into(Command::DO, Opt::TM, out);
+        self.outstanding.push_back(Duration::from_secs(0));
+    }
+
+    /// Advance the time of the unanswered marks by `elapsed`
+    pub fn tick(&mut self, elapsed: Duration) {
+        for since in &mut self.outstanding {
+            *since += elapsed;
+        }
+    }
+
+    /// Number of unanswered marks
+    pub fn outstanding(&self) -> usize {
+        self.outstanding.len()
+    }
+
+    /// Time since the oldest unanswered mark was sent, e.g. to give up on it
+    pub fn oldest(&self) -> Option<Duration> {
+        self.outstanding.front().copied()
+    }
+
+    /// Match a received negotiation to the oldest unanswered mark
+    ///
+    /// Returns `None` if it is not `WILL` or `WONT TIMING-MARK`, or no mark is unanswered.
+    pub fn answer(&mut self, command: Command, option: Opt) -> Option<RoundTrip> {
+        if option != Opt::TM {
+            return None;
+        }
+        let acknowledged = match command {
+            Command::WILL => true,
+            Command::WONT => false,
+            _ => return None,
+        };
+        self.outstanding
+            .pop_front()
+            .map(|rtt| RoundTrip { rtt, acknowledged })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{RoundTrip, TimingMark};
+    use crate::command::Command;
+    use crate::option::Opt;
+
+    #[test]
+    fn round_trips() {
+        let ms = Duration::from_millis;
+        let mut timing = TimingMark::new();
+        let mut out = Vec::new();
+
+        assert_eq!(timing.answer(Command::WILL, Opt::TM), None);
+        timing.request(&mut out);
+        timing.tick(ms(30));
+        timing.request(&mut out);
+        timing.tick(ms(20));
+        assert_eq!(out, &[255, 253, 6, 255, 253, 6]);
+        assert_eq!(timing.outstanding(), 2);
+        assert_eq!(timing.oldest(), Some(ms(50)));
+
+        assert_eq!(timing.answer(Command::WILL, Opt::ECHO), None);
+        assert_eq!(timing.answer(Command::DO, Opt::TM), None);
+        assert_eq!(
+            timing.answer(Command::WILL, Opt::TM),
+            Some(RoundTrip {
+                rtt: ms(50),
+                acknowledged: true
+            })
+        );
+        assert_eq!(
+            timing.answer(Command::WONT, Opt::TM),
+            Some(RoundTrip {
+                rtt: ms(20),
+                acknowledged: false
+            })
+        );
+        assert_eq!(timing.outstanding(), 0);
+    }
+}
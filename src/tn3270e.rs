@@ -0,0 +1,280 @@
+//! TN3270E as described in [RFC 2355]
+//!
+//! [`Message`] parses and encodes the parameters of `IAC SB TN3270E ... IAC SE`, which negotiate
+//! the device type and the functions of a 3270 session. Once negotiated, 3270 data is sent in
+//! records terminated by `IAC EOR`, each starting with a [`Header`]. [`Records`] reassembles
+//! records from parser events.
+//!
+//! [`Message`]: enum.Message.html
+//! [`Header`]: struct.Header.html
+//! [`Records`]: struct.Records.html
+//! [RFC 2355]: https://www.rfc-editor.org/rfc/rfc2355.html
+use crate::command::Command;
+use crate::event::Event;
+
+const ASSOCIATE: u8 = 0;
+const CONNECT: u8 = 1;
+const DEVICE_TYPE: u8 = 2;
+const FUNCTIONS: u8 = 3;
+const IS: u8 = 4;
+const REASON: u8 = 5;
+const REJECT: u8 = 6;
+const REQUEST: u8 = 7;
+const SEND: u8 = 8;
+
+/// Length of the header in front of every record
+pub const HEADER_LEN: usize = 5;
+
+/// Functions which can be negotiated
+pub mod function {
+    pub const BIND_IMAGE: u8 = 0;
+    pub const DATA_STREAM_CTL: u8 = 1;
+    pub const RESPONSES: u8 = 2;
+    pub const SCS_CTL_CODES: u8 = 3;
+    pub const SYSREQ: u8 = 4;
+}
+
+/// Reasons for rejecting a device type request
+pub mod reason {
+    pub const CONN_PARTNER: u8 = 0;
+    pub const DEVICE_IN_USE: u8 = 1;
+    pub const INV_ASSOCIATE: u8 = 2;
+    pub const INV_NAME: u8 = 3;
+    pub const INV_DEVICE_TYPE: u8 = 4;
+    pub const TYPE_NAME_ERROR: u8 = 5;
+    pub const UNKNOWN_ERROR: u8 = 6;
+    pub const UNSUPPORTED_REQ: u8 = 7;
+}
+
+/// Data types of records
+pub mod data_type {
+    pub const DATA_3270: u8 = 0;
+    pub const SCS_DATA: u8 = 1;
+    pub const RESPONSE: u8 = 2;
+    pub const BIND_IMAGE: u8 = 3;
+    pub const UNBIND: u8 = 4;
+    pub const NVT_DATA: u8 = 5;
+    pub const REQUEST: u8 = 6;
+    pub const SSCP_LU_DATA: u8 = 7;
+    pub const PRINT_EOJ: u8 = 8;
+}
+
+/// A TN3270E subnegotiation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// `SEND DEVICE-TYPE`, sent by the server to start negotiation
+    SendDeviceType,
+    /// `DEVICE-TYPE REQUEST <type> [CONNECT <name> | ASSOCIATE <name>]`
+    DeviceTypeRequest {
+        device_type: Vec<u8>,
+        connect: Option<Vec<u8>>,
+        associate: Option<Vec<u8>>,
+    },
+    /// `DEVICE-TYPE IS <type> CONNECT <name>`
+    DeviceTypeIs {
+        device_type: Vec<u8>,
+        device_name: Vec<u8>,
+    },
+    /// `DEVICE-TYPE REJECT REASON <reason>`, see [`reason`](reason/index.html)
+    DeviceTypeReject(u8),
+    /// `FUNCTIONS REQUEST <functions>`, see [`function`](function/index.html)
+    FunctionsRequest(Vec<u8>),
+    /// `FUNCTIONS IS <functions>`
+    FunctionsIs(Vec<u8>),
+}
+
+impl Message {
+    /// Parse subnegotiation parameters, without the option byte
+    pub fn parse(params: &[u8]) -> Option<Message> {
+        match params {
+            [SEND, DEVICE_TYPE] => Some(Message::SendDeviceType),
+            [DEVICE_TYPE, REQUEST, rest @ ..] => {
+                let (device_type, name) = split_name(rest);
+                let (connect, associate) = match name {
+                    None => (None, None),
+                    Some((CONNECT, name)) => (Some(name.to_vec()), None),
+                    Some((ASSOCIATE, name)) => (None, Some(name.to_vec())),
+                    Some(_) => return None,
+                };
+                Some(Message::DeviceTypeRequest {
+                    device_type: device_type.to_vec(),
+                    connect,
+                    associate,
+                })
+            }
+            [DEVICE_TYPE, IS, rest @ ..] => match split_name(rest) {
+                (device_type, Some((CONNECT, name))) => Some(Message::DeviceTypeIs {
+                    device_type: device_type.to_vec(),
+                    device_name: name.to_vec(),
+                }),
+                _ => None,
+            },
+            [DEVICE_TYPE, REJECT, REASON, reason] => Some(Message::DeviceTypeReject(*reason)),
+            [FUNCTIONS, REQUEST, functions @ ..] => {
+                Some(Message::FunctionsRequest(functions.to_vec()))
+            }
+            [FUNCTIONS, IS, functions @ ..] => Some(Message::FunctionsIs(functions.to_vec())),
+            _ => None,
+        }
+    }
+
+    /// Append the subnegotiation parameters, without the option byte, to `dst`
+    pub fn encode_into(&self, dst: &mut Vec<u8>) {
+        match self {
+            Message::SendDeviceType => dst.extend_from_slice(&[SEND, DEVICE_TYPE]),
+            Message::DeviceTypeRequest {
+                device_type,
+                connect,
+                associate,
+            } => {
+                dst.extend_from_slice(&[DEVICE_TYPE, REQUEST]);
+                dst.extend_from_slice(device_type);
+                if let Some(name) = connect {
+                    dst.push(CONNECT);
+                    dst.extend_from_slice(name);
+                } else if let Some(name) = associate {
+                    dst.push(ASSOCIATE);
+                    dst.extend_from_slice(name);
+                }
+            }
+            Message::DeviceTypeIs {
+                device_type,
+                device_name,
+            } => {
+                dst.extend_from_slice(&[DEVICE_TYPE, IS]);
+                dst.extend_from_slice(device_type);
+                dst.push(CONNECT);
+                dst.extend_from_slice(device_name);
+            }
+            Message::DeviceTypeReject(reason) => {
+                dst.extend_from_slice(&[DEVICE_TYPE, REJECT, REASON, *reason])
+            }
+            Message::FunctionsRequest(functions) => {
+                dst.extend_from_slice(&[FUNCTIONS, REQUEST]);
+                dst.extend_from_slice(functions);
+            }
+            Message::FunctionsIs(functions) => {
+                dst.extend_from_slice(&[FUNCTIONS, IS]);
+                dst.extend_from_slice(functions);
+            }
+        }
+    }
+}
+
+/// Split a device type from a following `CONNECT` or `ASSOCIATE` name
+fn split_name(bytes: &[u8]) -> (&[u8], Option<(u8, &[u8])>) {
+    match bytes.iter().position(|b| *b == CONNECT || *b == ASSOCIATE) {
+        Some(pos) => (&bytes[..pos], Some((bytes[pos], &bytes[pos + 1..]))),
+        None => (bytes, None),
+    }
+}
+
+/// The header in front of every TN3270E record
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Header {
+    /// See [`data_type`](data_type/index.html)
+    pub data_type: u8,
+    pub request_flag: u8,
+    pub response_flag: u8,
+    pub seq_number: u16,
+}
+
+impl Header {
+    /// Split a record into its header and data
+    pub fn parse(record: &[u8]) -> Option<(Header, &[u8])> {
+        if record.len() < HEADER_LEN {
+            return None;
+        }
+        let header = Header {
+            data_type: record[0],
+            request_flag: record[1],
+            response_flag: record[2],
+            seq_number: u16::from_be_bytes([record[3], record[4]]),
+        };
+        Some((header, &record[HEADER_LEN..]))
+    }
+
+    pub fn encode_into(&self, dst: &mut Vec<u8>) {
+        dst.extend_from_slice(&[self.data_type, self.request_flag, self.response_flag]);
+        dst.extend_from_slice(&self.seq_number.to_be_bytes());
+    }
+}
+
+/// Reassembles records terminated by `IAC EOR` from parser events
+///
+/// 3270 data is binary, so control bytes reported as [`Event::Execute`] are part of the record.
+///
+/// [`Event::Execute`]: ../event/enum.Event.html#variant.Execute
+#[derive(Debug, Default)]
+pub struct Records {
+    record: Vec<u8>,
+}
+
+impl Records {
+    pub fn new() -> Records {
+        Records::default()
+    }
+
+    /// Add an event, returning the record it completes
+    ///
+    /// Negotiation and commands other than EOR are ignored.
+    pub fn push(&mut self, event: &Event) -> Option<Vec<u8>> {
+        match event {
+            Event::Data(data) => self.record.extend_from_slice(data),
+            Event::Execute(byte) => self.record.push(*byte),
+            Event::Command(Command::EOR) => return Some(std::mem::take(&mut self.record)),
+            _ => (),
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{data_type, function, Header, Message, Records};
+    use crate::event::Event;
+    use crate::Parser;
+
+    #[test]
+    fn messages() {
+        let request = Message::DeviceTypeRequest {
+            device_type: b"IBM-3278-2-E".to_vec(),
+            connect: Some(b"LU1".to_vec()),
+            associate: None,
+        };
+        let mut params = Vec::new();
+        request.encode_into(&mut params);
+        assert_eq!(&params[..2], &[2, 7]);
+        assert_eq!(Message::parse(&params), Some(request));
+
+        assert_eq!(
+            Message::parse(&[3, 4, function::RESPONSES, function::SYSREQ]),
+            Some(Message::FunctionsIs(vec![2, 4]))
+        );
+        assert_eq!(
+            Message::parse(&[2, 6, 5, 3]),
+            Some(Message::DeviceTypeReject(3))
+        );
+        assert_eq!(Message::parse(&[2, 4, b'X']), None);
+    }
+
+    #[test]
+    fn records() {
+        let mut parser = Parser::new();
+        let mut events: Vec<Event> = Vec::new();
+        // Header, then 3270 data containing control bytes and an escaped IAC
+        parser.advance_bytes(
+            &mut events,
+            &[0, 0, 0, 0, 1, 0xf5, 0xc3, 0x11, 255, 255, 255, 239, 0],
+        );
+
+        let mut records = Records::new();
+        let found: Vec<Vec<u8>> = events.iter().filter_map(|e| records.push(e)).collect();
+        assert_eq!(found.len(), 1);
+
+        let (header, data) = Header::parse(&found[0]).unwrap();
+        assert_eq!(header.data_type, data_type::DATA_3270);
+        assert_eq!(header.seq_number, 1);
+        assert_eq!(data, &[0xf5, 0xc3, 0x11, 255]);
+    }
+}
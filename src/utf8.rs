@@ -0,0 +1,120 @@
+//! UTF-8 decoding of data
+//!
+//! [`Utf8Decoder`] turns data into text, replacing invalid sequences with U+FFFD. A code point
+//! split between reads, or interrupted by a command, is kept until the rest of it arrives.
+//!
+//! Servers often send UTF-8 without negotiating TRANSMIT-BINARY, so the parser reports the bytes
+//! above 0x7F as [`Event::Execute`]. [`Utf8Decoder::push`] treats those as data.
+//!
+//! [`Utf8Decoder`]: struct.Utf8Decoder.html
+//! [`Utf8Decoder::push`]: struct.Utf8Decoder.html#method.push
+//! [`Event::Execute`]: ../event/enum.Event.html#variant.Execute
+use std::str;
+
+use crate::event::Event;
+
+const REPLACEMENT: char = '\u{FFFD}';
+
+/// Decodes UTF-8 data split over any number of chunks
+#[derive(Debug, Default)]
+pub struct Utf8Decoder {
+    partial: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    pub fn new() -> Utf8Decoder {
+        Utf8Decoder::default()
+    }
+
+    /// Decode `bytes`, returning the text of all complete code points
+    ///
+    /// An incomplete code point at the end of `bytes` is kept for the next call.
+    pub fn decode(&mut self, bytes: &[u8]) -> String {
+        let mut text = String::with_capacity(bytes.len());
+        if self.partial.is_empty() {
+            self.decode_into(bytes, &mut text);
+        } else {
+            let mut joined = std::mem::take(&mut self.partial);
+            joined.extend_from_slice(bytes);
+            self.decode_into(&joined, &mut text);
+        }
+        text
+    }
+
+    /// Decode the data in `event`, returning `None` for events without data
+    ///
+    /// Control bytes below 0x80 are not data and leave any incomplete code point pending.
+    pub fn push(&mut self, event: &Event) -> Option<String> {
+        match event {
+            Event::Data(data) => Some(self.decode(data)),
+            Event::Execute(byte) if *byte >= 0x80 => Some(self.decode(&[*byte])),
+            _ => None,
+        }
+    }
+
+    /// Finish decoding, returning a replacement character for an incomplete code point
+    pub fn finish(&mut self) -> Option<char> {
+        if self.partial.is_empty() {
+            None
+        } else {
+            self.partial.clear();
+            Some(REPLACEMENT)
+        }
+    }
+
+    fn decode_into(&mut self, mut bytes: &[u8], text: &mut String) {
+        loop {
+            match str::from_utf8(bytes) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    return;
+                }
+                Err(err) => {
+                    let (valid, rest) = bytes.split_at(err.valid_up_to());
+                    // Safe as from_utf8 validated these bytes
+                    text.push_str(unsafe { str::from_utf8_unchecked(valid) });
+                    match err.error_len() {
+                        Some(len) => {
+                            text.push(REPLACEMENT);
+                            bytes = &rest[len..];
+                        }
+                        None => {
+                            self.partial.extend_from_slice(rest);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Utf8Decoder;
+    use crate::event::Event;
+    use crate::Parser;
+
+    #[test]
+    fn split_code_points() {
+        let mut decoder = Utf8Decoder::new();
+        assert_eq!(decoder.decode(b"a\xe2\x82"), "a");
+        assert_eq!(decoder.decode(b"\xac!"), "\u{20ac}!");
+        assert_eq!(decoder.decode(b"\xff\xc3"), "\u{fffd}");
+        assert_eq!(decoder.finish(), Some('\u{fffd}'));
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn interrupted_by_command() {
+        let mut parser = Parser::new();
+        let mut events: Vec<Event> = Vec::new();
+        // "ä" split by IAC NOP, without BINARY
+        parser.advance_bytes(&mut events, &[b'h', 0xc3, 255, 241, 0xa4, b'!']);
+        parser.flush(&mut events);
+
+        let mut decoder = Utf8Decoder::new();
+        let text: String = events.iter().filter_map(|e| decoder.push(e)).collect();
+        assert_eq!(text, "hä!");
+    }
+}
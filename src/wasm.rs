@@ -0,0 +1,228 @@
+//! WebAssembly bindings
+//!
+//! Exposes the parser and the negotiator to JavaScript with `wasm-bindgen`, for web clients
+//! talking to telnet servers through a WebSocket bridge. In JavaScript the types are named
+//! `Parser`, `Negotiator` and `Event`.
+use wasm_bindgen::prelude::*;
+
+use crate::command::Command;
+use crate::encode;
+use crate::event::Event;
+use crate::option::Opt;
+use crate::q::{self, Negotiator};
+use crate::session::Policy;
+use crate::Parser;
+
+/// Kind of a parser event, see [`Event`](../event/enum.Event.html)
+#[wasm_bindgen]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EventKind {
+    Data,
+    Execute,
+    Command,
+    Negotiate,
+    Subnegotiate,
+    LogoutRequested,
+    EndOfTurn,
+    ProtocolAbuse,
+    StatusMismatch,
+    EchoChanged,
+}
+
+/// A parser event as seen from JavaScript
+#[wasm_bindgen(js_name = Event)]
+pub struct JsEvent {
+    kind: EventKind,
+    byte: u8,
+    option: u8,
+    data: Vec<u8>,
+}
+
+#[wasm_bindgen(js_class = Event)]
+impl JsEvent {
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// The command of `Command` and `Negotiate` events, or the byte of `Execute` events
+    #[wasm_bindgen(getter)]
+    pub fn byte(&self) -> u8 {
+        self.byte
+    }
+
+    /// The option of `Negotiate` and `Subnegotiate` events
+    #[wasm_bindgen(getter)]
+    pub fn option(&self) -> u8 {
+        self.option
+    }
+
+    /// The data of `Data` events, or the parameters of `Subnegotiate` events
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+}
+
+impl From<Event> for JsEvent {
+    fn from(event: Event) -> JsEvent {
+        let (kind, byte, option, data) = match event {
+            Event::Data(data) => (EventKind::Data, 0, 0, data),
+            Event::Execute(byte) => (EventKind::Execute, byte, 0, Vec::new()),
+            Event::Command(command) => (EventKind::Command, command.as_u8(), 0, Vec::new()),
+            Event::Negotiate(command, option) => (
+                EventKind::Negotiate,
+                command.as_u8(),
+                option.as_u8(),
+                Vec::new(),
+            ),
+            Event::Subnegotiate(option, params) => {
+                (EventKind::Subnegotiate, 0, option.as_u8(), params)
+            }
+            Event::LogoutRequested => (EventKind::LogoutRequested, 0, 0, Vec::new()),
+            Event::EndOfTurn => (EventKind::EndOfTurn, 0, 0, Vec::new()),
+            Event::ProtocolAbuse(abuse) => (EventKind::ProtocolAbuse, abuse as u8, 0, Vec::new()),
+            Event::StatusMismatch(discrepancy) => (
+                EventKind::StatusMismatch,
+                discrepancy.reported as u8,
+                discrepancy.option.as_u8(),
+                Vec::new(),
+            ),
+            Event::EchoChanged(hint) => (EventKind::EchoChanged, hint as u8, 0, Vec::new()),
+        };
+        JsEvent {
+            kind,
+            byte,
+            option,
+            data,
+        }
+    }
+}
+
+/// Telnet parser
+#[wasm_bindgen(js_name = Parser)]
+#[derive(Default)]
+pub struct JsParser {
+    parser: Parser,
+    events: Vec<Event>,
+}
+
+#[wasm_bindgen(js_class = Parser)]
+impl JsParser {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsParser {
+        JsParser::default()
+    }
+
+    /// Parse bytes, e.g. a binary WebSocket message, and return the events found
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<JsEvent> {
+        self.parser.advance_bytes(&mut self.events, bytes);
+        self.parser.flush(&mut self.events);
+        self.events.drain(..).map(JsEvent::from).collect()
+    }
+}
+
+/// Collects negotiation answers as bytes
+struct Responder<'a> {
+    policy: &'a Policy,
+    output: Vec<u8>,
+}
+
+impl<'a> q::Perform for Responder<'a> {
+    fn send(&mut self, command: Command, option: u8) {
+        encode::negotiate_into(command, Opt::from_u8(option), &mut self.output);
+    }
+
+    fn accept_local(&mut self, option: u8) -> bool {
+        self.policy.is_local_accepted(Opt::from_u8(option))
+    }
+
+    fn accept_remote(&mut self, option: u8) -> bool {
+        self.policy.is_remote_accepted(Opt::from_u8(option))
+    }
+}
+
+/// Option negotiator, returning the bytes to send to the other party from every call
+#[wasm_bindgen(js_name = Negotiator)]
+#[derive(Default)]
+pub struct JsNegotiator {
+    negotiator: Negotiator,
+    policy: Policy,
+}
+
+#[wasm_bindgen(js_class = Negotiator)]
+impl JsNegotiator {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> JsNegotiator {
+        JsNegotiator::default()
+    }
+
+    /// Accept performing `option` ourselves when asked with DO
+    #[wasm_bindgen(js_name = acceptLocal)]
+    pub fn accept_local(&mut self, option: u8) {
+        self.policy = std::mem::take(&mut self.policy).accept_local(Opt::from_u8(option));
+    }
+
+    /// Accept the other party performing `option` when offered with WILL
+    #[wasm_bindgen(js_name = acceptRemote)]
+    pub fn accept_remote(&mut self, option: u8) {
+        self.policy = std::mem::take(&mut self.policy).accept_remote(Opt::from_u8(option));
+    }
+
+    /// Handle a received negotiation event
+    pub fn recv(&mut self, command: u8, option: u8) -> Vec<u8> {
+        let mut responder = Responder {
+            policy: &self.policy,
+            output: Vec::new(),
+        };
+        if let Ok(command) = Command::from_u8(command) {
+            // Errors show as what is sent, or not
+            let _ = self.negotiator.recv(&mut responder, command, option);
+        }
+        responder.output
+    }
+
+    /// Ask the other party to perform `option`
+    pub fn enable(&mut self, option: u8) -> Vec<u8> {
+        let mut responder = Responder {
+            policy: &self.policy,
+            output: Vec::new(),
+        };
+        let _ = self.negotiator.enable(&mut responder, option);
+        responder.output
+    }
+
+    /// Ask the other party to stop performing `option`
+    pub fn disable(&mut self, option: u8) -> Vec<u8> {
+        let mut responder = Responder {
+            policy: &self.policy,
+            output: Vec::new(),
+        };
+        let _ = self.negotiator.disable(&mut responder, option);
+        responder.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventKind, JsNegotiator, JsParser};
+
+    #[test]
+    fn parser_feed() {
+        let mut parser = JsParser::new();
+        let events = parser.feed(&[255, 253, 24, b'h', b'i']);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind(), EventKind::Negotiate);
+        assert_eq!((events[0].byte(), events[0].option()), (253, 24));
+        assert_eq!(events[1].kind(), EventKind::Data);
+        assert_eq!(events[1].data(), b"hi");
+    }
+
+    #[test]
+    fn negotiator_answers() {
+        let mut negotiator = JsNegotiator::new();
+        negotiator.accept_local(24);
+        assert_eq!(negotiator.recv(253, 24), &[255, 251, 24]);
+        assert_eq!(negotiator.recv(253, 31), &[255, 252, 31]);
+    }
+}
@@ -0,0 +1,18 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Generate the C header into `OUT_DIR`
+fn main() {
+    println!("cargo:rerun-if-changed=src/lib.rs");
+
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    cbindgen::Builder::new()
+        .with_crate(crate_dir)
+        .with_language(cbindgen::Language::C)
+        .with_include_guard("TELEVERKNET_H")
+        .generate()
+        .expect("unable to generate C header")
+        .write_to_file(out_dir.join("televerknet.h"));
+}
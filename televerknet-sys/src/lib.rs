@@ -0,0 +1,395 @@
+//! C API of televerknet
+//!
+//! Exposes the parser and the negotiator to C through opaque handles and tables of callbacks.
+//! The crate builds as a static and a dynamic library, and the header `televerknet.h` is
+//! generated with cbindgen into `OUT_DIR`.
+//!
+//! Every callback is optional, NULL callbacks are skipped. Options and commands are passed as
+//! their byte values.
+use std::os::raw::{c_int, c_void};
+use std::slice;
+
+use televerknet::command::Command;
+use televerknet::event::Event;
+use televerknet::q::{self, Negotiator, NegotiatorError};
+use televerknet::Parser;
+
+pub const TVK_OK: c_int = 0;
+pub const TVK_ALREADY_ENABLED: c_int = 1;
+pub const TVK_ALREADY_QUEUED: c_int = 2;
+pub const TVK_ALREADY_DISABLED: c_int = 3;
+pub const TVK_ALREADY_NEGOTIATING: c_int = 4;
+pub const TVK_DONT_ANSWERED_BY_WILL: c_int = 5;
+pub const TVK_WONT_ANSWERED_BY_DO: c_int = 6;
+pub const TVK_UNKNOWN_COMMAND: c_int = 7;
+pub const TVK_UNTRACKED_OPTION: c_int = 8;
+pub const TVK_REFUSED: c_int = 9;
+pub const TVK_NULL_POINTER: c_int = -1;
+
+/// Opaque parser handle
+pub struct TvkParser {
+    parser: Parser,
+    events: Vec<Event>,
+}
+
+/// Opaque negotiator handle
+pub struct TvkNegotiator {
+    negotiator: Negotiator,
+}
+
+/// Callbacks for parser events
+#[repr(C)]
+pub struct TvkParserCallbacks {
+    /// Passed as the first argument to every callback
+    pub user_data: *mut c_void,
+    /// Data with escaped IAC bytes unescaped
+    pub data: Option<extern "C" fn(user_data: *mut c_void, data: *const u8, len: usize)>,
+    /// Control byte in the data stream, e.g. CR or LF
+    pub execute: Option<extern "C" fn(user_data: *mut c_void, byte: u8)>,
+    /// `IAC <command>`
+    pub command: Option<extern "C" fn(user_data: *mut c_void, command: u8)>,
+    /// `IAC <WILL|WONT|DO|DONT> <option>`
+    pub negotiate: Option<extern "C" fn(user_data: *mut c_void, command: u8, option: u8)>,
+    /// `IAC SB <option> <params> IAC SE`
+    pub subnegotiate:
+        Option<extern "C" fn(user_data: *mut c_void, option: u8, params: *const u8, len: usize)>,
+}
+
+/// Callbacks for the negotiator
+#[repr(C)]
+pub struct TvkNegotiatorCallbacks {
+    /// Passed as the first argument to every callback
+    pub user_data: *mut c_void,
+    /// Send `IAC <command> <option>` to the other party
+    pub send: Option<extern "C" fn(user_data: *mut c_void, command: u8, option: u8)>,
+    /// Return true to accept performing `option` when asked with DO
+    pub accept_local: Option<extern "C" fn(user_data: *mut c_void, option: u8) -> bool>,
+    /// Return true to accept the other party performing `option` when offered with WILL
+    pub accept_remote: Option<extern "C" fn(user_data: *mut c_void, option: u8) -> bool>,
+}
+
+impl q::Perform for &TvkNegotiatorCallbacks {
+    fn send(&mut self, command: Command, option: u8) {
+        if let Some(send) = self.send {
+            send(self.user_data, command.as_u8(), option);
+        }
+    }
+
+    fn accept_local(&mut self, option: u8) -> bool {
+        match self.accept_local {
+            Some(accept_local) => accept_local(self.user_data, option),
+            None => false,
+        }
+    }
+
+    fn accept_remote(&mut self, option: u8) -> bool {
+        match self.accept_remote {
+            Some(accept_remote) => accept_remote(self.user_data, option),
+            None => false,
+        }
+    }
+}
+
+fn error_code(result: Result<(), NegotiatorError>) -> c_int {
+    match result {
+        Ok(()) => TVK_OK,
+        Err(NegotiatorError::AlreadyEnabled) => TVK_ALREADY_ENABLED,
+        Err(NegotiatorError::AlreadyQueued) => TVK_ALREADY_QUEUED,
+        Err(NegotiatorError::AlreadyDisabled) => TVK_ALREADY_DISABLED,
+        Err(NegotiatorError::AlreadyNegotiating) => TVK_ALREADY_NEGOTIATING,
+        Err(NegotiatorError::DontAnsweredByWill) => TVK_DONT_ANSWERED_BY_WILL,
+        Err(NegotiatorError::WontAnsweredByDo) => TVK_WONT_ANSWERED_BY_DO,
+        Err(NegotiatorError::UnknownCommand) => TVK_UNKNOWN_COMMAND,
+        Err(NegotiatorError::UntrackedOption) => TVK_UNTRACKED_OPTION,
+        Err(NegotiatorError::Refused) => TVK_REFUSED,
+    }
+}
+
+fn parser_handle(parser: Parser) -> *mut TvkParser {
+    Box::into_raw(Box::new(TvkParser {
+        parser,
+        events: Vec::new(),
+    }))
+}
+
+/// Create a parser, to be freed with `tvk_parser_free`
+#[no_mangle]
+pub extern "C" fn tvk_parser_new() -> *mut TvkParser {
+    parser_handle(Parser::new())
+}
+
+/// Create a parser buffering at most `max_data` bytes of data and `max_subnegotiation` bytes of
+/// a subnegotiation, to be freed with `tvk_parser_free`
+///
+/// Both sizes are at least one byte. Longer data is dispatched in parts, and longer
+/// subnegotiations are truncated.
+#[no_mangle]
+pub extern "C" fn tvk_parser_new_with_limits(
+    max_data: usize,
+    max_subnegotiation: usize,
+) -> *mut TvkParser {
+    parser_handle(
+        Parser::builder()
+            .max_data(max_data)
+            .max_subnegotiation(max_subnegotiation)
+            .build(),
+    )
+}
+
+/// Free a parser created with `tvk_parser_new`
+///
+/// # Safety
+///
+/// `parser` must be NULL or a pointer returned by `tvk_parser_new` or
+/// `tvk_parser_new_with_limits` which has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tvk_parser_free(parser: *mut TvkParser) {
+    if !parser.is_null() {
+        drop(Box::from_raw(parser));
+    }
+}
+
+/// Feed `len` bytes to the parser, invoking callbacks for every event found
+///
+/// Data at the end of `bytes` is dispatched before returning.
+///
+/// # Safety
+///
+/// `parser` must be a live parser, `bytes` must point to `len` readable bytes and `callbacks`
+/// must point to a valid callback table.
+#[no_mangle]
+pub unsafe extern "C" fn tvk_parser_feed(
+    parser: *mut TvkParser,
+    bytes: *const u8,
+    len: usize,
+    callbacks: *const TvkParserCallbacks,
+) -> c_int {
+    if parser.is_null() || callbacks.is_null() || (bytes.is_null() && len > 0) {
+        return TVK_NULL_POINTER;
+    }
+    let parser = &mut *parser;
+    let callbacks = &*callbacks;
+    let bytes = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(bytes, len)
+    };
+
+    parser.parser.advance_bytes(&mut parser.events, bytes);
+    parser.parser.flush(&mut parser.events);
+    let user_data = callbacks.user_data;
+    for event in parser.events.drain(..) {
+        match event {
+            Event::Data(data) => {
+                if let Some(f) = callbacks.data {
+                    f(user_data, data.as_ptr(), data.len());
+                }
+            }
+            Event::Execute(byte) => {
+                if let Some(f) = callbacks.execute {
+                    f(user_data, byte);
+                }
+            }
+            Event::Command(command) => {
+                if let Some(f) = callbacks.command {
+                    f(user_data, command.as_u8());
+                }
+            }
+            Event::Negotiate(command, option) => {
+                if let Some(f) = callbacks.negotiate {
+                    f(user_data, command.as_u8(), option.as_u8());
+                }
+            }
+            Event::Subnegotiate(option, params) => {
+                if let Some(f) = callbacks.subnegotiate {
+                    f(user_data, option.as_u8(), params.as_ptr(), params.len());
+                }
+            }
+            // Only added by TelnetSession
+            _ => (),
+        }
+    }
+    TVK_OK
+}
+
+/// Create a negotiator, to be freed with `tvk_negotiator_free`
+#[no_mangle]
+pub extern "C" fn tvk_negotiator_new() -> *mut TvkNegotiator {
+    Box::into_raw(Box::new(TvkNegotiator {
+        negotiator: Negotiator::new(),
+    }))
+}
+
+/// Free a negotiator created with `tvk_negotiator_new`
+///
+/// # Safety
+///
+/// `negotiator` must be NULL or a pointer returned by `tvk_negotiator_new` which has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn tvk_negotiator_free(negotiator: *mut TvkNegotiator) {
+    if !negotiator.is_null() {
+        drop(Box::from_raw(negotiator));
+    }
+}
+
+/// Handle a received WILL, WONT, DO or DONT for `option`
+///
+/// # Safety
+///
+/// `negotiator` must be a live negotiator and `callbacks` must point to a valid callback table.
+#[no_mangle]
+pub unsafe extern "C" fn tvk_negotiator_recv(
+    negotiator: *mut TvkNegotiator,
+    command: u8,
+    option: u8,
+    callbacks: *const TvkNegotiatorCallbacks,
+) -> c_int {
+    if negotiator.is_null() || callbacks.is_null() {
+        return TVK_NULL_POINTER;
+    }
+    let command = match Command::from_u8(command) {
+        Ok(command) => command,
+        Err(_) => return TVK_UNKNOWN_COMMAND,
+    };
+    let mut callbacks = &*callbacks;
+    error_code(
+        (*negotiator)
+            .negotiator
+            .recv(&mut callbacks, command, option),
+    )
+}
+
+/// Ask the other party to perform `option`
+///
+/// # Safety
+///
+/// `negotiator` must be a live negotiator and `callbacks` must point to a valid callback table.
+#[no_mangle]
+pub unsafe extern "C" fn tvk_negotiator_enable(
+    negotiator: *mut TvkNegotiator,
+    option: u8,
+    callbacks: *const TvkNegotiatorCallbacks,
+) -> c_int {
+    if negotiator.is_null() || callbacks.is_null() {
+        return TVK_NULL_POINTER;
+    }
+    let mut callbacks = &*callbacks;
+    error_code((*negotiator).negotiator.enable(&mut callbacks, option))
+}
+
+/// Ask the other party to stop performing `option`
+///
+/// # Safety
+///
+/// `negotiator` must be a live negotiator and `callbacks` must point to a valid callback table.
+#[no_mangle]
+pub unsafe extern "C" fn tvk_negotiator_disable(
+    negotiator: *mut TvkNegotiator,
+    option: u8,
+    callbacks: *const TvkNegotiatorCallbacks,
+) -> c_int {
+    if negotiator.is_null() || callbacks.is_null() {
+        return TVK_NULL_POINTER;
+    }
+    let mut callbacks = &*callbacks;
+    error_code((*negotiator).negotiator.disable(&mut callbacks, option))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    extern "C" fn on_data(user_data: *mut c_void, data: *const u8, len: usize) {
+        let seen = unsafe { &mut *(user_data as *mut Vec<u8>) };
+        seen.extend_from_slice(unsafe { slice::from_raw_parts(data, len) });
+    }
+
+    extern "C" fn on_negotiate(user_data: *mut c_void, command: u8, option: u8) {
+        let seen = unsafe { &mut *(user_data as *mut Vec<u8>) };
+        seen.extend_from_slice(&[command, option]);
+    }
+
+    #[test]
+    fn parser_feed() {
+        let mut seen: Vec<u8> = Vec::new();
+        let callbacks = TvkParserCallbacks {
+            user_data: &mut seen as *mut Vec<u8> as *mut c_void,
+            data: Some(on_data),
+            execute: None,
+            command: None,
+            negotiate: Some(on_negotiate),
+            subnegotiate: None,
+        };
+        let bytes = [b'h', b'i', 255, 251, 1];
+        unsafe {
+            let parser = tvk_parser_new();
+            assert_eq!(
+                tvk_parser_feed(parser, bytes.as_ptr(), bytes.len(), &callbacks),
+                TVK_OK
+            );
+            assert_eq!(
+                tvk_parser_feed(ptr::null_mut(), ptr::null(), 0, &callbacks),
+                TVK_NULL_POINTER
+            );
+            tvk_parser_free(parser);
+        }
+        assert_eq!(seen, &[b'h', b'i', 251, 1]);
+    }
+
+    extern "C" fn on_subnegotiate(
+        user_data: *mut c_void,
+        option: u8,
+        params: *const u8,
+        len: usize,
+    ) {
+        let seen = unsafe { &mut *(user_data as *mut Vec<u8>) };
+        seen.push(option);
+        seen.extend_from_slice(unsafe { slice::from_raw_parts(params, len) });
+    }
+
+    #[test]
+    fn parser_limits() {
+        let mut seen: Vec<u8> = Vec::new();
+        let callbacks = TvkParserCallbacks {
+            user_data: &mut seen as *mut Vec<u8> as *mut c_void,
+            data: None,
+            execute: None,
+            command: None,
+            negotiate: None,
+            subnegotiate: Some(on_subnegotiate),
+        };
+        let bytes = b"\xff\xfa\x18\x00xterm-256color\xff\xf0";
+        unsafe {
+            let parser = tvk_parser_new_with_limits(16, 8);
+            tvk_parser_feed(parser, bytes.as_ptr(), bytes.len(), &callbacks);
+            tvk_parser_free(parser);
+            let parser = tvk_parser_new();
+            tvk_parser_feed(parser, bytes.as_ptr(), bytes.len(), &callbacks);
+            tvk_parser_free(parser);
+        }
+        assert_eq!(seen, b"\x18\0xterm-\x18\0xterm-256color");
+    }
+
+    #[test]
+    fn negotiator_refuses() {
+        let mut sent: Vec<u8> = Vec::new();
+        let callbacks = TvkNegotiatorCallbacks {
+            user_data: &mut sent as *mut Vec<u8> as *mut c_void,
+            send: Some(on_negotiate),
+            accept_local: None,
+            accept_remote: None,
+        };
+        unsafe {
+            let negotiator = tvk_negotiator_new();
+            assert_eq!(tvk_negotiator_recv(negotiator, 253, 31, &callbacks), TVK_OK);
+            assert_eq!(
+                tvk_negotiator_recv(negotiator, 1, 31, &callbacks),
+                TVK_UNKNOWN_COMMAND
+            );
+            tvk_negotiator_free(negotiator);
+        }
+        assert_eq!(sent, &[252, 31]);
+    }
+}
@@ -0,0 +1,142 @@
+//! The parser and the negotiator must not allocate once they are built
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use televerknet::command::Command;
+use televerknet::q::{self, Negotiator};
+use televerknet::{DataEnd, Parser, ParserBuilder, Perform};
+
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // Counted per thread, as tests run in parallel
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations_in<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+/// Counts callbacks without keeping anything
+#[derive(Default)]
+struct Counter {
+    data: usize,
+    events: usize,
+}
+
+impl Perform for Counter {
+    fn data(&mut self, intermediates: &[u8], _ignore: bool, _end: DataEnd) {
+        self.data += intermediates.len();
+    }
+
+    fn execute(&mut self, _byte: u8) {
+        self.events += 1;
+    }
+
+    fn iac_dispatch(&mut self, _byte: u8) {
+        self.events += 1;
+    }
+
+    fn sub_dispatch(&mut self, _subs: &[u8]) {
+        self.events += 1;
+    }
+
+    fn negotiate_dispatch(&mut self, _cmd: u8, _opt: u8) {
+        self.events += 1;
+    }
+
+    fn subnegotiate_dispatch(&mut self, _params: &[u8], _opt: u8) {}
+    fn zmp_dispatch(&mut self, _params: &[&[u8]]) {}
+    fn ttypes_dispatch(&mut self, _cmd: u8, _terminal_type: &[u8]) {}
+    fn compress_dispatch(&mut self, _state: u8) {}
+}
+
+impl q::Perform for Counter {
+    fn send(&mut self, _command: Command, _option: u8) {
+        self.events += 1;
+    }
+
+    fn accept_local(&mut self, _option: u8) -> bool {
+        true
+    }
+
+    fn accept_remote(&mut self, option: u8) -> bool {
+        option < 128
+    }
+}
+
+#[test]
+fn parser_does_not_allocate() {
+    let mut input = b"Welcome!\r\n\xff\xfb\x01\xff\xfd\x1f".to_vec();
+    input.extend_from_slice(b"\xff\xfa\x18\x01\xff\xf0name: \xff\xf9\xff\xff\x80");
+    input.extend_from_slice(&[b'x'; 5000]);
+    input.extend_from_slice(b"\xff\xfa\xc9Core.Hello {}\xff\xf0\xff");
+
+    let mut counter = Counter::default();
+    let mut parser = Parser::new();
+    let mut strict = ParserBuilder::new().strict(true).max_data(16).build();
+    let allocations = allocations_in(|| {
+        parser.advance_bytes(&mut counter, &input);
+        for byte in &input {
+            parser.advance(&mut counter, *byte);
+            strict.advance(&mut counter, *byte);
+        }
+        parser.advance_partial(&mut counter, &input);
+        parser.flush(&mut counter);
+    });
+    assert_eq!(allocations, 0);
+    assert!(counter.data > 0 && counter.events > 0);
+}
+
+#[test]
+fn negotiator_does_not_allocate() {
+    let mut counter = Counter::default();
+    let mut negotiator = Negotiator::new();
+    let allocations = allocations_in(|| {
+        for option in 0..=255 {
+            for command in &[Command::WILL, Command::DO, Command::WONT, Command::DONT] {
+                let _ = negotiator.recv(&mut counter, *command, option);
+            }
+            let _ = negotiator.offer(&mut counter, option);
+            let _ = negotiator.enable(&mut counter, option);
+            let _ = negotiator.withdraw(&mut counter, option);
+            let _ = negotiator.disable(&mut counter, option);
+        }
+    });
+    assert_eq!(allocations, 0);
+    assert!(counter.events > 0);
+}
+
+#[cfg(feature = "heapless")]
+#[test]
+fn heapless_parser_does_not_allocate() {
+    let mut input = b"\xff\xfa\x18\x01\xff\xf0".to_vec();
+    input.extend_from_slice(&[b'x'; 5000]);
+
+    let mut counter = Counter::default();
+    let allocations = allocations_in(|| {
+        let mut parser = Parser::new();
+        let mut large = ParserBuilder::new().max_subnegotiation(1 << 16).build();
+        parser.advance_bytes(&mut counter, &input);
+        large.advance_bytes(&mut counter, &input);
+        let _copy = parser.clone();
+    });
+    assert_eq!(allocations, 0);
+    assert_eq!(counter.data, 10000);
+}